@@ -2,6 +2,7 @@ import { type ClassValue, clsx } from 'clsx';
 import { twMerge } from 'tailwind-merge';
 import { cubicOut } from 'svelte/easing';
 import type { TransitionConfig } from 'svelte/transition';
+import { PUBLIC_SPEKTRUM_CDN_URL } from '$env/static/public';
 
 export function cn(...inputs: ClassValue[]) {
 	return twMerge(clsx(inputs));
@@ -54,3 +55,25 @@ export const flyAndScale = (
 		easing: cubicOut
 	};
 };
+
+/**
+ * Image source for a stored media URL. The server returns absolute URLs when it
+ * has a media base URL configured; those are used as-is.
+ */
+export function mediaSrc(url: string): string {
+	if (/^https?:\/\//.test(url) || !PUBLIC_SPEKTRUM_CDN_URL) {
+		return url;
+	}
+	return `${PUBLIC_SPEKTRUM_CDN_URL}/${url}`;
+}
+
+export async function fetchAdminToken(serverUrl: string, password: string): Promise<string> {
+	const response = await fetch(`${serverUrl}/api/admin/login`, {
+		method: 'POST',
+		headers: { 'Content-Type': 'application/json' },
+		body: JSON.stringify({ password })
+	});
+	if (!response.ok) throw new Error(`HTTP error! status: ${response.status}`);
+	const { token } = await response.json();
+	return token;
+}
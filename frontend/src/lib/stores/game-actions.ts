@@ -3,7 +3,7 @@ import { gameStore } from '$lib/stores/game.svelte';
 import { youtubeStore } from '$lib/stores/youtube-store.svelte';
 import { timerStore } from '$lib/stores/timer.svelte';
 import { info, warn } from '$lib/utils/logger';
-import type { ClientMessage, AdminAction } from '../types/game';
+import type { ClientMessage, AdminAction, GameQuestionOption } from '../types/game';
 import { PUBLIC_SPEKTRUM_SERVER_URL } from '$env/static/public';
 import { removeSession } from '$lib/stores/game.svelte';
 
@@ -120,6 +120,10 @@ class GameActions {
 		this.sendAdminAction({ type: 'KickPlayer', player_name: playerName });
 	}
 
+	public approveReclaim(playerName: string) {
+		this.sendAdminAction({ type: 'ApproveReclaim', player_name: playerName });
+	}
+
 	public endGame(reason: string = 'Game ended by admin') {
 		timerStore.resetTimer();
 		this.sendAdminAction({ type: 'EndGame', reason });
@@ -135,6 +139,22 @@ class GameActions {
 		this.sendAdminAction({ type: 'LockLobby', locked });
 	}
 
+	public requestUpcoming(count: number) {
+		this.sendAdminAction({ type: 'RequestUpcoming', count });
+	}
+
+	public queueQuestion(questionId: number) {
+		this.sendAdminAction({ type: 'QueueQuestion', question_id: questionId });
+	}
+
+	public reorderUpcoming(questionIds: number[]) {
+		this.sendAdminAction({ type: 'ReorderUpcoming', question_ids: questionIds });
+	}
+
+	public injectQuestion(questionText: string, options: GameQuestionOption[]) {
+		this.sendAdminAction({ type: 'InjectQuestion', question_text: questionText, options });
+	}
+
 	/**
 	 * Leave the current game (if any), then clean up local state and YouTube player.
 	 */
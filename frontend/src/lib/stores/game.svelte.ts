@@ -7,7 +7,7 @@ import { info, warn } from '$lib/utils/logger';
 import { notifications } from '$lib/stores/notification-store.svelte';
 import { broadcastService } from '$lib/services/broadcast.service';
 
-import type { GameState, GameUpdate } from '../types/game';
+import type { ClientMessage, GameState, GameUpdate } from '../types/game';
 import { GamePhase } from '../types/game';
 import { PUBLIC_SPEKTRUM_SERVER_URL } from '$env/static/public';
 
@@ -22,8 +22,8 @@ export interface SessionInfo {
 	sessionToken: string;
 }
 
-/** SessionInfo enriched with server-validated last_update timestamp. */
-export type ValidatedSession = SessionInfo & { last_update: string };
+/** SessionInfo enriched with what the server knows about the session. */
+export type ValidatedSession = SessionInfo & { last_update: string; phase: GamePhase };
 
 export function loadSession(): SessionInfo | null {
 	if (!browser) return null;
@@ -83,9 +83,11 @@ function createGameStore() {
 			currentQuestion: undefined,
 			currentSong: undefined,
 			upcomingQuestions: undefined,
+			roundSummary: undefined,
 			error: undefined,
 			questionTimeRemainingMs: undefined,
-			answeredPlayerNames: undefined
+			answeredPlayerNames: undefined,
+			scoreboardSeq: undefined
 		});
 	}
 
@@ -117,7 +119,14 @@ function createGameStore() {
 			}
 
 			const data = (await res.json()) as {
-				valid_sessions: Array<{ player_id: string; last_update: string }>;
+				valid_sessions: Array<{
+					player_id: string;
+					last_update: string;
+					join_code: string;
+					name: string;
+					phase: GamePhase;
+					is_admin: boolean;
+				}>;
 			};
 
 			info('Session check response:', data.valid_sessions);
@@ -128,7 +137,11 @@ function createGameStore() {
 				// Explicitly return the combined type
 				return {
 					...session,
-					last_update: validSession.last_update
+					playerName: validSession.name,
+					isAdmin: validSession.is_admin,
+					joinCode: validSession.join_code,
+					last_update: validSession.last_update,
+					phase: validSession.phase
 				} satisfies ValidatedSession;
 			}
 
@@ -141,9 +154,10 @@ function createGameStore() {
 	}
 
 	/**
-	 * Processes an incoming GameUpdate message from the server.
+	 * Processes an incoming GameUpdate message from the server. Returns a
+	 * message to send back, if any.
 	 */
-	function processServerMessage(message: GameUpdate) {
+	function processServerMessage(message: GameUpdate): ClientMessage | undefined {
 		info('Handling server message:', message);
 
 		// Initialize broadcast service if not already done and this is admin
@@ -167,6 +181,7 @@ function createGameStore() {
 			return;
 		}
 
+		let reply: ClientMessage | undefined;
 		switch (message.type) {
 			case 'Connected': {
 				// Save the session to localStorage for reconnection.
@@ -232,6 +247,34 @@ function createGameStore() {
 					state.players = newPlayers;
 				}
 
+				// Score changes apply only on top of the previous update; after a
+				// gap, ask for the full lists instead.
+				if (message.score_changes) {
+					if (
+						state.scoreboardSeq !== undefined &&
+						message.scoreboard_seq === state.scoreboardSeq + 1
+					) {
+						const updated = new Map(state.players);
+						message.score_changes.forEach((change) => {
+							const player = updated.get(change.name);
+							updated.set(change.name, {
+								name: change.name,
+								score: change.score,
+								roundScore: change.round_score,
+								hasAnswered: player?.hasAnswered ?? false,
+								consecutiveMisses: change.consecutive_misses,
+								answer: player?.answer ?? null
+							});
+						});
+						state.players = updated;
+						state.scoreboardSeq = message.scoreboard_seq;
+					} else {
+						reply = { type: 'ResyncScoreboard' };
+					}
+				} else if (message.scoreboard_seq !== undefined) {
+					state.scoreboardSeq = message.scoreboard_seq;
+				}
+
 				// Update answered flags on existing players even if no scoreboard was provided.
 				if (message.answered_player_names && !message.scoreboard) {
 					const updated = new Map(state.players);
@@ -248,12 +291,24 @@ function createGameStore() {
 					state.currentQuestion = {
 						type: message.question_type ?? '',
 						text: message.question_text ?? undefined,
-						alternatives: message.alternatives
+						alternatives: message.alternatives,
+						colors: message.colors
 					};
 				}
 
-				if (message.question_time_remaining_ms !== undefined) {
-					state.questionTimeRemainingMs = message.question_time_remaining_ms;
+				// Take off the time the update spent in transit.
+				let remainingMs = message.question_time_remaining_ms;
+				if (
+					remainingMs !== undefined &&
+					message.server_time_ms !== undefined &&
+					state.clockOffsetMs !== undefined
+				) {
+					const delay = Date.now() + state.clockOffsetMs - message.server_time_ms;
+					remainingMs = Math.max(0, remainingMs - Math.max(0, delay));
+				}
+
+				if (remainingMs !== undefined) {
+					state.questionTimeRemainingMs = remainingMs;
 				}
 
 				// Sync answered players and currentAnswers from snapshot.
@@ -279,7 +334,7 @@ function createGameStore() {
 				const currentPhase = state.phase;
 				if (previousPhase !== currentPhase) {
 					if (currentPhase === GamePhase.Question) {
-						timerStore.startTimer(state.roundDuration, message.question_time_remaining_ms);
+						timerStore.startTimer(state.roundDuration, remainingMs);
 					}
 					if (currentPhase === GamePhase.Score) {
 						timerStore.stopTimer(true);
@@ -288,10 +343,10 @@ function createGameStore() {
 					}
 				} else if (
 					state.phase === GamePhase.Question &&
-					message.question_time_remaining_ms !== undefined
+					remainingMs !== undefined
 				) {
 					// Same phase but got a time snapshot — resync the timer.
-					timerStore.startTimer(state.roundDuration, message.question_time_remaining_ms);
+					timerStore.startTimer(state.roundDuration, remainingMs);
 				}
 
 				if (message.lobby_locked !== undefined) {
@@ -318,6 +373,18 @@ function createGameStore() {
 				break;
 			}
 
+			case 'Pong': {
+				const now = Date.now();
+				const rtt = now - message.client_time_ms;
+				state.clockOffsetMs = message.server_time_ms + rtt / 2 - now;
+				break;
+			}
+
+			case 'AnswerAck': {
+				// The broadcast `Answered` carries the same score.
+				break;
+			}
+
 			case 'PlayerLeft': {
 				info(`Player left: ${message.name}`);
 				const updated = new Map(state.players);
@@ -357,6 +424,12 @@ function createGameStore() {
 				break;
 			}
 
+			case 'PrefetchNext': {
+				info('Prefetching next video:', message.youtube_id);
+				youtubeStore.loadVideo(message.youtube_id);
+				break;
+			}
+
 			case 'AdminNextQuestions': {
 				const nextQuestion = message.upcoming_questions[0];
 				if (nextQuestion?.youtube_id) {
@@ -367,6 +440,32 @@ function createGameStore() {
 				break;
 			}
 
+			case 'AdminRoundSummary': {
+				state.roundSummary = message.answers;
+				break;
+			}
+
+			case 'InactivityWarning': {
+				const minutes = Math.max(1, Math.round(message.closes_in_secs / 60));
+				notifications.add(
+					`The lobby closes in ${minutes} min due to inactivity. Do anything to keep it open.`
+				);
+				break;
+			}
+
+			case 'RoundInterrupted': {
+				notifications.add('The server restarted, so the last round was cut short.');
+				break;
+			}
+
+			case 'ReclaimRequested': {
+				notifications.add(
+					`${message.name} is trying to rejoin and needs your approval.`
+				);
+				break;
+			}
+
+			case 'AnswerRejected':
 			case 'Error': {
 				state.error = message.message;
 				notifications.add(message.message, 'destructive');
@@ -376,6 +475,7 @@ function createGameStore() {
 			default:
 				warn('Unhandled message type:', message);
 		}
+		return reply;
 	}
 
 	function setAdminTo(value: boolean) {
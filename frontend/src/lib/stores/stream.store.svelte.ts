@@ -230,6 +230,24 @@ function createStreamStore() {
 					}
 				}
 
+				if (message.score_changes) {
+					const changed = new Set(message.score_changes.map(({ name }) => name));
+					message.score_changes.forEach(({ name, score }) => {
+						state.gameState!.players.set(name, { name, score });
+					});
+					const unchanged = state.gameState.realtimeScoreboard.filter(
+						(player) => !changed.has(player.name)
+					);
+					state.gameState.realtimeScoreboard = [
+						...unchanged,
+						...message.score_changes.map(({ name, score, round_score }) => ({
+							name,
+							score,
+							roundScore: round_score
+						}))
+					].sort((a, b) => b.score - a.score);
+				}
+
 				if (message.answered_player_names) {
 					const roundScoreMap = new Map(message.round_scores ?? []);
 					const now = Date.now();
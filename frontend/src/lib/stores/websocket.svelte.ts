@@ -48,6 +48,13 @@ type SupervisorEvent =
 	| { type: 'ONLINE' }
 	| { type: 'NETWORK_CHANGE' };
 
+/**
+ * Close codes the server uses when reconnecting won't help: 1000 when the
+ * game closed, 1008 when the player was kicked or sent too many messages.
+ * Other codes, e.g. 1001 on shutdown or 1013 after falling behind, are retried.
+ */
+const FINAL_CLOSE_CODES = new Set<number>([1000, 1008]);
+
 const SESSION_INVALID_MESSAGES = new Set<string>([
 	ErrorCode.GameClosed,
 	ErrorCode.LobbyNotFound,
@@ -415,7 +422,10 @@ export function createWebSocketStore() {
 				dispatch({ type: 'GAME_ENDED' });
 			}
 
-			gameStore.processServerMessage(message);
+			const reply = gameStore.processServerMessage(message);
+			if (reply) {
+				send(reply);
+			}
 			state.error = null;
 		} catch (error) {
 			warn('Failed to parse message:', error);
@@ -513,7 +523,9 @@ export function createWebSocketStore() {
 				markConnected();
 				if (sessionToken) {
 					gameStore.setSessionToken(sessionToken);
-					send({ type: 'Connect', session_token: sessionToken });
+					// Sent first so the clock offset is known before the question timer.
+					send({ type: 'Ping', client_time_ms: Date.now() });
+					send({ type: 'Connect', session_token: sessionToken, locale: navigator.language });
 				}
 				if (isVisible) {
 					startHeartbeat();
@@ -531,6 +543,14 @@ export function createWebSocketStore() {
 					markDisconnected();
 					return;
 				}
+				if (FINAL_CLOSE_CODES.has(event.code)) {
+					desiredConnection = false;
+					state.error = event.reason || null;
+					resetAttempts();
+					markDisconnected();
+					rejectPendingConnect(event.gen, event.reason || 'Connection closed');
+					return;
+				}
 				handleConnectionLoss('Connection closed');
 				rejectPendingConnect(event.gen, 'Connection closed');
 				return;
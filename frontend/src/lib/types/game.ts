@@ -64,6 +64,8 @@ export interface GameState {
 		type: string;
 		text?: string;
 		alternatives: string[];
+		/** Swatch details for color questions, matching `alternatives`. */
+		colors?: ColorInfo[];
 	};
 	currentSong?: {
 		songName: string;
@@ -72,10 +74,16 @@ export interface GameState {
 	};
 	error?: string;
 	upcomingQuestions?: GameQuestion[];
+	/** When each player answered the last round; admin only. */
+	roundSummary?: AnswerTiming[];
 	currentAnswers: PlayerAnswer[];
 	questionTimeRemainingMs?: number;
 	answeredPlayerNames?: string[];
 	lobbyLocked: boolean;
+	/** Last score update applied; see `scoreboard_seq`. */
+	scoreboardSeq?: number;
+	/** Server clock minus ours, estimated from a Ping. */
+	clockOffsetMs?: number;
 }
 
 /**
@@ -88,6 +96,8 @@ export interface GameQuestion {
 	title: string;
 	artist?: string;
 	youtube_id: string;
+	/** Track to play when hosting through Spotify; only sent to the admin. */
+	spotify_uri?: string;
 	options: GameQuestionOption[];
 }
 
@@ -96,6 +106,48 @@ export interface GameQuestionOption {
 	is_correct: boolean;
 }
 
+/**
+ * How to render a color alternative: its swatch color, a fill pattern that
+ * doesn't rely on hue, and a short label to print on it.
+ */
+export interface ColorInfo {
+	name: string;
+	hex: string;
+	pattern:
+		| 'solid'
+		| 'dots'
+		| 'horizontal_stripes'
+		| 'vertical_stripes'
+		| 'diagonal_stripes'
+		| 'grid'
+		| 'checker'
+		| 'crosshatch'
+		| 'waves'
+		| 'zigzag'
+		| 'diamonds';
+	label: string;
+}
+
+/**
+ * When and how a player answered a round; sent to the admin.
+ */
+export interface AnswerTiming {
+	name: string;
+	answer: string | null;
+	correct: boolean;
+	elapsed_ms: number | null;
+}
+
+/**
+ * A player's scores after they changed.
+ */
+export interface ScoreChange {
+	name: string;
+	score: number;
+	round_score: number;
+	consecutive_misses: number;
+}
+
 /* ------------------------------------------------------------------
    SERVER -> CLIENT MESSAGES
 ------------------------------------------------------------------ */
@@ -106,6 +158,10 @@ export type GameUpdate =
 			player_id: string;
 			name: string;
 			round_duration: number;
+			/** Keepalive agreed on for this connection. */
+			heartbeat: { interval_ms: number; max_missed: number };
+			/** Identifies the connection in server logs; quote it in bug reports. */
+			connection_id?: string;
 	  }
 	| {
 			type: 'StateDelta';
@@ -113,11 +169,15 @@ export type GameUpdate =
 			question_type?: string;
 			question_text?: string;
 			alternatives?: string[];
+			colors?: ColorInfo[];
 			question_time_remaining_ms?: number;
+			server_time_ms?: number;
 			answered_player_names?: string[];
 			scoreboard?: [string, number][];
 			round_scores?: [string, number][];
 			consecutive_misses?: [string, number][];
+			score_changes?: ScoreChange[];
+			scoreboard_seq?: number;
 			admin_extra?: { upcoming_questions: GameQuestion[] };
 			lobby_locked?: boolean;
 	  }
@@ -134,6 +194,17 @@ export type GameUpdate =
 			name: string;
 			score: number;
 	  }
+	| {
+			type: 'AnswerRejected';
+			reason: AnswerValidationError;
+			message: string;
+			client_msg_id?: string;
+	  }
+	| {
+			type: 'AnswerAck';
+			score: number;
+			client_msg_id?: string;
+	  }
 	| {
 			type: 'GameOver';
 			final_scores: [string, number][];
@@ -154,6 +225,29 @@ export type GameUpdate =
 	| {
 			type: 'AdminNextQuestions';
 			upcoming_questions: GameQuestion[];
+	  }
+	| {
+			type: 'PrefetchNext';
+			youtube_id: string;
+			start_seconds: number;
+	  }
+	| {
+			type: 'AdminRoundSummary';
+			answers: AnswerTiming[];
+	  }
+	| {
+			type: 'ReclaimRequested';
+			name: string;
+	  }
+	| { type: 'RoundInterrupted' }
+	| {
+			type: 'InactivityWarning';
+			closes_in_secs: number;
+	  }
+	| {
+			type: 'Pong';
+			client_time_ms: number;
+			server_time_ms: number;
 	  };
 
 /* ------------------------------------------------------------------
@@ -164,6 +258,10 @@ export type ClientMessage =
 	| {
 			type: 'Connect';
 			session_token: string;
+			/** Language for server error messages, e.g. `sv-SE`. */
+			locale?: string;
+			/** Heartbeat interval to ask for; the server keeps it within its limits. */
+			heartbeat_interval_ms?: number;
 	  }
 	| {
 			type: 'Leave';
@@ -175,6 +273,13 @@ export type ClientMessage =
 	| {
 			type: 'AdminAction';
 			action: AdminAction;
+	  }
+	| {
+			type: 'ResyncScoreboard';
+	  }
+	| {
+			type: 'Ping';
+			client_time_ms: number;
 	  };
 
 /**
@@ -188,12 +293,38 @@ export type AdminAction =
 	| { type: 'KickPlayer'; player_name: string }
 	| { type: 'EndGame'; reason: string }
 	| { type: 'CloseGame'; reason: string }
-	| { type: 'LockLobby'; locked: boolean };
+	| { type: 'LockLobby'; locked: boolean }
+	| { type: 'UnlockLobby' }
+	| { type: 'RequestUpcoming'; count: number }
+	| { type: 'QueueQuestion'; question_id: number }
+	| { type: 'ReorderUpcoming'; question_ids: number[] }
+	| { type: 'InjectQuestion'; question_text: string; options: GameQuestionOption[] }
+	| { type: 'ApproveReclaim'; player_name: string };
 
 /**
  * Common name validation errors that might be returned by the server or client.
  */
-export type NameValidationError = 'TooShort' | 'TooLong' | 'InvalidCharacters' | 'AlreadyTaken';
+export type NameValidationError =
+	| 'TooShort'
+	| 'TooLong'
+	| 'InvalidCharacters'
+	| 'AlreadyTaken'
+	| 'Reserved'
+	| 'Inappropriate';
+
+/**
+ * Why the server rejected an answer; the player may answer again.
+ */
+export type AnswerValidationError = 'TooLong' | 'InvalidCharacters' | 'NotAnAlternative';
+
+/**
+ * Name length as the server counts it: user-perceived characters, so "Å" is
+ * one whether typed precomposed or with a combining ring.
+ */
+export function nameLength(name: string): number {
+	const segmenter = new Intl.Segmenter(undefined, { granularity: 'grapheme' });
+	return [...segmenter.segment(name.trim())].length;
+}
 
 /**
  * Returns a user-friendly description for a name validation error.
@@ -208,5 +339,9 @@ export function getNameValidationErrorMessage(error: NameValidationError): strin
 			return 'Name can only contain letters, numbers, spaces, and the symbols: _ - .';
 		case 'AlreadyTaken':
 			return 'This name is already taken.';
+		case 'Reserved':
+			return 'This name is reserved.';
+		case 'Inappropriate':
+			return 'This name is not allowed.';
 	}
 }
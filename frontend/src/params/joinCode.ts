@@ -0,0 +1 @@
+export const match = (value: string): boolean => /^[A-Za-z0-9]{4,12}$/.test(value);
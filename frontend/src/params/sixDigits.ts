@@ -1 +0,0 @@
-export const match = (value: string): boolean => /^\d{6}$/.test(value);
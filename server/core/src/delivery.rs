@@ -0,0 +1,342 @@
+//! Delivery of a lobby's updates to its clients. Updates for everyone are
+//! sent once on the lobby's broadcast channel; updates for some clients go to
+//! each client's own channel. A client's [`Inbox`] merges the two back into
+//! the order the engine sent them.
+
+use bytes::Bytes;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::sync::{broadcast, mpsc};
+
+/// Broadcasts a lobby keeps for clients that are behind. A client further
+/// behind than this is disconnected, as one with a full channel is.
+pub const BROADCAST_CAPACITY: usize = 256;
+
+#[derive(Clone, Debug)]
+struct Broadcast {
+    seq: u64,
+    payload: Bytes,
+}
+
+/// What the engine queues on a client's channel.
+#[derive(Debug)]
+pub enum Outgoing {
+    /// An update for this client, to be written after the lobby broadcasts
+    /// up to `after`.
+    Update { after: u64, payload: Bytes },
+    /// Start writing the lobby's broadcasts.
+    Subscribe(Subscription),
+    /// Close the connection once the lobby broadcasts up to `after` are
+    /// written.
+    End { after: u64, reason: EndReason },
+}
+
+/// Why the engine ended a client's connection.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum EndReason {
+    GameClosed,
+    Kicked,
+}
+
+/// For updates that don't come from a lobby, e.g. errors before joining one.
+impl From<Bytes> for Outgoing {
+    fn from(payload: Bytes) -> Self {
+        Outgoing::Update { after: 0, payload }
+    }
+}
+
+#[derive(Debug)]
+pub struct Subscription {
+    rx: broadcast::Receiver<Broadcast>,
+    /// Last broadcast written.
+    seen: u64,
+    /// Last broadcast meant for the client; later ones are skipped.
+    until: Arc<AtomicU64>,
+}
+
+/// A lobby's broadcast channel.
+#[derive(Clone, Debug)]
+pub struct Broadcaster {
+    tx: broadcast::Sender<Broadcast>,
+    seq: u64,
+}
+
+impl Default for Broadcaster {
+    fn default() -> Self {
+        Self {
+            tx: broadcast::channel(BROADCAST_CAPACITY).0,
+            seq: 0,
+        }
+    }
+}
+
+impl Broadcaster {
+    /// Number of broadcasts sent so far.
+    pub fn seq(&self) -> u64 {
+        self.seq
+    }
+
+    pub fn send(&mut self, payload: Bytes) {
+        self.seq += 1;
+        // Fails only while nobody is connected.
+        let _ = self.tx.send(Broadcast {
+            seq: self.seq,
+            payload,
+        });
+    }
+
+    /// Connects `tx` to the lobby: it receives every broadcast from now on
+    /// until closed. Returns `None` if `tx` is already closed or full.
+    pub fn connect(&self, tx: mpsc::Sender<Outgoing>) -> Option<ClientTx> {
+        let until = Arc::new(AtomicU64::new(u64::MAX));
+        let subscription = Subscription {
+            rx: self.tx.subscribe(),
+            seen: self.seq,
+            until: until.clone(),
+        };
+        tx.try_send(Outgoing::Subscribe(subscription)).ok()?;
+        Some(ClientTx { tx, until })
+    }
+}
+
+/// The engine's end of a connected client's channel.
+#[derive(Clone, Debug)]
+pub struct ClientTx {
+    tx: mpsc::Sender<Outgoing>,
+    until: Arc<AtomicU64>,
+}
+
+impl ClientTx {
+    /// Queues `payload` after the broadcasts sent so far.
+    pub fn try_send(
+        &self,
+        broadcaster: &Broadcaster,
+        payload: Bytes,
+    ) -> Result<(), TrySendError<Outgoing>> {
+        self.tx.try_send(Outgoing::Update {
+            after: broadcaster.seq(),
+            payload,
+        })
+    }
+
+    pub fn is_closed(&self) -> bool {
+        self.tx.is_closed()
+    }
+
+    /// Stops the client's broadcasts after the ones sent so far, when the
+    /// client leaves the lobby or the connection is replaced.
+    pub fn close(&self, broadcaster: &Broadcaster) {
+        self.until.store(broadcaster.seq(), Ordering::Relaxed);
+    }
+
+    /// Closes the client's broadcasts and has its connection closed once
+    /// everything sent so far is written.
+    pub fn end(&self, broadcaster: &Broadcaster, reason: EndReason) {
+        self.close(broadcaster);
+        let _ = self.tx.try_send(Outgoing::End {
+            after: broadcaster.seq(),
+            reason,
+        });
+    }
+}
+
+/// The client's end: its own channel merged with the lobby's broadcasts.
+pub struct Inbox {
+    rx: mpsc::Receiver<Outgoing>,
+    feed: Option<Subscription>,
+    /// The next update from `rx`, waiting for the broadcasts sent before it.
+    update: Option<(u64, Bytes)>,
+    /// The next broadcast, waiting for the updates sent before it.
+    broadcast: Option<Broadcast>,
+    /// The end of the connection, waiting for the broadcasts sent before it.
+    end: Option<(u64, EndReason)>,
+    ended: Option<EndReason>,
+    closed: bool,
+}
+
+impl Inbox {
+    pub fn new(rx: mpsc::Receiver<Outgoing>) -> Self {
+        Self {
+            rx,
+            feed: None,
+            update: None,
+            broadcast: None,
+            end: None,
+            ended: None,
+            closed: false,
+        }
+    }
+
+    /// Why the engine ended the connection, once [`Inbox::recv`] returned
+    /// `None` for it.
+    pub fn ended(&self) -> Option<EndReason> {
+        self.ended
+    }
+
+    /// The next update to write, or `None` once the client's channel is
+    /// closed or it fell too far behind the lobby's broadcasts. Cancel safe.
+    pub async fn recv(&mut self) -> Option<Bytes> {
+        loop {
+            // A broadcast that was received is visible only after every
+            // update sent before it, so this finds those.
+            if self.update.is_none()
+                && self.end.is_none()
+                && let Ok(outgoing) = self.rx.try_recv()
+            {
+                self.queue(outgoing);
+                continue;
+            }
+            if let Some((after, _)) = self.update {
+                let seen = self.feed.as_ref().map_or(u64::MAX, |feed| feed.seen);
+                if after <= seen {
+                    return self.update.take().map(|(_, payload)| payload);
+                }
+            }
+            if let Some(broadcast) = self.broadcast.take() {
+                let Some(feed) = self.feed.as_mut() else {
+                    continue;
+                };
+                if broadcast.seq > feed.until.load(Ordering::Relaxed) {
+                    self.feed = None;
+                    continue;
+                }
+                feed.seen = broadcast.seq;
+                return Some(broadcast.payload);
+            }
+            if self
+                .feed
+                .as_ref()
+                .is_some_and(|feed| feed.seen >= feed.until.load(Ordering::Relaxed))
+            {
+                self.feed = None;
+            }
+            if let Some((after, reason)) = self.end
+                && self.update.is_none()
+                && self.feed.as_ref().is_none_or(|feed| feed.seen >= after)
+            {
+                self.end = None;
+                self.ended = Some(reason);
+                self.close();
+                return None;
+            }
+            if self.closed && self.feed.is_none() && self.update.is_none() {
+                return None;
+            }
+            tokio::select! {
+                outgoing = self.rx.recv(), if self.update.is_none() && self.end.is_none() && !self.closed => match outgoing {
+                    Some(outgoing) => self.queue(outgoing),
+                    // The engine dropped the client; what it was sent is still written.
+                    None => self.closed = true,
+                },
+                received = next_broadcast(&mut self.feed) => match received {
+                    Ok(broadcast) => self.broadcast = Some(broadcast),
+                    Err(RecvError::Lagged(_)) => return None,
+                    // The lobby is gone.
+                    Err(RecvError::Closed) => self.feed = None,
+                },
+            }
+        }
+    }
+
+    /// Closes the client's channel and drops its broadcasts.
+    pub fn close(&mut self) {
+        self.rx.close();
+        self.closed = true;
+        self.feed = None;
+        self.broadcast = None;
+    }
+
+    fn queue(&mut self, outgoing: Outgoing) {
+        match outgoing {
+            Outgoing::Subscribe(feed) => {
+                self.feed = Some(feed);
+                self.broadcast = None;
+            }
+            Outgoing::Update { after, payload } => self.update = Some((after, payload)),
+            Outgoing::End { after, reason } => self.end = Some((after, reason)),
+        }
+    }
+}
+
+async fn next_broadcast(feed: &mut Option<Subscription>) -> Result<Broadcast, RecvError> {
+    match feed {
+        Some(feed) => feed.rx.recv().await,
+        None => std::future::pending().await,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(payload: Bytes) -> String {
+        String::from_utf8(payload.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_updates_keep_send_order() {
+        let mut broadcaster = Broadcaster::default();
+        broadcaster.send(Bytes::from("before connecting"));
+        let (tx, rx) = mpsc::channel(8);
+        let mut inbox = Inbox::new(rx);
+        let client = broadcaster.connect(tx).unwrap();
+
+        broadcaster.send(Bytes::from("b1"));
+        client.try_send(&broadcaster, Bytes::from("d1")).unwrap();
+        broadcaster.send(Bytes::from("b2"));
+        broadcaster.send(Bytes::from("b3"));
+        client.try_send(&broadcaster, Bytes::from("d2")).unwrap();
+        client.close(&broadcaster);
+        broadcaster.send(Bytes::from("after leaving"));
+
+        let mut received = Vec::new();
+        while let Ok(Some(payload)) =
+            tokio::time::timeout(std::time::Duration::from_millis(20), inbox.recv()).await
+        {
+            received.push(text(payload));
+        }
+        assert_eq!(received, ["b1", "d1", "b2", "b3", "d2"]);
+    }
+
+    #[tokio::test]
+    async fn test_end_follows_earlier_updates() {
+        let mut broadcaster = Broadcaster::default();
+        let (tx, rx) = mpsc::channel(8);
+        let mut inbox = Inbox::new(rx);
+        let client = broadcaster.connect(tx).unwrap();
+
+        client.try_send(&broadcaster, Bytes::from("d1")).unwrap();
+        broadcaster.send(Bytes::from("closed"));
+        client.end(&broadcaster, EndReason::GameClosed);
+        broadcaster.send(Bytes::from("after ending"));
+
+        assert_eq!(inbox.recv().await.map(text).as_deref(), Some("d1"));
+        assert_eq!(inbox.recv().await.map(text).as_deref(), Some("closed"));
+        assert_eq!(inbox.ended(), None);
+        assert_eq!(inbox.recv().await, None);
+        assert_eq!(inbox.ended(), Some(EndReason::GameClosed));
+    }
+
+    #[tokio::test]
+    async fn test_lagging_client_is_dropped() {
+        let mut broadcaster = Broadcaster::default();
+        let (tx, rx) = mpsc::channel(8);
+        let mut inbox = Inbox::new(rx);
+        let _client = broadcaster.connect(tx).unwrap();
+
+        for _ in 0..=BROADCAST_CAPACITY {
+            broadcaster.send(Bytes::from("flood"));
+        }
+        assert_eq!(inbox.recv().await, None);
+    }
+
+    #[test]
+    fn test_connect_fails_on_closed_channel() {
+        let broadcaster = Broadcaster::default();
+        let (tx, rx) = mpsc::channel(8);
+        drop(rx);
+        assert!(broadcaster.connect(tx).is_none());
+    }
+}
@@ -0,0 +1,5993 @@
+use crate::delivery::{Broadcaster, ClientTx, EndReason, Outgoing};
+use crate::locale::Locale;
+use crate::names;
+use crate::question::{
+    Color, ColorInfo, GameQuestion, GameQuestionOption, QuestionSet, QuestionType, normalize_answer,
+};
+use crate::time::{Instant, SystemTime};
+use crate::uuid::Uuid;
+use bytes::Bytes;
+use chrono::{SecondsFormat, Utc};
+use lazy_static::lazy_static;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::any::Any;
+use std::collections::{HashMap, HashSet};
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::Sender;
+use tokio::sync::mpsc::error::TrySendError;
+use tracing::{debug, error, info, instrument, warn};
+use unicode_normalization::UnicodeNormalization;
+use unicode_segmentation::UnicodeSegmentation;
+
+lazy_static! {
+    pub(crate) static ref NAME_VALIDATION_REGEX: Regex =
+        Regex::new(r"^[\p{L}\p{M}\p{N}_\-\. ]+$").expect("Failed to compile player name regex");
+}
+
+/// Player name length in user-perceived characters (grapheme clusters).
+pub const MIN_NAME_GRAPHEMES: usize = 2;
+pub const MAX_NAME_GRAPHEMES: usize = 16;
+/// Limits the code points behind those, e.g. letters stacked with combining
+/// marks.
+const MAX_NAME_CHARS: usize = 32;
+
+#[derive(Debug)]
+pub enum NameValidationError {
+    TooShort,
+    TooLong,
+    InvalidCharacters,
+    AlreadyTaken,
+    Reserved,
+    Inappropriate,
+}
+
+impl NameValidationError {
+    fn to_message(&self) -> &'static str {
+        match self {
+            Self::TooShort => "Name must be at least 2 characters long.",
+            Self::TooLong => "Name cannot be longer than 16 characters.",
+            Self::InvalidCharacters => {
+                "Name can only contain letters, numbers, spaces, and the symbols: _ - ."
+            }
+            Self::AlreadyTaken => "This name is already taken.",
+            Self::Reserved => "This name is reserved.",
+            Self::Inappropriate => "This name is not allowed.",
+        }
+    }
+}
+
+impl std::fmt::Display for NameValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_message())
+    }
+}
+
+/// Answers longer than any alternative could be are rejected before they're
+/// compared.
+const MAX_ANSWER_CHARS: usize = 200;
+
+/// Result of [`GameEngine::reclaim_player`].
+#[derive(Debug, PartialEq)]
+pub enum Reclaim {
+    /// Join as this player.
+    Player(Uuid),
+    /// The admin was asked to approve; try again once they have.
+    AwaitingApproval,
+}
+
+/// Why an answer was rejected, sent to the player in
+/// [`GameUpdate::AnswerRejected`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AnswerValidationError {
+    TooLong,
+    InvalidCharacters,
+    NotAnAlternative,
+}
+
+impl AnswerValidationError {
+    fn to_message(self) -> &'static str {
+        match self {
+            Self::TooLong => "Answer is too long.",
+            Self::InvalidCharacters => "Answer contains invalid characters.",
+            Self::NotAnAlternative => "Answer is not one of the alternatives.",
+        }
+    }
+}
+
+impl std::fmt::Display for AnswerValidationError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}", self.to_message())
+    }
+}
+
+/// The alternative `answer` picks, compared as [`normalize_answer`] does.
+pub fn validate_answer(
+    answer: &str,
+    alternatives: &[Arc<str>],
+) -> Result<Arc<str>, AnswerValidationError> {
+    if answer.chars().count() > MAX_ANSWER_CHARS {
+        return Err(AnswerValidationError::TooLong);
+    }
+    if answer.chars().any(char::is_control) {
+        return Err(AnswerValidationError::InvalidCharacters);
+    }
+    let normalized = normalize_answer(answer);
+    alternatives
+        .iter()
+        .find(|a| normalize_answer(a) == normalized)
+        .cloned()
+        .ok_or(AnswerValidationError::NotAnAlternative)
+}
+
+/// The form names are stored and compared in: trimmed and NFC normalized,
+/// so "Å" typed as "A" plus a combining ring matches a precomposed "Å".
+pub fn normalize_player_name(name: &str) -> String {
+    name.trim().nfc().collect()
+}
+
+pub fn validate_player_name<'a>(
+    name: &str,
+    mut existing_names: impl Iterator<Item = &'a str>,
+) -> Result<(), NameValidationError> {
+    let name = normalize_player_name(name);
+    let name = name.as_str();
+
+    let grapheme_count = name.graphemes(true).count();
+    if grapheme_count < MIN_NAME_GRAPHEMES {
+        return Err(NameValidationError::TooShort);
+    }
+    if grapheme_count > MAX_NAME_GRAPHEMES || name.chars().count() > MAX_NAME_CHARS {
+        return Err(NameValidationError::TooLong);
+    }
+
+    if !NAME_VALIDATION_REGEX.is_match(name) {
+        return Err(NameValidationError::InvalidCharacters);
+    }
+
+    if names::is_reserved(name) {
+        return Err(NameValidationError::Reserved);
+    }
+    if names::contains_banned_word(name, &[]) {
+        return Err(NameValidationError::Inappropriate);
+    }
+
+    if existing_names.any(|existing_name| normalize_player_name(existing_name) == name) {
+        return Err(NameValidationError::AlreadyTaken);
+    }
+
+    Ok(())
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum GamePhase {
+    Lobby,
+    Score,
+    Question,
+    GameOver,
+    GameClosed,
+}
+
+/// How often a client sends a heartbeat, and how many in a row it may miss
+/// before the server closes the connection as dead.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Heartbeat {
+    pub interval_ms: u64,
+    pub max_missed: u32,
+}
+
+impl Default for Heartbeat {
+    fn default() -> Self {
+        Self {
+            interval_ms: 30_000,
+            max_missed: 2,
+        }
+    }
+}
+
+impl Heartbeat {
+    /// How long the connection may stay silent.
+    pub fn timeout(&self) -> Duration {
+        Duration::from_millis(self.interval_ms) * (self.max_missed + 1)
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct AdminExtraInfo {
+    pub upcoming_questions: Vec<GameQuestion>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+#[serde(tag = "type")]
+pub enum GameUpdate {
+    /// A lightweight acknowledgement of connection.
+    Connected {
+        player_id: Uuid,
+        name: Arc<str>,
+        round_duration: u64,
+        /// Keepalive agreed on for this connection.
+        heartbeat: Heartbeat,
+        /// Identifies the connection in the server's logs, for bug reports.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        connection_id: Option<Uuid>,
+    },
+    /// A partial (delta) state update.
+    /// All fields are optional; absent fields (None) are omitted from the JSON so
+    /// the client can distinguish "not updated" from an explicit null.
+    StateDelta {
+        #[serde(skip_serializing_if = "Option::is_none")]
+        phase: Option<GamePhase>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        question_type: Option<Arc<str>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        question_text: Option<Arc<str>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        alternatives: Option<Vec<Arc<str>>>,
+        /// Swatch details for each alternative of a color question, in the
+        /// same order as `alternatives`.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        colors: Option<Vec<ColorInfo>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        question_time_remaining_ms: Option<u64>,
+        /// Server clock when `question_time_remaining_ms` was measured, in
+        /// ms since the Unix epoch, so a client can correct for its delay.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        server_time_ms: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        answered_player_names: Option<Vec<Arc<str>>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        scoreboard: Option<Vec<(Arc<str>, i32)>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        round_scores: Option<Vec<(Arc<str>, i32)>>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        consecutive_misses: Option<Vec<(Arc<str>, u32)>>,
+        /// Players whose scores changed since the last score update, sent in
+        /// place of the three full lists above.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        score_changes: Option<Vec<ScoreChange>>,
+        /// Numbers the score updates. A client that sees one skipped sends
+        /// `ResyncScoreboard` to get the full lists again.
+        #[serde(skip_serializing_if = "Option::is_none")]
+        scoreboard_seq: Option<u64>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        admin_extra: Option<AdminExtraInfo>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        lobby_locked: Option<bool>,
+    },
+    PlayerLeft {
+        name: Arc<str>,
+    },
+    PlayerKicked {
+        reason: Arc<str>,
+    },
+    Answered {
+        name: Arc<str>,
+        score: i32,
+    },
+    /// Sent to a player whose answer was malformed or not one of the
+    /// alternatives. The player may answer again.
+    AnswerRejected {
+        reason: AnswerValidationError,
+        message: Arc<str>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        client_msg_id: Option<Arc<str>>,
+    },
+    /// Sent to the answering player once the answer counts, and again if it
+    /// resends the answer with the same `client_msg_id`.
+    AnswerAck {
+        score: i32,
+        /// Echo of the answer's `client_msg_id`, so the client can match the
+        /// acknowledgement to its request.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        client_msg_id: Option<Arc<str>>,
+    },
+    GameOver {
+        final_scores: Vec<(Arc<str>, i32)>,
+        reason: Arc<str>,
+    },
+    GameClosed {
+        reason: Arc<str>,
+    },
+    Error {
+        message: Arc<str>,
+        /// Echo of the `client_msg_id` of the rejected message, if it had one.
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        client_msg_id: Option<Arc<str>>,
+    },
+    AdminInfo {
+        current_question: GameQuestion,
+    },
+    AdminNextQuestions {
+        upcoming_questions: Vec<GameQuestion>,
+    },
+    /// The next question's track, sent to the admin when the score phase
+    /// begins so the host's player can buffer it before the round starts.
+    PrefetchNext {
+        youtube_id: Arc<str>,
+        /// Where in the track the round starts playing.
+        start_seconds: u32,
+    },
+    /// How each player answered the round that just ended, fastest first.
+    AdminRoundSummary {
+        answers: Vec<AnswerTiming>,
+    },
+    /// Someone tried to rejoin as the disconnected player `name`; the admin
+    /// may let them with `ApproveReclaim`.
+    ReclaimRequested {
+        name: Arc<str>,
+    },
+    /// The round in progress was cut short by a server restart. Sent once
+    /// to each client when it reconnects, before the next round starts.
+    RoundInterrupted,
+    /// The lobby closes in `closes_in_secs` unless someone does something.
+    InactivityWarning {
+        closes_in_secs: u64,
+    },
+    /// Reply to a client's ping, for estimating round trip time and clock
+    /// offset.
+    Pong {
+        client_time_ms: u64,
+        server_time_ms: u64,
+    },
+}
+
+/// Milliseconds since the Unix epoch, as sent in `server_time_ms`.
+pub fn unix_time_ms() -> u64 {
+    SystemTime::now()
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_millis() as u64
+}
+
+/// What `unix_time_ms` returned at `at`.
+fn unix_time_ms_at(at: Instant) -> u64 {
+    unix_time_ms().saturating_sub(at.elapsed().as_millis() as u64)
+}
+
+/// Points for a correct answer given `elapsed` into a round of
+/// `round_duration` seconds: 5000 right away, falling linearly to 0 at the
+/// end of the round.
+pub fn answer_score(elapsed: Duration, round_duration: u64) -> i32 {
+    let round_duration = round_duration as f64;
+    ((5000.0 * (round_duration - elapsed.as_secs_f64()) / round_duration).clamp(0.0, 5000.0)) as i32
+}
+
+/// One player's answer in an `AdminRoundSummary`.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct AnswerTiming {
+    pub name: Arc<str>,
+    /// `None` if the player did not answer.
+    pub answer: Option<Arc<str>>,
+    pub correct: bool,
+    /// Time from the start of the round to the answer.
+    pub elapsed_ms: Option<u64>,
+}
+
+/// A player's scores after they changed.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct ScoreChange {
+    pub name: Arc<str>,
+    pub score: i32,
+    pub round_score: i32,
+    pub consecutive_misses: u32,
+}
+
+/// Every this many score updates carries the full lists instead of changes,
+/// so a client that applied a change wrongly doesn't stay wrong.
+const FULL_SCOREBOARD_INTERVAL: u64 = 16;
+
+/// The score fields of a `StateDelta`.
+#[derive(Default)]
+struct ScoreUpdate {
+    scoreboard: Option<Vec<(Arc<str>, i32)>>,
+    round_scores: Option<Vec<(Arc<str>, i32)>>,
+    consecutive_misses: Option<Vec<(Arc<str>, u32)>>,
+    score_changes: Option<Vec<ScoreChange>>,
+    scoreboard_seq: Option<u64>,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[allow(dead_code)]
+pub enum Recipients {
+    Single(Uuid),
+    Multiple(Vec<Uuid>),
+    _AllExcept(Vec<Uuid>),
+    All,
+}
+
+#[derive(Clone, Debug)]
+pub struct EventContext {
+    pub sender_id: Uuid,
+    pub timestamp: Instant,
+}
+
+#[derive(Clone, Debug, Serialize)]
+#[serde(tag = "type")]
+pub enum GameAction {
+    Connect,
+    Leave,
+    Answer {
+        answer: String,
+        client_msg_id: Option<Arc<str>>,
+    },
+    StartGame,
+    StartRound,
+    EndRound,
+    SkipQuestion,
+    KickPlayer {
+        player_name: Arc<str>,
+    },
+    EndGame {
+        reason: Arc<str>,
+    },
+    CloseGame {
+        reason: Arc<str>,
+    },
+    LockLobby {
+        locked: bool,
+    },
+    /// Sets how many upcoming questions the admin is shown.
+    RequestUpcoming {
+        count: usize,
+    },
+    /// Moves a question to the front of the upcoming ones.
+    QueueQuestion {
+        question_id: i64,
+    },
+    /// Plays these upcoming questions next, in this order.
+    ReorderUpcoming {
+        question_ids: Vec<i64>,
+    },
+    /// Adds a question written by the admin and plays it next.
+    InjectQuestion {
+        question_text: Arc<str>,
+        options: Vec<GameQuestionOption>,
+    },
+    /// Lets the next player joining as `player_name` take over that
+    /// disconnected player, when reclaims need the admin's approval.
+    ApproveReclaim {
+        player_name: Arc<str>,
+    },
+    ResyncScoreboard,
+}
+
+impl GameAction {
+    /// Returns the variant name without any payload data (safe for logging)
+    pub fn kind(&self) -> &'static str {
+        match self {
+            GameAction::Connect => "Connect",
+            GameAction::Leave => "Leave",
+            GameAction::Answer { .. } => "Answer",
+            GameAction::StartGame => "StartGame",
+            GameAction::StartRound => "StartRound",
+            GameAction::EndRound => "EndRound",
+            GameAction::SkipQuestion => "SkipQuestion",
+            GameAction::KickPlayer { .. } => "KickPlayer",
+            GameAction::EndGame { .. } => "EndGame",
+            GameAction::CloseGame { .. } => "CloseGame",
+            GameAction::LockLobby { .. } => "LockLobby",
+            GameAction::RequestUpcoming { .. } => "RequestUpcoming",
+            GameAction::QueueQuestion { .. } => "QueueQuestion",
+            GameAction::ReorderUpcoming { .. } => "ReorderUpcoming",
+            GameAction::InjectQuestion { .. } => "InjectQuestion",
+            GameAction::ApproveReclaim { .. } => "ApproveReclaim",
+            GameAction::ResyncScoreboard => "ResyncScoreboard",
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct GameEvent {
+    pub context: EventContext,
+    pub action: GameAction,
+}
+
+#[derive(Clone, Debug)]
+pub struct GameState {
+    pub phase: GamePhase,
+    pub players: HashMap<Uuid, PlayerState>,
+    pub admin_id: Uuid,
+    pub admin: AdminConnection,
+    pub join_code: Arc<str>,
+    pub round_start_time: Option<Instant>,
+    pub round_duration: u64,
+    pub current_alternatives: Vec<Arc<str>>,
+    pub correct_answers: Option<Vec<Arc<str>>>,
+    pub current_question: Option<GameQuestion>,
+    pub all_questions: Arc<Vec<GameQuestion>>,
+    pub color_weights: [f64; Color::COUNT],
+    pub shuffled_question_indices: Vec<usize>,
+    pub current_question_index: usize,
+    pub created_at: Instant,
+    pub last_lobby_message: Option<Instant>,
+    /// Whether the players were warned that the lobby is about to close for
+    /// inactivity since the last activity.
+    pub inactivity_warned: bool,
+    /// Clients still to be told that a restart cut the round short.
+    pub round_interrupted_for: HashSet<Uuid>,
+    pub locked: bool,
+    /// Display name for lobbies that opted into the public lobby list.
+    pub public_name: Option<Arc<str>>,
+    /// Named question dataset the lobby plays, or `None` for the default one.
+    pub dataset: Option<Arc<str>>,
+    /// Question set the lobby plays, or `None` for all questions.
+    pub set_name: Option<Arc<str>>,
+    /// Record of the game being played.
+    pub game_record: Option<GameRecord>,
+    /// Record of the game that just ended, until taken to be saved.
+    pub finished_game: Option<GameRecord>,
+    /// Record of the last game that ended, kept for the results export.
+    pub last_game: Option<GameRecord>,
+    pub lifetime: LobbyLifetime,
+    pub event_log: Option<EventLog>,
+    pub broadcaster: Broadcaster,
+    /// Score updates sent so far; see `StateDelta::scoreboard_seq`.
+    pub scoreboard_seq: u64,
+    /// Upcoming questions shown to the admin.
+    pub upcoming_preview: usize,
+    /// Show each player the alternatives in their own order, so nobody can
+    /// shout out which position is right.
+    pub shuffle_per_player: bool,
+    /// Joining as a disconnected player doesn't need the admin's approval.
+    pub open_reclaims: bool,
+}
+
+#[derive(Clone, Debug, Serialize)]
+pub struct PlayerState {
+    pub name: Arc<str>,
+    pub score: i32,
+    pub round_score: i32,
+    pub has_answered: bool,
+    pub answer: Option<Arc<str>>,
+    /// Time from the start of the round to this round's answer.
+    pub answer_ms: Option<u64>,
+    pub consecutive_misses: u32,
+    pub answers_given: u32,
+    pub correct_answers: u32,
+    /// Number of WebSocket connections opened, including reconnects.
+    pub connections: u32,
+    /// The player's account, if they joined logged in.
+    pub account_id: Option<Uuid>,
+    #[serde(skip)]
+    pub tx: Option<ClientTx>,
+    #[serde(skip)]
+    pub connection_id: Option<Uuid>,
+    /// `client_msg_id` of this round's answer, to recognize a resend.
+    #[serde(skip)]
+    pub answer_msg_id: Option<Arc<str>>,
+    /// Scores as of the last score update, or `None` if never sent.
+    #[serde(skip)]
+    pub reported: Option<(i32, i32, u32)>,
+    /// Language of the errors sent to the player.
+    #[serde(skip)]
+    pub locale: Locale,
+    #[serde(skip)]
+    pub heartbeat: Heartbeat,
+    /// This round's alternatives in the order shown to the player, when
+    /// they are shuffled per player.
+    #[serde(skip)]
+    pub alternatives: Option<Vec<Arc<str>>>,
+    #[serde(skip)]
+    pub throttle: ActionThrottle,
+    /// The admin let the next player joining under this name take over.
+    #[serde(skip)]
+    pub reclaim_approved: bool,
+    /// The admin was asked to approve a reclaim of this player and hasn't
+    /// yet, so retries don't ask again.
+    #[serde(skip)]
+    pub reclaim_requested: bool,
+}
+
+/// A player with this many actions rejected within
+/// [`THROTTLE_WINDOW`] has their further actions dropped until it ends.
+const MAX_REJECTED_ACTIONS: u32 = 10;
+const THROTTLE_WINDOW: Duration = Duration::from_secs(10);
+
+/// Rejected actions of a player in the current throttle window, so a client
+/// spamming invalid actions costs the lobby little more than the lookup.
+#[derive(Clone, Debug, Default)]
+pub struct ActionThrottle {
+    window_start: Option<Instant>,
+    rejected: u32,
+}
+
+impl ActionThrottle {
+    /// Whether an action at `now` should be dropped, starting a new window
+    /// if the last one is over.
+    fn is_throttled(&mut self, now: Instant) -> bool {
+        match self.window_start {
+            Some(start) if now.saturating_duration_since(start) < THROTTLE_WINDOW => {}
+            _ => {
+                self.window_start = Some(now);
+                self.rejected = 0;
+            }
+        }
+        self.rejected >= MAX_REJECTED_ACTIONS
+    }
+}
+
+impl PlayerState {
+    pub fn new(name: Arc<str>) -> Self {
+        Self {
+            name,
+            score: 0,
+            round_score: 0,
+            has_answered: false,
+            answer: None,
+            answer_ms: None,
+            consecutive_misses: 0,
+            answers_given: 0,
+            correct_answers: 0,
+            connections: 0,
+            account_id: None,
+            tx: None,
+            connection_id: None,
+            answer_msg_id: None,
+            reported: None,
+            locale: Locale::default(),
+            heartbeat: Heartbeat::default(),
+            alternatives: None,
+            throttle: ActionThrottle::default(),
+            reclaim_approved: false,
+            reclaim_requested: false,
+        }
+    }
+
+    fn scores(&self) -> (i32, i32, u32) {
+        (self.score, self.round_score, self.consecutive_misses)
+    }
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct PlayerStats {
+    pub name: Arc<str>,
+    pub score: i32,
+    pub answers_given: u32,
+    pub correct_answers: u32,
+    pub connected: bool,
+    pub connections: u32,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct LobbyStats {
+    pub phase: GamePhase,
+    pub questions_played: usize,
+    pub total_answers: u32,
+    pub admin_connected: bool,
+    /// Sorted by score, highest first.
+    pub players: Vec<PlayerStats>,
+}
+
+/// Counters kept over a lobby's whole life, summarized when it is removed.
+/// Persisted with the lobby so restarts don't reset them.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct LobbyLifetime {
+    pub created: SystemTime,
+    pub peak_players: usize,
+    /// Rounds started, skipped questions excluded.
+    pub rounds_played: u32,
+    /// Game updates queued for the lobby's connections.
+    pub messages_sent: u64,
+    /// Player and admin connections that ended.
+    pub disconnects: u32,
+}
+
+impl LobbyLifetime {
+    fn new() -> Self {
+        Self {
+            created: SystemTime::now(),
+            peak_players: 0,
+            rounds_played: 0,
+            messages_sent: 0,
+            disconnects: 0,
+        }
+    }
+}
+
+/// Logged and sent as a webhook event when a finished lobby is removed.
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct LobbySummary {
+    pub duration_secs: u64,
+    pub peak_players: usize,
+    pub rounds_played: u32,
+    pub messages_sent: u64,
+    pub disconnects: u32,
+}
+
+/// A lobby without activity for this long is closed.
+pub const LOBBY_INACTIVITY_TIMEOUT: Duration = Duration::from_secs(3600);
+/// How long before closing an inactive lobby its players are warned.
+pub const INACTIVITY_WARNING_BEFORE: Duration = Duration::from_secs(5 * 60);
+
+/// Upcoming questions shown to the admin unless the lobby asks for more.
+pub const DEFAULT_UPCOMING_PREVIEW: usize = 3;
+pub const MAX_UPCOMING_PREVIEW: usize = 50;
+
+/// Limits on questions the admin writes during a game.
+const MAX_INJECTED_TEXT_CHARS: usize = 200;
+const MAX_INJECTED_OPTION_CHARS: usize = 100;
+const MAX_INJECTED_OPTIONS: usize = 8;
+
+/// Checks a question written by the admin, returning why it can't be asked.
+fn validate_injected_question(
+    question_text: &str,
+    options: &[GameQuestionOption],
+) -> Result<(), String> {
+    let text = question_text.trim();
+    if text.is_empty() || text.chars().count() > MAX_INJECTED_TEXT_CHARS {
+        return Err(format!(
+            "Question text must be 1-{MAX_INJECTED_TEXT_CHARS} characters."
+        ));
+    }
+    if !(2..=MAX_INJECTED_OPTIONS).contains(&options.len()) {
+        return Err(format!(
+            "A question needs 2-{MAX_INJECTED_OPTIONS} options."
+        ));
+    }
+    let mut seen = Vec::with_capacity(options.len());
+    for opt in options {
+        let option = opt.option.trim();
+        if option.is_empty() || option.chars().count() > MAX_INJECTED_OPTION_CHARS {
+            return Err(format!(
+                "Options must be 1-{MAX_INJECTED_OPTION_CHARS} characters."
+            ));
+        }
+        let normalized = normalize_answer(option);
+        if seen.contains(&normalized) {
+            return Err(format!("Option \"{option}\" is listed twice."));
+        }
+        seen.push(normalized);
+    }
+    if !options.iter().any(|opt| opt.is_correct) {
+        return Err("At least one option must be correct.".to_string());
+    }
+    Ok(())
+}
+
+/// Compact record of one game, kept as the server's game history.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct GameRecord {
+    /// Start time and join code, e.g. `20250301193000123-ABC123`. Sorts
+    /// chronologically.
+    pub id: String,
+    pub join_code: Arc<str>,
+    pub set_name: Option<Arc<str>>,
+    pub dataset: Option<Arc<str>>,
+    /// RFC 3339.
+    pub started_at: String,
+    /// RFC 3339, or `None` while the game is running.
+    pub ended_at: Option<String>,
+    pub end_reason: Option<Arc<str>>,
+    /// Rounds played, skipped questions excluded.
+    pub rounds: Vec<RoundRecord>,
+    /// Sorted by score, highest first. Empty while the game is running.
+    pub final_scores: Vec<PlayerScore>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct RoundRecord {
+    pub question_id: i64,
+    pub title: Arc<str>,
+    pub correct_answers: Vec<Arc<str>>,
+    /// Sorted by name.
+    pub results: Vec<RoundResult>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct RoundResult {
+    pub name: Arc<str>,
+    /// `None` if the player did not answer.
+    pub answer: Option<Arc<str>>,
+    pub round_score: i32,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PlayerScore {
+    pub name: Arc<str>,
+    pub score: i32,
+}
+
+impl GameRecord {
+    fn new(state: &GameState) -> Self {
+        let now = Utc::now();
+        Self {
+            id: format!("{}-{}", now.format("%Y%m%d%H%M%S%3f"), state.join_code),
+            join_code: state.join_code.clone(),
+            set_name: state.set_name.clone(),
+            dataset: state.dataset.clone(),
+            started_at: now.to_rfc3339_opts(SecondsFormat::Secs, true),
+            ended_at: None,
+            end_reason: None,
+            rounds: Vec::new(),
+            final_scores: Vec::new(),
+        }
+    }
+}
+
+/// Recorded events beyond this many bytes are dropped, bounding the memory
+/// a long-running lobby's log takes.
+const MAX_EVENT_LOG_BYTES: usize = 4 * 1024 * 1024;
+
+/// Opt-in record of every event a lobby processed and the phase it left the
+/// lobby in, as JSON lines, for settling disputes after the game.
+#[derive(Clone, Debug)]
+pub struct EventLog {
+    /// Storage name. Each recorder gets its own, so a lobby restored after a
+    /// restart starts a new log instead of overwriting the earlier one.
+    name: String,
+    jsonl: String,
+    /// Whether events were recorded since the log was last taken.
+    dirty: bool,
+    full: bool,
+}
+
+#[derive(Serialize)]
+struct RecordedEvent<'a> {
+    at: String,
+    sender_id: Uuid,
+    action: &'a GameAction,
+    /// Time since the round started, for events during a question.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    round_elapsed_ms: Option<u64>,
+    phase_before: GamePhase,
+    phase_after: GamePhase,
+}
+
+impl EventLog {
+    fn new(join_code: &str) -> Self {
+        Self {
+            name: format!("{join_code}-{}", Utc::now().format("%Y%m%d%H%M%S%3f")),
+            jsonl: String::new(),
+            dirty: false,
+            full: false,
+        }
+    }
+
+    fn record(&mut self, event: &RecordedEvent) {
+        if self.full {
+            return;
+        }
+        let line = match serde_json::to_string(event) {
+            Ok(line) => line,
+            Err(e) => {
+                error!("Failed to serialize recorded event: {}", e);
+                return;
+            }
+        };
+        if self.jsonl.len() + line.len() + 1 > MAX_EVENT_LOG_BYTES {
+            warn!(log = %self.name, "Event log is full, no longer recording events");
+            self.full = true;
+            return;
+        }
+        self.jsonl.push_str(&line);
+        self.jsonl.push('\n');
+        self.dirty = true;
+    }
+}
+
+/// Persisted subset of a player's state. Connections are not persisted;
+/// players reconnect with their existing session token.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct PlayerSnapshot {
+    pub id: Uuid,
+    pub name: Arc<str>,
+    pub score: i32,
+    pub consecutive_misses: u32,
+    pub answers_given: u32,
+    pub correct_answers: u32,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub account_id: Option<Uuid>,
+}
+
+/// Essential lobby state persisted across server restarts.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq)]
+pub struct LobbySnapshot {
+    pub join_code: Arc<str>,
+    pub admin_id: Uuid,
+    pub phase: GamePhase,
+    pub round_duration: u64,
+    pub players: Vec<PlayerSnapshot>,
+    /// Question ids in play order. Ids are stored instead of indices so the
+    /// order survives question data changes between restarts.
+    pub question_ids: Vec<i64>,
+    pub current_question_index: usize,
+    pub locked: bool,
+    pub public_name: Option<Arc<str>>,
+    #[serde(default)]
+    pub dataset: Option<Arc<str>>,
+    /// `None` in snapshots saved before lifetimes were tracked.
+    #[serde(default)]
+    pub lifetime: Option<LobbyLifetime>,
+    #[serde(default)]
+    pub set_name: Option<Arc<str>>,
+    /// Record of the game in progress, so a restart doesn't lose its rounds.
+    #[serde(default)]
+    pub game_record: Option<GameRecord>,
+    #[serde(default)]
+    pub upcoming_preview: Option<usize>,
+    #[serde(default)]
+    pub shuffle_per_player: bool,
+    #[serde(default)]
+    pub open_reclaims: bool,
+    /// Questions the admin injected, which exist only in this lobby.
+    #[serde(default)]
+    pub injected_questions: Vec<GameQuestion>,
+}
+
+#[derive(Clone, Debug)]
+pub struct AdminConnection {
+    pub name: Arc<str>,
+    pub tx: Option<ClientTx>,
+    pub connection_id: Option<Uuid>,
+    pub locale: Locale,
+    pub heartbeat: Heartbeat,
+}
+
+pub struct GameEngine {
+    state: GameState,
+}
+
+/// Drops the client's connection, ending its broadcasts.
+fn disconnect(slot: &mut Option<ClientTx>, broadcaster: &Broadcaster) {
+    if let Some(tx) = slot.take() {
+        tx.close(broadcaster);
+    }
+}
+
+/// Drops the client's connection and has the server close it once the
+/// client has everything sent so far.
+fn end_connection(slot: &mut Option<ClientTx>, broadcaster: &Broadcaster, reason: EndReason) {
+    if let Some(tx) = slot.take() {
+        tx.end(broadcaster, reason);
+    }
+}
+
+impl GameEngine {
+    pub fn new(
+        admin_id: Uuid,
+        join_code: Arc<str>,
+        questions: Arc<Vec<GameQuestion>>,
+        color_weights: [f64; Color::COUNT],
+        set: Option<&QuestionSet>,
+        round_duration: u64,
+    ) -> Self {
+        let indices = match set {
+            None => {
+                let mut all_indices: Vec<usize> = (0..questions.len()).collect();
+                fastrand::shuffle(&mut all_indices);
+                all_indices
+            }
+            Some(question_set) => {
+                let id_to_index: HashMap<i64, usize> = questions
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, q)| (q.id, idx))
+                    .collect();
+                let mut set_indices: Vec<usize> = question_set
+                    .question_ids
+                    .iter()
+                    .filter_map(|id| id_to_index.get(id).copied())
+                    .collect();
+                fastrand::shuffle(&mut set_indices);
+                set_indices
+            }
+        };
+
+        Self {
+            state: GameState {
+                phase: GamePhase::Lobby,
+                players: HashMap::new(),
+                admin_id,
+                admin: AdminConnection {
+                    name: Arc::from("Admin"),
+                    tx: None,
+                    connection_id: None,
+                    locale: Locale::default(),
+                    heartbeat: Heartbeat::default(),
+                },
+                join_code,
+                round_start_time: None,
+                round_duration,
+                current_alternatives: Vec::new(),
+                correct_answers: None,
+                current_question: None,
+                all_questions: questions,
+                color_weights,
+                shuffled_question_indices: indices,
+                current_question_index: 0,
+                created_at: Instant::now(),
+                last_lobby_message: Some(Instant::now()),
+                inactivity_warned: false,
+                round_interrupted_for: HashSet::new(),
+                locked: false,
+                public_name: None,
+                dataset: None,
+                set_name: set.map(|set| set.name.clone()),
+                game_record: None,
+                finished_game: None,
+                last_game: None,
+                lifetime: LobbyLifetime::new(),
+                event_log: None,
+                broadcaster: Broadcaster::default(),
+                scoreboard_seq: 0,
+                upcoming_preview: DEFAULT_UPCOMING_PREVIEW,
+                shuffle_per_player: false,
+                open_reclaims: false,
+            },
+        }
+    }
+
+    /// Rebuilds a lobby from a snapshot. A round that was in progress cannot
+    /// be resumed, so a lobby saved mid-question comes back in the score phase
+    /// with that question still up next.
+    pub fn from_snapshot(
+        mut snapshot: LobbySnapshot,
+        mut questions: Arc<Vec<GameQuestion>>,
+        color_weights: [f64; Color::COUNT],
+    ) -> Self {
+        if !snapshot.injected_questions.is_empty() {
+            Arc::make_mut(&mut questions).append(&mut snapshot.injected_questions);
+        }
+        let id_to_index: HashMap<i64, usize> = questions
+            .iter()
+            .enumerate()
+            .map(|(idx, q)| (q.id, idx))
+            .collect();
+        // Questions removed since the snapshot are dropped, shifting the
+        // current position back by the number removed before it.
+        let current_question_index = snapshot
+            .question_ids
+            .iter()
+            .take(snapshot.current_question_index)
+            .filter(|id| id_to_index.contains_key(id))
+            .count();
+        let shuffled_question_indices = snapshot
+            .question_ids
+            .iter()
+            .filter_map(|id| id_to_index.get(id).copied())
+            .collect();
+
+        let players: HashMap<Uuid, PlayerState> = snapshot
+            .players
+            .into_iter()
+            .map(|p| {
+                let mut player = PlayerState::new(p.name);
+                player.score = p.score;
+                player.consecutive_misses = p.consecutive_misses;
+                player.answers_given = p.answers_given;
+                player.correct_answers = p.correct_answers;
+                player.account_id = p.account_id;
+                (p.id, player)
+            })
+            .collect();
+
+        let lifetime = snapshot.lifetime.unwrap_or_else(|| LobbyLifetime {
+            peak_players: players.len(),
+            ..LobbyLifetime::new()
+        });
+
+        let (phase, round_interrupted_for) = match snapshot.phase {
+            GamePhase::Question => {
+                let clients = players.keys().copied().chain([snapshot.admin_id]);
+                (GamePhase::Score, clients.collect())
+            }
+            phase => (phase, HashSet::new()),
+        };
+
+        Self {
+            state: GameState {
+                phase,
+                players,
+                admin_id: snapshot.admin_id,
+                admin: AdminConnection {
+                    name: Arc::from("Admin"),
+                    tx: None,
+                    connection_id: None,
+                    locale: Locale::default(),
+                    heartbeat: Heartbeat::default(),
+                },
+                join_code: snapshot.join_code,
+                round_start_time: None,
+                round_duration: snapshot.round_duration,
+                current_alternatives: Vec::new(),
+                correct_answers: None,
+                current_question: None,
+                all_questions: questions,
+                color_weights,
+                shuffled_question_indices,
+                current_question_index,
+                created_at: Instant::now(),
+                last_lobby_message: Some(Instant::now()),
+                inactivity_warned: false,
+                round_interrupted_for,
+                locked: snapshot.locked,
+                public_name: snapshot.public_name,
+                dataset: snapshot.dataset,
+                set_name: snapshot.set_name,
+                game_record: snapshot.game_record,
+                finished_game: None,
+                last_game: None,
+                lifetime,
+                event_log: None,
+                broadcaster: Broadcaster::default(),
+                scoreboard_seq: 0,
+                upcoming_preview: snapshot
+                    .upcoming_preview
+                    .map_or(DEFAULT_UPCOMING_PREVIEW, |count| {
+                        count.min(MAX_UPCOMING_PREVIEW)
+                    }),
+                shuffle_per_player: snapshot.shuffle_per_player,
+                open_reclaims: snapshot.open_reclaims,
+            },
+        }
+    }
+
+    /// Snapshot for persistence, or `None` once the lobby is closed.
+    pub fn to_snapshot(&self) -> Option<LobbySnapshot> {
+        if self.is_finished() {
+            return None;
+        }
+        Some(LobbySnapshot {
+            join_code: self.state.join_code.clone(),
+            admin_id: self.state.admin_id,
+            phase: self.state.phase,
+            round_duration: self.state.round_duration,
+            players: self
+                .state
+                .players
+                .iter()
+                .map(|(id, p)| PlayerSnapshot {
+                    id: *id,
+                    name: p.name.clone(),
+                    score: p.score,
+                    consecutive_misses: p.consecutive_misses,
+                    answers_given: p.answers_given,
+                    correct_answers: p.correct_answers,
+                    account_id: p.account_id,
+                })
+                .collect(),
+            question_ids: self
+                .state
+                .shuffled_question_indices
+                .iter()
+                .map(|&idx| self.state.all_questions[idx].id)
+                .collect(),
+            current_question_index: self.state.current_question_index,
+            locked: self.state.locked,
+            public_name: self.state.public_name.clone(),
+            dataset: self.state.dataset.clone(),
+            lifetime: Some(self.state.lifetime.clone()),
+            set_name: self.state.set_name.clone(),
+            game_record: self.state.game_record.clone(),
+            upcoming_preview: Some(self.state.upcoming_preview),
+            shuffle_per_player: self.state.shuffle_per_player,
+            open_reclaims: self.state.open_reclaims,
+            injected_questions: self
+                .state
+                .all_questions
+                .iter()
+                .filter(|q| q.id < 0)
+                .cloned()
+                .collect(),
+        })
+    }
+
+    /// Sets the language errors are sent to the player or admin in.
+    pub fn set_locale(&mut self, player_id: Uuid, locale: Locale) {
+        if player_id == self.state.admin_id {
+            self.state.admin.locale = locale;
+        } else if let Some(player) = self.state.players.get_mut(&player_id) {
+            player.locale = locale;
+        }
+    }
+
+    /// The keepalive of the player's connection, announced when they connect.
+    pub fn set_heartbeat(&mut self, player_id: Uuid, heartbeat: Heartbeat) {
+        if player_id == self.state.admin_id {
+            self.state.admin.heartbeat = heartbeat;
+        } else if let Some(player) = self.state.players.get_mut(&player_id) {
+            player.heartbeat = heartbeat;
+        }
+    }
+
+    fn locale_of(&self, player_id: Uuid) -> Locale {
+        if player_id == self.state.admin_id {
+            return self.state.admin.locale;
+        }
+        self.state
+            .players
+            .get(&player_id)
+            .map_or_else(Locale::default, |player| player.locale)
+    }
+
+    pub fn update_player_connection(
+        &mut self,
+        player_id: Uuid,
+        tx: Sender<Outgoing>,
+        connection_id: Uuid,
+    ) {
+        let broadcaster = &self.state.broadcaster;
+        if player_id == self.state.admin_id {
+            disconnect(&mut self.state.admin.tx, broadcaster);
+            self.state.admin.tx = broadcaster.connect(tx);
+            self.state.admin.connection_id = Some(connection_id);
+        } else if let Some(player) = self.state.players.get_mut(&player_id) {
+            disconnect(&mut player.tx, broadcaster);
+            player.tx = broadcaster.connect(tx);
+            player.connection_id = Some(connection_id);
+            player.connections += 1;
+            player.reclaim_requested = false;
+        }
+    }
+
+    pub fn clear_player_connection(&mut self, player_id: Uuid, connection_id: Uuid) {
+        let broadcaster = &self.state.broadcaster;
+        if player_id == self.state.admin_id {
+            if self.state.admin.connection_id == Some(connection_id) {
+                disconnect(&mut self.state.admin.tx, broadcaster);
+                self.state.admin.connection_id = None;
+                self.state.lifetime.disconnects += 1;
+            }
+        } else if let Some(player) = self.state.players.get_mut(&player_id)
+            && player.connection_id == Some(connection_id)
+        {
+            disconnect(&mut player.tx, broadcaster);
+            player.connection_id = None;
+            self.state.lifetime.disconnects += 1;
+        }
+    }
+
+    /// Removes a player, ending their broadcasts.
+    fn remove_player(&mut self, player_id: &Uuid) -> Option<PlayerState> {
+        let mut player = self.state.players.remove(player_id)?;
+        disconnect(&mut player.tx, &self.state.broadcaster);
+        Some(player)
+    }
+
+    pub fn add_player(&mut self, player_id: Uuid, name: String) -> Result<(), NameValidationError> {
+        self.add_player_with_account(player_id, name, None)
+    }
+
+    pub fn add_player_with_account(
+        &mut self,
+        player_id: Uuid,
+        name: String,
+        account_id: Option<Uuid>,
+    ) -> Result<(), NameValidationError> {
+        let trimmed = normalize_player_name(&name);
+        let existing_names = self
+            .state
+            .players
+            .values()
+            .map(|p| p.name.as_ref())
+            .chain(std::iter::once(self.state.admin.name.as_ref()));
+        validate_player_name(&trimmed, existing_names)?;
+        let mut player = PlayerState::new(Arc::from(trimmed));
+        player.account_id = account_id;
+        self.state.players.insert(player_id, player);
+        let lifetime = &mut self.state.lifetime;
+        lifetime.peak_players = lifetime.peak_players.max(self.state.players.len());
+        Ok(())
+    }
+
+    /// Lets someone joining as `name` take over a disconnected player of
+    /// that name with their score, e.g. after losing their session on a
+    /// dead phone. Players with accounts rejoin by logging in instead.
+    /// Returns `None` if there's no such player to take over.
+    pub fn reclaim_player(&mut self, name: &str) -> Option<Reclaim> {
+        let name = normalize_player_name(name);
+        let (&player_id, player) = self.state.players.iter_mut().find(|(_, p)| {
+            // Not one who joined but has yet to connect.
+            p.name.as_ref() == name && p.tx.is_none() && p.connections > 0 && p.account_id.is_none()
+        })?;
+        if !self.state.open_reclaims && !player.reclaim_approved {
+            if !player.reclaim_requested {
+                player.reclaim_requested = true;
+                let name = player.name.clone();
+                self.push_update(
+                    Recipients::Single(self.state.admin_id),
+                    GameUpdate::ReclaimRequested { name },
+                );
+            }
+            return Some(Reclaim::AwaitingApproval);
+        }
+        player.reclaim_approved = false;
+        player.reclaim_requested = false;
+        info!(
+            "Lobby {}: {} rejoined by name",
+            self.state.join_code, player.name
+        );
+        Some(Reclaim::Player(player_id))
+    }
+
+    pub fn last_update(&self) -> Option<Instant> {
+        self.state.last_lobby_message
+    }
+
+    /// Counts activity at `at`, e.g. a player's message that isn't an event,
+    /// towards keeping the lobby open.
+    pub fn note_activity(&mut self, at: Instant) {
+        if self.state.last_lobby_message.is_none_or(|last| at > last) {
+            self.state.last_lobby_message = Some(at);
+            self.state.inactivity_warned = false;
+        }
+    }
+
+    /// Time since the last activity, if any was recorded.
+    fn idle_for(&self) -> Option<Duration> {
+        self.state
+            .last_lobby_message
+            .map(|last| Instant::now().saturating_duration_since(last))
+    }
+
+    pub fn is_finished(&self) -> bool {
+        if self.state.phase == GamePhase::GameClosed {
+            return true;
+        }
+        self.idle_for()
+            .is_some_and(|idle| idle > LOBBY_INACTIVITY_TIMEOUT)
+    }
+
+    /// Warns everyone once when the lobby has been inactive long enough to
+    /// close within [`INACTIVITY_WARNING_BEFORE`].
+    pub fn warn_if_inactive(&mut self) {
+        if self.state.phase == GamePhase::GameClosed || self.state.inactivity_warned {
+            return;
+        }
+        let Some(idle) = self.idle_for() else {
+            return;
+        };
+        let Some(remaining) = LOBBY_INACTIVITY_TIMEOUT.checked_sub(idle) else {
+            return;
+        };
+        if remaining <= INACTIVITY_WARNING_BEFORE {
+            self.state.inactivity_warned = true;
+            self.push_update(
+                Recipients::All,
+                GameUpdate::InactivityWarning {
+                    closes_in_secs: remaining.as_secs(),
+                },
+            );
+        }
+    }
+
+    pub fn close_if_inactive(&mut self) {
+        if self.state.phase != GamePhase::GameClosed
+            && self
+                .idle_for()
+                .is_some_and(|idle| idle > LOBBY_INACTIVITY_TIMEOUT)
+        {
+            let reason: Arc<str> = "Lobby closed due to inactivity".into();
+            self.finish_game_record(&reason);
+            self.push_update(Recipients::All, GameUpdate::GameClosed { reason });
+            self.state.phase = GamePhase::GameClosed;
+        }
+    }
+
+    /// Closes the lobby on behalf of the server operator, going through the
+    /// same path as the lobby admin's CloseGame.
+    pub fn close_by_operator(&mut self, reason: Arc<str>) {
+        let sender_id = self.state.admin_id;
+        self.process_event(GameEvent {
+            context: EventContext {
+                sender_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::CloseGame { reason },
+        });
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.state.players.len() >= 1024
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.state.locked
+    }
+
+    /// Lists the lobby in the public lobby browser under `name`.
+    pub fn set_public_name(&mut self, name: Arc<str>) {
+        self.state.public_name = Some(name);
+    }
+
+    /// Sets how many upcoming questions the admin is shown, at most
+    /// [`MAX_UPCOMING_PREVIEW`].
+    pub fn set_upcoming_preview(&mut self, count: usize) {
+        self.state.upcoming_preview = count.min(MAX_UPCOMING_PREVIEW);
+    }
+
+    /// How many upcoming questions the admin is shown,
+    /// [`DEFAULT_UPCOMING_PREVIEW`] unless the lobby set its own.
+    pub fn upcoming_preview(&self) -> usize {
+        self.state.upcoming_preview
+    }
+
+    /// Whether each player sees the alternatives in their own order instead
+    /// of the same order for everyone.
+    pub fn set_shuffle_per_player(&mut self, enabled: bool) {
+        self.state.shuffle_per_player = enabled;
+    }
+
+    /// Whether joining as a disconnected player skips the admin's approval.
+    pub fn set_open_reclaims(&mut self, enabled: bool) {
+        self.state.open_reclaims = enabled;
+    }
+
+    pub fn set_dataset(&mut self, dataset: Arc<str>) {
+        self.state.dataset = Some(dataset);
+    }
+
+    pub fn dataset(&self) -> Option<&str> {
+        self.state.dataset.as_deref()
+    }
+
+    /// Starts recording processed events, see [`EventLog`].
+    pub fn enable_event_log(&mut self) {
+        self.state.event_log = Some(EventLog::new(&self.state.join_code));
+    }
+
+    /// Record of the game that just ended, once; see [`GameRecord`].
+    pub fn take_finished_game(&mut self) -> Option<GameRecord> {
+        self.state.finished_game.take()
+    }
+
+    /// Record of the last finished game, while the lobby is showing its
+    /// results or closed after it.
+    pub fn final_results(&self) -> Option<&GameRecord> {
+        match self.state.phase {
+            GamePhase::GameOver | GamePhase::GameClosed => self.state.last_game.as_ref(),
+            _ => None,
+        }
+    }
+
+    /// Storage name and contents of the event log, if events were recorded
+    /// since it was last taken.
+    pub fn take_event_log(&mut self) -> Option<(String, String)> {
+        let log = self.state.event_log.as_mut().filter(|log| log.dirty)?;
+        log.dirty = false;
+        Some((log.name.clone(), log.jsonl.clone()))
+    }
+
+    /// Name, player count and phase for lobbies that are publicly listed and
+    /// still joinable.
+    pub fn public_listing(&self) -> Option<(Arc<str>, usize, GamePhase)> {
+        let name = self.state.public_name.clone()?;
+        if self.is_finished() || self.is_full() || self.is_locked() {
+            return None;
+        }
+        Some((name, self.state.players.len(), self.state.phase))
+    }
+
+    pub fn get_lobby_stats(&self) -> (usize, usize) {
+        (self.state.players.len(), self.state.current_question_index)
+    }
+
+    pub fn get_detailed_stats(&self) -> LobbyStats {
+        let mut players: Vec<PlayerStats> = self
+            .state
+            .players
+            .values()
+            .map(|p| PlayerStats {
+                name: p.name.clone(),
+                score: p.score,
+                answers_given: p.answers_given,
+                correct_answers: p.correct_answers,
+                connected: p.tx.is_some(),
+                connections: p.connections,
+            })
+            .collect();
+        players.sort_by(|a, b| b.score.cmp(&a.score).then_with(|| a.name.cmp(&b.name)));
+
+        LobbyStats {
+            phase: self.state.phase,
+            questions_played: self.state.current_question_index,
+            total_answers: players.iter().map(|p| p.answers_given).sum(),
+            admin_connected: self.state.admin.tx.is_some(),
+            players,
+        }
+    }
+
+    pub fn lifetime_summary(&self) -> LobbySummary {
+        let lifetime = &self.state.lifetime;
+        LobbySummary {
+            duration_secs: lifetime.created.elapsed().unwrap_or_default().as_secs(),
+            peak_players: lifetime.peak_players,
+            rounds_played: lifetime.rounds_played,
+            messages_sent: lifetime.messages_sent,
+            disconnects: lifetime.disconnects,
+        }
+    }
+
+    pub fn is_admin(&self, player_id: &Uuid) -> bool {
+        *player_id == self.state.admin_id
+    }
+
+    pub fn get_consecutive_misses(&self) -> Vec<(Arc<str>, u32)> {
+        self.state
+            .players
+            .values()
+            .map(|p| (p.name.clone(), p.consecutive_misses))
+            .collect()
+    }
+
+    #[allow(clippy::type_complexity)]
+    fn get_player_summary(
+        &self,
+    ) -> (
+        Vec<(Arc<str>, i32)>,
+        Vec<(Arc<str>, i32)>,
+        Vec<(Arc<str>, u32)>,
+    ) {
+        let player_count = self.state.players.len();
+        let mut scoreboard = Vec::with_capacity(player_count);
+        let mut round_scores = Vec::with_capacity(player_count);
+        let mut consecutive_misses = Vec::with_capacity(player_count);
+        for p in self.state.players.values() {
+            let name = p.name.clone();
+            scoreboard.push((name.clone(), p.score));
+            round_scores.push((name.clone(), p.round_score));
+            consecutive_misses.push((name, p.consecutive_misses));
+        }
+        (scoreboard, round_scores, consecutive_misses)
+    }
+
+    /// The full lists, for a client that has none or asked to resync.
+    fn full_scores(&self) -> ScoreUpdate {
+        let (scoreboard, round_scores, consecutive_misses) = self.get_player_summary();
+        ScoreUpdate {
+            scoreboard: Some(scoreboard),
+            round_scores: Some(round_scores),
+            consecutive_misses: Some(consecutive_misses),
+            score_changes: None,
+            scoreboard_seq: Some(self.state.scoreboard_seq),
+        }
+    }
+
+    /// The next score update for the whole lobby: the players whose scores
+    /// changed since the last one, or the full lists every
+    /// `FULL_SCOREBOARD_INTERVAL` updates. Empty if nothing changed.
+    fn next_score_update(&mut self) -> ScoreUpdate {
+        let mut changes = Vec::new();
+        for p in self.state.players.values_mut() {
+            let scores = p.scores();
+            if p.reported != Some(scores) {
+                p.reported = Some(scores);
+                changes.push(ScoreChange {
+                    name: p.name.clone(),
+                    score: p.score,
+                    round_score: p.round_score,
+                    consecutive_misses: p.consecutive_misses,
+                });
+            }
+        }
+        if changes.is_empty() {
+            return ScoreUpdate::default();
+        }
+        self.state.scoreboard_seq += 1;
+        if self
+            .state
+            .scoreboard_seq
+            .is_multiple_of(FULL_SCOREBOARD_INTERVAL)
+        {
+            return self.full_scores();
+        }
+        ScoreUpdate {
+            score_changes: Some(changes),
+            scoreboard_seq: Some(self.state.scoreboard_seq),
+            ..ScoreUpdate::default()
+        }
+    }
+
+    fn get_question_time_remaining_ms(&self, now: Instant) -> Option<u64> {
+        let start = self.state.round_start_time?;
+        let elapsed_ms = now.duration_since(start).as_millis();
+        let total_ms = self.state.round_duration as u128 * 1000;
+        Some(total_ms.saturating_sub(elapsed_ms) as u64)
+    }
+
+    fn get_answered_player_names(&self) -> Vec<Arc<str>> {
+        self.state
+            .players
+            .iter()
+            .filter(|(_, p)| p.has_answered)
+            .map(|(_, p)| p.name.clone())
+            .collect()
+    }
+
+    /// Swatch details for `alternatives`, if the current question is a color
+    /// question.
+    fn colors_for(&self, alternatives: &[Arc<str>]) -> Option<Vec<ColorInfo>> {
+        let question = self.state.current_question.as_ref()?;
+        if question.question_type != QuestionType::Color {
+            return None;
+        }
+        alternatives
+            .iter()
+            .map(|alternative| alternative.parse::<Color>().ok().map(Color::info))
+            .collect()
+    }
+
+    /// This round's alternatives in the order `id` sees them. With
+    /// per-player shuffling, a player's order is picked the first time it is
+    /// asked for and kept for the rest of the round.
+    fn alternatives_for(&mut self, id: Uuid) -> Vec<Arc<str>> {
+        if !self.state.shuffle_per_player {
+            return self.state.current_alternatives.clone();
+        }
+        let Some(player) = self.state.players.get_mut(&id) else {
+            return self.state.current_alternatives.clone();
+        };
+        let current = &self.state.current_alternatives;
+        player
+            .alternatives
+            .get_or_insert_with(|| {
+                let mut own = current.clone();
+                fastrand::shuffle(&mut own);
+                own
+            })
+            .clone()
+    }
+
+    /// Sends everyone their own order of this round's alternatives.
+    fn send_own_alternatives(&mut self, question_type: Arc<str>, question_text: Option<Arc<str>>) {
+        let ids: Vec<Uuid> = std::iter::once(self.state.admin_id)
+            .chain(self.state.players.keys().copied())
+            .collect();
+        for id in ids {
+            let alternatives = self.alternatives_for(id);
+            let colors = self.colors_for(&alternatives);
+            self.push_update(
+                Recipients::Single(id),
+                GameUpdate::StateDelta {
+                    phase: None,
+                    question_type: Some(question_type.clone()),
+                    question_text: question_text.clone(),
+                    alternatives: Some(alternatives),
+                    colors,
+                    question_time_remaining_ms: None,
+                    server_time_ms: None,
+                    answered_player_names: None,
+                    scoreboard: None,
+                    round_scores: None,
+                    consecutive_misses: None,
+                    score_changes: None,
+                    scoreboard_seq: None,
+                    admin_extra: None,
+                    lobby_locked: None,
+                },
+            );
+        }
+    }
+
+    fn log_game_state(&self, action: &str) {
+        use std::fmt::Write;
+        let total_players = self.state.players.len();
+        let questions_played = self.state.current_question_index;
+        let mut players_info = String::new();
+        for (i, p) in self.state.players.values().enumerate() {
+            if i > 0 {
+                players_info.push_str(", ");
+            }
+            let _ = write!(players_info, "{}:{}", p.name, p.score);
+        }
+        info!(
+            "Lobby {}: {} - {} players, {} questions played, players [{}]",
+            self.state.join_code, action, total_players, questions_played, players_info
+        );
+    }
+
+    pub fn get_phase(&self) -> GamePhase {
+        self.state.phase
+    }
+
+    /// Spotify track of the question currently being played, if any.
+    pub fn current_spotify_uri(&self) -> Option<Arc<str>> {
+        self.state.current_question.as_ref()?.spotify_uri.clone()
+    }
+
+    pub fn get_created_at(&self) -> Instant {
+        self.state.created_at
+    }
+
+    pub fn get_admin_id(&self) -> Uuid {
+        self.state.admin_id
+    }
+
+    pub fn get_round_duration(&self) -> u64 {
+        self.state.round_duration
+    }
+
+    pub fn get_player_count(&self) -> usize {
+        self.state.players.len()
+    }
+
+    /// The player joined with account `account_id`, if any.
+    pub fn player_with_account(&self, account_id: &Uuid) -> Option<Uuid> {
+        self.state
+            .players
+            .iter()
+            .find(|(_, player)| player.account_id.as_ref() == Some(account_id))
+            .map(|(id, _)| *id)
+    }
+
+    /// Name of the player or admin with `player_id`.
+    pub fn get_player_name(&self, player_id: &Uuid) -> Option<Arc<str>> {
+        if *player_id == self.state.admin_id {
+            return Some(self.state.admin.name.clone());
+        }
+        self.state.players.get(player_id).map(|p| p.name.clone())
+    }
+
+    pub fn has_player(&self, player_id: &Uuid) -> bool {
+        *player_id == self.state.admin_id || self.state.players.contains_key(player_id)
+    }
+
+    fn get_scoreboard(&self) -> Vec<(Arc<str>, i32)> {
+        self.state
+            .players
+            .values()
+            .map(|p| (p.name.clone(), p.score))
+            .collect()
+    }
+
+    /// Returns whether the update was queued; disconnects the client if not.
+    fn try_send_to(
+        slot: &mut Option<ClientTx>,
+        broadcaster: &Broadcaster,
+        payload: Bytes,
+        id: Uuid,
+    ) -> bool {
+        let Some(tx) = slot else {
+            return false;
+        };
+        match tx.try_send(broadcaster, payload) {
+            Ok(()) => return true,
+            Err(TrySendError::Full(_)) => warn!(%id, "Channel full, disconnecting"),
+            Err(TrySendError::Closed(_)) => warn!(%id, "Channel closed, disconnecting"),
+        }
+        disconnect(slot, broadcaster);
+        false
+    }
+
+    /// Whether the client gets the broadcast just sent; disconnects it if its
+    /// channel is closed.
+    fn receives_broadcast(
+        slot: &mut Option<ClientTx>,
+        broadcaster: &Broadcaster,
+        id: Uuid,
+    ) -> bool {
+        match slot {
+            Some(tx) if tx.is_closed() => {
+                warn!(%id, "Channel closed, disconnecting");
+                disconnect(slot, broadcaster);
+                false
+            }
+            Some(_) => true,
+            None => false,
+        }
+    }
+
+    /// Returns whether the update was queued for the admin.
+    fn send_to_admin(&mut self, payload: Bytes) -> bool {
+        Self::try_send_to(
+            &mut self.state.admin.tx,
+            &self.state.broadcaster,
+            payload,
+            self.state.admin_id,
+        )
+    }
+
+    /// Returns whether the update was queued for the player.
+    fn send_to_player(
+        player: &mut PlayerState,
+        broadcaster: &Broadcaster,
+        id: Uuid,
+        payload: Bytes,
+    ) -> bool {
+        Self::try_send_to(&mut player.tx, broadcaster, payload, id)
+    }
+
+    fn push_update(&mut self, recipients: Recipients, mut update: GameUpdate) {
+        if let (
+            Recipients::Single(target),
+            GameUpdate::Error { message, .. } | GameUpdate::AnswerRejected { message, .. },
+        ) = (&recipients, &mut update)
+        {
+            // Errors to a player answer something they sent.
+            if let Some(player) = self.state.players.get_mut(target) {
+                player.throttle.rejected += 1;
+            }
+            let locale = self.locale_of(*target);
+            if locale != Locale::En {
+                let localized = Arc::from(locale.localize(message));
+                *message = localized;
+            }
+        }
+        let json = match serde_json::to_string(&update) {
+            Ok(json) => json,
+            Err(e) => {
+                error!(
+                    lobby_key = %self.state.join_code,
+                    error = %e,
+                    "Failed to serialize game update"
+                );
+                return;
+            }
+        };
+        let payload = Bytes::from(json);
+
+        let mut sent = 0;
+        match recipients {
+            Recipients::Single(target) => {
+                if target == self.state.admin_id {
+                    sent += u64::from(self.send_to_admin(payload));
+                } else if let Some(player) = self.state.players.get_mut(&target) {
+                    let broadcaster = &self.state.broadcaster;
+                    sent += u64::from(Self::send_to_player(player, broadcaster, target, payload));
+                }
+            }
+            Recipients::Multiple(targets) => {
+                for target in targets {
+                    if target == self.state.admin_id {
+                        sent += u64::from(self.send_to_admin(payload.clone()));
+                    } else if let Some(player) = self.state.players.get_mut(&target) {
+                        let broadcaster = &self.state.broadcaster;
+                        sent += u64::from(Self::send_to_player(
+                            player,
+                            broadcaster,
+                            target,
+                            payload.clone(),
+                        ));
+                    }
+                }
+            }
+            Recipients::_AllExcept(exclusions) => {
+                if !exclusions.contains(&self.state.admin_id) {
+                    sent += u64::from(self.send_to_admin(payload.clone()));
+                }
+                let broadcaster = &self.state.broadcaster;
+                for (player_id, player) in self.state.players.iter_mut() {
+                    if !exclusions.contains(player_id) {
+                        sent += u64::from(Self::send_to_player(
+                            player,
+                            broadcaster,
+                            *player_id,
+                            payload.clone(),
+                        ));
+                    }
+                }
+            }
+            Recipients::All => {
+                // One send however big the lobby; each connection's writer
+                // picks it up from the lobby's broadcast channel.
+                self.state.broadcaster.send(payload);
+                let broadcaster = &self.state.broadcaster;
+                let admin = (self.state.admin_id, &mut self.state.admin.tx);
+                let players = self
+                    .state
+                    .players
+                    .iter_mut()
+                    .map(|(id, p)| (*id, &mut p.tx));
+                for (id, slot) in std::iter::once(admin).chain(players) {
+                    sent += u64::from(Self::receives_broadcast(slot, broadcaster, id));
+                }
+            }
+        }
+        self.state.lifetime.messages_sent += sent;
+        // Nothing follows a closed game, so its connections go with it.
+        if matches!(update, GameUpdate::GameClosed { .. }) {
+            self.end_connections(EndReason::GameClosed);
+        }
+    }
+
+    fn end_connections(&mut self, reason: EndReason) {
+        let broadcaster = &self.state.broadcaster;
+        end_connection(&mut self.state.admin.tx, broadcaster, reason);
+        for player in self.state.players.values_mut() {
+            end_connection(&mut player.tx, broadcaster, reason);
+        }
+    }
+
+    #[instrument(
+        level = "debug",
+        skip(self, event),
+        fields(
+            sender_id = %event.context.sender_id,
+            action = %event.action.kind(),
+            phase = ?self.state.phase,
+        )
+    )]
+    pub fn process_event(&mut self, event: GameEvent) {
+        // A bug hit by one lobby must not take down the connection task or
+        // the other lobbies, so the lobby is closed instead.
+        let result = panic::catch_unwind(AssertUnwindSafe(|| self.record_and_handle_event(event)));
+        if let Err(payload) = result {
+            self.close_after_panic(payload);
+        }
+    }
+
+    /// Closes the lobby after a panic while processing an event, as its state
+    /// may be left inconsistent.
+    fn close_after_panic(&mut self, payload: Box<dyn Any + Send>) {
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown panic");
+        error!(
+            lobby_key = %self.state.join_code,
+            panic = message,
+            "Closing lobby after a panic in event processing"
+        );
+        self.state.phase = GamePhase::GameClosed;
+        let reason: Arc<str> = "internal error".into();
+        self.finish_game_record(&reason);
+        self.push_update(Recipients::All, GameUpdate::GameClosed { reason });
+    }
+
+    fn record_and_handle_event(&mut self, event: GameEvent) {
+        if self.state.event_log.is_none() {
+            self.handle_event(event);
+            return;
+        }
+        let phase_before = self.state.phase;
+        let sender_id = event.context.sender_id;
+        let action = event.action.clone();
+        let round_elapsed_ms = match (phase_before, self.state.round_start_time) {
+            (GamePhase::Question, Some(start)) => Some(
+                event
+                    .context
+                    .timestamp
+                    .saturating_duration_since(start)
+                    .as_millis() as u64,
+            ),
+            _ => None,
+        };
+        self.handle_event(event);
+        let recorded = RecordedEvent {
+            at: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+            sender_id,
+            action: &action,
+            round_elapsed_ms,
+            phase_before,
+            phase_after: self.state.phase,
+        };
+        if let Some(log) = &mut self.state.event_log {
+            log.record(&recorded);
+        }
+    }
+
+    fn handle_event(&mut self, event: GameEvent) {
+        self.note_activity(Instant::now());
+        if self.is_throttled(&event) {
+            return;
+        }
+        // Check admin-only actions:
+        match &event.action {
+            GameAction::StartGame
+            | GameAction::StartRound
+            | GameAction::EndRound
+            | GameAction::SkipQuestion
+            | GameAction::KickPlayer { .. }
+            | GameAction::EndGame { .. }
+            | GameAction::CloseGame { .. }
+            | GameAction::LockLobby { .. }
+            | GameAction::RequestUpcoming { .. }
+            | GameAction::QueueQuestion { .. }
+            | GameAction::ReorderUpcoming { .. }
+            | GameAction::InjectQuestion { .. }
+            | GameAction::ApproveReclaim { .. }
+                if event.context.sender_id != self.state.admin_id =>
+            {
+                debug!(
+                    sender_id = %event.context.sender_id,
+                    action = %event.action.kind(),
+                    "Admin action denied: sender is not admin"
+                );
+                self.push_update(
+                    Recipients::Single(event.context.sender_id),
+                    GameUpdate::Error {
+                        message: "Admin action requires authorization".into(),
+                        client_msg_id: None,
+                    },
+                );
+                return;
+            }
+            _ => {}
+        }
+
+        match event.action {
+            GameAction::Connect => self.handle_connect(event.context),
+            GameAction::Leave => self.handle_leave(event.context),
+            GameAction::Answer {
+                answer,
+                client_msg_id,
+            } => self.handle_answer(event.context, answer, client_msg_id),
+            GameAction::StartGame => self.handle_start_game(event.context),
+            GameAction::StartRound => self.handle_start_round(event.context),
+            GameAction::EndRound => self.handle_end_round(event.context),
+            GameAction::SkipQuestion => self.handle_skip_question(event.context),
+            GameAction::KickPlayer { player_name } => {
+                self.handle_kick_player(event.context, player_name)
+            }
+            GameAction::EndGame { reason } => self.handle_end_game(event.context, reason),
+            GameAction::CloseGame { reason } => self.handle_close_game(event.context, reason),
+            GameAction::LockLobby { locked } => self.handle_lock_lobby(event.context, locked),
+            GameAction::RequestUpcoming { count } => self.handle_request_upcoming(count),
+            GameAction::QueueQuestion { question_id } => {
+                self.handle_queue_question(event.context, question_id)
+            }
+            GameAction::ReorderUpcoming { question_ids } => {
+                self.handle_reorder_upcoming(event.context, question_ids)
+            }
+            GameAction::InjectQuestion {
+                question_text,
+                options,
+            } => self.handle_inject_question(event.context, question_text, options),
+            GameAction::ApproveReclaim { player_name } => {
+                self.handle_approve_reclaim(event.context, player_name)
+            }
+            GameAction::ResyncScoreboard => self.handle_resync_scoreboard(event.context),
+        }
+    }
+
+    /// Whether the event's sender has had too many actions rejected lately.
+    /// Connecting and leaving always go through.
+    fn is_throttled(&mut self, event: &GameEvent) -> bool {
+        if matches!(event.action, GameAction::Connect | GameAction::Leave) {
+            return false;
+        }
+        let Some(player) = self.state.players.get_mut(&event.context.sender_id) else {
+            return false;
+        };
+        let throttled = player.throttle.is_throttled(event.context.timestamp);
+        if throttled {
+            debug!(
+                sender_id = %event.context.sender_id,
+                action = %event.action.kind(),
+                "Action dropped: too many rejected actions"
+            );
+        }
+        throttled
+    }
+
+    fn handle_connect(&mut self, ctx: EventContext) {
+        let is_admin = ctx.sender_id == self.state.admin_id;
+        let (name, heartbeat, connection_id) = if is_admin {
+            let admin = &self.state.admin;
+            (admin.name.clone(), admin.heartbeat, admin.connection_id)
+        } else {
+            match self.state.players.get(&ctx.sender_id) {
+                Some(player) => (player.name.clone(), player.heartbeat, player.connection_id),
+                None => {
+                    self.push_update(
+                        Recipients::Single(ctx.sender_id),
+                        GameUpdate::Error {
+                            message: "Player not found. Please register before connecting.".into(),
+                            client_msg_id: None,
+                        },
+                    );
+                    return;
+                }
+            }
+        };
+
+        // First send the initial connection acknowledgment
+        self.push_update(
+            Recipients::Single(ctx.sender_id),
+            GameUpdate::Connected {
+                player_id: ctx.sender_id,
+                name,
+                round_duration: self.state.round_duration,
+                heartbeat,
+                connection_id,
+            },
+        );
+
+        // Then send the complete current state
+        let ScoreUpdate {
+            scoreboard,
+            round_scores,
+            consecutive_misses,
+            score_changes,
+            scoreboard_seq,
+        } = self.full_scores();
+        let alternatives = self.alternatives_for(ctx.sender_id);
+        let state_update = GameUpdate::StateDelta {
+            phase: Some(self.state.phase),
+            question_type: self
+                .state
+                .current_question
+                .as_ref()
+                .map(|q| Arc::from(q.get_question_type())),
+            question_text: self
+                .state
+                .current_question
+                .as_ref()
+                .and_then(|q| q.question_text.clone()),
+            colors: self.colors_for(&alternatives),
+            alternatives: Some(alternatives),
+            question_time_remaining_ms: if self.state.phase == GamePhase::Question {
+                self.get_question_time_remaining_ms(ctx.timestamp)
+            } else {
+                None
+            },
+            server_time_ms: if self.state.phase == GamePhase::Question {
+                Some(unix_time_ms_at(ctx.timestamp))
+            } else {
+                None
+            },
+            answered_player_names: if self.state.phase == GamePhase::Question {
+                Some(self.get_answered_player_names())
+            } else {
+                None
+            },
+            scoreboard,
+            round_scores,
+            consecutive_misses,
+            score_changes,
+            scoreboard_seq,
+            admin_extra: if is_admin {
+                Some(AdminExtraInfo {
+                    upcoming_questions: self.get_upcoming_questions(self.state.upcoming_preview),
+                })
+            } else {
+                None
+            },
+            lobby_locked: if is_admin {
+                Some(self.state.locked)
+            } else {
+                None
+            },
+        };
+
+        self.push_update(Recipients::Single(ctx.sender_id), state_update);
+        if self.state.round_interrupted_for.remove(&ctx.sender_id) {
+            self.push_update(
+                Recipients::Single(ctx.sender_id),
+                GameUpdate::RoundInterrupted,
+            );
+        }
+
+        // In lobby phase, let everyone see the player who joined. The
+        // connecting client gets it too, so its scoreboard_seq stays in step.
+        if self.state.phase == GamePhase::Lobby {
+            let ScoreUpdate {
+                scoreboard,
+                round_scores,
+                consecutive_misses,
+                score_changes,
+                scoreboard_seq,
+            } = self.next_score_update();
+            if scoreboard_seq.is_some() {
+                self.push_update(
+                    Recipients::All,
+                    GameUpdate::StateDelta {
+                        phase: None,
+                        question_type: None,
+                        question_text: None,
+                        alternatives: None,
+                        colors: None,
+                        question_time_remaining_ms: None,
+                        server_time_ms: None,
+                        answered_player_names: None,
+                        scoreboard,
+                        round_scores,
+                        consecutive_misses,
+                        score_changes,
+                        scoreboard_seq,
+                        admin_extra: None,
+                        lobby_locked: None,
+                    },
+                );
+            }
+        }
+
+        // If this is the admin and we're in Question phase, send the current question
+        if is_admin
+            && self.state.phase == GamePhase::Question
+            && let Some(question) = &self.state.current_question
+        {
+            self.push_update(
+                Recipients::Single(self.state.admin_id),
+                GameUpdate::AdminInfo {
+                    current_question: question.clone(),
+                },
+            );
+        }
+    }
+
+    fn handle_leave(&mut self, ctx: EventContext) {
+        if ctx.sender_id == self.state.admin_id {
+            self.state.phase = GamePhase::GameClosed;
+            let reason: Arc<str> = "Host left the game".into();
+            self.finish_game_record(&reason);
+            self.push_update(Recipients::All, GameUpdate::GameClosed { reason });
+        } else if let Some(player) = self.remove_player(&ctx.sender_id) {
+            self.push_update(
+                Recipients::All,
+                GameUpdate::PlayerLeft { name: player.name },
+            );
+        } else {
+            self.push_update(
+                Recipients::Single(ctx.sender_id),
+                GameUpdate::Error {
+                    message: "Player not found".into(),
+                    client_msg_id: None,
+                },
+            );
+        }
+    }
+
+    fn handle_answer(
+        &mut self,
+        ctx: EventContext,
+        answer: String,
+        client_msg_id: Option<Arc<str>>,
+    ) {
+        if ctx.sender_id == self.state.admin_id {
+            return;
+        }
+        if self.state.phase != GamePhase::Question {
+            debug!(
+                sender_id = %ctx.sender_id,
+                current_phase = ?self.state.phase,
+                "Answer rejected: wrong phase"
+            );
+            self.push_update(
+                Recipients::Single(ctx.sender_id),
+                GameUpdate::Error {
+                    message: "Not in Question phase".into(),
+                    client_msg_id: client_msg_id.clone(),
+                },
+            );
+            return;
+        }
+        let answer = match validate_answer(&answer, &self.state.current_alternatives) {
+            Ok(alternative) => alternative,
+            Err(reason) => {
+                debug!(sender_id = %ctx.sender_id, ?reason, "Answer rejected");
+                self.push_update(
+                    Recipients::Single(ctx.sender_id),
+                    GameUpdate::AnswerRejected {
+                        reason,
+                        message: reason.to_message().into(),
+                        client_msg_id,
+                    },
+                );
+                return;
+            }
+        };
+        let (player_name, score) = {
+            let player = match self.state.players.get_mut(&ctx.sender_id) {
+                Some(p) => p,
+                None => {
+                    self.push_update(
+                        Recipients::Single(ctx.sender_id),
+                        GameUpdate::Error {
+                            message: "Player not found".into(),
+                            client_msg_id: client_msg_id.clone(),
+                        },
+                    );
+                    return;
+                }
+            };
+            if player.has_answered {
+                // A retry of an answer that already counted, e.g. after a
+                // dropped connection: acknowledge it again.
+                if client_msg_id.is_some() && player.answer_msg_id == client_msg_id {
+                    let score = player.round_score;
+                    self.push_update(
+                        Recipients::Single(ctx.sender_id),
+                        GameUpdate::AnswerAck {
+                            score,
+                            client_msg_id,
+                        },
+                    );
+                    return;
+                }
+                self.push_update(
+                    Recipients::Single(ctx.sender_id),
+                    GameUpdate::Error {
+                        message: "Already answered this round".into(),
+                        client_msg_id: client_msg_id.clone(),
+                    },
+                );
+                return;
+            }
+            let elapsed = match self.state.round_start_time {
+                Some(start_time) => ctx.timestamp.duration_since(start_time),
+                None => {
+                    warn!("Round start time not set; using default duration");
+                    Duration::from_secs(self.state.round_duration / 2)
+                }
+            };
+            if elapsed.as_secs() > self.state.round_duration {
+                self.push_update(
+                    Recipients::Single(ctx.sender_id),
+                    GameUpdate::Error {
+                        message: "Time expired for this round".into(),
+                        client_msg_id: client_msg_id.clone(),
+                    },
+                );
+                return;
+            }
+            let normalized = normalize_answer(&answer);
+            let correct =
+                self.state.correct_answers.as_ref().is_some_and(|answers| {
+                    answers.iter().any(|a| normalize_answer(a) == normalized)
+                });
+            let score_delta = if correct {
+                answer_score(elapsed, self.state.round_duration)
+            } else {
+                0
+            };
+            if correct {
+                player.score += score_delta;
+                player.correct_answers += 1;
+            }
+            player.answers_given += 1;
+            player.round_score = score_delta;
+            player.has_answered = true;
+            player.answer = Some(answer);
+            player.answer_ms = Some(elapsed.as_millis() as u64);
+            player.answer_msg_id = client_msg_id.clone();
+            (player.name.clone(), score_delta)
+        };
+        self.push_update(
+            Recipients::Single(ctx.sender_id),
+            GameUpdate::AnswerAck {
+                score,
+                client_msg_id,
+            },
+        );
+        self.push_update(
+            Recipients::All,
+            GameUpdate::Answered {
+                name: player_name,
+                score,
+            },
+        );
+    }
+
+    fn handle_start_game(&mut self, ctx: EventContext) {
+        if self.state.phase != GamePhase::Lobby && self.state.phase != GamePhase::GameOver {
+            debug!(
+                sender_id = %ctx.sender_id,
+                current_phase = ?self.state.phase,
+                "StartGame rejected: wrong phase"
+            );
+            self.push_update(
+                Recipients::Single(ctx.sender_id),
+                GameUpdate::Error {
+                    message: "Can only start game from lobby or after a finished game".into(),
+                    client_msg_id: None,
+                },
+            );
+            return;
+        }
+
+        let from_phase = self.state.phase;
+
+        // if we came from a finished game, zero everything out
+        if self.state.phase == GamePhase::GameOver {
+            info!(
+                "Lobby {}: Game restarted in same lobby",
+                self.state.join_code
+            );
+            self.reset_for_new_game();
+        }
+
+        self.state.game_record = Some(GameRecord::new(&self.state));
+        self.state.phase = GamePhase::Score;
+        debug!(from = ?from_phase, to = ?self.state.phase, "Phase transition");
+        let ScoreUpdate {
+            scoreboard,
+            round_scores,
+            consecutive_misses,
+            score_changes,
+            scoreboard_seq,
+        } = self.next_score_update();
+        self.push_update(
+            Recipients::All,
+            GameUpdate::StateDelta {
+                phase: Some(GamePhase::Score),
+                question_type: None,
+                question_text: None,
+                alternatives: None,
+                colors: None,
+                question_time_remaining_ms: None,
+                server_time_ms: None,
+                answered_player_names: None,
+                scoreboard,
+                round_scores,
+                consecutive_misses,
+                score_changes,
+                scoreboard_seq,
+                admin_extra: None,
+                lobby_locked: None,
+            },
+        );
+        self.send_upcoming_questions();
+    }
+
+    fn handle_start_round(&mut self, ctx: EventContext) {
+        if self.state.phase != GamePhase::Score {
+            debug!(
+                sender_id = %ctx.sender_id,
+                current_phase = ?self.state.phase,
+                "StartRound rejected: wrong phase"
+            );
+            self.push_update(
+                Recipients::Single(ctx.sender_id),
+                GameUpdate::Error {
+                    message: "Can only start round from score phase".into(),
+                    client_msg_id: None,
+                },
+            );
+            return;
+        }
+        if self.state.current_question_index >= self.state.all_questions.len() {
+            debug!(
+                question_index = self.state.current_question_index,
+                total_questions = self.state.all_questions.len(),
+                "StartRound rejected: no more questions"
+            );
+            self.push_update(
+                Recipients::Single(self.state.admin_id),
+                GameUpdate::Error {
+                    message: "No more questions available. Please end the game.".into(),
+                    client_msg_id: None,
+                },
+            );
+            return;
+        }
+        // Reset all players for the new round.
+        for player in self.state.players.values_mut() {
+            player.has_answered = false;
+            player.answer = None;
+            player.answer_ms = None;
+            player.answer_msg_id = None;
+            player.round_score = 0;
+        }
+        match self.setup_round() {
+            Ok(()) => {
+                let Some(ref question) = self.state.current_question else {
+                    self.push_update(
+                        Recipients::Single(self.state.admin_id),
+                        GameUpdate::Error {
+                            message: "Invalid state: question not set".into(),
+                            client_msg_id: None,
+                        },
+                    );
+                    return;
+                };
+                let question_type: Arc<str> = Arc::from(question.get_question_type());
+                let question_text = question.question_text.clone();
+                let admin_question = question.clone();
+                self.state.phase = GamePhase::Question;
+                self.state.round_start_time = Some(ctx.timestamp);
+                self.state.lifetime.rounds_played += 1;
+                self.state.round_interrupted_for.clear();
+                debug!(
+                    from = ?GamePhase::Score,
+                    to = ?GamePhase::Question,
+                    question_index = self.state.current_question_index,
+                    "Phase transition"
+                );
+                let ScoreUpdate {
+                    scoreboard,
+                    round_scores,
+                    consecutive_misses,
+                    score_changes,
+                    scoreboard_seq,
+                } = self.next_score_update();
+                // With per-player shuffling the question follows separately
+                // for each recipient.
+                let shared = !self.state.shuffle_per_player;
+                self.push_update(
+                    Recipients::All,
+                    GameUpdate::StateDelta {
+                        phase: Some(GamePhase::Question),
+                        question_type: shared.then(|| question_type.clone()),
+                        question_text: question_text.clone().filter(|_| shared),
+                        alternatives: shared.then(|| self.state.current_alternatives.clone()),
+                        colors: self
+                            .colors_for(&self.state.current_alternatives)
+                            .filter(|_| shared),
+                        question_time_remaining_ms: Some(self.state.round_duration * 1000),
+                        server_time_ms: Some(unix_time_ms_at(ctx.timestamp)),
+                        answered_player_names: Some(Vec::new()),
+                        scoreboard,
+                        round_scores,
+                        consecutive_misses,
+                        score_changes,
+                        scoreboard_seq,
+                        admin_extra: None,
+                        lobby_locked: None,
+                    },
+                );
+                if !shared {
+                    self.send_own_alternatives(question_type, question_text);
+                }
+                self.push_update(
+                    Recipients::Single(self.state.admin_id),
+                    GameUpdate::AdminInfo {
+                        current_question: admin_question,
+                    },
+                );
+            }
+            Err(msg) => {
+                self.push_update(
+                    Recipients::Single(ctx.sender_id),
+                    GameUpdate::Error {
+                        message: Arc::from(msg),
+                        client_msg_id: None,
+                    },
+                );
+            }
+        }
+    }
+
+    fn handle_end_round(&mut self, ctx: EventContext) {
+        if self.state.phase != GamePhase::Question {
+            debug!(
+                sender_id = %ctx.sender_id,
+                current_phase = ?self.state.phase,
+                "EndRound rejected: wrong phase"
+            );
+            self.push_update(
+                Recipients::Single(ctx.sender_id),
+                GameUpdate::Error {
+                    message: "Can only end round from question phase".into(),
+                    client_msg_id: None,
+                },
+            );
+            return;
+        }
+
+        self.record_round();
+        self.send_round_summary();
+
+        // AFK check: if a player has not answered, increment their consecutive misses
+        for player in self.state.players.values_mut() {
+            if !player.has_answered {
+                player.consecutive_misses += 1;
+            } else {
+                player.consecutive_misses = 0;
+            }
+        }
+
+        self.state.current_question = None;
+        self.state.current_question_index += 1;
+        self.state.current_alternatives.clear();
+        self.state.correct_answers = None;
+        self.state.phase = GamePhase::Score;
+        debug!(from = ?GamePhase::Question, to = ?GamePhase::Score, "Phase transition");
+        let ScoreUpdate {
+            scoreboard,
+            round_scores,
+            consecutive_misses,
+            score_changes,
+            scoreboard_seq,
+        } = self.next_score_update();
+        self.push_update(
+            Recipients::All,
+            GameUpdate::StateDelta {
+                phase: Some(GamePhase::Score),
+                question_type: None,
+                question_text: None,
+                alternatives: None,
+                colors: None,
+                question_time_remaining_ms: None,
+                server_time_ms: None,
+                answered_player_names: None,
+                scoreboard,
+                round_scores,
+                consecutive_misses,
+                score_changes,
+                scoreboard_seq,
+                admin_extra: None,
+                lobby_locked: None,
+            },
+        );
+        self.send_upcoming_questions();
+    }
+
+    fn handle_skip_question(&mut self, ctx: EventContext) {
+        if self.state.phase != GamePhase::Score {
+            self.push_update(
+                Recipients::Single(ctx.sender_id),
+                GameUpdate::Error {
+                    message: "Can only skip question during score phase".into(),
+                    client_msg_id: None,
+                },
+            );
+            return;
+        }
+        self.state.current_question = None;
+        self.state.current_question_index += 1;
+        self.state.current_alternatives.clear();
+        self.state.correct_answers = None;
+        self.send_upcoming_questions();
+    }
+
+    fn handle_kick_player(&mut self, ctx: EventContext, target_player_name: Arc<str>) {
+        // Find the player ID based on the name
+        let target_player_id = self
+            .state
+            .players
+            .iter()
+            .find(|(_, p)| p.name.as_ref() == target_player_name.as_ref())
+            .map(|(id, _)| *id);
+        let target_player_id = match target_player_id {
+            Some(id) => id,
+            None => {
+                self.push_update(
+                    Recipients::Single(ctx.sender_id), // Send error to admin
+                    GameUpdate::Error {
+                        message: Arc::from(format!("Player '{}' not found.", target_player_name)),
+                        client_msg_id: None,
+                    },
+                );
+                return;
+            }
+        };
+
+        // Get player name for notification before removing
+        if let Some(kicked_player) = self.state.players.get(&target_player_id) {
+            let kicked_player_name = kicked_player.name.clone();
+
+            // Notify the kicked player specifically - BEFORE removing them
+            self.push_update(
+                Recipients::Single(target_player_id),
+                GameUpdate::PlayerKicked {
+                    reason: Arc::from("Kicked by admin"),
+                },
+            );
+
+            // Now we can safely remove the player
+            if let Some(mut player) = self.state.players.remove(&target_player_id) {
+                end_connection(&mut player.tx, &self.state.broadcaster, EndReason::Kicked);
+            }
+
+            // Notify remaining players
+            self.push_update(
+                Recipients::All,
+                GameUpdate::PlayerLeft {
+                    name: kicked_player_name,
+                },
+            );
+        } else {
+            // This case should theoretically not happen if find worked, but handle defensively
+            self.push_update(
+                Recipients::Single(ctx.sender_id), // Send error to admin
+                GameUpdate::Error {
+                    message: Arc::from(format!(
+                        "Failed to remove player '{}' internally.",
+                        target_player_name
+                    )),
+                    client_msg_id: None,
+                },
+            );
+        }
+    }
+
+    fn handle_end_game(&mut self, _ctx: EventContext, reason: Arc<str>) {
+        let from_phase = self.state.phase;
+        self.state.phase = GamePhase::GameOver;
+        debug!(from = ?from_phase, to = ?GamePhase::GameOver, "Phase transition");
+        self.log_game_state(&format!("Game ended: {}", reason));
+        self.finish_game_record(&reason);
+        self.push_update(
+            Recipients::All,
+            GameUpdate::GameOver {
+                final_scores: self.get_scoreboard(),
+                reason,
+            },
+        );
+    }
+
+    /// Completes the record of the running game, if any, so it is saved
+    /// whether the game ended normally or the lobby closed mid-game.
+    fn finish_game_record(&mut self, reason: &Arc<str>) {
+        let Some(mut record) = self.state.game_record.take() else {
+            return;
+        };
+        let mut final_scores: Vec<PlayerScore> = self
+            .get_scoreboard()
+            .into_iter()
+            .map(|(name, score)| PlayerScore { name, score })
+            .collect();
+        final_scores.sort_by(|a, b| b.score.cmp(&a.score).then(a.name.cmp(&b.name)));
+        record.final_scores = final_scores;
+        record.ended_at = Some(Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true));
+        record.end_reason = Some(reason.clone());
+        self.state.last_game = Some(record.clone());
+        self.state.finished_game = Some(record);
+    }
+
+    fn handle_close_game(&mut self, _ctx: EventContext, reason: Arc<str>) {
+        let from_phase = self.state.phase;
+        self.state.phase = GamePhase::GameClosed;
+        debug!(from = ?from_phase, to = ?GamePhase::GameClosed, "Phase transition");
+        self.log_game_state(&format!("Game closed: {}", reason));
+        self.finish_game_record(&reason);
+        self.push_update(Recipients::All, GameUpdate::GameClosed { reason });
+    }
+
+    fn handle_approve_reclaim(&mut self, ctx: EventContext, player_name: Arc<str>) {
+        let Some(player) = self
+            .state
+            .players
+            .values_mut()
+            .find(|p| p.name.as_ref() == normalize_player_name(&player_name) && p.tx.is_none())
+        else {
+            self.push_update(
+                Recipients::Single(ctx.sender_id),
+                GameUpdate::Error {
+                    message: Arc::from(format!("No disconnected player named '{}'.", player_name)),
+                    client_msg_id: None,
+                },
+            );
+            return;
+        };
+        player.reclaim_approved = true;
+    }
+
+    fn handle_lock_lobby(&mut self, ctx: EventContext, locked: bool) {
+        if self.state.locked == locked {
+            return;
+        }
+        self.state.locked = locked;
+        info!(
+            "Lobby {}: {} by admin",
+            self.state.join_code,
+            if locked { "locked" } else { "unlocked" }
+        );
+        self.push_update(
+            Recipients::Single(ctx.sender_id),
+            GameUpdate::StateDelta {
+                phase: None,
+                question_type: None,
+                question_text: None,
+                alternatives: None,
+                colors: None,
+                question_time_remaining_ms: None,
+                server_time_ms: None,
+                answered_player_names: None,
+                scoreboard: None,
+                round_scores: None,
+                consecutive_misses: None,
+                score_changes: None,
+                scoreboard_seq: None,
+                admin_extra: None,
+                lobby_locked: Some(locked),
+            },
+        );
+    }
+
+    fn handle_resync_scoreboard(&mut self, ctx: EventContext) {
+        let ScoreUpdate {
+            scoreboard,
+            round_scores,
+            consecutive_misses,
+            score_changes,
+            scoreboard_seq,
+        } = self.full_scores();
+        self.push_update(
+            Recipients::Single(ctx.sender_id),
+            GameUpdate::StateDelta {
+                phase: None,
+                question_type: None,
+                question_text: None,
+                alternatives: None,
+                colors: None,
+                question_time_remaining_ms: None,
+                server_time_ms: None,
+                answered_player_names: None,
+                scoreboard,
+                round_scores,
+                consecutive_misses,
+                score_changes,
+                scoreboard_seq,
+                admin_extra: None,
+                lobby_locked: None,
+            },
+        );
+    }
+
+    /// Tells the admin which track comes next. Sent ahead of the upcoming
+    /// question list, and only in the score phase when that track is next up.
+    fn send_prefetch_next(&mut self) {
+        if self.state.phase != GamePhase::Score {
+            return;
+        }
+        let Some(youtube_id) = self
+            .state
+            .shuffled_question_indices
+            .get(self.state.current_question_index)
+            .map(|&idx| self.state.all_questions[idx].youtube_id.clone())
+            .filter(|id| !id.is_empty())
+        else {
+            return;
+        };
+        self.push_update(
+            Recipients::Single(self.state.admin_id),
+            GameUpdate::PrefetchNext {
+                youtube_id,
+                start_seconds: 0,
+            },
+        );
+    }
+
+    fn send_upcoming_questions(&mut self) {
+        self.send_prefetch_next();
+        let upcoming = self.get_upcoming_questions(self.state.upcoming_preview);
+        if !upcoming.is_empty() {
+            self.push_update(
+                Recipients::Single(self.state.admin_id),
+                GameUpdate::AdminNextQuestions {
+                    upcoming_questions: upcoming,
+                },
+            );
+        }
+    }
+
+    fn handle_request_upcoming(&mut self, count: usize) {
+        self.set_upcoming_preview(count);
+        let upcoming_questions = self.get_upcoming_questions(self.state.upcoming_preview);
+        self.push_update(
+            Recipients::Single(self.state.admin_id),
+            GameUpdate::AdminNextQuestions { upcoming_questions },
+        );
+    }
+
+    /// Whether the upcoming questions may be changed: not while one is being
+    /// asked, nor after the game.
+    fn check_can_change_upcoming(&mut self, ctx: &EventContext) -> bool {
+        if matches!(self.state.phase, GamePhase::Lobby | GamePhase::Score) {
+            return true;
+        }
+        self.push_update(
+            Recipients::Single(ctx.sender_id),
+            GameUpdate::Error {
+                message: "Can only change upcoming questions in the lobby or score phase".into(),
+                client_msg_id: None,
+            },
+        );
+        false
+    }
+
+    fn handle_queue_question(&mut self, ctx: EventContext, question_id: i64) {
+        if !self.check_can_change_upcoming(&ctx) {
+            return;
+        }
+        let Some(idx) = self
+            .state
+            .all_questions
+            .iter()
+            .position(|q| q.id == question_id)
+        else {
+            self.push_update(
+                Recipients::Single(ctx.sender_id),
+                GameUpdate::Error {
+                    message: Arc::from(format!("Question {question_id} not found.")),
+                    client_msg_id: None,
+                },
+            );
+            return;
+        };
+        let current = self.state.current_question_index;
+        let order = &mut self.state.shuffled_question_indices;
+        match order.iter().position(|&i| i == idx) {
+            Some(pos) if pos < current => {
+                self.push_update(
+                    Recipients::Single(ctx.sender_id),
+                    GameUpdate::Error {
+                        message: Arc::from(format!("Question {question_id} was already played.")),
+                        client_msg_id: None,
+                    },
+                );
+                return;
+            }
+            Some(pos) => {
+                order.remove(pos);
+                order.insert(current, idx);
+            }
+            // Outside the lobby's question set; the admin may still pick it.
+            None => order.insert(current.min(order.len()), idx),
+        }
+        self.send_upcoming_questions();
+    }
+
+    fn handle_reorder_upcoming(&mut self, ctx: EventContext, question_ids: Vec<i64>) {
+        if !self.check_can_change_upcoming(&ctx) {
+            return;
+        }
+        let current = self
+            .state
+            .current_question_index
+            .min(self.state.shuffled_question_indices.len());
+        let all_questions = &self.state.all_questions;
+        let mut upcoming = self.state.shuffled_question_indices[current..].to_vec();
+        let mut reordered = Vec::with_capacity(upcoming.len());
+        for question_id in &question_ids {
+            let Some(pos) = upcoming
+                .iter()
+                .position(|&idx| all_questions[idx].id == *question_id)
+            else {
+                self.push_update(
+                    Recipients::Single(ctx.sender_id),
+                    GameUpdate::Error {
+                        message: Arc::from(format!("Question {question_id} is not upcoming.")),
+                        client_msg_id: None,
+                    },
+                );
+                return;
+            };
+            reordered.push(upcoming.remove(pos));
+        }
+        reordered.append(&mut upcoming);
+        self.state.shuffled_question_indices.truncate(current);
+        self.state.shuffled_question_indices.append(&mut reordered);
+        self.send_upcoming_questions();
+    }
+
+    fn handle_inject_question(
+        &mut self,
+        ctx: EventContext,
+        question_text: Arc<str>,
+        options: Vec<GameQuestionOption>,
+    ) {
+        if !self.check_can_change_upcoming(&ctx) {
+            return;
+        }
+        if let Err(message) = validate_injected_question(&question_text, &options) {
+            self.push_update(
+                Recipients::Single(ctx.sender_id),
+                GameUpdate::Error {
+                    message: Arc::from(message),
+                    client_msg_id: None,
+                },
+            );
+            return;
+        }
+        // Negative ids can't clash with stored questions.
+        let id = self
+            .state
+            .all_questions
+            .iter()
+            .map(|q| q.id)
+            .min()
+            .unwrap_or(0)
+            .min(0)
+            - 1;
+        let question_text: Arc<str> = Arc::from(question_text.trim());
+        let question = GameQuestion {
+            id,
+            media_id: 0,
+            question_type: QuestionType::Text,
+            question_text: Some(question_text.clone()),
+            title: question_text,
+            artist: None,
+            youtube_id: Arc::from(""),
+            spotify_uri: None,
+            options: options
+                .into_iter()
+                .map(|opt| GameQuestionOption {
+                    option: Arc::from(opt.option.trim()),
+                    is_correct: opt.is_correct,
+                })
+                .collect(),
+        };
+        // Copies the shared question list once; other lobbies keep theirs.
+        let questions = Arc::make_mut(&mut self.state.all_questions);
+        questions.push(question);
+        let idx = questions.len() - 1;
+        let current = self
+            .state
+            .current_question_index
+            .min(self.state.shuffled_question_indices.len());
+        self.state.shuffled_question_indices.insert(current, idx);
+        info!(
+            "Lobby {}: admin injected question {}",
+            self.state.join_code, id
+        );
+        self.send_upcoming_questions();
+    }
+
+    /// Adds the round being ended to the game record.
+    fn record_round(&mut self) {
+        let (Some(record), Some(question)) =
+            (&mut self.state.game_record, &self.state.current_question)
+        else {
+            return;
+        };
+        let mut results: Vec<RoundResult> = self
+            .state
+            .players
+            .values()
+            .map(|p| RoundResult {
+                name: p.name.clone(),
+                answer: p.answer.clone(),
+                round_score: p.round_score,
+            })
+            .collect();
+        results.sort_by(|a, b| a.name.cmp(&b.name));
+        record.rounds.push(RoundRecord {
+            question_id: question.id,
+            title: question.title.clone(),
+            correct_answers: self.state.correct_answers.clone().unwrap_or_default(),
+            results,
+        });
+    }
+
+    /// Tells the admin when each player answered the round being ended.
+    fn send_round_summary(&mut self) {
+        let correct_answers = self.state.correct_answers.as_deref().unwrap_or_default();
+        let mut answers: Vec<AnswerTiming> = self
+            .state
+            .players
+            .values()
+            .map(|p| AnswerTiming {
+                name: p.name.clone(),
+                correct: p.answer.as_ref().is_some_and(|answer| {
+                    let answer = normalize_answer(answer);
+                    correct_answers
+                        .iter()
+                        .any(|a| normalize_answer(a) == answer)
+                }),
+                answer: p.answer.clone(),
+                elapsed_ms: p.answer_ms,
+            })
+            .collect();
+        // Fastest first, players who did not answer last.
+        answers.sort_by(|a, b| {
+            (a.elapsed_ms.is_none(), a.elapsed_ms, &a.name).cmp(&(
+                b.elapsed_ms.is_none(),
+                b.elapsed_ms,
+                &b.name,
+            ))
+        });
+        self.push_update(
+            Recipients::Single(self.state.admin_id),
+            GameUpdate::AdminRoundSummary { answers },
+        );
+    }
+
+    fn setup_round(&mut self) -> Result<(), String> {
+        if self.state.current_question_index >= self.state.shuffled_question_indices.len() {
+            return Err("No more questions available".to_string());
+        }
+        let shuffled_idx = self.state.shuffled_question_indices[self.state.current_question_index];
+        let next_question = &self.state.all_questions[shuffled_idx];
+        self.state.current_question = Some(next_question.clone());
+        self.state.correct_answers = Some(next_question.get_correct_answer());
+        self.state.current_alternatives =
+            next_question.generate_round_alternatives(&self.state.color_weights);
+        if let Some(ref correct_answers) = self.state.correct_answers {
+            for answer in correct_answers {
+                if !self.state.current_alternatives.contains(answer) {
+                    self.state.current_alternatives.push(answer.clone());
+                }
+            }
+        }
+        self.state.current_alternatives.sort();
+        self.state.current_alternatives.dedup();
+        fastrand::shuffle(&mut self.state.current_alternatives);
+        for player in self.state.players.values_mut() {
+            player.alternatives = None;
+        }
+        Ok(())
+    }
+
+    fn reset_for_new_game(&mut self) {
+        // scramble the questions again
+        self.state.shuffled_question_indices = {
+            let mut v: Vec<usize> = (0..self.state.all_questions.len()).collect();
+            fastrand::shuffle(&mut v);
+            v
+        };
+        self.state.current_question_index = 0;
+        self.state.current_question = None;
+        self.state.current_alternatives.clear();
+        self.state.correct_answers = None;
+
+        // wipe every player’s scoreboard
+        for p in self.state.players.values_mut() {
+            p.score = 0;
+            p.round_score = 0;
+            p.has_answered = false;
+            p.answer = None;
+            p.answer_ms = None;
+            p.answer_msg_id = None;
+        }
+    }
+
+    pub fn get_upcoming_questions(&self, count: usize) -> Vec<GameQuestion> {
+        if self.state.current_question_index >= self.state.shuffled_question_indices.len() {
+            return Vec::new();
+        }
+        let start = self.state.current_question_index;
+        let end = std::cmp::min(start + count, self.state.shuffled_question_indices.len());
+        self.state.shuffled_question_indices[start..end]
+            .iter()
+            .map(|&idx| self.state.all_questions[idx].clone())
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::delivery::Inbox;
+    use crate::question::{
+        Color, GameQuestion, GameQuestionOption, QuestionType, baseline_weights,
+    };
+    use proptest::prelude::*;
+    use serde::Deserialize;
+    use std::time::Duration;
+
+    async fn receive_and_deserialize<T>(rx: &mut Inbox) -> T
+    where
+        T: for<'de> Deserialize<'de> + std::fmt::Debug,
+    {
+        let payload = rx
+            .recv()
+            .await
+            .expect("Test failed: Channel closed unexpectedly or failed to receive message.");
+        let json = std::str::from_utf8(&payload).expect("Test failed: Payload is not UTF-8");
+        serde_json::from_str::<T>(json).unwrap_or_else(|_| {
+            panic!(
+                "Test failed: Failed to deserialize received JSON: '{}'",
+                json
+            )
+        })
+    }
+
+    fn create_test_questions() -> Vec<GameQuestion> {
+        vec![
+            // Color question
+            GameQuestion {
+                id: 1,
+                media_id: 0,
+                question_type: QuestionType::Color,
+                question_text: None,
+                title: Arc::from("What color is predominantly used in this video?"),
+                artist: Some(Arc::from("Test Artist")),
+                youtube_id: Arc::from("test123"),
+                spotify_uri: None,
+                options: vec![
+                    GameQuestionOption {
+                        option: Arc::from("Red"),
+                        is_correct: true,
+                    },
+                    GameQuestionOption {
+                        option: Arc::from("Blue"),
+                        is_correct: false,
+                    },
+                ],
+            },
+            // Text question
+            GameQuestion {
+                id: 2,
+                media_id: 0,
+                question_type: QuestionType::Text,
+                question_text: None,
+                title: Arc::from("What is the main theme of this video?"),
+                artist: Some(Arc::from("Test Artist")),
+                youtube_id: Arc::from("test456"),
+                spotify_uri: None,
+                options: vec![
+                    GameQuestionOption {
+                        option: Arc::from("Love"),
+                        is_correct: true,
+                    },
+                    GameQuestionOption {
+                        option: Arc::from("War"),
+                        is_correct: false,
+                    },
+                    GameQuestionOption {
+                        option: Arc::from("Peace"),
+                        is_correct: false,
+                    },
+                ],
+            },
+            // Year question
+            GameQuestion {
+                id: 3,
+                media_id: 0,
+                question_type: QuestionType::Year,
+                question_text: None,
+                title: Arc::from("When was this video released?"),
+                artist: Some(Arc::from("Test Artist")),
+                youtube_id: Arc::from("test789"),
+                spotify_uri: None,
+                options: vec![GameQuestionOption {
+                    option: Arc::from("2020"),
+                    is_correct: true,
+                }],
+            },
+        ]
+    }
+
+    fn setup_test_game() -> (GameEngine, Uuid) {
+        let admin_id = Uuid::new_v4();
+        let questions = Arc::new(create_test_questions());
+        let color_weights = baseline_weights();
+        let (tx, _rx) = tokio::sync::mpsc::channel(128);
+        let admin_conn_id = Uuid::new_v4();
+        let mut engine = GameEngine::new(
+            admin_id,
+            Arc::from("TEST"),
+            questions,
+            color_weights,
+            None,
+            30,
+        );
+        engine.update_player_connection(admin_id, tx, admin_conn_id);
+
+        (engine, admin_id)
+    }
+
+    fn add_test_player(engine: &mut GameEngine, name: &str) -> Uuid {
+        let player_id = Uuid::new_v4();
+        let (tx, _rx) = tokio::sync::mpsc::channel(128);
+        let conn_id = Uuid::new_v4();
+        engine.add_player(player_id, name.to_string()).unwrap();
+        engine.update_player_connection(player_id, tx, conn_id);
+        player_id
+    }
+
+    fn add_test_player_with_channel(engine: &mut GameEngine, name: &str) -> (Uuid, Inbox) {
+        let player_id = add_test_player(engine, name);
+        let (tx, rx) = tokio::sync::mpsc::channel(128);
+        let conn_id = Uuid::new_v4();
+        engine.update_player_connection(player_id, tx, conn_id);
+        (player_id, Inbox::new(rx))
+    }
+
+    #[tokio::test]
+    async fn test_panic_closes_only_the_lobby() {
+        let (mut engine, admin_id) = setup_test_game();
+        let (_player_id, mut rx) = add_test_player_with_channel(&mut engine, "Player1");
+        // Points past the question list, so setting up the round panics.
+        engine.state.shuffled_question_indices = vec![999];
+
+        for action in [GameAction::StartGame, GameAction::StartRound] {
+            engine.process_event(GameEvent {
+                context: EventContext {
+                    sender_id: admin_id,
+                    timestamp: Instant::now(),
+                },
+                action,
+            });
+        }
+        assert_eq!(engine.get_phase(), GamePhase::GameClosed);
+        assert!(engine.is_finished());
+
+        let _: GameUpdate = receive_and_deserialize(&mut rx).await;
+        let closed: GameUpdate = receive_and_deserialize(&mut rx).await;
+        assert_eq!(
+            closed,
+            GameUpdate::GameClosed {
+                reason: "internal error".into()
+            }
+        );
+    }
+
+    #[test]
+    fn test_full_game_flow() {
+        let (mut engine, admin_id) = setup_test_game();
+        let player1_id = add_test_player(&mut engine, "Player1");
+        let player2_id = add_test_player(&mut engine, "Player2");
+
+        let now = Instant::now();
+
+        // Start game
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: now,
+            },
+            action: GameAction::StartGame,
+        });
+        assert_eq!(engine.state.phase, GamePhase::Score);
+
+        // Play through all questions
+        for _ in 0..create_test_questions().len() {
+            // Start round
+            engine.process_event(GameEvent {
+                context: EventContext {
+                    sender_id: admin_id,
+                    timestamp: now,
+                },
+                action: GameAction::StartRound,
+            });
+            assert_eq!(engine.state.phase, GamePhase::Question);
+
+            // Players answer
+            let correct_answer = engine.state.correct_answers.as_ref().unwrap()[0].clone();
+
+            // Player 1 answers correctly quickly
+            engine.process_event(GameEvent {
+                context: EventContext {
+                    sender_id: player1_id,
+                    timestamp: now + Duration::from_secs(1),
+                },
+                action: GameAction::Answer {
+                    answer: correct_answer.to_string(),
+                    client_msg_id: None,
+                },
+            });
+
+            // Player 2 answers correctly but slower
+            engine.process_event(GameEvent {
+                context: EventContext {
+                    sender_id: player2_id,
+                    timestamp: now + Duration::from_secs(5),
+                },
+                action: GameAction::Answer {
+                    answer: correct_answer.to_string(),
+                    client_msg_id: None,
+                },
+            });
+
+            // Verify both players answered
+            assert!(engine.state.players[&player1_id].has_answered);
+            assert!(engine.state.players[&player2_id].has_answered);
+
+            // Verify Player 1 got more points (answered faster)
+            assert!(
+                engine.state.players[&player1_id].round_score
+                    > engine.state.players[&player2_id].round_score
+            );
+
+            // End round
+            engine.process_event(GameEvent {
+                context: EventContext {
+                    sender_id: admin_id,
+                    timestamp: now,
+                },
+                action: GameAction::EndRound,
+            });
+            assert_eq!(engine.state.phase, GamePhase::Score);
+        }
+
+        // End game
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: now,
+            },
+            action: GameAction::EndGame {
+                reason: Arc::from("Game completed"),
+            },
+        });
+        assert_eq!(engine.state.phase, GamePhase::GameOver);
+
+        let num_questions = create_test_questions().len() as u32;
+        let stats = engine.get_detailed_stats();
+        assert_eq!(stats.total_answers, 2 * num_questions);
+        assert_eq!(stats.players[0].name.as_ref(), "Player1");
+        assert_eq!(stats.players[0].answers_given, num_questions);
+        assert_eq!(stats.players[0].correct_answers, num_questions);
+        assert_eq!(stats.players[0].connections, 1);
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        let (mut engine, admin_id) = setup_test_game();
+        let player_id = add_test_player(&mut engine, "Player1");
+        let now = Instant::now();
+        for action in [GameAction::StartGame, GameAction::StartRound] {
+            engine.process_event(GameEvent {
+                context: EventContext {
+                    sender_id: admin_id,
+                    timestamp: now,
+                },
+                action,
+            });
+        }
+        engine.state.players.get_mut(&player_id).unwrap().score = 1234;
+
+        let snapshot = engine.to_snapshot().unwrap();
+        let json = serde_json::to_string(&snapshot).unwrap();
+        let snapshot: LobbySnapshot = serde_json::from_str(&json).unwrap();
+        assert_eq!(snapshot, engine.to_snapshot().unwrap());
+
+        // Drop the first question in play order from the question data
+        let removed_id = snapshot.question_ids[0];
+        let questions: Vec<GameQuestion> = create_test_questions()
+            .into_iter()
+            .filter(|q| q.id != removed_id)
+            .collect();
+        let restored =
+            GameEngine::from_snapshot(snapshot.clone(), Arc::new(questions), baseline_weights());
+
+        assert_eq!(restored.state.phase, GamePhase::Score);
+        // Everyone is told about the cut-short round when they reconnect.
+        assert_eq!(
+            restored.state.round_interrupted_for,
+            HashSet::from([admin_id, player_id])
+        );
+        assert_eq!(restored.state.admin_id, admin_id);
+        assert_eq!(restored.state.players[&player_id].score, 1234);
+        assert_eq!(restored.state.current_question_index, 0);
+        assert_eq!(
+            restored.state.shuffled_question_indices.len(),
+            snapshot.question_ids.len() - 1
+        );
+
+        engine.state.phase = GamePhase::GameClosed;
+        assert!(engine.to_snapshot().is_none());
+    }
+
+    #[test]
+    fn test_game_record() {
+        let (mut engine, admin_id) = setup_test_game();
+        let player_id = add_test_player(&mut engine, "Player1");
+        add_test_player(&mut engine, "Player2");
+        let event = |sender_id, action| GameEvent {
+            context: EventContext {
+                sender_id,
+                timestamp: Instant::now(),
+            },
+            action,
+        };
+        engine.process_event(event(admin_id, GameAction::StartGame));
+        engine.process_event(event(admin_id, GameAction::StartRound));
+        let correct = engine.state.correct_answers.clone().unwrap()[0].clone();
+        engine.process_event(event(
+            player_id,
+            GameAction::Answer {
+                answer: correct.to_string(),
+                client_msg_id: None,
+            },
+        ));
+        engine.process_event(event(admin_id, GameAction::EndRound));
+        engine.process_event(event(admin_id, GameAction::SkipQuestion));
+        assert_eq!(engine.take_finished_game(), None);
+        assert_eq!(engine.final_results(), None);
+
+        // A restart mid-game keeps the rounds played so far.
+        let snapshot = engine.to_snapshot().unwrap();
+        let mut engine = GameEngine::from_snapshot(
+            snapshot,
+            Arc::new(create_test_questions()),
+            baseline_weights(),
+        );
+        engine.process_event(event(
+            admin_id,
+            GameAction::EndGame {
+                reason: Arc::from("Done"),
+            },
+        ));
+        let record = engine.take_finished_game().unwrap();
+        assert_eq!(engine.take_finished_game(), None);
+        assert_eq!(engine.final_results(), Some(&record));
+        assert!(record.id.ends_with("-TEST"));
+        assert_eq!(record.end_reason.as_deref(), Some("Done"));
+        assert!(record.ended_at.is_some());
+        assert_eq!(record.rounds.len(), 1);
+        let round = &record.rounds[0];
+        assert!(round.correct_answers.contains(&correct));
+        assert_eq!(round.results[0].name.as_ref(), "Player1");
+        assert_eq!(round.results[0].answer, Some(correct));
+        assert!(round.results[0].round_score > 0);
+        assert_eq!(round.results[1].answer, None);
+        assert_eq!(record.final_scores[0].name.as_ref(), "Player1");
+        assert_eq!(record.final_scores[0].score, round.results[0].round_score);
+        assert_eq!(record.final_scores[1].score, 0);
+    }
+
+    #[test]
+    fn test_closing_mid_game_records_game() {
+        let (mut engine, admin_id) = setup_test_game();
+        add_test_player(&mut engine, "Player1");
+        let event = |action| GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action,
+        };
+        engine.process_event(event(GameAction::StartGame));
+        engine.process_event(event(GameAction::StartRound));
+        engine.process_event(event(GameAction::CloseGame {
+            reason: Arc::from("Closing early"),
+        }));
+        let record = engine.take_finished_game().unwrap();
+        assert_eq!(record.end_reason.as_deref(), Some("Closing early"));
+        assert!(record.ended_at.is_some());
+        assert_eq!(record.final_scores.len(), 1);
+        assert_eq!(engine.final_results(), Some(&record));
+
+        // A game that already ended isn't recorded again when closed.
+        let (mut engine, admin_id) = setup_test_game();
+        let event = |action| GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action,
+        };
+        engine.process_event(event(GameAction::StartGame));
+        engine.process_event(event(GameAction::EndGame {
+            reason: Arc::from("Done"),
+        }));
+        assert!(engine.take_finished_game().is_some());
+        engine.close_by_operator(Arc::from("Closed"));
+        assert_eq!(engine.take_finished_game(), None);
+    }
+
+    #[tokio::test]
+    async fn test_lifetime_summary() {
+        let (mut engine, admin_id) = setup_test_game();
+        let (player_id, mut rx) = add_test_player_with_channel(&mut engine, "Player1");
+        let (kicked_id, mut kicked_rx) = add_test_player_with_channel(&mut engine, "Player2");
+        let now = Instant::now();
+        for action in [
+            GameAction::KickPlayer {
+                player_name: Arc::from("Player2"),
+            },
+            GameAction::StartGame,
+            GameAction::StartRound,
+            GameAction::EndRound,
+            GameAction::SkipQuestion,
+        ] {
+            engine.process_event(GameEvent {
+                context: EventContext {
+                    sender_id: admin_id,
+                    timestamp: now,
+                },
+                action,
+            });
+        }
+        let connection_id = engine.state.players[&player_id].connection_id.unwrap();
+        engine.clear_player_connection(player_id, connection_id);
+        // A connection replaced by a newer one does not count again.
+        engine.clear_player_connection(player_id, connection_id);
+
+        // The admin's receiver was dropped, so only the players count.
+        let mut received = 0;
+        for inbox in [&mut rx, &mut kicked_rx] {
+            while let Ok(Some(_)) =
+                tokio::time::timeout(Duration::from_millis(20), inbox.recv()).await
+            {
+                received += 1;
+            }
+        }
+        assert!(!engine.state.players.contains_key(&kicked_id));
+        let summary = engine.lifetime_summary();
+        assert_eq!(summary.peak_players, 2);
+        assert_eq!(summary.rounds_played, 1);
+        assert_eq!(summary.messages_sent, received);
+        assert_eq!(summary.disconnects, 1);
+
+        let snapshot = engine.to_snapshot().unwrap();
+        let restored = GameEngine::from_snapshot(
+            snapshot,
+            Arc::new(create_test_questions()),
+            baseline_weights(),
+        );
+        assert_eq!(restored.state.lifetime, engine.state.lifetime);
+    }
+
+    #[test]
+    fn test_question_types() {
+        let (mut engine, admin_id) = setup_test_game();
+        add_test_player(&mut engine, "Player1");
+
+        // Start game
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::StartGame,
+        });
+
+        // Test each question type
+        for _ in 0..create_test_questions().len() {
+            engine.process_event(GameEvent {
+                context: EventContext {
+                    sender_id: admin_id,
+                    timestamp: Instant::now(),
+                },
+                action: GameAction::StartRound,
+            });
+
+            // Verify alternatives are generated correctly
+            match engine
+                .state
+                .current_question
+                .as_ref()
+                .unwrap()
+                .question_type
+            {
+                QuestionType::Color => {
+                    assert_eq!(engine.state.current_alternatives.len(), 6);
+                    assert!(
+                        engine
+                            .state
+                            .current_alternatives
+                            .iter()
+                            .all(|c| c.parse::<Color>().is_ok())
+                    );
+                    let colors = engine
+                        .colors_for(&engine.state.current_alternatives)
+                        .unwrap();
+                    let names: Vec<_> = colors.into_iter().map(|c| c.name).collect();
+                    assert_eq!(names, engine.state.current_alternatives);
+                }
+                QuestionType::Year => {
+                    assert_eq!(engine.state.current_alternatives.len(), 5);
+                    assert!(
+                        engine
+                            .state
+                            .current_alternatives
+                            .iter()
+                            .all(|y| y.parse::<i32>().is_ok())
+                    );
+                    assert!(
+                        engine
+                            .colors_for(&engine.state.current_alternatives)
+                            .is_none()
+                    );
+                }
+                _ => {
+                    assert!(!engine.state.current_alternatives.is_empty());
+                    assert!(
+                        engine
+                            .colors_for(&engine.state.current_alternatives)
+                            .is_none()
+                    );
+                }
+            }
+
+            engine.process_event(GameEvent {
+                context: EventContext {
+                    sender_id: admin_id,
+                    timestamp: Instant::now(),
+                },
+                action: GameAction::EndRound,
+            });
+        }
+    }
+
+    #[tokio::test]
+    async fn test_error_conditions() {
+        let (mut engine, admin_id) = setup_test_game();
+        let player_id = add_test_player(&mut engine, "Player1");
+
+        // Test answering before game starts
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: player_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::Answer {
+                answer: "test".to_string(),
+                client_msg_id: None,
+            },
+        });
+        assert!(!engine.state.players[&player_id].has_answered);
+
+        // Start game properly
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::StartGame,
+        });
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::StartRound,
+        });
+
+        // Test double answer
+        let answer = engine.state.current_alternatives[0].clone();
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: player_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::Answer {
+                answer: answer.to_string(),
+                client_msg_id: None,
+            },
+        });
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: player_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::Answer {
+                answer: answer.to_string(),
+                client_msg_id: None,
+            },
+        });
+
+        // Test late answer
+        let late_player_id = add_test_player(&mut engine, "LatePlayer");
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: late_player_id,
+                timestamp: Instant::now() + Duration::from_secs(31),
+            },
+            action: GameAction::Answer {
+                answer: "test".to_string(),
+                client_msg_id: None,
+            },
+        });
+        assert!(!engine.state.players[&late_player_id].has_answered);
+    }
+
+    #[test]
+    fn test_admin_features() {
+        let (mut engine, admin_id) = setup_test_game();
+        let player_id = add_test_player(&mut engine, "Player1");
+
+        // Test player can't start game
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: player_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::StartGame,
+        });
+        assert_eq!(engine.state.phase, GamePhase::Lobby);
+
+        // Test admin can start game
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::StartGame,
+        });
+        assert_eq!(engine.state.phase, GamePhase::Score);
+
+        // Verify upcoming questions are available
+        let upcoming = engine.get_upcoming_questions(3);
+        assert!(!upcoming.is_empty());
+        assert!(upcoming.len() <= 3);
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::CloseGame {
+                reason: Arc::from("Test close"),
+            },
+        });
+    }
+
+    #[tokio::test]
+    async fn test_request_upcoming() {
+        let (mut engine, admin_id) = setup_test_game();
+        let (admin_tx, admin_rx) = tokio::sync::mpsc::channel(128);
+        let mut admin_rx = Inbox::new(admin_rx);
+        engine.update_player_connection(admin_id, admin_tx, Uuid::new_v4());
+        let player_id = add_test_player(&mut engine, "Player1");
+        let request = |sender_id, count| GameEvent {
+            context: EventContext {
+                sender_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::RequestUpcoming { count },
+        };
+        assert_eq!(engine.state.upcoming_preview, DEFAULT_UPCOMING_PREVIEW);
+
+        engine.process_event(request(player_id, 10));
+        assert_eq!(engine.state.upcoming_preview, DEFAULT_UPCOMING_PREVIEW);
+
+        engine.process_event(request(admin_id, 1));
+        assert_eq!(engine.state.upcoming_preview, 1);
+        match receive_and_deserialize(&mut admin_rx).await {
+            GameUpdate::AdminNextQuestions { upcoming_questions } => {
+                assert_eq!(upcoming_questions.len(), 1);
+            }
+            other => panic!("Expected AdminNextQuestions, got {:?}", other),
+        }
+
+        engine.process_event(request(admin_id, 1000));
+        assert_eq!(engine.state.upcoming_preview, MAX_UPCOMING_PREVIEW);
+        match receive_and_deserialize(&mut admin_rx).await {
+            GameUpdate::AdminNextQuestions { upcoming_questions } => {
+                assert_eq!(upcoming_questions.len(), engine.state.all_questions.len());
+            }
+            other => panic!("Expected AdminNextQuestions, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_shuffle_per_player() {
+        let (mut engine, admin_id) = setup_test_game();
+        engine.set_shuffle_per_player(true);
+        let (player_id, mut rx) = add_test_player_with_channel(&mut engine, "Player1");
+        for action in [GameAction::StartGame, GameAction::StartRound] {
+            engine.process_event(GameEvent {
+                context: EventContext {
+                    sender_id: admin_id,
+                    timestamp: Instant::now(),
+                },
+                action,
+            });
+        }
+
+        // The phase change is shared; the alternatives follow on their own.
+        loop {
+            match receive_and_deserialize(&mut rx).await {
+                GameUpdate::StateDelta {
+                    phase: Some(GamePhase::Question),
+                    alternatives,
+                    ..
+                } => {
+                    assert!(alternatives.is_none());
+                    break;
+                }
+                GameUpdate::StateDelta { .. } => {}
+                other => panic!("Expected StateDelta, got {:?}", other),
+            }
+        }
+        let own = match receive_and_deserialize(&mut rx).await {
+            GameUpdate::StateDelta {
+                phase: None,
+                question_type: Some(_),
+                alternatives: Some(alternatives),
+                ..
+            } => alternatives,
+            other => panic!("Expected own alternatives, got {:?}", other),
+        };
+        let mut sorted = own.clone();
+        sorted.sort();
+        let mut shared = engine.state.current_alternatives.clone();
+        shared.sort();
+        assert_eq!(sorted, shared);
+        // A reconnect during the round gets the same order.
+        assert_eq!(engine.alternatives_for(player_id), own);
+        assert_eq!(
+            engine.alternatives_for(admin_id),
+            engine.state.current_alternatives
+        );
+
+        // Answers are matched by value, whatever position they were shown at.
+        let correct = engine.state.correct_answers.as_ref().unwrap()[0].clone();
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: player_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::Answer {
+                answer: correct.to_string(),
+                client_msg_id: None,
+            },
+        });
+        assert!(engine.state.players[&player_id].score > 0);
+    }
+
+    #[test]
+    fn test_queue_and_reorder_upcoming() {
+        let (mut engine, admin_id) = setup_test_game();
+        let admin_event = |action| GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action,
+        };
+        let upcoming_ids = |engine: &GameEngine| -> Vec<i64> {
+            engine
+                .get_upcoming_questions(engine.state.all_questions.len())
+                .iter()
+                .map(|q| q.id)
+                .collect()
+        };
+        let ids = upcoming_ids(&engine);
+        let last = *ids.last().unwrap();
+
+        engine.process_event(admin_event(GameAction::QueueQuestion { question_id: last }));
+        assert_eq!(upcoming_ids(&engine)[0], last);
+        assert_eq!(upcoming_ids(&engine).len(), ids.len());
+
+        let reversed: Vec<i64> = ids.iter().rev().copied().collect();
+        engine.process_event(admin_event(GameAction::ReorderUpcoming {
+            question_ids: reversed.clone(),
+        }));
+        assert_eq!(upcoming_ids(&engine), reversed);
+
+        // Unknown ids leave the order untouched.
+        engine.process_event(admin_event(GameAction::ReorderUpcoming {
+            question_ids: vec![ids[0], 999],
+        }));
+        engine.process_event(admin_event(GameAction::QueueQuestion { question_id: 999 }));
+        assert_eq!(upcoming_ids(&engine), reversed);
+
+        // Not while a question is being asked.
+        engine.process_event(admin_event(GameAction::StartGame));
+        engine.process_event(admin_event(GameAction::StartRound));
+        assert_eq!(engine.state.phase, GamePhase::Question);
+        let before = upcoming_ids(&engine);
+        engine.process_event(admin_event(GameAction::QueueQuestion {
+            question_id: *before.last().unwrap(),
+        }));
+        assert_eq!(upcoming_ids(&engine), before);
+
+        // Played questions can't be queued again.
+        engine.process_event(admin_event(GameAction::EndRound));
+        assert_eq!(engine.state.phase, GamePhase::Score);
+        let before = upcoming_ids(&engine);
+        engine.process_event(admin_event(GameAction::QueueQuestion {
+            question_id: reversed[0],
+        }));
+        assert_eq!(upcoming_ids(&engine), before);
+
+        // The score phase is where the host picks what plays next.
+        engine.process_event(admin_event(GameAction::QueueQuestion {
+            question_id: *before.last().unwrap(),
+        }));
+        assert_eq!(upcoming_ids(&engine)[0], *before.last().unwrap());
+    }
+
+    #[test]
+    fn test_inject_question() {
+        let (mut engine, admin_id) = setup_test_game();
+        let player_id = add_test_player(&mut engine, "Player1");
+        let event = |sender_id, action| GameEvent {
+            context: EventContext {
+                sender_id,
+                timestamp: Instant::now(),
+            },
+            action,
+        };
+        let option = |option: &str, is_correct| GameQuestionOption {
+            option: Arc::from(option),
+            is_correct,
+        };
+        let question_count = engine.state.all_questions.len();
+        let inject = |options| GameAction::InjectQuestion {
+            question_text: Arc::from(" Whose birthday is it? "),
+            options,
+        };
+
+        // Rejected: no correct option, duplicates, too few options.
+        for options in [
+            vec![option("Alice", false), option("Bob", false)],
+            vec![option("Alice", true), option(" alice", false)],
+            vec![option("Alice", true)],
+        ] {
+            engine.process_event(event(admin_id, inject(options)));
+        }
+        engine.process_event(event(
+            player_id,
+            inject(vec![option("Alice", true), option("Bob", false)]),
+        ));
+        assert_eq!(engine.state.all_questions.len(), question_count);
+
+        engine.process_event(event(
+            admin_id,
+            inject(vec![option("Alice", true), option("Bob", false)]),
+        ));
+        assert_eq!(engine.state.all_questions.len(), question_count + 1);
+        let upcoming = engine.get_upcoming_questions(1);
+        assert!(upcoming[0].id < 0);
+        assert_eq!(
+            upcoming[0].question_text.as_deref(),
+            Some("Whose birthday is it?")
+        );
+
+        // Survives a restart, though the stored questions don't include it.
+        let restored = GameEngine::from_snapshot(
+            engine.to_snapshot().unwrap(),
+            Arc::new(create_test_questions()),
+            baseline_weights(),
+        );
+        assert_eq!(restored.get_upcoming_questions(1)[0], upcoming[0]);
+
+        // Played and scored like any other round.
+        engine.process_event(event(admin_id, GameAction::StartGame));
+        engine.process_event(event(admin_id, GameAction::StartRound));
+        assert_eq!(engine.state.phase, GamePhase::Question);
+        assert_eq!(
+            engine.state.correct_answers.as_deref(),
+            Some(&[Arc::from("Alice")][..])
+        );
+        engine.process_event(event(
+            player_id,
+            GameAction::Answer {
+                answer: "Alice".to_string(),
+                client_msg_id: None,
+            },
+        ));
+        assert!(engine.state.players[&player_id].round_score > 0);
+    }
+
+    #[tokio::test]
+    async fn test_player_reconnect_lobby() {
+        let (mut engine, admin_id) = setup_test_game();
+        // Keep admin receiver alive so broadcasts don't fail
+        let (admin_tx, _admin_rx) = tokio::sync::mpsc::channel(128);
+        let admin_conn_id = Uuid::new_v4();
+        engine.update_player_connection(admin_id, admin_tx, admin_conn_id);
+
+        let player_id = add_test_player(&mut engine, "Player1");
+        // Stay in lobby; do not start the game
+        let player_initial_state = engine.state.players.get(&player_id).unwrap().clone();
+        let initial_conn_id = player_initial_state
+            .connection_id
+            .expect("connection id should be set");
+
+        // Simulate disconnection (remove the connection)
+        engine.clear_player_connection(player_id, initial_conn_id);
+
+        // Re-add the player with a new connection
+        let (player_tx, player_rx) = tokio::sync::mpsc::channel(128);
+        let mut player_rx = Inbox::new(player_rx);
+        let reconnect_conn_id = Uuid::new_v4();
+        engine.update_player_connection(player_id, player_tx, reconnect_conn_id);
+        let heartbeat = Heartbeat {
+            interval_ms: 60_000,
+            max_missed: 4,
+        };
+        engine.set_heartbeat(player_id, heartbeat);
+
+        // Reconnect
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: player_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::Connect,
+        });
+
+        // Verify Connected message
+        match receive_and_deserialize(&mut player_rx).await {
+            GameUpdate::Connected {
+                player_id: pid,
+                heartbeat: sent,
+                connection_id,
+                ..
+            } => {
+                assert_eq!(pid, player_id, "Correct player ID in Connected");
+                assert_eq!(connection_id, Some(reconnect_conn_id));
+                assert_eq!(sent, heartbeat);
+                assert_eq!(sent.timeout(), Duration::from_secs(300));
+            }
+            other => panic!("Expected Connected, got {:?}", other),
+        }
+        // Verify StateDelta message
+        match receive_and_deserialize(&mut player_rx).await {
+            GameUpdate::StateDelta {
+                phase,
+                scoreboard,
+                admin_extra,
+                ..
+            } => {
+                assert_eq!(phase, Some(GamePhase::Lobby), "Correct phase");
+                assert!(scoreboard.is_some(), "Scoreboard should be present");
+                assert!(admin_extra.is_none(), "Admin extra should not be present");
+                // Additional checks for player state (important!)
+                let player_state = engine.state.players.get(&player_id).unwrap();
+                assert_eq!(
+                    player_state.has_answered, player_initial_state.has_answered,
+                    "has_answered should be preserved"
+                );
+                assert_eq!(
+                    player_state.score, player_initial_state.score,
+                    "Score should be preserved"
+                );
+                assert_eq!(
+                    player_state.round_score, player_initial_state.round_score,
+                    "Round score should be preserved"
+                );
+                assert_eq!(
+                    player_state.answer, player_initial_state.answer,
+                    "Answer should be preserved"
+                );
+            }
+            other => panic!("Expected StateDelta, got {:?}", other),
+        }
+
+        player_rx.close();
+
+        // Ensure admin connection is unaffected
+        assert!(
+            engine.state.admin.tx.is_some(),
+            "Admin connection should remain intact"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_player_reconnect_score() {
+        let (mut engine, admin_id) = setup_test_game();
+        let player_id = add_test_player(&mut engine, "Player1");
+
+        // Start the game and a round
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::StartGame,
+        });
+
+        let player_initial_state = engine.state.players.get(&player_id).unwrap().clone();
+        let initial_conn_id = player_initial_state
+            .connection_id
+            .expect("connection id should be set");
+
+        // Simulate disconnection (remove the connection)
+        engine.clear_player_connection(player_id, initial_conn_id);
+
+        // Re-add the player with a new connection
+        let (player_tx, player_rx) = tokio::sync::mpsc::channel(128);
+        let mut player_rx = Inbox::new(player_rx);
+        let reconnection_id = Uuid::new_v4();
+        engine.update_player_connection(player_id, player_tx, reconnection_id);
+
+        // Reconnect
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: player_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::Connect,
+        });
+
+        // Verify Connected message
+        match receive_and_deserialize(&mut player_rx).await {
+            GameUpdate::Connected { player_id: pid, .. } => {
+                assert_eq!(pid, player_id, "Correct player ID in Connected");
+            }
+            other => panic!("Expected Connected, got {:?}", other),
+        }
+
+        // Verify StateDelta message
+        match receive_and_deserialize(&mut player_rx).await {
+            GameUpdate::StateDelta {
+                phase,
+                scoreboard,
+                admin_extra,
+                ..
+            } => {
+                assert_eq!(phase, Some(GamePhase::Score), "Correct phase");
+                assert!(scoreboard.is_some(), "Scoreboard should be present");
+                assert!(admin_extra.is_none(), "Admin extra should not be present");
+
+                // Additional checks for player state (important!)
+                let player_state = engine.state.players.get(&player_id).unwrap();
+                assert_eq!(
+                    player_state.has_answered, player_initial_state.has_answered,
+                    "has_answered should be preserved"
+                );
+                assert_eq!(
+                    player_state.score, player_initial_state.score,
+                    "Score should be preserved"
+                );
+                assert_eq!(
+                    player_state.round_score, player_initial_state.round_score,
+                    "Round score should be preserved"
+                );
+                assert_eq!(
+                    player_state.answer, player_initial_state.answer,
+                    "Answer should be preserved"
+                );
+            }
+            other => panic!("Expected StateDelta, got {:?}", other),
+        }
+
+        player_rx.close();
+    }
+
+    #[test]
+    fn test_name_validation_errors() {
+        let empty_names = std::iter::empty();
+
+        // Test too short name
+        assert!(matches!(
+            validate_player_name("a", empty_names.clone()),
+            Err(NameValidationError::TooShort)
+        ));
+
+        // Test too long name
+        assert!(matches!(
+            validate_player_name(&"a".repeat(17), empty_names.clone()),
+            Err(NameValidationError::TooLong)
+        ));
+
+        // Test invalid characters
+        assert!(matches!(
+            validate_player_name("Invalid@Name", empty_names.clone()),
+            Err(NameValidationError::InvalidCharacters)
+        ));
+
+        assert!(matches!(
+            validate_player_name("H0st", empty_names.clone()),
+            Err(NameValidationError::Reserved)
+        ));
+        assert!(matches!(
+            validate_player_name("Shit", empty_names.clone()),
+            Err(NameValidationError::Inappropriate)
+        ));
+
+        // Test duplicate name
+        let existing_names = ["TestName"];
+        assert!(matches!(
+            validate_player_name("TestName", existing_names.iter().copied()),
+            Err(NameValidationError::AlreadyTaken)
+        ));
+    }
+
+    #[test]
+    fn test_multibyte_names() {
+        let empty_names = std::iter::empty();
+        // Two bytes each in UTF-8, but 16 letters.
+        assert!(validate_player_name(&"Å".repeat(16), empty_names.clone()).is_ok());
+        assert!(matches!(
+            validate_player_name(&"Ö".repeat(17), empty_names.clone()),
+            Err(NameValidationError::TooLong)
+        ));
+        // "Å" as "A" plus a combining ring counts as one letter.
+        let decomposed = "A\u{30a}sa";
+        assert!(validate_player_name(decomposed, empty_names.clone()).is_ok());
+        assert!(matches!(
+            validate_player_name("A\u{30a}", empty_names.clone()),
+            Err(NameValidationError::TooShort)
+        ));
+        // ...and is the same name as the precomposed one.
+        assert!(matches!(
+            validate_player_name(decomposed, ["\u{c5}sa"].into_iter()),
+            Err(NameValidationError::AlreadyTaken)
+        ));
+        // Few letters, but stacked with marks.
+        let stacked = format!("Zo{}e", "\u{301}".repeat(40));
+        assert!(matches!(
+            validate_player_name(&stacked, empty_names.clone()),
+            Err(NameValidationError::TooLong)
+        ));
+        assert!(validate_player_name("Łukasz Żółć", empty_names.clone()).is_ok());
+        assert!(validate_player_name("太郎", empty_names).is_ok());
+
+        let (mut engine, _admin_id) = setup_test_game();
+        let player_id = Uuid::new_v4();
+        engine
+            .add_player(player_id, format!(" {decomposed} "))
+            .unwrap();
+        assert_eq!(engine.state.players[&player_id].name.as_ref(), "\u{c5}sa");
+    }
+
+    #[test]
+    fn test_name_validation_error_messages() {
+        // Test all variants of NameValidationError and their messages
+        let errors = vec![
+            (
+                NameValidationError::TooShort,
+                "Name must be at least 2 characters long.",
+            ),
+            (
+                NameValidationError::TooLong,
+                "Name cannot be longer than 16 characters.",
+            ),
+            (
+                NameValidationError::InvalidCharacters,
+                "Name can only contain letters, numbers, spaces, and the symbols: _ - .",
+            ),
+            (
+                NameValidationError::AlreadyTaken,
+                "This name is already taken.",
+            ),
+            (NameValidationError::Reserved, "This name is reserved."),
+            (
+                NameValidationError::Inappropriate,
+                "This name is not allowed.",
+            ),
+        ];
+
+        // Test to_message() method
+        for (error, expected_message) in errors.iter() {
+            assert_eq!(error.to_message(), *expected_message);
+        }
+
+        // Test Display implementation
+        for (error, expected_message) in errors {
+            assert_eq!(format!("{}", error), expected_message);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connection_handling() {
+        let (mut engine, _admin_id) = setup_test_game();
+        let player_id = add_test_player(&mut engine, "Player1");
+
+        // Test leave for regular player
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: player_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::Leave,
+        });
+        assert!(!engine.state.players.contains_key(&player_id));
+
+        // Test leave for non-existent player
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: Uuid::new_v4(),
+                timestamp: Instant::now(),
+            },
+            action: GameAction::Leave,
+        });
+    }
+
+    #[test]
+    fn test_game_state_transitions() {
+        let (mut engine, admin_id) = setup_test_game();
+        add_test_player(&mut engine, "Player1");
+
+        // Test skip question
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::StartGame,
+        });
+
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::SkipQuestion,
+        });
+
+        // Test close game
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::CloseGame {
+                reason: Arc::from("Test close"),
+            },
+        });
+    }
+
+    #[test]
+    fn test_game_timeout() {
+        let (mut engine, _) = setup_test_game();
+        assert!(!engine.is_finished());
+
+        // Set last message to more than an hour ago
+        engine.state.last_lobby_message = Some(Instant::now() - Duration::from_secs(3601));
+        assert!(engine.is_finished());
+
+        // Test GameOver phase
+        engine.state.phase = GamePhase::GameOver;
+        assert!(engine.is_finished());
+
+        // Test last_update
+        assert_eq!(engine.last_update(), engine.state.last_lobby_message);
+    }
+
+    #[test]
+    fn test_question_set_handling() {
+        let admin_id = Uuid::new_v4();
+        let questions = Arc::new(create_test_questions());
+
+        let question_set = QuestionSet {
+            id: 1,
+            question_ids: vec![1, 2], // Only include first two questions
+            name: Arc::from("Test Set"),
+        };
+
+        let engine = GameEngine::new(
+            admin_id,
+            Arc::from("TEST"),
+            questions,
+            baseline_weights(),
+            Some(&question_set),
+            30,
+        );
+
+        assert_eq!(engine.state.shuffled_question_indices.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_admin_reconnect() {
+        let (mut engine, admin_id) = setup_test_game();
+
+        // Create channel to capture admin messages
+        let (admin_tx, admin_rx) = tokio::sync::mpsc::channel(128);
+        let mut admin_rx = Inbox::new(admin_rx);
+        let admin_conn_id = Uuid::new_v4();
+        engine.update_player_connection(admin_id, admin_tx, admin_conn_id);
+
+        // First test: admin reconnects in lobby, should get upcoming questions
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::Connect,
+        });
+
+        // Should receive Connected message first
+        match receive_and_deserialize(&mut admin_rx).await {
+            GameUpdate::Connected { player_id, .. } => {
+                assert_eq!(player_id, admin_id);
+            }
+            other => panic!("Expected Connected message, got {:?}", other),
+        }
+
+        // Should receive StateDelta with admin_extra next
+        match receive_and_deserialize(&mut admin_rx).await {
+            GameUpdate::StateDelta { admin_extra, .. } => {
+                assert!(admin_extra.is_some(), "Admin should receive admin_extra");
+            }
+            other => panic!("Expected StateDelta message, got {:?}", other),
+        }
+
+        // Second test: admin reconnects during Question phase
+        engine.state.phase = GamePhase::Question;
+        let test_question = engine.state.all_questions[0].clone();
+        engine.state.current_question = Some(test_question.clone());
+
+        // Create new channel for clean message capture
+        let (admin_tx2, admin_rx2) = tokio::sync::mpsc::channel(128);
+        let mut admin_rx2 = Inbox::new(admin_rx2);
+        let admin_conn_id2 = Uuid::new_v4();
+        engine.update_player_connection(admin_id, admin_tx2, admin_conn_id2);
+
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::Connect,
+        });
+
+        // Should receive Connected message first
+        match receive_and_deserialize(&mut admin_rx2).await {
+            GameUpdate::Connected { player_id, .. } => {
+                assert_eq!(player_id, admin_id);
+            }
+            other => panic!("Expected Connected message, got {:?}", other),
+        }
+
+        // Should receive StateDelta next
+        match receive_and_deserialize(&mut admin_rx2).await {
+            GameUpdate::StateDelta { admin_extra, .. } => {
+                assert!(admin_extra.is_some(), "Admin should receive admin_extra");
+            }
+            other => panic!("Expected StateDelta message, got {:?}", other),
+        }
+
+        // Should receive current question info
+        match receive_and_deserialize(&mut admin_rx2).await {
+            GameUpdate::AdminInfo { current_question } => {
+                assert_eq!(current_question.id, test_question.id);
+            }
+            other => panic!("Expected AdminInfo message, got {:?}", other),
+        }
+        admin_rx2.close();
+        admin_rx.close();
+    }
+
+    #[tokio::test]
+    async fn test_admin_leave_message() {
+        let (mut engine, admin_id) = setup_test_game();
+
+        // Add a player
+        let (_player_id, mut player_rx) = add_test_player_with_channel(&mut engine, "TestPlayer");
+
+        // Admin leaves
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::Leave,
+        });
+
+        // Use tokio::spawn to handle message receiving in parallel
+        let message: GameUpdate = receive_and_deserialize(&mut player_rx).await;
+
+        // Verify the message
+        match message {
+            GameUpdate::GameClosed { reason } => {
+                assert_eq!(reason.as_ref(), "Host left the game");
+            }
+            other => panic!("Expected GameClosed message, got {:?}", other),
+        }
+        // The connection is closed after it.
+        assert_eq!(player_rx.recv().await, None);
+        assert_eq!(player_rx.ended(), Some(EndReason::GameClosed));
+
+        // State verifications
+        assert_eq!(engine.state.phase, GamePhase::GameClosed);
+    }
+
+    #[test]
+    fn test_error_paths() {
+        let (mut engine, admin_id) = setup_test_game();
+
+        // Test starting round in wrong phase
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::StartRound,
+        });
+        assert_eq!(engine.state.phase, GamePhase::Lobby);
+
+        // Start game and test ending round in wrong phase
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::StartGame,
+        });
+
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::EndRound,
+        });
+        assert_eq!(engine.state.phase, GamePhase::Score);
+
+        // Test answer with no round_start_time
+        let player_id = add_test_player(&mut engine, "Player1");
+        engine.state.round_start_time = None;
+        engine.state.phase = GamePhase::Question;
+
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: player_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::Answer {
+                answer: "test".to_string(),
+                client_msg_id: None,
+            },
+        });
+    }
+
+    #[tokio::test]
+    async fn test_round_start_errors() {
+        let (mut engine, admin_id) = setup_test_game();
+
+        // First get into Score phase properly
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::StartGame,
+        });
+        assert_eq!(
+            engine.state.phase,
+            GamePhase::Score,
+            "Should be in Score phase before test"
+        );
+
+        // Set current_question_index past the end to trigger "no more questions" error
+        engine.state.current_question_index = engine.state.shuffled_question_indices.len();
+
+        // Store the current index to verify it doesn't change
+        let initial_index = engine.state.current_question_index;
+
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::StartRound,
+        });
+
+        // Verify error conditions:
+        // 1. Phase should not have changed
+        assert_eq!(engine.state.phase, GamePhase::Score);
+        // 2. Question index should not have changed
+        assert_eq!(engine.state.current_question_index, initial_index);
+        // 3. No current question should be set
+        assert!(engine.state.current_question.is_none());
+        // 4. No alternatives should be set
+        assert!(engine.state.current_alternatives.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_skip_question() {
+        let (mut engine, admin_id) = setup_test_game();
+
+        // Start game to get into Score phase
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::StartGame,
+        });
+        assert_eq!(
+            engine.state.phase,
+            GamePhase::Score,
+            "Game should start in Score phase"
+        );
+
+        // Store initial state
+        let initial_index = engine.state.current_question_index;
+
+        // Skip question in correct phase
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::SkipQuestion,
+        });
+
+        // Verify skip worked:
+        assert_eq!(
+            engine.state.phase,
+            GamePhase::Score,
+            "Phase should remain Score"
+        );
+        assert_eq!(
+            engine.state.current_question_index,
+            initial_index + 1,
+            "Question index should increment"
+        );
+        assert!(
+            engine.state.current_question.is_none(),
+            "Current question should be cleared"
+        );
+        assert!(
+            engine.state.current_alternatives.is_empty(),
+            "Alternatives should be cleared"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_skip_question_errors() {
+        let (mut engine, admin_id) = setup_test_game();
+
+        // Start game to get into proper state
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::StartGame,
+        });
+        assert_eq!(
+            engine.state.phase,
+            GamePhase::Score,
+            "Game should start in Score phase"
+        );
+
+        // Move to Question phase (wrong phase for skip)
+        engine.state.phase = GamePhase::Question;
+
+        // Store state before attempting skip
+        let initial_index = engine.state.current_question_index;
+        let initial_question = engine.state.current_question.clone();
+        let initial_alternatives = engine.state.current_alternatives.clone();
+
+        // Try to skip in wrong phase
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::SkipQuestion,
+        });
+
+        // Verify nothing changed due to error:
+        assert_eq!(
+            engine.state.phase,
+            GamePhase::Question,
+            "Phase should not change"
+        );
+        assert_eq!(
+            engine.state.current_question_index, initial_index,
+            "Question index should not change"
+        );
+        assert_eq!(
+            engine.state.current_question, initial_question,
+            "Current question should not change"
+        );
+        assert_eq!(
+            engine.state.current_alternatives, initial_alternatives,
+            "Alternatives should not change"
+        );
+    }
+
+    #[test]
+    fn test_setup_round_errors() {
+        let (mut engine, _) = setup_test_game();
+
+        // Test setup_round error path
+        engine.state.current_question_index = engine.state.shuffled_question_indices.len();
+        let result = engine.setup_round();
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_error_handling_branches() {
+        let (mut engine, admin_id) = setup_test_game();
+        let player_id = add_test_player(&mut engine, "Player1");
+
+        // Test admin leave
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::Leave,
+        });
+
+        // Assert game was closed after admin left
+        assert_eq!(
+            engine.state.phase,
+            GamePhase::GameClosed,
+            "Game should be closed after admin leaves"
+        );
+
+        // Test answer with non-existent player
+        let invalid_id = Uuid::new_v4();
+        engine.state.phase = GamePhase::Question;
+        engine.state.round_start_time = Some(Instant::now());
+
+        // Store state before invalid player attempts to answer
+        let initial_scoreboard = engine.get_scoreboard();
+
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: invalid_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::Answer {
+                answer: "test".to_string(),
+                client_msg_id: None,
+            },
+        });
+
+        // Assert nothing changed after invalid player's answer
+        assert_eq!(
+            engine.get_scoreboard(),
+            initial_scoreboard,
+            "Scoreboard should not change from invalid player's answer"
+        );
+        assert!(
+            !engine.state.players.contains_key(&invalid_id),
+            "Invalid player should not be added"
+        );
+
+        // Test connection removal cases
+        let conn_id = engine.state.players[&player_id]
+            .connection_id
+            .expect("connection id should be present");
+        engine.clear_player_connection(player_id, conn_id);
+        assert!(
+            engine
+                .state
+                .players
+                .get(&player_id)
+                .and_then(|p| p.tx.clone())
+                .is_none(),
+            "Connection should be cleared"
+        );
+
+        // Test message sending to removed connection
+        // Note: Since we can't directly observe message sending, we at least verify the system doesn't crash
+        let pre_update_state = engine.state.clone();
+
+        // Test Single recipient
+        engine.push_update(
+            Recipients::Single(player_id),
+            GameUpdate::Error {
+                message: "Test error".into(),
+                client_msg_id: None,
+            },
+        );
+        assert_eq!(
+            engine.state.phase, pre_update_state.phase,
+            "Game state should not change after failed message"
+        );
+
+        // Test Multiple recipients
+        engine.push_update(
+            Recipients::Multiple(vec![player_id]),
+            GameUpdate::Error {
+                message: "Test error".into(),
+                client_msg_id: None,
+            },
+        );
+        assert_eq!(
+            engine.state.phase, pre_update_state.phase,
+            "Game state should not change after failed multiple message"
+        );
+
+        // Test AllExcept
+        engine.push_update(
+            Recipients::_AllExcept(vec![admin_id]),
+            GameUpdate::Error {
+                message: "Test error".into(),
+                client_msg_id: None,
+            },
+        );
+        assert_eq!(
+            engine.state.phase, pre_update_state.phase,
+            "Game state should not change after failed except message"
+        );
+
+        // Test All
+        engine.push_update(
+            Recipients::All,
+            GameUpdate::Error {
+                message: "Test error".into(),
+                client_msg_id: None,
+            },
+        );
+        assert_eq!(
+            engine.state.phase, pre_update_state.phase,
+            "Game state should not change after failed broadcast"
+        );
+
+        assert!(
+            engine
+                .state
+                .players
+                .get(&player_id)
+                .and_then(|p| p.tx.clone())
+                .is_none(),
+            "Connection should remain cleared"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_regular_player_connect() {
+        let (mut engine, _admin_id) = setup_test_game();
+
+        // Add a player with message capture
+        let (player_id, mut player_rx) = add_test_player_with_channel(&mut engine, "Player1");
+
+        // Test connect
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: player_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::Connect,
+        });
+
+        // Verify player receives correct messages
+        match receive_and_deserialize(&mut player_rx).await {
+            GameUpdate::Connected {
+                player_id: pid,
+                name,
+                ..
+            } => {
+                assert_eq!(pid, player_id);
+                assert_eq!(name.as_ref(), "Player1");
+            }
+            other => panic!("Expected Connected message, got {:?}", other),
+        }
+
+        match receive_and_deserialize(&mut player_rx).await {
+            GameUpdate::StateDelta {
+                phase, admin_extra, ..
+            } => {
+                assert_eq!(phase, Some(GamePhase::Lobby));
+                assert!(
+                    admin_extra.is_none(),
+                    "Regular player should not get admin_extra"
+                );
+            }
+            other => panic!("Expected StateDelta, got {:?}", other),
+        }
+        player_rx.close();
+    }
+
+    #[tokio::test]
+    async fn test_admin_connect_during_question() {
+        let (mut engine, admin_id) = setup_test_game();
+        let (admin_tx, admin_rx) = tokio::sync::mpsc::channel(128);
+        let mut admin_rx = Inbox::new(admin_rx);
+        let admin_conn_id = Uuid::new_v4();
+        engine.update_player_connection(admin_id, admin_tx, admin_conn_id);
+
+        // Set up question phase
+        engine.state.phase = GamePhase::Question;
+        let mut question = engine.state.all_questions[0].clone();
+        question.spotify_uri = Some(Arc::from("spotify:track:abc"));
+        engine.state.current_question = Some(question.clone());
+
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::Connect,
+        });
+
+        // Verify admin receives all expected messages
+        match receive_and_deserialize(&mut admin_rx).await {
+            GameUpdate::Connected {
+                player_id: pid,
+                name,
+                ..
+            } => {
+                assert_eq!(pid, admin_id);
+                assert_eq!(name.as_ref(), "Admin");
+            }
+            other => panic!("Expected Connected message, got {:?}", other),
+        }
+
+        match receive_and_deserialize(&mut admin_rx).await {
+            GameUpdate::StateDelta {
+                phase, admin_extra, ..
+            } => {
+                assert_eq!(phase, Some(GamePhase::Question));
+                assert!(admin_extra.is_some(), "Admin should get admin_extra");
+            }
+            other => panic!("Expected StateDelta, got {:?}", other),
+        }
+
+        match receive_and_deserialize(&mut admin_rx).await {
+            GameUpdate::AdminInfo {
+                current_question: q,
+            } => {
+                assert_eq!(q.id, question.id);
+                assert_eq!(q.spotify_uri.as_deref(), Some("spotify:track:abc"));
+            }
+            other => panic!("Expected AdminInfo, got {:?}", other),
+        }
+        admin_rx.close();
+    }
+
+    #[tokio::test]
+    async fn test_player_reconnect_during_question_receives_timer_and_answers() {
+        let (mut engine, admin_id) = setup_test_game();
+        let player1_id = add_test_player(&mut engine, "Player1");
+        let (player2_id, mut player2_rx) = add_test_player_with_channel(&mut engine, "Player2");
+
+        let start_time = Instant::now();
+
+        // Move into question phase
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: start_time,
+            },
+            action: GameAction::StartGame,
+        });
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: start_time,
+            },
+            action: GameAction::StartRound,
+        });
+
+        // Player 1 answers so they appear in the answered list
+        let answer = engine.state.current_alternatives[0].clone();
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: player1_id,
+                timestamp: start_time + Duration::from_secs(3),
+            },
+            action: GameAction::Answer {
+                answer: answer.to_string(),
+                client_msg_id: None,
+            },
+        });
+
+        // Simulate player 2 reconnecting mid-question
+        let initial_conn_id = engine.state.players[&player2_id]
+            .connection_id
+            .expect("connection id should be set");
+        engine.clear_player_connection(player2_id, initial_conn_id);
+        let (reconnect_tx, reconnect_rx) = tokio::sync::mpsc::channel(128);
+        let mut reconnect_rx = Inbox::new(reconnect_rx);
+        let reconnect_conn_id = Uuid::new_v4();
+        engine.update_player_connection(player2_id, reconnect_tx, reconnect_conn_id);
+
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: player2_id,
+                timestamp: start_time + Duration::from_secs(10),
+            },
+            action: GameAction::Connect,
+        });
+
+        // Skip Connected message
+        let _ = receive_and_deserialize::<GameUpdate>(&mut reconnect_rx).await;
+
+        match receive_and_deserialize(&mut reconnect_rx).await {
+            GameUpdate::StateDelta {
+                question_time_remaining_ms,
+                answered_player_names,
+                ..
+            } => {
+                let remaining = question_time_remaining_ms
+                    .expect("question_time_remaining_ms should be present");
+                assert!(
+                    (19_000..=30_000).contains(&remaining),
+                    "remaining time should be within expected window, got {}",
+                    remaining
+                );
+                let answered = answered_player_names
+                    .expect("answered_player_names should be present in question");
+                assert!(
+                    answered.contains(&engine.state.players[&player1_id].name),
+                    "answered list should include player1"
+                );
+                assert!(
+                    !answered.contains(&engine.state.players[&player2_id].name),
+                    "answered list should not include reconnecting player who has not answered"
+                );
+            }
+            other => panic!("Expected StateDelta, got {:?}", other),
+        }
+        player2_rx.close();
+        reconnect_rx.close();
+    }
+
+    #[tokio::test]
+    async fn test_connect_unregistered_player() {
+        let (mut engine, _admin_id) = setup_test_game();
+        let initial_player_count = engine.get_player_count();
+
+        // Attempt to connect with an unknown player id
+        let invalid_id = Uuid::new_v4();
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: invalid_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::Connect,
+        });
+
+        // Ensure state is unchanged
+        assert_eq!(engine.get_player_count(), initial_player_count);
+        assert!(!engine.state.players.contains_key(&invalid_id));
+    }
+
+    #[tokio::test]
+    async fn test_admin_leave() {
+        let (mut engine, admin_id) = setup_test_game();
+
+        // Add a player and capture their messages
+        let (_player_id, mut player_rx) = add_test_player_with_channel(&mut engine, "Player1");
+
+        // Test admin disconnect
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::Leave,
+        });
+
+        // Verify game was closed
+        assert_eq!(engine.state.phase, GamePhase::GameClosed);
+
+        // Verify player received game closed message
+        match receive_and_deserialize(&mut player_rx).await {
+            GameUpdate::GameClosed { reason } => {
+                assert_eq!(reason.as_ref(), "Host left the game");
+            }
+            other => panic!("Expected GameClosed message, got {:?}", other),
+        }
+        player_rx.close();
+    }
+
+    #[tokio::test]
+    async fn test_errors_in_player_locale() {
+        let (mut engine, _admin_id) = setup_test_game();
+        let (swede_id, mut swede_rx) = add_test_player_with_channel(&mut engine, "Swede");
+        let (other_id, mut other_rx) = add_test_player_with_channel(&mut engine, "Other");
+        engine.set_locale(swede_id, Locale::Sv);
+        for (sender_id, rx, expected) in [
+            (swede_id, &mut swede_rx, "Ingen fråga pågår"),
+            (other_id, &mut other_rx, "Not in Question phase"),
+        ] {
+            engine.process_event(GameEvent {
+                context: EventContext {
+                    sender_id,
+                    timestamp: Instant::now(),
+                },
+                action: GameAction::Answer {
+                    answer: "Red".to_string(),
+                    client_msg_id: None,
+                },
+            });
+            match receive_and_deserialize(rx).await {
+                GameUpdate::Error { message, .. } => assert_eq!(message.as_ref(), expected),
+                other => panic!("Expected Error message, got {:?}", other),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_answer_echoes_client_msg_id() {
+        let (mut engine, admin_id) = setup_test_game();
+        let (player_id, mut player_rx) = add_test_player_with_channel(&mut engine, "Player1");
+        let answer = |answer: &str, id: &str| GameEvent {
+            context: EventContext {
+                sender_id: player_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::Answer {
+                answer: answer.to_string(),
+                client_msg_id: Some(id.into()),
+            },
+        };
+
+        engine.process_event(answer("test", "early"));
+        match receive_and_deserialize(&mut player_rx).await {
+            GameUpdate::Error { client_msg_id, .. } => {
+                assert_eq!(client_msg_id.as_deref(), Some("early"));
+            }
+            other => panic!("Expected Error message, got {:?}", other),
+        }
+
+        for action in [GameAction::StartGame, GameAction::StartRound] {
+            engine.process_event(GameEvent {
+                context: EventContext {
+                    sender_id: admin_id,
+                    timestamp: Instant::now(),
+                },
+                action,
+            });
+        }
+        // Anything but an alternative is rejected without using up the turn.
+        engine.process_event(answer("Not an option", "bad"));
+        let rejected = loop {
+            match receive_and_deserialize(&mut player_rx).await {
+                GameUpdate::StateDelta { .. } => {}
+                other => break other,
+            }
+        };
+        assert_eq!(
+            rejected,
+            GameUpdate::AnswerRejected {
+                reason: AnswerValidationError::NotAnAlternative,
+                message: "Answer is not one of the alternatives.".into(),
+                client_msg_id: Some("bad".into()),
+            }
+        );
+        assert!(!engine.state.players[&player_id].has_answered);
+
+        let alternative = engine.state.current_alternatives[0].clone();
+        engine.process_event(answer(&alternative, "a1"));
+        let score = loop {
+            if let GameUpdate::AnswerAck {
+                score,
+                client_msg_id,
+            } = receive_and_deserialize(&mut player_rx).await
+            {
+                assert_eq!(client_msg_id.as_deref(), Some("a1"));
+                break score;
+            }
+        };
+        assert!(matches!(
+            receive_and_deserialize(&mut player_rx).await,
+            GameUpdate::Answered { .. }
+        ));
+
+        // Resending the same answer is acknowledged again, not rejected.
+        engine.process_event(answer(&alternative, "a1"));
+        assert_eq!(
+            receive_and_deserialize::<GameUpdate>(&mut player_rx).await,
+            GameUpdate::AnswerAck {
+                score,
+                client_msg_id: Some("a1".into()),
+            }
+        );
+        assert_eq!(engine.state.players[&player_id].answers_given, 1);
+
+        engine.process_event(answer(&alternative, "a2"));
+        match receive_and_deserialize(&mut player_rx).await {
+            GameUpdate::Error {
+                message,
+                client_msg_id,
+            } => {
+                assert_eq!(message.as_ref(), "Already answered this round");
+                assert_eq!(client_msg_id.as_deref(), Some("a2"));
+            }
+            other => panic!("Expected Error message, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rejected_actions_are_throttled() {
+        let (mut engine, _admin_id) = setup_test_game();
+        let (player_id, mut player_rx) = add_test_player_with_channel(&mut engine, "Player1");
+        let start = Instant::now();
+        let answer = |timestamp| GameEvent {
+            context: EventContext {
+                sender_id: player_id,
+                timestamp,
+            },
+            action: GameAction::Answer {
+                answer: "Red".to_string(),
+                client_msg_id: None,
+            },
+        };
+
+        for _ in 0..MAX_REJECTED_ACTIONS + 5 {
+            engine.process_event(answer(start));
+        }
+        for _ in 0..MAX_REJECTED_ACTIONS {
+            assert!(matches!(
+                receive_and_deserialize(&mut player_rx).await,
+                GameUpdate::Error { .. }
+            ));
+        }
+        let timeout = Duration::from_millis(50);
+        assert!(
+            tokio::time::timeout(timeout, player_rx.recv())
+                .await
+                .is_err(),
+            "Actions past the limit should be dropped"
+        );
+
+        // The limit resets once the window is over.
+        engine.process_event(answer(start + THROTTLE_WINDOW));
+        assert!(matches!(
+            receive_and_deserialize(&mut player_rx).await,
+            GameUpdate::Error { .. }
+        ));
+    }
+
+    #[test]
+    fn test_validate_answer() {
+        let alternatives = [Arc::from("Red"), Arc::from("Blue")];
+        assert_eq!(
+            validate_answer(" blue", &alternatives),
+            Ok(Arc::from("Blue"))
+        );
+        assert_eq!(
+            validate_answer("Green", &alternatives),
+            Err(AnswerValidationError::NotAnAlternative)
+        );
+        assert_eq!(
+            validate_answer("Red\u{0}", &alternatives),
+            Err(AnswerValidationError::InvalidCharacters)
+        );
+        assert_eq!(
+            validate_answer(&"a".repeat(MAX_ANSWER_CHARS + 1), &alternatives),
+            Err(AnswerValidationError::TooLong)
+        );
+    }
+
+    #[test]
+    fn test_answer_is_matched_normalized() {
+        let (mut engine, admin_id) = setup_test_game();
+        let player_id = add_test_player(&mut engine, "Player1");
+        for action in [GameAction::StartGame, GameAction::StartRound] {
+            engine.process_event(GameEvent {
+                context: EventContext {
+                    sender_id: admin_id,
+                    timestamp: Instant::now(),
+                },
+                action,
+            });
+        }
+        let correct = engine.state.correct_answers.as_ref().unwrap()[0].clone();
+
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: player_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::Answer {
+                answer: format!(" {} ", correct.to_uppercase()),
+                client_msg_id: None,
+            },
+        });
+
+        let player = &engine.state.players[&player_id];
+        assert_eq!(player.correct_answers, 1);
+        assert_eq!(player.answer.as_ref(), Some(&correct));
+    }
+
+    #[tokio::test]
+    async fn test_admin_round_summary() {
+        let (mut engine, admin_id) = setup_test_game();
+        let (admin_tx, admin_rx) = tokio::sync::mpsc::channel(128);
+        let mut admin_rx = Inbox::new(admin_rx);
+        engine.update_player_connection(admin_id, admin_tx, Uuid::new_v4());
+        let fast_id = add_test_player(&mut engine, "Fast");
+        let slow_id = add_test_player(&mut engine, "Slow");
+        add_test_player(&mut engine, "Idle");
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::StartGame,
+        });
+        let start = Instant::now();
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: start,
+            },
+            action: GameAction::StartRound,
+        });
+        let correct = engine.state.correct_answers.as_ref().unwrap()[0].to_string();
+        let wrong = engine
+            .state
+            .current_alternatives
+            .iter()
+            .find(|a| a.as_ref() != correct)
+            .unwrap()
+            .to_string();
+        for (sender_id, ms, answer) in [(slow_id, 2500, wrong), (fast_id, 800, correct)] {
+            engine.process_event(GameEvent {
+                context: EventContext {
+                    sender_id,
+                    timestamp: start + Duration::from_millis(ms),
+                },
+                action: GameAction::Answer {
+                    answer,
+                    client_msg_id: None,
+                },
+            });
+        }
+        assert_eq!(engine.state.players[&fast_id].answer_ms, Some(800));
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::EndRound,
+        });
+
+        let answers = loop {
+            if let GameUpdate::AdminRoundSummary { answers } =
+                receive_and_deserialize(&mut admin_rx).await
+            {
+                break answers;
+            }
+        };
+        let summary: Vec<_> = answers
+            .iter()
+            .map(|a| (a.name.as_ref(), a.elapsed_ms, a.correct))
+            .collect();
+        assert_eq!(
+            summary,
+            [
+                ("Fast", Some(800), true),
+                ("Slow", Some(2500), false),
+                ("Idle", None, false),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_score_updates_send_only_changes() {
+        let (mut engine, admin_id) = setup_test_game();
+        let player1_id = add_test_player(&mut engine, "Player1");
+        let (player2_id, mut player2_rx) = add_test_player_with_channel(&mut engine, "Player2");
+        let event = |sender_id, action| GameEvent {
+            context: EventContext {
+                sender_id,
+                timestamp: Instant::now(),
+            },
+            action,
+        };
+        async fn next_scores(rx: &mut Inbox) -> (Option<Vec<ScoreChange>>, Option<u64>, bool) {
+            loop {
+                if let GameUpdate::StateDelta {
+                    score_changes,
+                    scoreboard_seq,
+                    scoreboard,
+                    ..
+                } = receive_and_deserialize(rx).await
+                {
+                    return (score_changes, scoreboard_seq, scoreboard.is_some());
+                }
+            }
+        }
+
+        // Nobody has been sent their scores yet.
+        engine.process_event(event(admin_id, GameAction::StartGame));
+        let (changes, seq, full) = next_scores(&mut player2_rx).await;
+        assert_eq!(changes.map(|c| c.len()), Some(2));
+        assert_eq!(seq, Some(1));
+        assert!(!full);
+
+        // Nothing changed.
+        engine.process_event(event(admin_id, GameAction::StartRound));
+        assert_eq!(next_scores(&mut player2_rx).await, (None, None, false));
+
+        let correct = engine.state.correct_answers.as_ref().unwrap()[0].to_string();
+        engine.process_event(event(
+            player1_id,
+            GameAction::Answer {
+                answer: correct,
+                client_msg_id: None,
+            },
+        ));
+        engine.process_event(event(admin_id, GameAction::EndRound));
+        let (changes, seq, _) = next_scores(&mut player2_rx).await;
+        let changes = changes.unwrap();
+        let player1 = changes
+            .iter()
+            .find(|c| c.name.as_ref() == "Player1")
+            .unwrap();
+        let player2 = changes
+            .iter()
+            .find(|c| c.name.as_ref() == "Player2")
+            .unwrap();
+        assert!(player1.score > 0);
+        assert_eq!(player1.round_score, player1.score);
+        assert_eq!(player2.consecutive_misses, 1);
+        assert_eq!(seq, Some(2));
+
+        // Only Player1's round score is reset.
+        engine.process_event(event(admin_id, GameAction::StartRound));
+        let (changes, seq, _) = next_scores(&mut player2_rx).await;
+        let changes = changes.unwrap();
+        assert_eq!(changes.len(), 1);
+        assert_eq!(changes[0].name.as_ref(), "Player1");
+        assert_eq!(changes[0].round_score, 0);
+        assert_eq!(seq, Some(3));
+
+        engine.process_event(event(player2_id, GameAction::ResyncScoreboard));
+        let (changes, seq, full) = next_scores(&mut player2_rx).await;
+        assert_eq!(changes, None);
+        assert_eq!(seq, Some(3));
+        assert!(full);
+    }
+
+    #[tokio::test]
+    async fn test_no_prefetch_for_injected_question() {
+        let (mut engine, admin_id) = setup_test_game();
+        let (admin_tx, admin_rx) = tokio::sync::mpsc::channel(128);
+        let mut admin_rx = Inbox::new(admin_rx);
+        engine.update_player_connection(admin_id, admin_tx, Uuid::new_v4());
+        let event = |action| GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action,
+        };
+        let mut drain = async || {
+            let mut updates = Vec::new();
+            while let Ok(update) = tokio::time::timeout(
+                Duration::from_millis(20),
+                receive_and_deserialize::<GameUpdate>(&mut admin_rx),
+            )
+            .await
+            {
+                updates.push(update);
+            }
+            updates
+        };
+        engine.process_event(event(GameAction::StartGame));
+        drain().await;
+
+        engine.process_event(event(GameAction::InjectQuestion {
+            question_text: Arc::from("Whose birthday is it?"),
+            options: vec![
+                GameQuestionOption {
+                    option: Arc::from("Alice"),
+                    is_correct: true,
+                },
+                GameQuestionOption {
+                    option: Arc::from("Bob"),
+                    is_correct: false,
+                },
+            ],
+        }));
+        let updates = drain().await;
+        assert!(
+            updates
+                .iter()
+                .any(|u| matches!(u, GameUpdate::AdminNextQuestions { .. }))
+        );
+        assert!(
+            !updates
+                .iter()
+                .any(|u| matches!(u, GameUpdate::PrefetchNext { .. }))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_start_game_twice() {
+        let (mut engine, admin_id) = setup_test_game();
+        // Create channel to capture admin messages
+        let (admin_tx, admin_rx) = tokio::sync::mpsc::channel(128);
+        let mut admin_rx = Inbox::new(admin_rx);
+        let admin_conn_id = Uuid::new_v4();
+        engine.update_player_connection(admin_id, admin_tx, admin_conn_id);
+        // First start - should succeed
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::StartGame,
+        });
+
+        // Collect all messages from the first start
+        let mut found_state_delta = false;
+        let mut found_prefetch = false;
+        let mut found_admin_next_questions = false;
+
+        // Use a timeout to prevent infinite loop
+        let timeout = tokio::time::Duration::from_millis(100);
+
+        loop {
+            // Use timeout to prevent infinite waiting
+            let msg = tokio::time::timeout(
+                timeout,
+                receive_and_deserialize::<GameUpdate>(&mut admin_rx),
+            )
+            .await;
+
+            // Break if timeout occurs (no more messages)
+            if msg.is_err() {
+                break;
+            }
+
+            let msg = msg.unwrap();
+            match msg {
+                GameUpdate::StateDelta { phase, .. } => {
+                    assert_eq!(
+                        phase,
+                        Some(GamePhase::Score),
+                        "First start should move to Score phase"
+                    );
+                    found_state_delta = true;
+                }
+                GameUpdate::PrefetchNext { youtube_id, .. } => {
+                    let next = &engine.get_upcoming_questions(1)[0];
+                    assert_eq!(youtube_id, next.youtube_id);
+                    assert!(
+                        !found_admin_next_questions,
+                        "Prefetch should come before the upcoming questions"
+                    );
+                    found_prefetch = true;
+                }
+                GameUpdate::AdminNextQuestions { .. } => {
+                    // Expected message
+                    found_admin_next_questions = true;
+                }
+                other => panic!("Unexpected message during first start: {:?}", other),
+            }
+
+            // Break if we've found both expected message types
+            if found_state_delta && found_admin_next_questions {
+                break;
+            }
+        }
+
+        // Ensure we found both message types
+        assert!(found_state_delta, "Did not receive StateDelta message");
+        assert!(found_prefetch, "Did not receive PrefetchNext message");
+        assert!(
+            found_admin_next_questions,
+            "Did not receive AdminNextQuestions message"
+        );
+
+        // Second start - should fail
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::StartGame,
+        });
+        // Should receive error message
+        match receive_and_deserialize(&mut admin_rx).await {
+            GameUpdate::Error { message, .. } => {
+                assert_eq!(
+                    message.as_ref(),
+                    "Can only start game from lobby or after a finished game"
+                );
+            }
+            other => panic!("Expected Error message, got {:?}", other),
+        }
+        // Verify game is still in Score phase
+        assert_eq!(engine.state.phase, GamePhase::Score);
+    }
+
+    #[test]
+    fn test_inactivity_timeout() {
+        let (mut engine, _) = setup_test_game();
+
+        // Test case where last_lobby_message is None
+        engine.state.last_lobby_message = None;
+        assert!(!engine.is_finished());
+    }
+
+    #[tokio::test]
+    async fn test_reclaim_player() {
+        let (mut engine, admin_id) = setup_test_game();
+        let (admin_tx, admin_rx) = tokio::sync::mpsc::channel(128);
+        let mut admin_rx = Inbox::new(admin_rx);
+        engine.update_player_connection(admin_id, admin_tx, Uuid::new_v4());
+        let player_id = Uuid::new_v4();
+        engine.add_player(player_id, "Player1".into()).unwrap();
+        // Not before the player has connected, nor while they are.
+        assert_eq!(engine.reclaim_player("Player1"), None);
+        let (tx, _rx) = tokio::sync::mpsc::channel(128);
+        let conn_id = Uuid::new_v4();
+        engine.update_player_connection(player_id, tx, conn_id);
+        assert_eq!(engine.reclaim_player("Player1"), None);
+
+        engine.clear_player_connection(player_id, conn_id);
+        assert_eq!(engine.reclaim_player("Player2"), None);
+        // The admin has to approve by default.
+        assert_eq!(
+            engine.reclaim_player(" Player1 "),
+            Some(Reclaim::AwaitingApproval)
+        );
+        assert_eq!(
+            receive_and_deserialize::<GameUpdate>(&mut admin_rx).await,
+            GameUpdate::ReclaimRequested {
+                name: "Player1".into()
+            }
+        );
+        // Retries while the request is pending don't ask the admin again.
+        assert_eq!(
+            engine.reclaim_player("Player1"),
+            Some(Reclaim::AwaitingApproval)
+        );
+        let res = tokio::time::timeout(Duration::from_millis(20), admin_rx.recv()).await;
+        assert!(res.is_err());
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::ApproveReclaim {
+                player_name: "Player1 ".into(),
+            },
+        });
+        assert_eq!(
+            engine.reclaim_player("Player1"),
+            Some(Reclaim::Player(player_id))
+        );
+        // An approval is good for one rejoin.
+        assert_eq!(
+            engine.reclaim_player("Player1"),
+            Some(Reclaim::AwaitingApproval)
+        );
+
+        engine.set_open_reclaims(true);
+        assert_eq!(
+            engine.reclaim_player(" Player1 "),
+            Some(Reclaim::Player(player_id))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_inactivity_warning() {
+        let (mut engine, _) = setup_test_game();
+        let (_player_id, mut rx) = add_test_player_with_channel(&mut engine, "Player1");
+        let idle = LOBBY_INACTIVITY_TIMEOUT - Duration::from_secs(120);
+        engine.state.last_lobby_message = Some(Instant::now() - idle);
+
+        engine.warn_if_inactive();
+        match receive_and_deserialize(&mut rx).await {
+            GameUpdate::InactivityWarning { closes_in_secs } => {
+                assert!(closes_in_secs <= 120 && closes_in_secs > 100);
+            }
+            other => panic!("Expected InactivityWarning, got {:?}", other),
+        }
+        // Only once per idle stretch.
+        engine.warn_if_inactive();
+        assert!(engine.state.inactivity_warned);
+
+        // Activity seen outside the engine, e.g. a ping, keeps the lobby open.
+        engine.note_activity(Instant::now());
+        assert!(!engine.state.inactivity_warned);
+        engine.warn_if_inactive();
+        engine.close_if_inactive();
+        assert!(!engine.is_finished());
+        let timeout = Duration::from_millis(50);
+        assert!(tokio::time::timeout(timeout, rx.recv()).await.is_err());
+    }
+
+    #[test]
+    fn test_restart_game_in_same_lobby() {
+        use std::time::Instant;
+
+        // build a game with an admin and some questions
+        let (mut engine, admin_id) = setup_test_game();
+
+        // ── first game starts ────────────────────────────────────────────────────
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::StartGame,
+        });
+        assert_eq!(engine.state.phase, GamePhase::Score);
+
+        // ── admin ends the game ──────────────────────────────────────────────────
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::EndGame {
+                reason: Arc::from("finished"),
+            },
+        });
+        assert_eq!(engine.state.phase, GamePhase::GameOver);
+
+        // ── admin starts a new game in the same lobby ────────────────────────────
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: Instant::now(),
+            },
+            action: GameAction::StartGame,
+        });
+        assert_eq!(engine.state.phase, GamePhase::Score);
+
+        // every player’s score must be back to zero
+        assert!(
+            engine
+                .state
+                .players
+                .values()
+                .all(|p| p.score == 0 && p.round_score == 0)
+        );
+
+        // fresh question sequence
+        assert_eq!(engine.state.current_question_index, 0);
+    }
+
+    #[tokio::test]
+    async fn test_admin_kick_player() {
+        let (mut engine, admin_id) = setup_test_game();
+        let (player1_id, mut player1_rx) = add_test_player_with_channel(&mut engine, "Player1");
+        let (player2_id, mut player2_rx) = add_test_player_with_channel(&mut engine, "Player2");
+
+        let now = Instant::now();
+
+        // Admin kicks Player1
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: now,
+            },
+            action: GameAction::KickPlayer {
+                player_name: Arc::from("Player1"),
+            },
+        });
+
+        // Verify Player1 is removed; Player2 remains
+        assert!(!engine.state.players.contains_key(&player1_id));
+        assert!(engine.state.players.contains_key(&player2_id));
+
+        // Verify Player1 received the Kicked message
+        match receive_and_deserialize(&mut player1_rx).await {
+            GameUpdate::PlayerKicked { reason } => {
+                assert_eq!(reason.as_ref(), "Kicked by admin");
+            }
+            other => panic!("Player1 expected PlayerKicked, got {:?}", other),
+        }
+        assert_eq!(player1_rx.recv().await, None);
+        assert_eq!(player1_rx.ended(), Some(EndReason::Kicked));
+
+        // Player2 should be notified that Player1 left
+        match receive_and_deserialize(&mut player2_rx).await {
+            GameUpdate::PlayerLeft { name } => {
+                assert_eq!(name.as_ref(), "Player1");
+            }
+            other => panic!("Player2 expected PlayerLeft, got {:?}", other),
+        }
+
+        player1_rx.close();
+        player2_rx.close();
+    }
+
+    #[tokio::test]
+    async fn test_admin_kick_nonexistent_player() {
+        let (mut engine, admin_id) = setup_test_game();
+        add_test_player(&mut engine, "Player1");
+
+        // Capture messages for admin
+        let (admin_tx, admin_rx) = tokio::sync::mpsc::channel(128);
+        let mut admin_rx = Inbox::new(admin_rx);
+        let admin_conn_id = Uuid::new_v4();
+        engine.update_player_connection(admin_id, admin_tx, admin_conn_id);
+
+        let initial_player_count = engine.state.players.len();
+        let now = Instant::now();
+
+        // Admin tries to kick a player who doesn't exist
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: now,
+            },
+            action: GameAction::KickPlayer {
+                player_name: Arc::from("Ghost"),
+            },
+        });
+
+        // Verify no players were removed
+        assert_eq!(engine.state.players.len(), initial_player_count);
+
+        // Verify admin received an error message
+        match receive_and_deserialize(&mut admin_rx).await {
+            GameUpdate::Error { message, .. } => {
+                assert!(message.contains("Player 'Ghost' not found"));
+            }
+            other => panic!("Admin expected Error, got {:?}", other),
+        }
+        admin_rx.close();
+    }
+
+    #[tokio::test]
+    async fn test_admin_kick_self() {
+        let (mut engine, admin_id) = setup_test_game();
+
+        // Capture messages for admin
+        let (admin_tx, admin_rx) = tokio::sync::mpsc::channel(128);
+        let mut admin_rx = Inbox::new(admin_rx);
+        let admin_conn_id = Uuid::new_v4();
+        engine.update_player_connection(admin_id, admin_tx, admin_conn_id);
+
+        let initial_player_count = engine.state.players.len();
+        let now = Instant::now();
+
+        // Admin tries to kick "Admin" — not found in players since admin is separate
+        engine.process_event(GameEvent {
+            context: EventContext {
+                sender_id: admin_id,
+                timestamp: now,
+            },
+            action: GameAction::KickPlayer {
+                player_name: Arc::from("Admin"),
+            },
+        });
+
+        // Verify player count unchanged
+        assert_eq!(engine.state.players.len(), initial_player_count);
+
+        // Verify admin received a "not found" error message
+        match receive_and_deserialize(&mut admin_rx).await {
+            GameUpdate::Error { message, .. } => {
+                assert!(message.contains("not found"));
+            }
+            other => panic!("Admin expected Error, got {:?}", other),
+        }
+
+        admin_rx.close();
+    }
+
+    #[test]
+    fn test_answer_score() {
+        assert_eq!(answer_score(Duration::ZERO, 30), 5000);
+        assert_eq!(answer_score(Duration::from_secs(15), 30), 2500);
+        assert_eq!(answer_score(Duration::from_secs(30), 30), 0);
+        assert_eq!(answer_score(Duration::from_secs(31), 30), 0);
+    }
+
+    /// A player's answer in a generated round: whether it is right and when
+    /// it arrives, possibly after the round's 30 seconds.
+    #[derive(Clone, Debug)]
+    struct GeneratedAnswer {
+        correct: bool,
+        elapsed_ms: u64,
+    }
+
+    /// Up to three rounds, each with an answer or none for every player.
+    fn generated_rounds() -> impl Strategy<Value = Vec<Vec<Option<GeneratedAnswer>>>> {
+        let answer =
+            (any::<bool>(), 0..32_000u64).prop_map(|(correct, elapsed_ms)| GeneratedAnswer {
+                correct,
+                elapsed_ms,
+            });
+        (1..6usize).prop_flat_map(move |players| {
+            prop::collection::vec(
+                prop::collection::vec(prop::option::of(answer.clone()), players),
+                1..=3,
+            )
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn prop_scoring_invariants(rounds in generated_rounds()) {
+            let (mut engine, admin_id) = setup_test_game();
+            let player_ids: Vec<Uuid> = (0..rounds[0].len())
+                .map(|i| add_test_player(&mut engine, &format!("Player{i}")))
+                .collect();
+            let admin_event = |action| GameEvent {
+                context: EventContext {
+                    sender_id: admin_id,
+                    timestamp: Instant::now(),
+                },
+                action,
+            };
+            engine.process_event(admin_event(GameAction::StartGame));
+
+            let mut round_score_sums = vec![0; player_ids.len()];
+            for answers in &rounds {
+                engine.process_event(admin_event(GameAction::StartRound));
+                if engine.state.phase != GamePhase::Question {
+                    break;
+                }
+                let start = engine.state.round_start_time.unwrap();
+                let correct = engine.state.correct_answers.clone().unwrap_or_default();
+                let is_correct =
+                    |a: &Arc<str>| correct.iter().any(|c| normalize_answer(c) == normalize_answer(a));
+                let alternatives = engine.state.current_alternatives.clone();
+                let right = alternatives.iter().find(|a| is_correct(a));
+                let wrong = alternatives.iter().find(|a| !is_correct(a));
+
+                let mut correct_times = Vec::new();
+                for (&player_id, answer) in player_ids.iter().zip(answers) {
+                    let Some(answer) = answer else { continue };
+                    let Some(text) = (if answer.correct { right } else { wrong }) else {
+                        continue;
+                    };
+                    let before = engine.state.players[&player_id].score;
+                    engine.process_event(GameEvent {
+                        context: EventContext {
+                            sender_id: player_id,
+                            timestamp: start + Duration::from_millis(answer.elapsed_ms),
+                        },
+                        action: GameAction::Answer {
+                            answer: text.to_string(),
+                            client_msg_id: None,
+                        },
+                    });
+                    let player = &engine.state.players[&player_id];
+                    prop_assert!(player.score >= before, "a correct answer lowered the score");
+                    if answer.correct && player.has_answered {
+                        correct_times.push((answer.elapsed_ms, player.round_score));
+                    }
+                }
+
+                correct_times.sort_unstable();
+                for pair in correct_times.windows(2) {
+                    prop_assert!(
+                        pair[0].1 >= pair[1].1,
+                        "answer after {} ms scored less than one after {} ms",
+                        pair[0].0,
+                        pair[1].0
+                    );
+                }
+
+                engine.process_event(admin_event(GameAction::EndRound));
+                for (sum, player_id) in round_score_sums.iter_mut().zip(&player_ids) {
+                    *sum += engine.state.players[player_id].round_score;
+                }
+            }
+
+            for (sum, player_id) in round_score_sums.iter().zip(&player_ids) {
+                prop_assert_eq!(engine.state.players[player_id].score, *sum);
+            }
+        }
+    }
+}
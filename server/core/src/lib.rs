@@ -0,0 +1,25 @@
+//! The game engine: lobby state, phases, scoring and the question types it
+//! plays with. Free of any web framework so bots, alternative frontends and
+//! the stress test can run the same rules as the server.
+//!
+//! Updates are queued to each client as serialized JSON, see [`delivery`];
+//! how they reach the client is up to the embedder.
+//!
+//! Builds for `wasm32-unknown-unknown` as well, so a web client can run the
+//! same countdown and scoring as the server, e.g. [`game::answer_score`].
+
+pub mod delivery;
+pub mod game;
+pub mod locale;
+pub mod names;
+pub mod question;
+pub mod uuid;
+
+/// The clocks the engine reads. In the browser, where the standard ones
+/// panic, they are backed by `performance.now()` and `Date.now()`.
+pub mod time {
+    #[cfg(not(target_arch = "wasm32"))]
+    pub use std::time::{Instant, SystemTime};
+    #[cfg(target_arch = "wasm32")]
+    pub use web_time::{Instant, SystemTime};
+}
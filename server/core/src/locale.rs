@@ -0,0 +1,163 @@
+//! Player-facing messages in the player's language. Messages are written in
+//! English and looked up in the locale's catalog when sent; a message missing
+//! from the catalog is sent in English.
+
+use serde::Deserialize;
+
+/// Language a client asked for. Parsed from a language tag like `sv-SE`;
+/// anything without a catalog falls back to English.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize)]
+#[serde(from = "String")]
+pub enum Locale {
+    #[default]
+    En,
+    Sv,
+}
+
+impl From<&str> for Locale {
+    fn from(tag: &str) -> Self {
+        let language = tag.split(['-', '_']).next().unwrap_or_default();
+        if language.eq_ignore_ascii_case("sv") {
+            Locale::Sv
+        } else {
+            Locale::En
+        }
+    }
+}
+
+impl From<String> for Locale {
+    fn from(tag: String) -> Self {
+        Locale::from(tag.as_str())
+    }
+}
+
+impl Locale {
+    /// `message` in this locale, or unchanged if it has no translation.
+    pub fn localize(self, message: &str) -> &str {
+        let catalog = match self {
+            Locale::En => return message,
+            Locale::Sv => SV,
+        };
+        catalog
+            .iter()
+            .find(|(english, _)| *english == message)
+            .map_or(message, |(_, translated)| translated)
+    }
+}
+
+const SV: &[(&str, &str)] = &[
+    // Name validation
+    (
+        "Name must be at least 2 characters long.",
+        "Namnet måste vara minst 2 tecken långt.",
+    ),
+    (
+        "Name cannot be longer than 16 characters.",
+        "Namnet får inte vara längre än 16 tecken.",
+    ),
+    (
+        "Name can only contain letters, numbers, spaces, and the symbols: _ - .",
+        "Namnet får bara innehålla bokstäver, siffror, mellanslag och tecknen: _ - .",
+    ),
+    ("This name is already taken.", "Namnet är redan upptaget."),
+    ("This name is reserved.", "Namnet är reserverat."),
+    ("This name is not allowed.", "Namnet är inte tillåtet."),
+    (
+        "This name belongs to an account. Log in to use it.",
+        "Namnet tillhör ett konto. Logga in för att använda det.",
+    ),
+    // Joining
+    ("Invalid join code.", "Ogiltig kod."),
+    ("Lobby is full.", "Lobbyn är full."),
+    ("Lobby is locked.", "Lobbyn är låst."),
+    (
+        "This name is taken. The host has been asked to let you rejoin.",
+        "Namnet är upptaget. Värden har tillfrågats om du får gå med igen.",
+    ),
+    (
+        "The server has too many players, please try again later.",
+        "Servern har för många spelare, försök igen senare.",
+    ),
+    // Playing
+    (
+        "Player not found. Please register before connecting.",
+        "Spelaren hittades inte. Gå med i lobbyn innan du ansluter.",
+    ),
+    ("Player not found", "Spelaren hittades inte"),
+    ("Not in Question phase", "Ingen fråga pågår"),
+    (
+        "Already answered this round",
+        "Du har redan svarat den här rundan",
+    ),
+    (
+        "Time expired for this round",
+        "Tiden för den här rundan är slut",
+    ),
+    ("Answer is too long.", "Svaret är för långt."),
+    (
+        "Answer contains invalid characters.",
+        "Svaret innehåller ogiltiga tecken.",
+    ),
+    (
+        "Answer is not one of the alternatives.",
+        "Svaret är inte ett av alternativen.",
+    ),
+    // Hosting
+    (
+        "Admin action requires authorization",
+        "Åtgärden kräver att du är värd",
+    ),
+    (
+        "Can only start game from lobby or after a finished game",
+        "Spelet kan bara startas från lobbyn eller efter ett avslutat spel",
+    ),
+    (
+        "Can only start round from score phase",
+        "En runda kan bara startas från poängvisningen",
+    ),
+    (
+        "No more questions available. Please end the game.",
+        "Det finns inga fler frågor. Avsluta spelet.",
+    ),
+    (
+        "Can only end round from question phase",
+        "En runda kan bara avslutas medan en fråga pågår",
+    ),
+    (
+        "Can only skip question during score phase",
+        "Frågor kan bara hoppas över under poängvisningen",
+    ),
+    (
+        "Can only change upcoming questions in the lobby or score phase",
+        "Kommande frågor kan bara ändras i lobbyn eller under poängvisningen",
+    ),
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::game::NameValidationError;
+
+    #[test]
+    fn test_locale_from_tag() {
+        assert_eq!(Locale::from("sv"), Locale::Sv);
+        assert_eq!(Locale::from("sv-SE"), Locale::Sv);
+        assert_eq!(Locale::from("SV_fi"), Locale::Sv);
+        assert_eq!(Locale::from("en-GB"), Locale::En);
+        assert_eq!(Locale::from("xx"), Locale::En);
+        let locale: Locale = serde_json::from_str("\"sv-SE\"").unwrap();
+        assert_eq!(locale, Locale::Sv);
+    }
+
+    #[test]
+    fn test_localize() {
+        let message = NameValidationError::AlreadyTaken.to_string();
+        assert_eq!(Locale::En.localize(&message), message);
+        assert_eq!(Locale::Sv.localize(&message), "Namnet är redan upptaget.");
+        // Untranslated messages stay in English.
+        assert_eq!(
+            Locale::Sv.localize("Question 7 not found."),
+            "Question 7 not found."
+        );
+    }
+}
@@ -0,0 +1,123 @@
+//! Screening of player names beyond the allowed characters: names posing as
+//! the host and words nobody wants on the scoreboard. Names are compared by
+//! their skeleton, so "H0st", "ＡＤＭＩＮ" and a Cyrillic "Аdmin" are caught
+//! as well.
+
+use unicode_normalization::UnicodeNormalization;
+
+/// Names only the host's side may look like.
+const RESERVED_NAMES: &[&str] = &[
+    "admin",
+    "administrator",
+    "host",
+    "moderator",
+    "server",
+    "spektrum",
+    "system",
+];
+
+/// Words players may not use. Server operators can add their own.
+const BANNED_WORDS: &[&str] = &[
+    "bitch", "cunt", "fitta", "fuck", "hitler", "jävla", "kuk", "nazi", "nigger", "shit", "slut",
+    "whore",
+];
+
+/// Lookalikes of Latin letters, including digits used as letters.
+fn unconfuse(c: char) -> char {
+    match c {
+        'а' | 'α' | '4' => 'a',
+        'в' | 'β' => 'b',
+        'с' | 'ϲ' => 'c',
+        'ԁ' => 'd',
+        'е' | 'ε' | 'ё' | '3' => 'e',
+        'ɡ' => 'g',
+        'һ' | 'η' => 'h',
+        'і' | 'ι' | 'ı' | '1' | '!' | '|' => 'i',
+        'ј' => 'j',
+        'к' | 'κ' => 'k',
+        'м' | 'μ' => 'm',
+        'п' => 'n',
+        'о' | 'ο' | 'σ' | '0' => 'o',
+        'р' | 'ρ' => 'p',
+        'ѕ' | '5' | '$' => 's',
+        'т' | 'τ' | '7' => 't',
+        'υ' => 'u',
+        'ν' => 'v',
+        'ш' | 'ω' => 'w',
+        'х' | 'χ' => 'x',
+        'у' | 'γ' => 'y',
+        'ᴢ' | '2' => 'z',
+        c => c,
+    }
+}
+
+/// The words of `name` as compared against the lists: compatibility
+/// normalized, case folded and with lookalike characters replaced.
+fn skeleton_words(name: &str) -> Vec<String> {
+    let folded = caseless::default_case_fold_str(&name.nfkc().collect::<String>());
+    folded
+        .nfc()
+        .map(unconfuse)
+        .collect::<String>()
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// Whether `name` looks like one of the reserved names, ignoring spaces and
+/// separators, e.g. "A.d.m.i.n".
+pub fn is_reserved(name: &str) -> bool {
+    let joined = skeleton_words(name).concat();
+    RESERVED_NAMES.contains(&joined.as_str())
+}
+
+/// Whether `name` contains a built-in banned word or one of `extra_words`.
+/// Only whole words match, so "Scunthorpe" or "Kukkonen" are fine.
+pub fn contains_banned_word(name: &str, extra_words: &[String]) -> bool {
+    let words = skeleton_words(name);
+    let extra = extra_words.iter().map(String::as_str);
+    BANNED_WORDS.iter().copied().chain(extra).any(|banned| {
+        let banned = skeleton_words(banned);
+        !banned.is_empty() && words.windows(banned.len()).any(|window| window == banned)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reserved_names() {
+        for name in [
+            "Admin",
+            "HOST",
+            "H0st",
+            "A.d.m.i.n",
+            "ａｄｍｉｎ",
+            "\u{410}dmin",
+        ] {
+            assert!(is_reserved(name), "{name} should be reserved");
+        }
+        for name in ["Hosted", "Admiral", "Alice"] {
+            assert!(!is_reserved(name), "{name} should be allowed");
+        }
+    }
+
+    #[test]
+    fn test_banned_words() {
+        assert!(contains_banned_word("Sh1t happens", &[]));
+        assert!(contains_banned_word("xX_fuck_Xx", &[]));
+        assert!(contains_banned_word("kuk", &[]));
+        // Banned words inside longer words don't count.
+        assert!(!contains_banned_word("Scunthorpe", &[]));
+        assert!(!contains_banned_word("Kukkonen", &[]));
+        assert!(!contains_banned_word("Sam", &["".into()]));
+
+        let extra = vec!["Bingo".to_string(), "big cheese".to_string()];
+        assert!(contains_banned_word("B1NGO-fan", &extra));
+        assert!(!contains_banned_word("B1NGO-fan", &[]));
+        assert!(contains_banned_word("The Big Cheese", &extra));
+        assert!(!contains_banned_word("Big Sam", &extra));
+    }
+}
@@ -0,0 +1,425 @@
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use unicode_normalization::UnicodeNormalization;
+
+/// The form answers are compared in: trimmed, NFC normalized and case
+/// folded, so "  red" from a custom client matches "Red".
+pub fn normalize_answer(answer: &str) -> String {
+    let folded = caseless::default_case_fold_str(&answer.trim().nfc().collect::<String>());
+    // Folding can leave the string unnormalized again, e.g. for "ǰ".
+    folded.nfc().collect()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QuestionType {
+    Color,
+    Character,
+    Text,
+    Year,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Color {
+    Red,
+    Green,
+    Blue,
+    Yellow,
+    Purple,
+    Gold,
+    Silver,
+    Pink,
+    Black,
+    White,
+    Brown,
+    Orange,
+    Gray,
+}
+
+impl Color {
+    pub const COUNT: usize = 13;
+
+    pub fn all() -> &'static [Color] {
+        use Color::{
+            Black, Blue, Brown, Gold, Gray, Green, Orange, Pink, Purple, Red, Silver, White, Yellow,
+        };
+        &[
+            Red, Green, Blue, Yellow, Purple, Gold, Silver, Pink, Black, White, Brown, Orange, Gray,
+        ]
+    }
+
+    /// Canonical dense index for array-backed color data. Do not assume Color::all() order matches.
+    pub fn idx(self) -> usize {
+        match self {
+            Color::Red => 0,
+            Color::Green => 1,
+            Color::Blue => 2,
+            Color::Yellow => 3,
+            Color::Purple => 4,
+            Color::Gold => 5,
+            Color::Silver => 6,
+            Color::Pink => 7,
+            Color::Black => 8,
+            Color::White => 9,
+            Color::Brown => 10,
+            Color::Orange => 11,
+            Color::Gray => 12,
+        }
+    }
+}
+
+/// Fill pattern a client can draw over a color swatch, so colors that look
+/// alike to color-blind players still tell apart.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ColorPattern {
+    Solid,
+    Dots,
+    HorizontalStripes,
+    VerticalStripes,
+    DiagonalStripes,
+    Grid,
+    Checker,
+    Crosshatch,
+    Waves,
+    Zigzag,
+    Diamonds,
+}
+
+/// How to render a color alternative, sent alongside the alternatives of a
+/// color question.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ColorInfo {
+    /// The alternative this describes, as in `alternatives`.
+    pub name: Arc<str>,
+    /// Swatch color as `#RRGGBB`.
+    pub hex: Arc<str>,
+    pub pattern: ColorPattern,
+    /// Short text to print on the swatch.
+    pub label: Arc<str>,
+}
+
+impl Color {
+    pub fn hex(self) -> &'static str {
+        match self {
+            Color::Red => "#FF0000",
+            Color::Green => "#00FF00",
+            Color::Blue => "#0000FF",
+            Color::Yellow => "#FFFF00",
+            Color::Purple => "#800080",
+            Color::Gold => "#FFD700",
+            Color::Silver => "#C0C0C0",
+            Color::Pink => "#FFC0CB",
+            Color::Black => "#000000",
+            Color::White => "#FFFFFF",
+            Color::Brown => "#964B00",
+            Color::Orange => "#FFA500",
+            Color::Gray => "#808080",
+        }
+    }
+
+    /// Pattern for the swatch. Only colors that differ clearly in lightness
+    /// share one.
+    pub fn pattern(self) -> ColorPattern {
+        match self {
+            Color::Red => ColorPattern::Solid,
+            Color::Green => ColorPattern::Dots,
+            Color::Blue => ColorPattern::HorizontalStripes,
+            Color::Yellow => ColorPattern::Grid,
+            Color::Purple => ColorPattern::DiagonalStripes,
+            Color::Gold => ColorPattern::Checker,
+            Color::Silver => ColorPattern::VerticalStripes,
+            Color::Pink => ColorPattern::Waves,
+            Color::Black => ColorPattern::Solid,
+            Color::White => ColorPattern::Solid,
+            Color::Brown => ColorPattern::Crosshatch,
+            Color::Orange => ColorPattern::Zigzag,
+            Color::Gray => ColorPattern::Diamonds,
+        }
+    }
+
+    /// Two-letter abbreviation for the swatch.
+    pub fn label(self) -> &'static str {
+        match self {
+            Color::Red => "Rd",
+            Color::Green => "Gn",
+            Color::Blue => "Bu",
+            Color::Yellow => "Ye",
+            Color::Purple => "Pu",
+            Color::Gold => "Au",
+            Color::Silver => "Ag",
+            Color::Pink => "Pk",
+            Color::Black => "Bk",
+            Color::White => "Wh",
+            Color::Brown => "Bn",
+            Color::Orange => "Or",
+            Color::Gray => "Gy",
+        }
+    }
+
+    pub fn info(self) -> ColorInfo {
+        ColorInfo {
+            name: Arc::from(self.to_string()),
+            hex: Arc::from(self.hex()),
+            pattern: self.pattern(),
+            label: Arc::from(self.label()),
+        }
+    }
+}
+
+pub fn baseline_weights() -> [f64; Color::COUNT] {
+    debug_assert_eq!(
+        Color::COUNT,
+        Color::all().len(),
+        "Color::COUNT must match Color::all().len()"
+    );
+    [0.15; Color::COUNT]
+}
+
+impl std::fmt::Display for Color {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let s = match self {
+            Color::Red => "Red",
+            Color::Green => "Green",
+            Color::Blue => "Blue",
+            Color::Yellow => "Yellow",
+            Color::Purple => "Purple",
+            Color::Gold => "Gold",
+            Color::Silver => "Silver",
+            Color::Pink => "Pink",
+            Color::Black => "Black",
+            Color::White => "White",
+            Color::Brown => "Brown",
+            Color::Orange => "Orange",
+            Color::Gray => "Gray",
+        };
+        write!(f, "{}", s)
+    }
+}
+
+impl std::str::FromStr for Color {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "Red" => Ok(Color::Red),
+            "Green" => Ok(Color::Green),
+            "Blue" => Ok(Color::Blue),
+            "Yellow" => Ok(Color::Yellow),
+            "Purple" => Ok(Color::Purple),
+            "Gold" => Ok(Color::Gold),
+            "Silver" => Ok(Color::Silver),
+            "Pink" => Ok(Color::Pink),
+            "Black" => Ok(Color::Black),
+            "White" => Ok(Color::White),
+            "Brown" => Ok(Color::Brown),
+            "Orange" => Ok(Color::Orange),
+            "Gray" | "Grey" => Ok(Color::Gray),
+            _ => Err(format!("Invalid color: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GameQuestionOption {
+    pub option: Arc<str>,
+    pub is_correct: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GameQuestion {
+    pub id: i64,
+    /// ID of the media entry, for fetching its self-hosted audio clip.
+    #[serde(default)]
+    pub media_id: i64,
+    pub question_type: QuestionType,
+    pub question_text: Option<Arc<str>>,
+    pub title: Arc<str>,
+    pub artist: Option<Arc<str>>,
+    pub youtube_id: Arc<str>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub spotify_uri: Option<Arc<str>>,
+    pub options: Vec<GameQuestionOption>,
+}
+
+impl GameQuestion {
+    pub fn get_correct_options(&self) -> Vec<&GameQuestionOption> {
+        self.options.iter().filter(|opt| opt.is_correct).collect()
+    }
+
+    pub fn get_question_type(&self) -> &'static str {
+        match self.question_type {
+            QuestionType::Color => "color",
+            QuestionType::Character => "character",
+            QuestionType::Text => "text",
+            QuestionType::Year => "year",
+        }
+    }
+
+    pub fn get_correct_answer(&self) -> Vec<Arc<str>> {
+        self.get_correct_options()
+            .iter()
+            .map(|opt| opt.option.clone())
+            .collect()
+    }
+
+    pub fn generate_round_alternatives(
+        &self,
+        color_weights: &[f64; Color::COUNT],
+    ) -> Vec<Arc<str>> {
+        match self.question_type {
+            QuestionType::Color => self
+                .generate_color_alternatives(color_weights)
+                .into_iter()
+                .map(Arc::from)
+                .collect(),
+            QuestionType::Character | QuestionType::Text => {
+                let mut alternatives: Vec<Arc<str>> =
+                    self.options.iter().map(|opt| opt.option.clone()).collect();
+                fastrand::shuffle(&mut alternatives);
+                alternatives
+            }
+            QuestionType::Year => {
+                if let Some(year) = self
+                    .get_correct_answer()
+                    .first()
+                    .and_then(|y| y.parse().ok())
+                {
+                    self.generate_year_alternatives(year)
+                        .into_iter()
+                        .map(Arc::from)
+                        .collect()
+                } else {
+                    vec![]
+                }
+            }
+        }
+    }
+
+    fn generate_color_alternatives(
+        &self,
+        color_weights: &[f64; Color::COUNT],
+    ) -> Vec<&'static str> {
+        const TARGET_SIZE: usize = 6;
+
+        // Get initial colors from correct options
+        let mut round_colors: Vec<Color> = self
+            .get_correct_options()
+            .iter()
+            .filter_map(|opt| opt.option.parse().ok())
+            .collect();
+
+        // Get available colors (excluding ones we already have)
+        let mut available_colors: Vec<(Color, f64)> = Color::all()
+            .iter()
+            .copied()
+            .filter(|color| !round_colors.contains(color))
+            .map(|color| (color, color_weights[color.idx()]))
+            .collect();
+
+        // Select additional colors based on weights until we have TARGET_SIZE
+        while round_colors.len() < TARGET_SIZE && !available_colors.is_empty() {
+            let total_weight: f64 = available_colors.iter().map(|(_, w)| w).sum();
+
+            if total_weight <= 0.0 {
+                // Fallback to random selection if weights are invalid
+                let idx = fastrand::usize(..available_colors.len());
+                let (color, _) = available_colors.remove(idx);
+                round_colors.push(color);
+                continue;
+            }
+
+            let mut selection = fastrand::f64() * total_weight;
+            let mut selected_idx = 0;
+
+            for (idx, (_, weight)) in available_colors.iter().enumerate() {
+                selection -= weight;
+                if selection <= 0.0 {
+                    selected_idx = idx;
+                    break;
+                }
+            }
+
+            let (color, _) = available_colors.remove(selected_idx);
+            round_colors.push(color);
+        }
+
+        fastrand::shuffle(&mut round_colors);
+        round_colors
+            .into_iter()
+            .map(|c| match c {
+                Color::Red => "Red",
+                Color::Green => "Green",
+                Color::Blue => "Blue",
+                Color::Yellow => "Yellow",
+                Color::Purple => "Purple",
+                Color::Gold => "Gold",
+                Color::Silver => "Silver",
+                Color::Pink => "Pink",
+                Color::Black => "Black",
+                Color::White => "White",
+                Color::Brown => "Brown",
+                Color::Orange => "Orange",
+                Color::Gray => "Gray",
+            })
+            .collect()
+    }
+
+    fn generate_year_alternatives(&self, correct_year: i32) -> Vec<String> {
+        let mut alternatives = [
+            correct_year - 2,
+            correct_year - 1,
+            correct_year,
+            correct_year + 1,
+            correct_year + 2,
+        ];
+        fastrand::shuffle(&mut alternatives);
+
+        alternatives.iter().map(|y| y.to_string()).collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct QuestionSet {
+    pub id: i64,
+    pub name: Arc<str>,
+    pub question_ids: Vec<i64>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_answer() {
+        assert_eq!(normalize_answer("  Red\t"), "red");
+        assert_eq!(normalize_answer("STRASSE"), normalize_answer("Straße"));
+        // "é" precomposed and as "e" plus a combining acute accent.
+        assert_eq!(
+            normalize_answer("Caf\u{e9}"),
+            normalize_answer("CAFE\u{301}")
+        );
+        assert_ne!(normalize_answer("Red"), normalize_answer("Re d"));
+    }
+    #[test]
+    fn test_color_info() {
+        for color in Color::all() {
+            let info = color.info();
+            assert_eq!(info.name.parse::<Color>(), Ok(*color));
+            assert_eq!(info.hex.len(), 7);
+            assert!(info.hex.starts_with('#'));
+            // Patterns only repeat for solid swatches.
+            assert!(
+                info.pattern == ColorPattern::Solid
+                    || Color::all()
+                        .iter()
+                        .filter(|other| other.pattern() == info.pattern)
+                        .count()
+                        == 1,
+                "{color} shares its pattern"
+            );
+        }
+        let json = serde_json::to_value(Color::Blue.info()).unwrap();
+        assert_eq!(json["pattern"], "horizontal_stripes");
+    }
+}
@@ -0,0 +1,222 @@
+//! Optional player accounts: a name and a PIN that reserve the name in every
+//! lobby and give the player a stable ID from game to game. Creating an
+//! account or logging in returns an account token, which `join-lobby` takes
+//! in place of a name.
+
+use crate::auth::{hash_password, password_matches};
+use crate::db::DbError;
+use crate::game::{normalize_player_name, validate_player_name};
+use crate::question::QuestionStore;
+use crate::server::ApiError;
+use crate::uuid::Uuid;
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use chrono::{SecondsFormat, Utc};
+use dashmap::DashMap;
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// Wrong PINs in a row before an account's logins are refused for
+/// [`LOCKOUT`], as a 4-digit PIN is quick to guess otherwise.
+const MAX_PIN_FAILURES: u32 = 5;
+const LOCKOUT: Duration = Duration::from_secs(15 * 60);
+/// Tokens kept per account. Logging in on another device adds one and drops
+/// the oldest past this.
+const MAX_TOKENS: usize = 5;
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Account {
+    pub id: Uuid,
+    pub name: Arc<str>,
+    /// RFC 3339.
+    pub created_at: String,
+    /// Argon2 hash of the PIN.
+    pin_hash: String,
+    /// SHA-256 of each token issued, oldest first.
+    token_hashes: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AccountRequest {
+    pub name: String,
+    pub pin: String,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct AccountResponse {
+    pub account_id: Uuid,
+    pub name: Arc<str>,
+    pub account_token: String,
+}
+
+/// Names are reserved regardless of case, as players tell them apart by eye.
+fn name_key(name: &str) -> String {
+    normalize_player_name(name).to_lowercase()
+}
+
+fn token_hash(token: &str) -> String {
+    format!("{:x}", Sha256::digest(token.as_bytes()))
+}
+
+fn validate_pin(pin: &str) -> Result<(), ApiError> {
+    if !(4..=12).contains(&pin.len()) || !pin.bytes().all(|b| b.is_ascii_digit()) {
+        return Err(ApiError::Validation("PIN must be 4 to 12 digits.".into()));
+    }
+    Ok(())
+}
+
+pub struct AccountStore {
+    /// Keyed by [`name_key`].
+    accounts: RwLock<HashMap<String, Account>>,
+    /// Held while changing and saving accounts, so saves happen in order.
+    save_lock: tokio::sync::Mutex<()>,
+    /// Wrong PINs in a row and when the last one was entered, by account.
+    failures: DashMap<Uuid, (u32, Instant)>,
+    store: Arc<QuestionStore>,
+}
+
+impl AccountStore {
+    pub async fn load(store: Arc<QuestionStore>) -> Result<Self, DbError> {
+        let accounts = store
+            .load_accounts()
+            .await?
+            .into_iter()
+            .map(|account| (name_key(&account.name), account))
+            .collect();
+        Ok(Self {
+            accounts: RwLock::new(accounts),
+            save_lock: tokio::sync::Mutex::new(()),
+            failures: DashMap::new(),
+            store,
+        })
+    }
+
+    /// Whether `name` belongs to an account, so only its owner may use it.
+    pub fn is_reserved(&self, name: &str) -> bool {
+        self.accounts.read().unwrap().contains_key(&name_key(name))
+    }
+
+    /// ID and name of the account `token` was issued for.
+    pub fn verify_token(&self, token: &str) -> Option<(Uuid, Arc<str>)> {
+        let (id, _) = token.split_once('.')?;
+        let id: Uuid = id.parse().ok()?;
+        let hash = token_hash(token);
+        let accounts = self.accounts.read().unwrap();
+        accounts
+            .values()
+            .find(|account| account.id == id)
+            .filter(|account| account.token_hashes.contains(&hash))
+            .map(|account| (account.id, account.name.clone()))
+    }
+
+    pub async fn create(&self, req: AccountRequest) -> Result<AccountResponse, ApiError> {
+        let name = normalize_player_name(&req.name);
+        let name = name.as_str();
+        validate_player_name(name, std::iter::empty())?;
+        validate_pin(&req.pin)?;
+        let pin_hash = hash_password(&req.pin).map_err(ApiError::Database)?;
+
+        let _save = self.save_lock.lock().await;
+        let key = name_key(name);
+        let id = Uuid::new_v4();
+        let token = issue_token(id)?;
+        {
+            let mut accounts = self.accounts.write().unwrap();
+            if accounts.contains_key(&key) {
+                return Err(ApiError::Validation("This name is already taken.".into()));
+            }
+            accounts.insert(
+                key.clone(),
+                Account {
+                    id,
+                    name: Arc::from(name),
+                    created_at: Utc::now().to_rfc3339_opts(SecondsFormat::Secs, true),
+                    pin_hash,
+                    token_hashes: vec![token_hash(&token)],
+                },
+            );
+        }
+        if let Err(e) = self.save().await {
+            self.accounts.write().unwrap().remove(&key);
+            return Err(e);
+        }
+        info!(account_id = %id, "Account created");
+        Ok(AccountResponse {
+            account_id: id,
+            name: Arc::from(name),
+            account_token: token,
+        })
+    }
+
+    /// Issues a new token for the account named `req.name` if `req.pin` is
+    /// its PIN.
+    pub async fn login(&self, req: AccountRequest) -> Result<AccountResponse, ApiError> {
+        let key = name_key(&req.name);
+        let Some((id, pin_hash)) = self
+            .accounts
+            .read()
+            .unwrap()
+            .get(&key)
+            .map(|account| (account.id, account.pin_hash.clone()))
+        else {
+            return Err(ApiError::Unauthorized);
+        };
+        if let Some(failures) = self.failures.get(&id)
+            && failures.0 >= MAX_PIN_FAILURES
+            && failures.1.elapsed() < LOCKOUT
+        {
+            return Err(ApiError::Forbidden(
+                "Too many wrong PINs, try again later.".into(),
+            ));
+        }
+        if !password_matches(std::slice::from_ref(&pin_hash), &req.pin) {
+            let mut failures = self.failures.entry(id).or_insert((0, Instant::now()));
+            failures.0 += 1;
+            failures.1 = Instant::now();
+            warn!(account_id = %id, failures = failures.0, "Wrong account PIN");
+            return Err(ApiError::Unauthorized);
+        }
+        self.failures.remove(&id);
+
+        let _save = self.save_lock.lock().await;
+        let token = issue_token(id)?;
+        let name = {
+            let mut accounts = self.accounts.write().unwrap();
+            let account = accounts.get_mut(&key).ok_or(ApiError::Unauthorized)?;
+            account.token_hashes.push(token_hash(&token));
+            let excess = account.token_hashes.len().saturating_sub(MAX_TOKENS);
+            account.token_hashes.drain(..excess);
+            account.name.clone()
+        };
+        self.save().await?;
+        Ok(AccountResponse {
+            account_id: id,
+            name,
+            account_token: token,
+        })
+    }
+
+    /// Writes every account to storage. Callers hold `save_lock`.
+    async fn save(&self) -> Result<(), ApiError> {
+        let accounts: Vec<Account> = self.accounts.read().unwrap().values().cloned().collect();
+        self.store
+            .save_accounts(&accounts)
+            .await
+            .map_err(|e| ApiError::Database(e.to_string()))
+    }
+}
+
+/// A new token for account `id`: the ID, so the account can be found, and a
+/// random secret.
+fn issue_token(id: Uuid) -> Result<String, ApiError> {
+    let mut secret = [0u8; 32];
+    SystemRandom::new()
+        .fill(&mut secret)
+        .map_err(|_| ApiError::Database("Failed to generate account token".into()))?;
+    Ok(format!("{id}.{}", URL_SAFE_NO_PAD.encode(secret)))
+}
@@ -0,0 +1,54 @@
+//! Formats accepted for self-hosted question audio clips.
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum AudioFormat {
+    Ogg,
+    Mp3,
+}
+
+impl AudioFormat {
+    pub const ALL: [AudioFormat; 2] = [AudioFormat::Ogg, AudioFormat::Mp3];
+
+    /// Detects the format from the file's leading bytes, since browsers
+    /// disagree on the content type they send for the same file.
+    pub fn sniff(data: &[u8]) -> Option<Self> {
+        match data {
+            [b'O', b'g', b'g', b'S', ..] => Some(Self::Ogg),
+            // ID3v2 tag, or a bare MPEG audio frame sync.
+            [b'I', b'D', b'3', ..] => Some(Self::Mp3),
+            [0xff, second, ..] if second & 0xe0 == 0xe0 => Some(Self::Mp3),
+            _ => None,
+        }
+    }
+
+    pub fn extension(self) -> &'static str {
+        match self {
+            Self::Ogg => "ogg",
+            Self::Mp3 => "mp3",
+        }
+    }
+
+    pub fn content_type(self) -> &'static str {
+        match self {
+            Self::Ogg => "audio/ogg",
+            Self::Mp3 => "audio/mpeg",
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(AudioFormat::sniff(b"OggS\0\x02"), Some(AudioFormat::Ogg));
+        assert_eq!(AudioFormat::sniff(b"ID3\x04\0"), Some(AudioFormat::Mp3));
+        assert_eq!(
+            AudioFormat::sniff(&[0xff, 0xfb, 0x90]),
+            Some(AudioFormat::Mp3)
+        );
+        assert_eq!(AudioFormat::sniff(b"RIFF\0\0\0\0WAVE"), None);
+        assert_eq!(AudioFormat::sniff(b""), None);
+    }
+}
@@ -0,0 +1,355 @@
+use crate::server::{ApiError, AppState};
+use argon2::Argon2;
+use argon2::password_hash::{PasswordHash, PasswordHasher, PasswordVerifier, SaltString};
+use axum::extract::FromRequestParts;
+use axum::http::request::Parts;
+use axum::http::{HeaderMap, header};
+use base64::Engine;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use hmac::{Hmac, Mac};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+use std::time::{SystemTime, UNIX_EPOCH};
+use tracing::debug;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Pre-encoded `{"alg":"HS256","typ":"JWT"}` header. Only HS256 tokens issued by
+/// this server are accepted, so the header is compared verbatim.
+const JWT_HEADER: &str = "eyJhbGciOiJIUzI1NiIsInR5cCI6IkpXVCJ9";
+const ADMIN_SUBJECT: &str = "admin";
+
+#[derive(Debug, PartialEq, Eq)]
+pub enum TokenError {
+    Malformed,
+    InvalidSignature,
+    Expired,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct Claims {
+    sub: String,
+    iat: u64,
+    exp: u64,
+    /// Named dataset the token is limited to; unrestricted when absent.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    dataset: Option<String>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct IssuedToken {
+    pub token: String,
+    pub expires_in: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub dataset: Option<String>,
+}
+
+fn unix_now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or(0)
+}
+
+/// Issues and verifies short-lived HS256 JWTs for the management endpoints.
+pub struct JwtKeys {
+    secret: Vec<u8>,
+    ttl_secs: u64,
+}
+
+impl JwtKeys {
+    /// Uses the configured secret, or a random per-process secret when none is
+    /// set (tokens then become invalid on restart).
+    pub fn new(secret: Option<&str>, ttl_secs: u64) -> Result<Self, String> {
+        let secret = match secret {
+            Some(secret) if !secret.is_empty() => secret.as_bytes().to_vec(),
+            _ => {
+                let mut bytes = vec![0u8; 32];
+                SystemRandom::new()
+                    .fill(&mut bytes)
+                    .map_err(|_| "Failed to generate JWT secret".to_string())?;
+                bytes
+            }
+        };
+        Ok(Self { secret, ttl_secs })
+    }
+
+    fn sign(&self, signing_input: &str) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(signing_input.as_bytes());
+        mac
+    }
+
+    pub fn issue(&self) -> IssuedToken {
+        self.issue_at(unix_now(), None)
+    }
+
+    /// A token limited to managing the questions of `dataset`.
+    pub fn issue_for_dataset(&self, dataset: &str) -> IssuedToken {
+        self.issue_at(unix_now(), Some(dataset))
+    }
+
+    fn issue_at(&self, now: u64, dataset: Option<&str>) -> IssuedToken {
+        let claims = Claims {
+            sub: ADMIN_SUBJECT.to_string(),
+            iat: now,
+            exp: now + self.ttl_secs,
+            dataset: dataset.map(str::to_string),
+        };
+        let payload = serde_json::to_vec(&claims).expect("claims are always serializable");
+        let signing_input = format!("{JWT_HEADER}.{}", URL_SAFE_NO_PAD.encode(payload));
+        let signature = self.sign(&signing_input).finalize().into_bytes();
+        IssuedToken {
+            token: format!("{signing_input}.{}", URL_SAFE_NO_PAD.encode(signature)),
+            expires_in: self.ttl_secs,
+            dataset: claims.dataset,
+        }
+    }
+
+    /// Returns the dataset the token is limited to, if any.
+    pub fn verify(&self, token: &str) -> Result<Option<String>, TokenError> {
+        self.verify_at(token, unix_now())
+    }
+
+    fn verify_at(&self, token: &str, now: u64) -> Result<Option<String>, TokenError> {
+        let (signing_input, signature) = token.rsplit_once('.').ok_or(TokenError::Malformed)?;
+        let (header, payload) = signing_input.split_once('.').ok_or(TokenError::Malformed)?;
+        if header != JWT_HEADER {
+            return Err(TokenError::Malformed);
+        }
+
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| TokenError::Malformed)?;
+        // verify_slice compares in constant time.
+        self.sign(signing_input)
+            .verify_slice(&signature)
+            .map_err(|_| TokenError::InvalidSignature)?;
+
+        let payload = URL_SAFE_NO_PAD
+            .decode(payload)
+            .map_err(|_| TokenError::Malformed)?;
+        let claims: Claims = serde_json::from_slice(&payload).map_err(|_| TokenError::Malformed)?;
+        if claims.sub != ADMIN_SUBJECT {
+            return Err(TokenError::Malformed);
+        }
+        if claims.exp <= now {
+            return Err(TokenError::Expired);
+        }
+        Ok(claims.dataset)
+    }
+}
+
+/// Returns the token from an `Authorization: Bearer <token>` header.
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    headers
+        .get(header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .map(str::trim)
+}
+
+/// Extractor that only succeeds when the request carries a valid admin JWT in
+/// `Authorization: Bearer <token>`. Add it as a handler argument to protect an
+/// endpoint.
+#[derive(Debug, Default)]
+pub struct AdminSession {
+    /// Set for admins of a single named dataset, who may only manage its
+    /// questions.
+    pub dataset: Option<String>,
+}
+
+impl AdminSession {
+    /// Rejects dataset admins, for endpoints that act on every lobby.
+    pub fn require_global(&self) -> Result<(), ApiError> {
+        match &self.dataset {
+            None => Ok(()),
+            Some(_) => Err(ApiError::Forbidden(
+                "Dataset admins cannot manage lobbies".into(),
+            )),
+        }
+    }
+}
+
+impl FromRequestParts<AppState> for AdminSession {
+    type Rejection = ApiError;
+
+    async fn from_request_parts(
+        parts: &mut Parts,
+        state: &AppState,
+    ) -> Result<Self, Self::Rejection> {
+        let token = bearer_token(&parts.headers).ok_or(ApiError::Unauthorized)?;
+
+        let dataset = state.jwt.verify(token).map_err(|e| {
+            debug!(error = ?e, "Rejected admin token");
+            ApiError::Unauthorized
+        })?;
+        Ok(AdminSession { dataset })
+    }
+}
+
+/// Configured admin passwords starting with this are argon2 hashes.
+const HASH_PREFIX: &str = "$argon2";
+
+/// Argon2id PHC string for `password`, for `admin_password` in the config.
+pub fn hash_password(password: &str) -> Result<String, String> {
+    let mut salt = [0u8; 16];
+    SystemRandom::new()
+        .fill(&mut salt)
+        .map_err(|_| "Failed to generate salt".to_string())?;
+    let salt = SaltString::encode_b64(&salt).map_err(|e| e.to_string())?;
+    Argon2::default()
+        .hash_password(password.as_bytes(), &salt)
+        .map(|hash| hash.to_string())
+        .map_err(|e| e.to_string())
+}
+
+/// Checks that a configured password that looks like a hash parses as one.
+pub fn validate_password_hash(stored: &str) -> Result<(), String> {
+    if stored.starts_with(HASH_PREFIX) {
+        PasswordHash::new(stored).map_err(|e| format!("Invalid argon2 hash: {e}"))?;
+    }
+    Ok(())
+}
+
+/// Puts back together hashes split by the comma-separated environment
+/// variable list, as their parameters (`m=19456,t=2,p=1`) contain commas.
+pub fn rejoin_password_hashes(parts: Vec<String>) -> Vec<String> {
+    let mut passwords: Vec<String> = Vec::with_capacity(parts.len());
+    for part in parts {
+        match passwords.last_mut() {
+            // A complete hash has five `$`-separated fields.
+            Some(last) if last.starts_with(HASH_PREFIX) && last.matches('$').count() < 5 => {
+                last.push(',');
+                last.push_str(&part);
+            }
+            _ => passwords.push(part),
+        }
+    }
+    passwords
+}
+
+/// Whether `candidate` matches one of `admin_passwords`, each a plaintext
+/// password or an argon2 hash. Every entry is checked and plaintext is
+/// compared in constant time, so the time taken doesn't reveal which matched.
+pub fn password_matches(admin_passwords: &[String], candidate: &str) -> bool {
+    let mut matched = false;
+    for stored in admin_passwords {
+        if stored.starts_with(HASH_PREFIX) {
+            // The hash is validated at startup; an unparsable one never matches.
+            let verified = PasswordHash::new(stored).is_ok_and(|hash| {
+                Argon2::default()
+                    .verify_password(candidate.as_bytes(), &hash)
+                    .is_ok()
+            });
+            matched |= verified;
+            continue;
+        }
+        let stored_bytes = stored.as_bytes();
+        let candidate_bytes = candidate.as_bytes();
+        // Compare all bytes without short-circuiting.
+        let len_match = stored_bytes.len() == candidate_bytes.len();
+        let mut acc = 0u8;
+        for (a, b) in stored_bytes.iter().zip(candidate_bytes.iter()) {
+            acc |= a ^ b;
+        }
+        if len_match && acc == 0 {
+            matched = true;
+        }
+    }
+    matched
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_header_constant_matches_encoding() {
+        assert_eq!(
+            URL_SAFE_NO_PAD.encode(r#"{"alg":"HS256","typ":"JWT"}"#),
+            JWT_HEADER
+        );
+    }
+
+    #[test]
+    fn test_issue_and_verify() {
+        let keys = JwtKeys::new(Some("secret"), 60).unwrap();
+        let issued = keys.issue_at(1_000, None);
+        assert_eq!(issued.expires_in, 60);
+        assert_eq!(keys.verify_at(&issued.token, 1_059), Ok(None));
+        assert_eq!(
+            keys.verify_at(&issued.token, 1_060),
+            Err(TokenError::Expired)
+        );
+
+        let issued = keys.issue_at(1_000, Some("sv"));
+        assert_eq!(issued.dataset.as_deref(), Some("sv"));
+        assert_eq!(keys.verify_at(&issued.token, 1_000), Ok(Some("sv".into())));
+    }
+
+    #[test]
+    fn test_rejects_foreign_and_tampered_tokens() {
+        let keys = JwtKeys::new(Some("secret"), 60).unwrap();
+        let other = JwtKeys::new(Some("other-secret"), 60).unwrap();
+        let token = other.issue_at(1_000, None).token;
+        assert_eq!(
+            keys.verify_at(&token, 1_000),
+            Err(TokenError::InvalidSignature)
+        );
+
+        let token = keys.issue_at(1_000, None).token;
+        let mut parts: Vec<&str> = token.split('.').collect();
+        let forged_payload =
+            URL_SAFE_NO_PAD.encode(r#"{"sub":"admin","iat":1000,"exp":99999999999}"#);
+        parts[1] = &forged_payload;
+        assert_eq!(
+            keys.verify_at(&parts.join("."), 1_000),
+            Err(TokenError::InvalidSignature)
+        );
+
+        assert_eq!(
+            keys.verify_at("not-a-token", 1_000),
+            Err(TokenError::Malformed)
+        );
+    }
+
+    #[test]
+    fn test_random_secret_when_unset() {
+        let a = JwtKeys::new(None, 60).unwrap();
+        let b = JwtKeys::new(Some(""), 60).unwrap();
+        let token = a.issue_at(1_000, None).token;
+        assert_eq!(
+            b.verify_at(&token, 1_000),
+            Err(TokenError::InvalidSignature)
+        );
+    }
+
+    #[test]
+    fn test_password_hashes() {
+        let hash = hash_password("hunter22").unwrap();
+        assert!(validate_password_hash(&hash).is_ok());
+        let passwords = vec!["plain-password".to_string(), hash.clone()];
+        assert!(password_matches(&passwords, "hunter22"));
+        assert!(password_matches(&passwords, "plain-password"));
+        assert!(!password_matches(&passwords, "hunter2"));
+
+        // Never matches, not even compared as plaintext
+        let invalid = "$argon2id$v=19$m=19456,t=2,p=1$!!!$!!!";
+        assert!(validate_password_hash(invalid).is_err());
+        assert!(!password_matches(&[invalid.into()], invalid));
+    }
+
+    #[test]
+    fn test_rejoin_password_hashes() {
+        let hash = hash_password("hunter22").unwrap();
+        let listed = format!("first,{hash},last");
+        let parts = listed.split(',').map(str::to_string).collect();
+        assert_eq!(
+            rejoin_password_hashes(parts),
+            vec!["first".to_string(), hash, "last".to_string()]
+        );
+    }
+}
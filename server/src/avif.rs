@@ -0,0 +1,427 @@
+//! Structural checks for uploaded AVIF files, which the server stores as-is
+//! apart from blanking metadata. Only the ISOBMFF boxes needed to find the
+//! image size and any attached metadata are parsed; the AV1 payload itself
+//! is not decoded.
+
+use std::ops::Range;
+use thiserror::Error;
+
+#[derive(Error, Debug, PartialEq)]
+pub enum AvifError {
+    #[error("Not an AVIF file")]
+    NotAvif,
+    #[error("Malformed AVIF file: {0}")]
+    Malformed(&'static str),
+    #[error("Image is {width}x{height}, the maximum is {max}x{max}")]
+    TooLarge { width: u32, height: u32, max: u32 },
+}
+
+/// Width and height of the largest image in the file.
+#[derive(Debug, PartialEq)]
+pub struct Dimensions {
+    pub width: u32,
+    pub height: u32,
+}
+
+/// Checks that `data` is an AVIF image no larger than `max_dimension` on
+/// either side and overwrites the contents of any Exif or XMP items with
+/// zeros. The items stay in place, so no offsets change.
+pub fn strip_metadata(data: &mut [u8], max_dimension: u32) -> Result<Dimensions, AvifError> {
+    let (dimensions, metadata) = inspect(data, max_dimension)?;
+    for range in metadata {
+        data[range].fill(0);
+    }
+    Ok(dimensions)
+}
+
+/// The image size and the byte ranges of metadata items in `data`.
+fn inspect(data: &[u8], max_dimension: u32) -> Result<(Dimensions, Vec<Range<usize>>), AvifError> {
+    let mut boxes = Boxes(data);
+    match boxes.next() {
+        Some(Ok((b"ftyp", ftyp))) if is_avif_brand(ftyp) => {}
+        _ => return Err(AvifError::NotAvif),
+    }
+
+    let mut inspected = None;
+    for entry in boxes {
+        let (kind, body) = entry?;
+        if kind == b"meta" {
+            inspected = Some(inspect_meta(data, full_box_body(body)?)?);
+        }
+    }
+    let (dimensions, metadata) = inspected.ok_or(AvifError::Malformed("missing image size"))?;
+    if dimensions.width > max_dimension || dimensions.height > max_dimension {
+        return Err(AvifError::TooLarge {
+            width: dimensions.width,
+            height: dimensions.height,
+            max: max_dimension,
+        });
+    }
+    Ok((dimensions, metadata))
+}
+
+fn is_avif_brand(ftyp: &[u8]) -> bool {
+    // major_brand, minor_version, then compatible_brands.
+    let major = ftyp.get(..4);
+    let compatible = ftyp.get(8..).unwrap_or_default().chunks_exact(4);
+    major
+        .into_iter()
+        .chain(compatible)
+        .any(|brand| brand == b"avif" || brand == b"avis")
+}
+
+/// Offset of `part` within `whole`, which it must be a subslice of.
+fn offset_in(whole: &[u8], part: &[u8]) -> usize {
+    part.as_ptr() as usize - whole.as_ptr() as usize
+}
+
+fn inspect_meta(file: &[u8], meta: &[u8]) -> Result<(Dimensions, Vec<Range<usize>>), AvifError> {
+    let mut largest: Option<Dimensions> = None;
+    let mut metadata_items = Vec::new();
+    let mut locations = Vec::new();
+    let mut idat = None;
+    for entry in Boxes(meta) {
+        let (kind, body) = entry?;
+        match kind {
+            b"iinf" => metadata_items = metadata_item_ids(body)?,
+            b"iloc" => locations = item_locations(body)?,
+            b"idat" => idat = Some(offset_in(file, body)..offset_in(file, body) + body.len()),
+            b"iprp" => {
+                for entry in Boxes(body) {
+                    let (kind, ipco) = entry?;
+                    if kind != b"ipco" {
+                        continue;
+                    }
+                    for entry in Boxes(ipco) {
+                        let (kind, property) = entry?;
+                        if kind != b"ispe" {
+                            continue;
+                        }
+                        let ispe = full_box_body(property)?;
+                        let width = read_u32(ispe, 0)?;
+                        let height = read_u32(ispe, 4)?;
+                        let area = u64::from(width) * u64::from(height);
+                        if largest
+                            .as_ref()
+                            .is_none_or(|d| area > u64::from(d.width) * u64::from(d.height))
+                        {
+                            largest = Some(Dimensions { width, height });
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+    let dimensions = largest.ok_or(AvifError::Malformed("missing image size"))?;
+
+    let mut ranges = Vec::new();
+    for location in locations {
+        if !metadata_items.contains(&location.item_id) {
+            continue;
+        }
+        let container = match location.construction_method {
+            0 => 0..file.len(),
+            1 => idat.clone().ok_or(AvifError::Malformed("missing idat"))?,
+            // Items built from other items hold no bytes of their own.
+            _ => continue,
+        };
+        for (offset, length) in location.extents {
+            let start = offset
+                .checked_add(location.base_offset)
+                .and_then(|offset| usize::try_from(offset).ok())
+                .and_then(|offset| offset.checked_add(container.start))
+                .filter(|&start| start <= container.end)
+                .ok_or(AvifError::Malformed("item extent out of range"))?;
+            // A length of zero means the rest of the container.
+            let end = match usize::try_from(length).ok() {
+                Some(0) => container.end,
+                Some(length) => start.saturating_add(length),
+                None => usize::MAX,
+            };
+            if end > container.end {
+                return Err(AvifError::Malformed("item extent out of range"));
+            }
+            ranges.push(start..end);
+        }
+    }
+    Ok((dimensions, ranges))
+}
+
+/// Ids of Exif items and `mime` items, which is how AVIF embeds XMP.
+fn metadata_item_ids(iinf: &[u8]) -> Result<Vec<u32>, AvifError> {
+    let version = *iinf.first().ok_or(AvifError::Malformed("truncated iinf"))?;
+    let entries_start = if version == 0 { 6 } else { 8 };
+    let entries = iinf
+        .get(entries_start..)
+        .ok_or(AvifError::Malformed("truncated iinf"))?;
+    let mut ids = Vec::new();
+    for entry in Boxes(entries) {
+        let (kind, infe) = entry?;
+        if kind != b"infe" {
+            continue;
+        }
+        let version = *infe.first().ok_or(AvifError::Malformed("truncated infe"))?;
+        // Versions before 2 carry no item type.
+        let (id, type_offset) = match version {
+            0 | 1 => continue,
+            2 => (read_u16(infe, 4)? as u32, 8),
+            _ => (read_u32(infe, 4)?, 10),
+        };
+        match infe.get(type_offset..type_offset + 4) {
+            Some(b"Exif" | b"mime") => ids.push(id),
+            Some(_) => {}
+            None => return Err(AvifError::Malformed("truncated infe")),
+        }
+    }
+    Ok(ids)
+}
+
+/// Where an item's bytes are, from the `iloc` box.
+struct ItemLocation {
+    item_id: u32,
+    /// 0 for offsets into the file, 1 for offsets into `idat`.
+    construction_method: u8,
+    base_offset: u64,
+    /// Offset and length of each extent.
+    extents: Vec<(u64, u64)>,
+}
+
+fn item_locations(iloc: &[u8]) -> Result<Vec<ItemLocation>, AvifError> {
+    let version = *iloc.first().ok_or(AvifError::Malformed("truncated iloc"))?;
+    let mut reader = Reader { data: iloc, pos: 4 };
+    let sizes = reader.read(2)?;
+    let offset_size = (sizes >> 12) as usize;
+    let length_size = ((sizes >> 8) & 0xf) as usize;
+    let base_offset_size = ((sizes >> 4) & 0xf) as usize;
+    let index_size = if version >= 1 {
+        (sizes & 0xf) as usize
+    } else {
+        0
+    };
+    let item_count = reader.read(if version < 2 { 2 } else { 4 })?;
+    let mut locations = Vec::new();
+    for _ in 0..item_count {
+        let item_id = reader.read(if version < 2 { 2 } else { 4 })? as u32;
+        let construction_method = if version >= 1 {
+            (reader.read(2)? & 0xf) as u8
+        } else {
+            0
+        };
+        let _data_reference_index = reader.read(2)?;
+        let base_offset = reader.read(base_offset_size)?;
+        let extent_count = reader.read(2)?;
+        let mut extents = Vec::new();
+        for _ in 0..extent_count {
+            reader.read(index_size)?;
+            let offset = reader.read(offset_size)?;
+            let length = reader.read(length_size)?;
+            extents.push((offset, length));
+        }
+        locations.push(ItemLocation {
+            item_id,
+            construction_method,
+            base_offset,
+            extents,
+        });
+    }
+    Ok(locations)
+}
+
+/// Reads big-endian fields of varying width, as `iloc` declares them.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl Reader<'_> {
+    fn read(&mut self, size: usize) -> Result<u64, AvifError> {
+        if !matches!(size, 0 | 2 | 4 | 8) {
+            return Err(AvifError::Malformed("unsupported iloc field size"));
+        }
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + size)
+            .ok_or(AvifError::Malformed("truncated iloc"))?;
+        self.pos += size;
+        Ok(bytes
+            .iter()
+            .fold(0, |value, &b| (value << 8) | u64::from(b)))
+    }
+}
+
+/// Strips the version and flags of an ISOBMFF full box.
+fn full_box_body(body: &[u8]) -> Result<&[u8], AvifError> {
+    body.get(4..).ok_or(AvifError::Malformed("truncated box"))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, AvifError> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+        .ok_or(AvifError::Malformed("truncated box"))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, AvifError> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or(AvifError::Malformed("truncated box"))
+}
+
+/// Iterates the ISOBMFF boxes in a buffer as (type, body) pairs.
+struct Boxes<'a>(&'a [u8]);
+
+impl<'a> Iterator for Boxes<'a> {
+    type Item = Result<(&'a [u8; 4], &'a [u8]), AvifError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.0.is_empty() {
+            return None;
+        }
+        let data = self.0;
+        let parsed = (|| {
+            let size = read_u32(data, 0)? as u64;
+            let kind: &[u8; 4] = data
+                .get(4..8)
+                .and_then(|k| k.try_into().ok())
+                .ok_or(AvifError::Malformed("truncated box"))?;
+            let (header, size) = match size {
+                0 => (8, data.len() as u64),
+                1 => {
+                    let high = read_u32(data, 8)? as u64;
+                    let low = read_u32(data, 12)? as u64;
+                    (16, (high << 32) | low)
+                }
+                size => (8, size),
+            };
+            let size = usize::try_from(size)
+                .ok()
+                .filter(|&size| size >= header && size <= data.len())
+                .ok_or(AvifError::Malformed("box size out of range"))?;
+            Ok((kind, &data[header..size], size))
+        })();
+        match parsed {
+            Ok((kind, body, size)) => {
+                self.0 = &data[size..];
+                Some(Ok((kind, body)))
+            }
+            Err(e) => {
+                self.0 = &[];
+                Some(Err(e))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bx(kind: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut out = ((body.len() + 8) as u32).to_be_bytes().to_vec();
+        out.extend_from_slice(kind);
+        out.extend_from_slice(body);
+        out
+    }
+
+    fn full(kind: &[u8; 4], version: u8, body: &[u8]) -> Vec<u8> {
+        let mut payload = vec![version, 0, 0, 0];
+        payload.extend_from_slice(body);
+        bx(kind, &payload)
+    }
+
+    fn infe(id: u16, item_type: &[u8; 4]) -> Vec<u8> {
+        // item_ID, item_protection_index, item_type, empty item_name.
+        let mut body = id.to_be_bytes().to_vec();
+        body.extend_from_slice(&[0, 0]);
+        body.extend_from_slice(item_type);
+        body.push(0);
+        full(b"infe", 2, &body)
+    }
+
+    /// An image whose items each hold four 0xAA bytes in `idat`.
+    fn avif(width: u32, height: u32, item_types: &[&[u8; 4]]) -> Vec<u8> {
+        let mut ispe = width.to_be_bytes().to_vec();
+        ispe.extend_from_slice(&height.to_be_bytes());
+        let ipco = bx(b"ipco", &full(b"ispe", 0, &ispe));
+        let mut iinf = (item_types.len() as u16).to_be_bytes().to_vec();
+        // Four-byte offsets and lengths, no base offset or index.
+        let mut iloc = vec![0x44, 0x00];
+        iloc.extend_from_slice(&(item_types.len() as u16).to_be_bytes());
+        for (index, item_type) in item_types.iter().enumerate() {
+            let id = index as u16 + 1;
+            iinf.extend(infe(id, item_type));
+            iloc.extend_from_slice(&id.to_be_bytes());
+            // construction_method 1, data_reference_index 0, one extent.
+            iloc.extend_from_slice(&[0, 1, 0, 0, 0, 1]);
+            iloc.extend_from_slice(&(index as u32 * 4).to_be_bytes());
+            iloc.extend_from_slice(&4u32.to_be_bytes());
+        }
+        let mut meta = full(b"iinf", 0, &iinf);
+        meta.extend(full(b"iloc", 1, &iloc));
+        meta.extend(bx(b"idat", &vec![0xAA; item_types.len() * 4]));
+        meta.extend(bx(b"iprp", &ipco));
+
+        let mut file = bx(b"ftyp", b"avif\0\0\0\0mif1miaf");
+        file.extend(full(b"meta", 0, &meta));
+        file.extend(bx(b"mdat", &[0; 16]));
+        file
+    }
+
+    #[test]
+    fn test_valid_avif() {
+        assert_eq!(
+            strip_metadata(&mut avif(300, 300, &[b"av01"]), 300),
+            Ok(Dimensions {
+                width: 300,
+                height: 300
+            })
+        );
+    }
+
+    #[test]
+    fn test_rejects_oversized_image() {
+        let mut image = avif(1024, 300, &[b"av01"]);
+        assert_eq!(
+            strip_metadata(&mut image.clone(), 512),
+            Err(AvifError::TooLarge {
+                width: 1024,
+                height: 300,
+                max: 512
+            })
+        );
+        assert!(strip_metadata(&mut image, 1024).is_ok());
+    }
+
+    #[test]
+    fn test_strips_metadata() {
+        let mut image = avif(300, 300, &[b"av01", b"Exif", b"mime"]);
+        let original = image.clone();
+        assert!(strip_metadata(&mut image, 300).is_ok());
+        assert_eq!(image.len(), original.len());
+
+        let idat = image.windows(4).position(|w| w == b"idat").unwrap() + 4;
+        assert_eq!(&image[idat..idat + 4], &[0xAA; 4]);
+        assert_eq!(&image[idat + 4..idat + 12], &[0; 8]);
+        assert_eq!(image[..idat], original[..idat]);
+        assert_eq!(image[idat + 12..], original[idat + 12..]);
+        assert!(strip_metadata(&mut image, 300).is_ok());
+    }
+
+    #[test]
+    fn test_rejects_non_avif() {
+        assert_eq!(
+            strip_metadata(&mut b"\x89PNG\r\n\x1a\n".to_vec(), 300),
+            Err(AvifError::NotAvif)
+        );
+        assert_eq!(
+            strip_metadata(&mut bx(b"ftyp", b"heic\0\0\0\0mif1"), 300),
+            Err(AvifError::NotAvif)
+        );
+        let mut truncated = avif(300, 300, &[b"av01"]);
+        truncated.truncate(40);
+        assert!(matches!(
+            strip_metadata(&mut truncated, 300),
+            Err(AvifError::Malformed(_))
+        ));
+    }
+}
@@ -0,0 +1,279 @@
+//! `--check-config`: validates the configuration like startup does, prints a
+//! summary without secrets and fails on any problem, so a deploy pipeline can
+//! vet a config before swapping the running server.
+
+use crate::auth;
+use crate::client_ip::TrustedProxies;
+use crate::cors::CorsOrigin;
+use crate::encryption::DataCipher;
+use crate::question::QuestionStore;
+use crate::{AppConfig, StorageConfig};
+
+/// Where questions are stored, without credentials.
+fn describe_storage(storage: &StorageConfig) -> String {
+    match storage {
+        StorageConfig::Filesystem {
+            base_path,
+            file_path,
+        } => format!("filesystem {}", base_path.join(file_path).display()),
+        StorageConfig::S3 {
+            bucket,
+            region,
+            prefix,
+            ..
+        } => format!("s3 bucket {bucket} ({region}), prefix '{prefix}'"),
+        StorageConfig::Azure {
+            account,
+            container,
+            prefix,
+            sas_token,
+            ..
+        } => format!(
+            "azure {account}/{container}, prefix '{prefix}', {}",
+            if sas_token.is_some() {
+                "SAS token"
+            } else {
+                "account key"
+            }
+        ),
+    }
+}
+
+fn on_off(enabled: bool) -> &'static str {
+    if enabled { "on" } else { "off" }
+}
+
+fn print_summary(config: &AppConfig) {
+    let server = &config.server;
+    println!("Configuration:");
+    match &server.unix_socket {
+        Some(path) => println!("  Listen: unix socket {}", path.display()),
+        None => println!("  Listen: port {}", server.port),
+    }
+    match &config.tls {
+        Some(tls) => println!("  TLS: certificate {}", tls.cert_path.display()),
+        None => println!("  TLS: off"),
+    }
+    println!("  CORS origins: {}", server.cors_origins.join(", "));
+    println!("  Trusted proxies: {}", server.trusted_proxies.len());
+    println!("  Storage: {}", describe_storage(&config.storage));
+    let mut datasets: Vec<_> = config.datasets.iter().collect();
+    datasets.sort_by_key(|(name, _)| *name);
+    for (name, dataset) in datasets {
+        println!("  Dataset {name}: {}", describe_storage(&dataset.storage));
+    }
+    println!("  Admin passwords: {}", config.admin_password.len());
+    println!(
+        "  JWT secret: {}",
+        if config.auth.jwt_secret.is_some() {
+            "set"
+        } else {
+            "random per start"
+        }
+    );
+    println!("  Encryption: {}", on_off(config.encryption.is_some()));
+    println!(
+        "  Lobby persistence: {}",
+        on_off(config.persistence.enabled)
+    );
+    println!(
+        "  Question refresh: {}",
+        on_off(config.question_refresh.enabled)
+    );
+    println!("  Event log: {}", on_off(config.event_log.enabled));
+    println!("  Webhook URLs: {}", config.webhooks.urls.len());
+    println!("  Spotify: {}", on_off(config.spotify.is_some()));
+    #[cfg(feature = "discord")]
+    println!("  Discord: {}", on_off(config.discord.is_some()));
+    #[cfg(feature = "otlp")]
+    println!(
+        "  Trace export: {}",
+        if config.otlp.enabled {
+            config.otlp.endpoint.as_str()
+        } else {
+            "off"
+        }
+    );
+    #[cfg(feature = "sentry")]
+    println!("  Error reporting: {}", on_off(config.sentry.is_some()));
+    if let Some(dir) = &server.static_dir {
+        println!("  Frontend: {}", dir.display());
+    }
+    println!("  Read-only: {}", on_off(server.read_only));
+}
+
+/// Problems found without touching storage.
+fn config_problems(config: &AppConfig) -> Vec<String> {
+    let mut problems = Vec::new();
+    for origin in &config.server.cors_origins {
+        if let Err(e) = CorsOrigin::parse(origin) {
+            problems.push(e);
+        }
+    }
+    if let Err(e) = TrustedProxies::parse(&config.server.trusted_proxies) {
+        problems.push(e);
+    }
+    if config.admin_password.is_empty() {
+        problems.push("admin_password lists no passwords".to_string());
+    } else if config.admin_password.iter().any(|p| p.trim().is_empty()) {
+        problems.push("admin_password contains an empty password".to_string());
+    }
+    for password in &config.admin_password {
+        if let Err(e) = auth::validate_password_hash(password) {
+            problems.push(format!("admin_password: {e}"));
+        }
+    }
+    for (name, dataset) in &config.datasets {
+        if dataset
+            .admin_password
+            .split(',')
+            .all(|p| p.trim().is_empty())
+        {
+            problems.push(format!("Dataset {name} has no admin password"));
+        }
+        let passwords = dataset.admin_password.split(',').map(str::to_string);
+        for password in auth::rejoin_password_hashes(passwords.collect()) {
+            if let Err(e) = auth::validate_password_hash(password.trim()) {
+                problems.push(format!("Dataset {name} admin_password: {e}"));
+            }
+        }
+    }
+    if config.limits.http_replenish_ms == 0 || config.limits.http_burst == 0 {
+        problems.push(
+            "Invalid rate limit config: http_replenish_ms and http_burst must be non-zero"
+                .to_string(),
+        );
+    }
+    if let Some(encryption) = &config.encryption
+        && let Err(e) = DataCipher::from_base64(&encryption.key)
+    {
+        problems.push(format!("Invalid encryption key: {e}"));
+    }
+    if let Some(dir) = &config.server.static_dir
+        && !dir.join("index.html").is_file()
+    {
+        problems.push(format!("No index.html in static_dir '{}'", dir.display()));
+    }
+    if let Some(tls) = &config.tls {
+        if config.server.unix_socket.is_some() {
+            problems.push("server.unix_socket cannot be combined with tls".to_string());
+        }
+        for path in [&tls.cert_path, &tls.key_path] {
+            if !path.is_file() {
+                problems.push(format!("TLS file '{}' does not exist", path.display()));
+            }
+        }
+    }
+    problems
+}
+
+/// Loads every question store, as startup would.
+async fn storage_problems(config: &AppConfig) -> Vec<String> {
+    let cipher = || {
+        config
+            .encryption
+            .as_ref()
+            .and_then(|encryption| DataCipher::from_base64(&encryption.key).ok())
+    };
+    let mut problems = Vec::new();
+    if let Err(e) = QuestionStore::new(&config.storage, cipher()).await {
+        problems.push(format!("Storage: {e}"));
+    }
+    for (name, dataset) in &config.datasets {
+        if let Err(e) = QuestionStore::new(&dataset.storage, cipher()).await {
+            problems.push(format!("Dataset {name} storage: {e}"));
+        }
+    }
+    problems
+}
+
+pub async fn check_config(config: &AppConfig) -> Result<(), String> {
+    print_summary(config);
+    let mut problems = config_problems(config);
+    problems.extend(storage_problems(config).await);
+    if problems.is_empty() {
+        println!("Configuration OK");
+        return Ok(());
+    }
+    for problem in &problems {
+        println!("Problem: {problem}");
+    }
+    Err(format!("{} configuration problems", problems.len()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(toml: &str) -> AppConfig {
+        config::Config::builder()
+            .add_source(config::File::from_str(toml, config::FileFormat::Toml))
+            .build()
+            .unwrap()
+            .try_deserialize()
+            .unwrap()
+    }
+
+    const VALID: &str = r#"
+        admin_password = ["secret"]
+        [server]
+        port = 8765
+        cors_origins = ["https://*.example.com"]
+        [logging]
+        text = true
+        [storage]
+        type = "s3"
+        bucket = "questions"
+        region = "eu-north-1"
+        prefix = "prod"
+        question_folder = "data"
+        question_file = "questions.json"
+        access_key_id = "AKIAEXAMPLE"
+        secret_access_key = "very-secret"
+    "#;
+
+    #[test]
+    fn valid_config_has_no_problems() {
+        assert!(config_problems(&parse(VALID)).is_empty());
+    }
+
+    #[test]
+    fn reports_every_problem() {
+        let config = parse(
+            &VALID
+                .replace(r#"["secret"]"#, "[]")
+                .replace("https://*.example.com", "example.com"),
+        );
+        let problems = config_problems(&config);
+        assert_eq!(problems.len(), 2, "{problems:?}");
+        assert!(problems[0].contains("missing scheme"));
+        assert_eq!(problems[1], "admin_password lists no passwords");
+    }
+
+    #[test]
+    fn storage_summary_leaves_out_credentials() {
+        let summary = describe_storage(&parse(VALID).storage);
+        assert_eq!(summary, "s3 bucket questions (eu-north-1), prefix 'prod'");
+    }
+
+    #[tokio::test]
+    async fn storage_check_leaves_storage_alone() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("questions.json"), "{}").unwrap();
+        let (settings, _) = VALID.split_once("[storage]").unwrap();
+        let config = parse(&format!(
+            r#"{settings}
+            [storage]
+            type = "filesystem"
+            base_path = "{}"
+            file_path = "questions.json"
+            "#,
+            dir.path().display()
+        ));
+        // Creating or removing a file would change the directory's mtime.
+        let modified = || std::fs::metadata(dir.path()).unwrap().modified().unwrap();
+        let before = modified();
+        storage_problems(&config).await;
+        assert_eq!(modified(), before);
+    }
+}
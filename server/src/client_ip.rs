@@ -0,0 +1,240 @@
+use axum::extract::{ConnectInfo, Request, State};
+use axum::middleware::Next;
+use axum::response::Response;
+use http::HeaderMap;
+use http::header::FORWARDED;
+use ipnet::IpNet;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+use tower_governor::GovernorError;
+use tower_governor::key_extractor::KeyExtractor;
+
+const X_FORWARDED_FOR: &str = "x-forwarded-for";
+
+/// The address a request originates from, after unwrapping trusted proxies.
+/// Inserted into request extensions by [`resolve_client_ip`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct ClientIp(pub IpAddr);
+
+/// Reverse proxies whose `Forwarded` / `X-Forwarded-For` headers are
+/// believed. Headers from any other peer are ignored, since clients can set
+/// them to anything.
+#[derive(Debug, Default)]
+pub struct TrustedProxies {
+    networks: Vec<IpNet>,
+}
+
+impl TrustedProxies {
+    /// Accepts plain addresses and CIDR ranges, e.g. `10.0.0.0/8`.
+    pub fn parse(entries: &[String]) -> Result<Self, String> {
+        let networks = entries
+            .iter()
+            .map(|entry| {
+                entry
+                    .parse::<IpNet>()
+                    .or_else(|_| entry.parse::<IpAddr>().map(IpNet::from))
+                    .map_err(|_| format!("Invalid trusted proxy '{entry}'"))
+            })
+            .collect::<Result<_, _>>()?;
+        Ok(Self { networks })
+    }
+
+    fn contains(&self, ip: IpAddr) -> bool {
+        let ip = ip.to_canonical();
+        self.networks.iter().any(|net| net.contains(&ip))
+    }
+
+    /// Walks the forwarding chain from the nearest hop outwards and returns
+    /// the first address that is not a trusted proxy.
+    pub fn client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        if !self.contains(peer) {
+            return peer;
+        }
+        self.forwarded_client_ip(peer, headers)
+    }
+
+    /// Like [`Self::client_ip`] for a peer that is trusted regardless of
+    /// configuration, such as a proxy on the other end of a unix socket.
+    fn forwarded_client_ip(&self, peer: IpAddr, headers: &HeaderMap) -> IpAddr {
+        let chain = if headers.contains_key(FORWARDED) {
+            forwarded_for(headers)
+        } else {
+            x_forwarded_for(headers)
+        };
+        let mut client = peer;
+        for hop in chain.into_iter().rev() {
+            // An unparseable hop ends the chain we can vouch for.
+            let Some(ip) = hop else {
+                break;
+            };
+            client = ip;
+            if !self.contains(ip) {
+                break;
+            }
+        }
+        client
+    }
+}
+
+fn x_forwarded_for(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    headers
+        .get_all(X_FORWARDED_FOR)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(|hop| hop.trim().parse().ok())
+        .collect()
+}
+
+/// `for=` values of an RFC 7239 `Forwarded` header, e.g.
+/// `for=192.0.2.60;proto=https, for="[2001:db8::1]:4711"`.
+fn forwarded_for(headers: &HeaderMap) -> Vec<Option<IpAddr>> {
+    headers
+        .get_all(FORWARDED)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|element| {
+            element.split(';').find_map(|pair| {
+                let (key, value) = pair.trim().split_once('=')?;
+                key.eq_ignore_ascii_case("for").then(|| parse_node(value))
+            })
+        })
+        .collect()
+}
+
+fn parse_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim().trim_matches('"');
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split_once(']')?.0.parse().ok();
+    }
+    node.parse()
+        .ok()
+        .or_else(|| node.rsplit_once(':')?.0.parse().ok())
+}
+
+/// Resolves the [`ClientIp`] for every request so rate limiting, lobby caps
+/// and logs see the real client behind a trusted proxy. Requests without a
+/// socket address arrived over a unix socket, whose only possible peer is a
+/// local reverse proxy.
+pub async fn resolve_client_ip(
+    State(trusted): State<Arc<TrustedProxies>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    let peer = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip());
+    let ip = match peer {
+        Some(peer) => trusted.client_ip(peer, request.headers()),
+        None => trusted.forwarded_client_ip(Ipv4Addr::LOCALHOST.into(), request.headers()),
+    };
+    request.extensions_mut().insert(ClientIp(ip));
+    next.run(request).await
+}
+
+/// Rate limit key for `tower_governor`, read from [`ClientIp`].
+#[derive(Clone)]
+pub struct ClientIpKeyExtractor;
+
+impl KeyExtractor for ClientIpKeyExtractor {
+    type Key = IpAddr;
+
+    fn extract<T>(&self, req: &http::Request<T>) -> Result<Self::Key, GovernorError> {
+        req.extensions()
+            .get::<ClientIp>()
+            .map(|client| client.0)
+            .ok_or(GovernorError::UnableToExtractKey)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn proxies(entries: &[&str]) -> TrustedProxies {
+        let entries: Vec<String> = entries.iter().map(|e| e.to_string()).collect();
+        TrustedProxies::parse(&entries).unwrap()
+    }
+
+    fn headers(name: &str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            http::HeaderName::from_bytes(name.as_bytes()).unwrap(),
+            value.parse().unwrap(),
+        );
+        headers
+    }
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_untrusted_peer_ignores_headers() {
+        let trusted = proxies(&["10.0.0.1"]);
+        let spoofed = headers(X_FORWARDED_FOR, "1.2.3.4");
+        assert_eq!(
+            trusted.client_ip(ip("203.0.113.7"), &spoofed),
+            ip("203.0.113.7")
+        );
+    }
+
+    #[test]
+    fn test_x_forwarded_for() {
+        let trusted = proxies(&["10.0.0.0/8"]);
+        let peer = ip("10.0.0.1");
+        assert_eq!(
+            trusted.client_ip(peer, &headers(X_FORWARDED_FOR, "203.0.113.7")),
+            ip("203.0.113.7")
+        );
+        // Only the hops appended by trusted proxies are believed.
+        assert_eq!(
+            trusted.client_ip(
+                peer,
+                &headers(X_FORWARDED_FOR, "1.2.3.4, 203.0.113.7, 10.1.2.3")
+            ),
+            ip("203.0.113.7")
+        );
+        assert_eq!(
+            trusted.client_ip(peer, &headers(X_FORWARDED_FOR, "garbage")),
+            peer
+        );
+        assert_eq!(trusted.client_ip(peer, &HeaderMap::new()), peer);
+    }
+
+    #[test]
+    fn test_forwarded() {
+        let trusted = proxies(&["10.0.0.1", "2001:db8::/32"]);
+        let value = r#"for="[2001:db8:cafe::17]:4711", for=192.0.2.60;proto=https"#;
+        assert_eq!(
+            trusted.client_ip(ip("10.0.0.1"), &headers("forwarded", value)),
+            ip("192.0.2.60")
+        );
+        let value = "for=198.51.100.17:8080;proto=https, for=2001:db8::1";
+        assert_eq!(
+            trusted.client_ip(ip("10.0.0.1"), &headers("forwarded", value)),
+            ip("198.51.100.17")
+        );
+    }
+
+    #[test]
+    fn test_unix_socket_peer_is_trusted() {
+        let trusted = TrustedProxies::default();
+        let localhost = Ipv4Addr::LOCALHOST.into();
+        assert_eq!(
+            trusted.forwarded_client_ip(localhost, &headers(X_FORWARDED_FOR, "203.0.113.7")),
+            ip("203.0.113.7")
+        );
+        assert_eq!(
+            trusted.forwarded_client_ip(localhost, &HeaderMap::new()),
+            localhost
+        );
+    }
+
+    #[test]
+    fn test_invalid_trusted_proxy() {
+        assert!(TrustedProxies::parse(&["not-an-ip".to_string()]).is_err());
+    }
+}
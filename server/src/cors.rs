@@ -0,0 +1,129 @@
+use http::HeaderValue;
+
+/// An entry of `server.cors_origins`: either an exact origin or a pattern
+/// with a wildcard first host label, e.g. `https://*.vercel.app`, for
+/// preview deployments that get a fresh subdomain per branch.
+#[derive(Debug, PartialEq)]
+pub enum CorsOrigin {
+    Exact(HeaderValue),
+    Wildcard {
+        /// Everything before the `*`, e.g. `https://`.
+        prefix: String,
+        /// Everything after the `*`, e.g. `.vercel.app`.
+        suffix: String,
+    },
+}
+
+impl CorsOrigin {
+    pub fn parse(origin: &str) -> Result<Self, String> {
+        let Some((scheme, host)) = origin.split_once("://") else {
+            return Err(format!("Invalid CORS origin '{origin}': missing scheme"));
+        };
+        if host.contains('/') {
+            return Err(format!(
+                "Invalid CORS origin '{origin}': must not contain a path"
+            ));
+        }
+        if !origin.contains('*') {
+            return origin
+                .parse()
+                .map(CorsOrigin::Exact)
+                .map_err(|e| format!("Invalid CORS origin '{origin}': {e}"));
+        }
+        match host.strip_prefix('*') {
+            Some(rest) if rest.starts_with('.') && rest.len() > 1 && !rest.contains('*') => {
+                Ok(CorsOrigin::Wildcard {
+                    prefix: format!("{scheme}://"),
+                    suffix: rest.to_string(),
+                })
+            }
+            _ => Err(format!(
+                "Invalid CORS origin '{origin}': '*' is only allowed as the whole first host label, e.g. https://*.example.com"
+            )),
+        }
+    }
+
+    pub fn matches(&self, origin: &HeaderValue) -> bool {
+        match self {
+            CorsOrigin::Exact(allowed) => allowed == origin,
+            CorsOrigin::Wildcard { prefix, suffix } => {
+                let Ok(origin) = origin.to_str() else {
+                    return false;
+                };
+                let Some(label) = origin
+                    .strip_prefix(prefix.as_str())
+                    .and_then(|rest| rest.strip_suffix(suffix.as_str()))
+                else {
+                    return false;
+                };
+                // A single DNS label, so nested subdomains are not admitted.
+                !label.is_empty()
+                    && label
+                        .bytes()
+                        .all(|b| b.is_ascii_alphanumeric() || b == b'-')
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn allows(pattern: &str, origin: &str) -> bool {
+        CorsOrigin::parse(pattern)
+            .unwrap()
+            .matches(&HeaderValue::from_str(origin).unwrap())
+    }
+
+    #[test]
+    fn test_exact_origin() {
+        assert!(allows(
+            "https://quiz.example.com",
+            "https://quiz.example.com"
+        ));
+        assert!(!allows(
+            "https://quiz.example.com",
+            "https://other.example.com"
+        ));
+        assert!(!allows(
+            "https://quiz.example.com",
+            "http://quiz.example.com"
+        ));
+    }
+
+    #[test]
+    fn test_wildcard_origin() {
+        let pattern = "https://*.vercel.app";
+        assert!(allows(pattern, "https://spektrum-git-main.vercel.app"));
+        assert!(!allows(pattern, "https://vercel.app"));
+        assert!(!allows(pattern, "https://.vercel.app"));
+        assert!(!allows(pattern, "http://preview.vercel.app"));
+        assert!(!allows(pattern, "https://a.b.vercel.app"));
+        assert!(!allows(pattern, "https://preview.vercel.app.evil.com"));
+        assert!(allows(
+            "http://*.localhost:5173",
+            "http://app.localhost:5173"
+        ));
+        assert!(!allows(
+            "http://*.localhost:5173",
+            "http://app.localhost:8080"
+        ));
+    }
+
+    #[test]
+    fn test_invalid_patterns() {
+        for pattern in [
+            "*",
+            "https://*",
+            "https://*.",
+            "https://app*.example.com",
+            "https://*.*.example.com",
+            "https://example.*",
+            "https://*.example.com/path",
+            "example.com",
+        ] {
+            assert!(CorsOrigin::parse(pattern).is_err(), "{pattern}");
+        }
+    }
+}
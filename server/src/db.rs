@@ -1,22 +1,42 @@
 use crate::StorageConfig;
-use crate::question::{Color, GameQuestion, GameQuestionOption, QuestionType};
+use crate::accounts::Account;
+use crate::audio::AudioFormat;
+use crate::encryption::{self, DataCipher, EncryptionError};
+use crate::game::{GameRecord, LobbySnapshot};
+use crate::migrate::LegacyQuestion;
+use crate::question::{
+    Color, GameQuestion, GameQuestionOption, QuestionSet, QuestionType, normalize_answer,
+};
+use crate::retry::{self, CircuitBreaker, RetryError, RetryPolicy};
 use aws_sdk_s3::Client;
+use aws_sdk_s3::config::http::HttpResponse;
+use aws_sdk_s3::config::retry::RetryConfig;
 use aws_sdk_s3::config::{
     Credentials, Region, RequestChecksumCalculation, ResponseChecksumValidation,
 };
 use aws_sdk_s3::error::SdkError;
+use aws_sdk_s3::presigning::PresigningConfig;
 use aws_sdk_s3::primitives::ByteStream;
-use chrono::Utc;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use bytes::Bytes;
+use chrono::{NaiveDateTime, SecondsFormat, Utc};
+use dashmap::DashMap;
 use flate2::Compression;
 use flate2::{read::GzDecoder, write::GzEncoder};
+use hmac::{Hmac, Mac};
+use reqwest::{Method, StatusCode};
 use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 use std::collections::{HashMap, HashSet};
 use std::io::Read;
 use std::io::Write;
+use std::net::IpAddr;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use thiserror::Error;
-use tracing::{info, instrument, warn};
+use tracing::{error, info, instrument, warn};
 
 #[derive(Error, Debug)]
 pub enum DbError {
@@ -34,6 +54,16 @@ pub enum DbError {
         #[source]
         source: Box<dyn std::error::Error + Send + Sync>,
     },
+    #[error("Encryption error: {0}")]
+    Encryption(#[from] EncryptionError),
+    #[error("Corrupted data: {0}")]
+    Corrupted(String),
+    #[error("Azure error: {msg}")]
+    Azure {
+        msg: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+    },
 }
 
 fn s3_error<E: std::error::Error + Send + Sync + 'static>(
@@ -46,6 +76,16 @@ fn s3_error<E: std::error::Error + Send + Sync + 'static>(
     }
 }
 
+fn azure_error<E: std::error::Error + Send + Sync + 'static>(
+    msg: impl Into<String>,
+    source: E,
+) -> DbError {
+    DbError::Azure {
+        msg: msg.into(),
+        source: Box::new(source),
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct Media {
     id: i64,
@@ -81,13 +121,6 @@ struct QuestionOption {
     is_correct: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct QuestionSet {
-    pub id: i64,
-    pub name: Arc<str>,
-    pub question_ids: Vec<i64>,
-}
-
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct StoredData {
     media: Vec<Media>,
@@ -98,6 +131,123 @@ pub struct StoredData {
 }
 
 impl StoredData {
+    /// Prefixes relative image URLs with `base_url`, for clients that load
+    /// media from a CDN instead of this server.
+    pub fn resolve_media_urls(&mut self, base_url: &str) {
+        self.map_media_urls(|url| url.starts_with('/').then(|| format!("{base_url}{url}")));
+    }
+
+    /// Reverses [`Self::resolve_media_urls`], so stored URLs stay relative
+    /// and survive a change of CDN.
+    pub fn relativize_media_urls(&mut self, base_url: &str) {
+        self.map_media_urls(|url| {
+            url.strip_prefix(base_url)
+                .filter(|path| path.starts_with('/'))
+                .map(str::to_string)
+        });
+    }
+
+    fn map_media_urls(&mut self, rewrite: impl Fn(&str) -> Option<String>) {
+        let urls = self.characters.iter_mut().map(|c| &mut c.image_url).chain(
+            self.questions
+                .iter_mut()
+                .filter_map(|q| q.image_url.as_mut()),
+        );
+        for url in urls {
+            if let Some(rewritten) = rewrite(url) {
+                *url = rewritten.into();
+            }
+        }
+    }
+
+    /// Names of the characters whose image is `image_url`, with or without a
+    /// leading slash.
+    pub fn characters_using_image(&self, image_url: &str) -> Vec<Arc<str>> {
+        let image_url = image_url.trim_start_matches('/');
+        self.characters
+            .iter()
+            .filter(|c| c.image_url.trim_start_matches('/') == image_url)
+            .map(|c| c.name.clone())
+            .collect()
+    }
+
+    /// Adds a question converted from the legacy CSV format, reusing media
+    /// with the same YouTube ID and creating missing characters. Returns
+    /// `false` without changes if the media already has a question of this type.
+    pub fn merge_legacy_question(&mut self, legacy: &LegacyQuestion) -> Result<bool, DbError> {
+        let media_id = match self
+            .media
+            .iter()
+            .find(|m| *m.youtube_id == *legacy.youtube_id)
+        {
+            Some(media) => media.id,
+            None => {
+                let id = next_id(self.media.iter().map(|m| m.id));
+                self.media.push(Media {
+                    id,
+                    title: legacy.title.as_str().into(),
+                    artist: legacy.artist.as_str().into(),
+                    release_year: None,
+                    spotify_uri: legacy.spotify_uri.as_deref().map(Arc::from),
+                    youtube_id: legacy.youtube_id.as_str().into(),
+                });
+                id
+            }
+        };
+        if self
+            .questions
+            .iter()
+            .any(|q| q.media_id == media_id && q.question_type == legacy.question_type)
+        {
+            return Ok(false);
+        }
+
+        if legacy.question_type == QuestionType::Character {
+            for name in legacy.correct.iter().chain(&legacy.incorrect) {
+                if !self.characters.iter().any(|c| *c.name == **name) {
+                    let image_url = character_image_url(name)?;
+                    self.characters.push(Character {
+                        id: next_id(self.characters.iter().map(|c| c.id)),
+                        name: name.as_str().into(),
+                        image_url: image_url.into(),
+                    });
+                }
+            }
+        }
+
+        let question_id = next_id(self.questions.iter().map(|q| q.id));
+        self.questions.push(Question {
+            id: question_id,
+            media_id,
+            question_type: legacy.question_type,
+            question_text: None,
+            image_url: None,
+            is_active: true,
+        });
+        let options = (legacy.correct.iter().map(|o| (o, true)))
+            .chain(legacy.incorrect.iter().map(|o| (o, false)));
+        for (option_text, is_correct) in options {
+            self.options.push(QuestionOption {
+                id: next_id(self.options.iter().map(|o| o.id)),
+                question_id,
+                option_text: option_text.as_str().into(),
+                is_correct,
+            });
+        }
+        Ok(true)
+    }
+
+    /// Puts every question in an "All Questions" set if there are no sets.
+    pub fn ensure_default_set(&mut self) {
+        if self.sets.is_empty() && !self.questions.is_empty() {
+            self.sets.push(QuestionSet {
+                id: 1,
+                name: "All Questions".into(),
+                question_ids: self.questions.iter().map(|q| q.id).collect(),
+            });
+        }
+    }
+
     /// Validates the integrity of the stored data by checking:
     /// - Duplicate IDs of any type (media, characters, questions, options, sets)
     /// - Duplicate character names or image URLs
@@ -198,6 +348,7 @@ impl StoredData {
             }
         }
 
+        let mut seen_answers: HashMap<i64, HashMap<String, &Arc<str>>> = HashMap::new();
         for option in &self.options {
             if !question_ids.contains(&option.question_id) {
                 return Err(DbError::Validation(format!(
@@ -243,6 +394,17 @@ impl StoredData {
                 }
                 QuestionType::Year => {}
             }
+
+            // Answers are matched normalized, so this would accept both.
+            let answers = seen_answers.entry(option.question_id).or_default();
+            if let Some(other) =
+                answers.insert(normalize_answer(&option.option_text), &option.option_text)
+            {
+                return Err(DbError::Validation(format!(
+                    "Options '{}' and '{}' of question {} are the same answer",
+                    other, option.option_text, option.question_id
+                )));
+            }
         }
 
         for set in &self.sets {
@@ -260,9 +422,80 @@ impl StoredData {
     }
 }
 
+/// Problems found by [`QuestionDatabase::check_integrity`]. Apart from
+/// `validation_error`, none of these stop the data from loading.
+#[derive(Debug, Default, Serialize)]
+pub struct IntegrityReport {
+    /// First error from [`StoredData::validate_stored_data`].
+    pub validation_error: Option<String>,
+    /// Media no question refers to.
+    pub unused_media: Vec<IntegrityItem>,
+    /// Characters whose image is not in storage.
+    pub missing_character_images: Vec<IntegrityItem>,
+    /// Sets that would start a lobby without any playable question.
+    pub inactive_sets: Vec<IntegrityItem>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct IntegrityItem {
+    pub id: i64,
+    pub name: Arc<str>,
+}
+
+impl IntegrityReport {
+    pub fn is_ok(&self) -> bool {
+        self.validation_error.is_none()
+            && self.unused_media.is_empty()
+            && self.missing_character_images.is_empty()
+            && self.inactive_sets.is_empty()
+    }
+}
+
+impl StoredData {
+    /// Everything in the integrity report that doesn't need storage access.
+    fn integrity_report(&self) -> IntegrityReport {
+        let used_media: HashSet<i64> = self.questions.iter().map(|q| q.media_id).collect();
+        let active_questions: HashSet<i64> = self
+            .questions
+            .iter()
+            .filter(|q| q.is_active)
+            .map(|q| q.id)
+            .collect();
+        IntegrityReport {
+            validation_error: self.validate_stored_data().err().map(|e| e.to_string()),
+            unused_media: self
+                .media
+                .iter()
+                .filter(|m| !used_media.contains(&m.id))
+                .map(|m| IntegrityItem {
+                    id: m.id,
+                    name: m.title.clone(),
+                })
+                .collect(),
+            missing_character_images: Vec::new(),
+            inactive_sets: self
+                .sets
+                .iter()
+                .filter(|set| {
+                    !set.question_ids.is_empty()
+                        && !set
+                            .question_ids
+                            .iter()
+                            .any(|id| active_questions.contains(id))
+                })
+                .map(|set| IntegrityItem {
+                    id: set.id,
+                    name: set.name.clone(),
+                })
+                .collect(),
+        }
+    }
+}
+
 pub enum Storage {
     Filesystem(FilesystemBackend),
     S3(S3Backend),
+    Azure(AzureBackend),
 }
 
 impl Storage {
@@ -270,6 +503,26 @@ impl Storage {
         match self {
             Self::Filesystem(fs) => fs.read_file(path).await,
             Self::S3(s3) => s3.read_file(path).await,
+            Self::Azure(azure) => azure.read_file(path).await,
+        }
+    }
+
+    /// Raw contents of a media file, or `None` if it does not exist.
+    async fn read_bytes(&self, path: &str) -> Result<Option<Vec<u8>>, DbError> {
+        match self {
+            Self::Filesystem(fs) => fs.read_bytes(path).await,
+            Self::S3(s3) => s3.read_bytes(path).await,
+            Self::Azure(azure) => azure.read_bytes(path).await,
+        }
+    }
+
+    /// Opaque token that changes whenever `path` is rewritten, or `None` if
+    /// the file does not exist.
+    async fn file_version(&self, path: &str) -> Result<Option<String>, DbError> {
+        match self {
+            Self::Filesystem(fs) => fs.file_version(path).await,
+            Self::S3(s3) => s3.file_version(path).await,
+            Self::Azure(azure) => azure.file_version(path).await,
         }
     }
 
@@ -277,6 +530,7 @@ impl Storage {
         match self {
             Self::Filesystem(fs) => fs.write_file(path, data).await,
             Self::S3(s3) => s3.write_file(path, data).await,
+            Self::Azure(azure) => azure.write_file(path, data).await,
         }
     }
 
@@ -284,6 +538,107 @@ impl Storage {
         match self {
             Self::Filesystem(fs) => fs.create_backup(content, file_stem).await,
             Self::S3(s3) => s3.create_backup(content, file_stem).await,
+            Self::Azure(azure) => azure.create_backup(content, file_stem).await,
+        }
+    }
+
+    /// Backups of `file_stem`, newest first.
+    async fn list_backups(&self, file_stem: &str) -> Result<Vec<String>, DbError> {
+        let mut names = match self {
+            Self::Filesystem(fs) => fs.list_backups().await?,
+            Self::S3(s3) => {
+                s3.list_keys(&backup_prefix(&s3.prefix, &s3.question_folder, file_stem))
+                    .await?
+            }
+            Self::Azure(azure) => {
+                azure
+                    .list_blobs(&backup_prefix(
+                        &azure.prefix,
+                        &azure.question_folder,
+                        file_stem,
+                    ))
+                    .await?
+            }
+        };
+        names.retain(|name| is_backup_of(name, file_stem));
+        // Timestamps sort chronologically as text.
+        names.sort_unstable_by(|a, b| b.cmp(a));
+        Ok(names)
+    }
+
+    /// Names of the JSON files in `dir`, without the extension.
+    async fn list_json_files(&self, dir: &str) -> Result<Vec<String>, DbError> {
+        let paths = match self {
+            Self::Filesystem(fs) => fs.list_files(dir).await?,
+            Self::S3(s3) => {
+                let folder = format!("{}/{}/", s3.prefix, s3.question_folder);
+                strip_folder(s3.list_keys(&format!("{folder}{dir}/")).await?, &folder)
+            }
+            Self::Azure(azure) => {
+                let folder = format!("{}/{}/", azure.prefix, azure.question_folder);
+                strip_folder(azure.list_blobs(&format!("{folder}{dir}/")).await?, &folder)
+            }
+        };
+        Ok(paths
+            .iter()
+            .filter_map(|path| {
+                let name = path.strip_prefix(dir)?.strip_prefix('/')?;
+                let name = name.strip_suffix(".json.gz").unwrap_or(name);
+                name.strip_suffix(".json").map(str::to_string)
+            })
+            .collect())
+    }
+
+    /// Contents of a backup returned by [`Storage::list_backups`].
+    async fn read_backup(&self, name: &str) -> Result<String, DbError> {
+        let compressed = match self {
+            Self::Filesystem(fs) => Some(tokio::fs::read(fs.backup_dir.join(name)).await?),
+            Self::S3(s3) => s3.get_bytes(name).await?,
+            Self::Azure(azure) => azure.get_blob(name).await?,
+        };
+        let compressed =
+            compressed.ok_or_else(|| DbError::Validation(format!("Backup {name} not found")))?;
+        Ok(gunzip(&compressed)?)
+    }
+
+    /// Where `path` lives, for log messages.
+    fn location(&self, path: &str) -> String {
+        match self {
+            Self::Filesystem(fs) => fs.base_path.join(path).display().to_string(),
+            Self::S3(s3) => format!(
+                "s3://{}/{}",
+                s3.bucket,
+                object_key(&s3.prefix, &s3.question_folder, path)
+            ),
+            Self::Azure(azure) => format!(
+                "{}/{}/{}",
+                azure.endpoint,
+                azure.container,
+                object_key(&azure.prefix, &azure.question_folder, path)
+            ),
+        }
+    }
+
+    /// What to look at when reading or writing fails.
+    fn access_hint(&self) -> &'static str {
+        match self {
+            Self::Filesystem(_) => {
+                "Check that SPEKTRUM__STORAGE__BASE_PATH exists and is readable and writable by the server user"
+            }
+            Self::S3(_) => {
+                "Check SPEKTRUM__STORAGE__BUCKET and REGION, and that the access key may get, put and delete objects under the prefix"
+            }
+            Self::Azure(_) => {
+                "Check SPEKTRUM__STORAGE__ACCOUNT and CONTAINER, and that the SAS token or account key grants read, write and delete"
+            }
+        }
+    }
+
+    async fn delete_file(&self, path: &str) -> Result<(), DbError> {
+        match self {
+            Self::Filesystem(fs) => fs.delete_file(path).await,
+            Self::S3(s3) => s3.delete_file(path).await,
+            Self::Azure(azure) => azure.delete_file(path).await,
         }
     }
 
@@ -292,22 +647,126 @@ impl Storage {
         character_name: &str,
         data: &[u8],
     ) -> Result<String, DbError> {
-        if character_name.is_empty()
-            || !character_name
-                .chars()
-                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
-        {
-            return Err(DbError::Validation(format!(
-                "Invalid character name: {character_name}"
-            )));
-        }
-        let filename = format!("{character_name}.avif");
-        let path = format!("img/{filename}");
+        let path = character_image_path(character_name)?;
         self.write_file(&path, data).await?;
-        Ok(format!("/img/{filename}"))
+        Ok(format!("/{path}"))
+    }
+
+    pub async fn delete_character_image(&self, character_name: &str) -> Result<String, DbError> {
+        let path = character_image_path(character_name)?;
+        self.delete_file(&path).await?;
+        Ok(format!("/{path}"))
+    }
+
+    /// Stores the audio clip for a media entry, replacing a clip in another
+    /// format so each media entry has at most one.
+    pub async fn store_audio_clip(
+        &self,
+        media_id: i64,
+        format: AudioFormat,
+        data: &[u8],
+    ) -> Result<(), DbError> {
+        let path = audio_clip_path(media_id, format);
+        self.write_file(&path, data).await?;
+        for other in AudioFormat::ALL.into_iter().filter(|&f| f != format) {
+            self.delete_file(&audio_clip_path(media_id, other)).await?;
+        }
+        Ok(())
+    }
+
+    pub async fn read_audio_clip(
+        &self,
+        media_id: i64,
+    ) -> Result<Option<(AudioFormat, Vec<u8>)>, DbError> {
+        for format in AudioFormat::ALL {
+            if let Some(data) = self.read_bytes(&audio_clip_path(media_id, format)).await? {
+                return Ok(Some((format, data)));
+            }
+        }
+        Ok(None)
+    }
+
+    /// A signed request that uploads `path` straight to the bucket, or
+    /// `None` when the backend has no way to hand out upload URLs.
+    async fn presign_upload(
+        &self,
+        path: &str,
+        expires_in: Duration,
+    ) -> Result<Option<PresignedUpload>, DbError> {
+        match self {
+            Self::S3(s3) => s3.presign_upload(path, expires_in).await.map(Some),
+            Self::Filesystem(_) | Self::Azure(_) => Ok(None),
+        }
     }
 }
 
+/// An upload the client sends directly to object storage.
+#[derive(Debug, Serialize)]
+pub struct PresignedUpload {
+    pub method: String,
+    pub url: String,
+    /// Headers that were signed and must be sent unchanged.
+    pub headers: HashMap<String, String>,
+    /// Where the file will be served from once uploaded.
+    pub media_url: String,
+    /// RFC 3339 timestamp after which the signature is rejected.
+    pub expires_at: String,
+}
+
+/// Extensions accepted for directly uploaded media, and the content type
+/// each is stored with.
+const MEDIA_TYPES: &[(&str, &str)] = &[
+    ("avif", "image/avif"),
+    ("png", "image/png"),
+    ("jpg", "image/jpeg"),
+    ("jpeg", "image/jpeg"),
+    ("webp", "image/webp"),
+    ("mp3", "audio/mpeg"),
+    ("m4a", "audio/mp4"),
+    ("ogg", "audio/ogg"),
+    ("opus", "audio/ogg"),
+    ("webm", "video/webm"),
+];
+
+fn is_valid_object_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// URL a character's image is served from, as stored in [`Character`] rows.
+pub fn character_image_url(character_name: &str) -> Result<String, DbError> {
+    Ok(format!("/{}", character_image_path(character_name)?))
+}
+
+/// Storage path of a character's image, relative to the storage root.
+fn character_image_path(character_name: &str) -> Result<String, DbError> {
+    if !is_valid_object_name(character_name) {
+        return Err(DbError::Validation(format!(
+            "Invalid character name: {character_name}"
+        )));
+    }
+    Ok(format!("img/{character_name}.avif"))
+}
+
+fn audio_clip_path(media_id: i64, format: AudioFormat) -> String {
+    format!("audio/{media_id}.{}", format.extension())
+}
+
+/// Storage path for a directly uploaded media file such as `intro.mp3`.
+pub fn media_path(file_name: &str) -> Result<String, DbError> {
+    let valid = file_name.rsplit_once('.').is_some_and(|(stem, ext)| {
+        is_valid_object_name(stem) && MEDIA_TYPES.iter().any(|(e, _)| *e == ext)
+    });
+    if !valid {
+        return Err(DbError::Validation(format!(
+            "Invalid media file name: {file_name}"
+        )));
+    }
+    Ok(format!("media/{file_name}"))
+}
+
 // Filesystem implementation
 pub struct FilesystemBackend {
     base_path: PathBuf,
@@ -315,6 +774,15 @@ pub struct FilesystemBackend {
 }
 
 impl FilesystemBackend {
+    #[instrument(target = "storage", level = "debug", skip(self), fields(path = %path))]
+    async fn read_bytes(&self, path: &str) -> Result<Option<Vec<u8>>, DbError> {
+        match tokio::fs::read(self.base_path.join(path)).await {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(DbError::from(e)),
+        }
+    }
+
     #[instrument(target = "storage", level = "debug", skip(self), fields(path = %path))]
     async fn read_file(&self, path: &str) -> Result<String, DbError> {
         let full_path = self.base_path.join(path);
@@ -327,6 +795,20 @@ impl FilesystemBackend {
         }
     }
 
+    #[instrument(target = "storage", level = "debug", skip(self), fields(path = %path))]
+    async fn file_version(&self, path: &str) -> Result<Option<String>, DbError> {
+        let metadata = match tokio::fs::metadata(self.base_path.join(path)).await {
+            Ok(metadata) => metadata,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(DbError::from(e)),
+        };
+        let modified = metadata
+            .modified()?
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        Ok(Some(format!("{}-{}", modified.as_nanos(), metadata.len())))
+    }
+
     #[instrument(target = "storage", level = "debug", skip(self, data), fields(path = %path, size_bytes = data.len()))]
     async fn write_file(&self, path: &str, data: &[u8]) -> Result<(), DbError> {
         let full_path = self.base_path.join(path);
@@ -343,6 +825,14 @@ impl FilesystemBackend {
             .map_err(DbError::from)
     }
 
+    #[instrument(target = "storage", level = "debug", skip(self), fields(path = %path))]
+    async fn delete_file(&self, path: &str) -> Result<(), DbError> {
+        match tokio::fs::remove_file(self.base_path.join(path)).await {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(DbError::from(e)),
+            _ => Ok(()),
+        }
+    }
+
     #[instrument(target = "storage", level = "debug", skip(self, content), fields(file_stem = %file_stem))]
     async fn create_backup(&self, content: &str, file_stem: &str) -> Result<(), DbError> {
         let backup_dir = self.backup_dir.clone();
@@ -351,7 +841,7 @@ impl FilesystemBackend {
         tokio::task::spawn_blocking(move || {
             std::fs::create_dir_all(&backup_dir)?;
             let now = Utc::now();
-            let timestamp = now.format("%y%m%d_%H%M%S").to_string();
+            let timestamp = now.format(BACKUP_TIMESTAMP_FORMAT).to_string();
             let filename = format!("{file_stem}_{timestamp}.json.gz");
             let full_path = backup_dir.join(filename);
             let file = std::fs::File::create(&full_path)?;
@@ -363,6 +853,112 @@ impl FilesystemBackend {
         .await
         .map_err(|e| DbError::Io(std::io::Error::other(e)))?
     }
+
+    /// Paths of the files in `dir`, relative to the base path.
+    async fn list_files(&self, dir: &str) -> Result<Vec<String>, DbError> {
+        let mut entries = match tokio::fs::read_dir(self.base_path.join(dir)).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(DbError::from(e)),
+        };
+        let mut paths = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            if let Some(name) = entry.file_name().to_str() {
+                paths.push(format!("{dir}/{name}"));
+            }
+        }
+        Ok(paths)
+    }
+
+    async fn list_backups(&self) -> Result<Vec<String>, DbError> {
+        let mut entries = match tokio::fs::read_dir(&self.backup_dir).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(DbError::from(e)),
+        };
+        let mut names = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            if let Some(name) = entry.file_name().to_str() {
+                names.push(name.to_string());
+            }
+        }
+        Ok(names)
+    }
+}
+
+// Object storage layout shared by the S3 and Azure backends: question data
+// is gzipped in a hidden folder, everything else sits under the prefix.
+fn is_json(path: &str) -> bool {
+    Path::new(path)
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("json"))
+}
+
+fn object_key(prefix: &str, question_folder: &str, path: &str) -> String {
+    if is_json(path) {
+        format!("{prefix}/{question_folder}/{path}.gz")
+    } else {
+        format!("{prefix}/{path}")
+    }
+}
+
+/// `keys` relative to `folder`, the object key of the storage root.
+fn strip_folder(keys: Vec<String>, folder: &str) -> Vec<String> {
+    keys.into_iter()
+        .filter_map(|key| key.strip_prefix(folder).map(str::to_string))
+        .collect()
+}
+
+fn content_type(path: &str) -> &'static str {
+    let Some(ext) = Path::new(path).extension().and_then(|ext| ext.to_str()) else {
+        return "application/octet-stream";
+    };
+    if ext.eq_ignore_ascii_case("json") {
+        return "application/gzip";
+    }
+    MEDIA_TYPES
+        .iter()
+        .find(|(e, _)| e.eq_ignore_ascii_case(ext))
+        .map_or("application/octet-stream", |(_, content_type)| content_type)
+}
+
+const BACKUP_TIMESTAMP_FORMAT: &str = "%y%m%d_%H%M%S";
+
+fn backup_prefix(prefix: &str, question_folder: &str, file_stem: &str) -> String {
+    format!("{prefix}/{question_folder}/backup/{file_stem}_")
+}
+
+fn backup_key(prefix: &str, question_folder: &str, file_stem: &str) -> String {
+    let timestamp = Utc::now().format(BACKUP_TIMESTAMP_FORMAT);
+    format!(
+        "{}{timestamp}.json.gz",
+        backup_prefix(prefix, question_folder, file_stem)
+    )
+}
+
+/// Whether `name`, a backup file name or object key, is a backup of
+/// `file_stem` rather than of another file sharing its prefix.
+fn is_backup_of(name: &str, file_stem: &str) -> bool {
+    let file_name = name.rsplit('/').next().unwrap_or(name);
+    file_name
+        .strip_prefix(file_stem)
+        .and_then(|rest| rest.strip_prefix('_'))
+        .and_then(|rest| rest.strip_suffix(".json.gz"))
+        .is_some_and(|timestamp| {
+            NaiveDateTime::parse_from_str(timestamp, BACKUP_TIMESTAMP_FORMAT).is_ok()
+        })
+}
+
+pub fn gzip(data: &[u8]) -> std::io::Result<Vec<u8>> {
+    let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+    encoder.write_all(data)?;
+    encoder.finish()
+}
+
+fn gunzip(data: &[u8]) -> std::io::Result<String> {
+    let mut decompressed = String::new();
+    GzDecoder::new(data).read_to_string(&mut decompressed)?;
+    Ok(decompressed)
 }
 
 // S3 implementation
@@ -371,172 +967,660 @@ pub struct S3Backend {
     bucket: String,
     prefix: String,
     question_folder: String,
+    retry: RetryPolicy,
+    breaker: CircuitBreaker,
+    /// Last successfully read or written content per path, served when S3 is
+    /// unreachable so a brief outage doesn't take the admin panel down.
+    last_good: DashMap<String, String>,
+}
+
+type S3Error<E> = RetryError<SdkError<E, HttpResponse>>;
+
+/// Timeouts, connection failures, throttling and 5xx responses.
+fn is_transient_s3_error<E>(err: &SdkError<E, HttpResponse>) -> bool {
+    match err {
+        SdkError::TimeoutError(_) | SdkError::DispatchFailure(_) | SdkError::ResponseError(_) => {
+            true
+        }
+        SdkError::ServiceError(service_err) => {
+            let status = service_err.raw().status();
+            status.is_server_error() || status.as_u16() == 429
+        }
+        _ => false,
+    }
 }
 
 impl S3Backend {
+    async fn send<T, E, F, Fut>(&self, op: F) -> Result<T, S3Error<E>>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = Result<T, SdkError<E, HttpResponse>>>,
+    {
+        retry::call(&self.retry, &self.breaker, is_transient_s3_error, op).await
+    }
+
     #[instrument(target = "storage", level = "debug", skip(self), fields(path = %path))]
     async fn read_file(&self, path: &str) -> Result<String, DbError> {
-        let mut key = format!("{}/{}", self.prefix, path);
-
-        let is_json = std::path::Path::new(path)
-            .extension()
-            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
-
-        // Read json question data from hidden folder
-        if is_json {
-            key = format!("{}/{}/{}.gz", self.prefix, self.question_folder, path);
-        }
+        let key = object_key(&self.prefix, &self.question_folder, path);
+        let is_json = is_json(path);
 
         info!(target: "storage", s3_key = %key, "Reading from S3");
 
-        match self
-            .client
-            .get_object()
-            .bucket(&self.bucket)
-            .key(&key)
-            .send()
+        let response = match self
+            .send(|| {
+                self.client
+                    .get_object()
+                    .bucket(&self.bucket)
+                    .key(&key)
+                    .send()
+            })
             .await
         {
-            Ok(response) => {
-                let bytes = response
-                    .body
-                    .collect()
-                    .await
-                    .map_err(|e| s3_error("Failed to collect bytes", e))?;
-
-                // If it's a JSON file, decompress it
-                if is_json {
-                    let bytes = bytes.into_bytes();
-                    let mut decoder = GzDecoder::new(&bytes[..]);
-                    let mut decompressed = String::new();
-                    decoder
-                        .read_to_string(&mut decompressed)
-                        .map_err(|e| s3_error("Failed to decompress", e))?;
-                    Ok(decompressed)
-                } else {
-                    String::from_utf8(bytes.to_vec()).map_err(|e| s3_error("Invalid UTF-8", e))
-                }
+            Ok(response) => response,
+            Err(RetryError::Failed(SdkError::ServiceError(service_err)))
+                if service_err.err().is_no_such_key() =>
+            {
+                return Ok(String::new());
             }
             Err(err) => {
-                if let SdkError::ServiceError(service_err) = &err {
-                    warn!(target: "storage", error = ?err, "S3 read error");
-                    if service_err.err().is_no_such_key() {
-                        return Ok(String::new());
-                    }
+                warn!(target: "storage", error = ?err, "S3 read error");
+                let unavailable = match &err {
+                    RetryError::CircuitOpen => true,
+                    RetryError::Failed(e) => is_transient_s3_error(e),
+                };
+                if let Some(content) = self.last_good.get(path).filter(|_| unavailable) {
+                    warn!(target: "storage", s3_key = %key, "Serving last good copy");
+                    return Ok(content.clone());
                 }
-                Err(s3_error("S3 read failed", err))
+                return Err(s3_error("S3 read failed", err));
+            }
+        };
+        let bytes = response
+            .body
+            .collect()
+            .await
+            .map_err(|e| s3_error("Failed to collect bytes", e))?;
+
+        // If it's a JSON file, decompress it
+        let content = if is_json {
+            gunzip(&bytes.into_bytes()).map_err(|e| s3_error("Failed to decompress", e))?
+        } else {
+            String::from_utf8(bytes.to_vec()).map_err(|e| s3_error("Invalid UTF-8", e))?
+        };
+        self.last_good.insert(path.to_string(), content.clone());
+        Ok(content)
+    }
+
+    #[instrument(target = "storage", level = "debug", skip(self), fields(path = %path))]
+    async fn read_bytes(&self, path: &str) -> Result<Option<Vec<u8>>, DbError> {
+        self.get_bytes(&object_key(&self.prefix, &self.question_folder, path))
+            .await
+    }
+
+    async fn get_bytes(&self, key: &str) -> Result<Option<Vec<u8>>, DbError> {
+        info!(target: "storage", s3_key = %key, "Reading from S3");
+
+        let response = match self
+            .send(|| {
+                self.client
+                    .get_object()
+                    .bucket(&self.bucket)
+                    .key(key)
+                    .send()
+            })
+            .await
+        {
+            Ok(response) => response,
+            Err(RetryError::Failed(SdkError::ServiceError(service_err)))
+                if service_err.err().is_no_such_key() =>
+            {
+                return Ok(None);
             }
+            Err(err) => return Err(s3_error("S3 read failed", err)),
+        };
+        let bytes = response
+            .body
+            .collect()
+            .await
+            .map_err(|e| s3_error("Failed to collect bytes", e))?;
+        Ok(Some(bytes.to_vec()))
+    }
+
+    #[instrument(target = "storage", level = "debug", skip(self), fields(path = %path))]
+    async fn file_version(&self, path: &str) -> Result<Option<String>, DbError> {
+        let key = object_key(&self.prefix, &self.question_folder, path);
+        match self
+            .send(|| {
+                self.client
+                    .head_object()
+                    .bucket(&self.bucket)
+                    .key(&key)
+                    .send()
+            })
+            .await
+        {
+            Ok(head) => Ok(head.e_tag),
+            Err(RetryError::Failed(SdkError::ServiceError(service_err)))
+                if service_err.err().is_not_found() =>
+            {
+                Ok(None)
+            }
+            Err(err) => Err(s3_error("S3 head failed", err)),
         }
     }
 
     #[instrument(target = "storage", level = "debug", skip(self, data), fields(path = %path, size_bytes = data.len()))]
     async fn write_file(&self, path: &str, data: &[u8]) -> Result<(), DbError> {
-        let mut key = format!("{}/{}", self.prefix, path);
+        let key = object_key(&self.prefix, &self.question_folder, path);
+        let content_type = content_type(path);
 
-        // Determine if we're writing a JSON file
-        let is_json = std::path::Path::new(path)
-            .extension()
-            .is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+        info!(target: "storage", s3_key = %key, %content_type, "Writing to S3");
 
-        // Store question data in hidden folder and append .gz for JSON files
-        if is_json {
-            key = format!("{}/{}/{}.gz", self.prefix, self.question_folder, path);
+        let body = Bytes::from(if is_json(path) {
+            gzip(data).map_err(|e| s3_error("Failed to compress", e))?
+        } else {
+            data.to_vec()
+        });
+
+        self.send(|| {
+            self.client
+                .put_object()
+                .bucket(&self.bucket)
+                .key(&key)
+                .body(ByteStream::from(body.clone()))
+                .content_type(content_type)
+                .send()
+        })
+        .await
+        .map_err(|err| {
+            warn!(target: "storage", error = ?err, "S3 write error");
+            s3_error("S3 write failed", err)
+        })?;
+        if self.last_good.contains_key(path)
+            && let Ok(text) = std::str::from_utf8(data)
+        {
+            self.last_good.insert(path.to_string(), text.to_string());
         }
+        Ok(())
+    }
 
-        // Determine content type based on file extension
-        let content_type = if std::path::Path::new(path)
-            .extension()
-            .is_some_and(|ext| ext.eq_ignore_ascii_case("avif"))
-        {
-            "image/avif"
-        } else if std::path::Path::new(path)
-            .extension()
-            .is_some_and(|ext| ext.eq_ignore_ascii_case("webm"))
-        {
-            "video/webm"
-        } else if is_json {
-            "application/gzip" // Store JSON data as compressed
+    #[instrument(target = "storage", level = "debug", skip(self), fields(path = %path))]
+    async fn presign_upload(
+        &self,
+        path: &str,
+        expires_in: Duration,
+    ) -> Result<PresignedUpload, DbError> {
+        let key = object_key(&self.prefix, &self.question_folder, path);
+        info!(target: "storage", s3_key = %key, "Presigning S3 upload");
+
+        let config = PresigningConfig::expires_in(expires_in)
+            .map_err(|e| s3_error("Invalid presigning expiry", e))?;
+        let request = self
+            .client
+            .put_object()
+            .bucket(&self.bucket)
+            .key(&key)
+            .content_type(content_type(path))
+            .presigned(config)
+            .await
+            .map_err(|e| s3_error("S3 presigning failed", e))?;
+        Ok(PresignedUpload {
+            method: request.method().to_string(),
+            url: request.uri().to_string(),
+            headers: request
+                .headers()
+                .map(|(name, value)| (name.to_string(), value.to_string()))
+                .collect(),
+            media_url: format!("/{path}"),
+            expires_at: (Utc::now() + expires_in).to_rfc3339_opts(SecondsFormat::Secs, true),
+        })
+    }
+
+    #[instrument(target = "storage", level = "debug", skip(self), fields(path = %path))]
+    async fn delete_file(&self, path: &str) -> Result<(), DbError> {
+        let key = object_key(&self.prefix, &self.question_folder, path);
+        info!(target: "storage", s3_key = %key, "Deleting from S3");
+
+        self.send(|| {
+            self.client
+                .delete_object()
+                .bucket(&self.bucket)
+                .key(&key)
+                .send()
+        })
+        .await
+        .map_err(|e| s3_error("S3 delete failed", e))?;
+        self.last_good.remove(path);
+        Ok(())
+    }
+
+    #[instrument(target = "storage", level = "debug", skip(self))]
+    async fn list_keys(&self, prefix: &str) -> Result<Vec<String>, DbError> {
+        let mut keys = Vec::new();
+        let mut continuation_token = None;
+        loop {
+            let page = self
+                .send(|| {
+                    self.client
+                        .list_objects_v2()
+                        .bucket(&self.bucket)
+                        .prefix(prefix)
+                        .set_continuation_token(continuation_token.clone())
+                        .send()
+                })
+                .await
+                .map_err(|e| s3_error("S3 list failed", e))?;
+            keys.extend(
+                page.contents()
+                    .iter()
+                    .filter_map(|object| object.key().map(str::to_string)),
+            );
+            match page.next_continuation_token() {
+                Some(token) => continuation_token = Some(token.to_string()),
+                None => return Ok(keys),
+            }
+        }
+    }
+
+    #[instrument(target = "storage", level = "debug", skip(self, content), fields(file_stem = %file_stem))]
+    async fn create_backup(&self, content: &str, file_stem: &str) -> Result<(), DbError> {
+        let key = backup_key(&self.prefix, &self.question_folder, file_stem);
+        info!(target: "storage", s3_key = %key, "Creating backup on S3");
+
+        let content_owned = content.to_owned();
+        let compressed = tokio::task::spawn_blocking(move || gzip(content_owned.as_bytes()))
+            .await
+            .map_err(|e| s3_error("Compression task failed", e))?
+            .map_err(|e| s3_error("Failed to compress", e))?;
+
+        let compressed = Bytes::from(compressed);
+        self.send(|| {
+            self.client
+                .put_object()
+                .bucket(&self.bucket)
+                .key(&key)
+                .body(ByteStream::from(compressed.clone()))
+                .content_type("application/gzip")
+                .send()
+        })
+        .await
+        .map_err(|e| s3_error("S3 backup write failed", e))?;
+
+        Ok(())
+    }
+}
+
+// Azure Blob Storage implementation
+const AZURE_API_VERSION: &str = "2021-08-06";
+
+type HmacSha256 = Hmac<Sha256>;
+
+pub enum AzureAuth {
+    /// Shared access signature query string, without the leading `?`.
+    Sas(String),
+    /// Decoded storage account key for Shared Key request signing.
+    SharedKey(Vec<u8>),
+}
+
+pub struct AzureBackend {
+    client: reqwest::Client,
+    account: String,
+    /// Blob service URL without a trailing slash.
+    endpoint: String,
+    container: String,
+    prefix: String,
+    question_folder: String,
+    auth: AzureAuth,
+}
+
+impl AzureBackend {
+    #[instrument(target = "storage", level = "debug", skip(self), fields(path = %path))]
+    async fn read_bytes(&self, path: &str) -> Result<Option<Vec<u8>>, DbError> {
+        self.get_blob(&object_key(&self.prefix, &self.question_folder, path))
+            .await
+    }
+
+    async fn get_blob(&self, blob: &str) -> Result<Option<Vec<u8>>, DbError> {
+        info!(target: "storage", azure_blob = %blob, "Reading from Azure");
+
+        let response = self
+            .request(Method::GET, blob, None)?
+            .send()
+            .await
+            .map_err(|e| azure_error("Azure read failed", e))?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let bytes = response
+            .error_for_status()
+            .map_err(|e| azure_error("Azure read failed", e))?
+            .bytes()
+            .await
+            .map_err(|e| azure_error("Failed to collect bytes", e))?;
+        Ok(Some(bytes.to_vec()))
+    }
+
+    #[instrument(target = "storage", level = "debug", skip(self), fields(path = %path))]
+    async fn read_file(&self, path: &str) -> Result<String, DbError> {
+        let blob = object_key(&self.prefix, &self.question_folder, path);
+        info!(target: "storage", azure_blob = %blob, "Reading from Azure");
+
+        let response = self
+            .request(Method::GET, &blob, None)?
+            .send()
+            .await
+            .map_err(|e| azure_error("Azure read failed", e))?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(String::new());
+        }
+        let bytes = response
+            .error_for_status()
+            .map_err(|e| azure_error("Azure read failed", e))?
+            .bytes()
+            .await
+            .map_err(|e| azure_error("Failed to collect bytes", e))?;
+
+        if is_json(path) {
+            gunzip(&bytes).map_err(|e| azure_error("Failed to decompress", e))
         } else {
-            "application/octet-stream" // Default binary type
-        };
+            String::from_utf8(bytes.to_vec()).map_err(|e| azure_error("Invalid UTF-8", e))
+        }
+    }
 
-        info!(target: "storage", s3_key = %key, %content_type, "Writing to S3");
+    #[instrument(target = "storage", level = "debug", skip(self), fields(path = %path))]
+    async fn file_version(&self, path: &str) -> Result<Option<String>, DbError> {
+        let blob = object_key(&self.prefix, &self.question_folder, path);
+        let response = self
+            .request(Method::HEAD, &blob, None)?
+            .send()
+            .await
+            .map_err(|e| azure_error("Azure head failed", e))?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Ok(None);
+        }
+        let response = response
+            .error_for_status()
+            .map_err(|e| azure_error("Azure head failed", e))?;
+        Ok(response
+            .headers()
+            .get(reqwest::header::ETAG)
+            .and_then(|etag| etag.to_str().ok())
+            .map(str::to_string))
+    }
+
+    #[instrument(target = "storage", level = "debug", skip(self, data), fields(path = %path, size_bytes = data.len()))]
+    async fn write_file(&self, path: &str, data: &[u8]) -> Result<(), DbError> {
+        let blob = object_key(&self.prefix, &self.question_folder, path);
+        let content_type = content_type(path);
+        info!(target: "storage", azure_blob = %blob, %content_type, "Writing to Azure");
 
-        let body = if is_json {
-            // Compress JSON data
-            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
-            encoder
-                .write_all(data)
-                .map_err(|e| s3_error("Failed to compress", e))?;
-            encoder
-                .finish()
-                .map_err(|e| s3_error("Failed to finish compression", e))?
+        let body = if is_json(path) {
+            gzip(data).map_err(|e| azure_error("Failed to compress", e))?
         } else {
             data.to_vec()
         };
+        self.put_blob(&blob, body, content_type).await
+    }
 
-        match self
-            .client
-            .put_object()
-            .bucket(&self.bucket)
-            .key(&key)
-            .body(ByteStream::from(body))
-            .content_type(content_type)
+    #[instrument(target = "storage", level = "debug", skip(self), fields(path = %path))]
+    async fn delete_file(&self, path: &str) -> Result<(), DbError> {
+        let blob = object_key(&self.prefix, &self.question_folder, path);
+        info!(target: "storage", azure_blob = %blob, "Deleting from Azure");
+
+        let response = self
+            .request(Method::DELETE, &blob, None)?
             .send()
             .await
-        {
-            Ok(_) => Ok(()),
-            Err(err) => {
-                warn!(target: "storage", error = ?err, "S3 write error");
-                Err(s3_error("S3 write failed", err))
-            }
+            .map_err(|e| azure_error("Azure delete failed", e))?;
+        if response.status() != StatusCode::NOT_FOUND {
+            response
+                .error_for_status()
+                .map_err(|e| azure_error("Azure delete failed", e))?;
         }
+        Ok(())
     }
 
     #[instrument(target = "storage", level = "debug", skip(self, content), fields(file_stem = %file_stem))]
     async fn create_backup(&self, content: &str, file_stem: &str) -> Result<(), DbError> {
-        let now = Utc::now();
-        let timestamp = now.format("%y%m%d_%H%M%S").to_string();
-        let key = format!(
-            "{}/{}/backup/{file_stem}_{timestamp}.json.gz",
-            self.prefix, self.question_folder
-        );
-        info!(target: "storage", s3_key = %key, "Creating backup on S3");
+        let blob = backup_key(&self.prefix, &self.question_folder, file_stem);
+        info!(target: "storage", azure_blob = %blob, "Creating backup on Azure");
 
         let content_owned = content.to_owned();
-        let compressed = tokio::task::spawn_blocking(move || {
-            let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
-            encoder
-                .write_all(content_owned.as_bytes())
-                .map_err(|e| s3_error("Failed to compress", e))?;
-            encoder
-                .finish()
-                .map_err(|e| s3_error("Failed to finish compression", e))
-        })
-        .await
-        .map_err(|e| s3_error("Compression task failed", e))??;
+        let compressed = tokio::task::spawn_blocking(move || gzip(content_owned.as_bytes()))
+            .await
+            .map_err(|e| azure_error("Compression task failed", e))?
+            .map_err(|e| azure_error("Failed to compress", e))?;
+        self.put_blob(&blob, compressed, "application/gzip").await
+    }
 
-        self.client
-            .put_object()
-            .bucket(&self.bucket)
-            .key(&key)
-            .body(ByteStream::from(compressed))
-            .content_type("application/gzip")
+    async fn put_blob(&self, blob: &str, body: Vec<u8>, content_type: &str) -> Result<(), DbError> {
+        self.request(Method::PUT, blob, Some((body.len(), content_type)))?
+            .header(reqwest::header::CONTENT_TYPE, content_type)
+            .body(body)
             .send()
             .await
-            .map_err(|e| s3_error("S3 backup write failed", e))?;
-
+            .and_then(|response| response.error_for_status())
+            .map_err(|e| {
+                warn!(target: "storage", error = ?e, "Azure write error");
+                azure_error("Azure write failed", e)
+            })?;
         Ok(())
     }
+
+    /// Names of the blobs starting with `prefix`.
+    #[instrument(target = "storage", level = "debug", skip(self))]
+    async fn list_blobs(&self, prefix: &str) -> Result<Vec<String>, DbError> {
+        let url = reqwest::Url::parse(&format!("{}/{}", self.endpoint, self.container))
+            .map_err(|e| azure_error("Invalid container URL", e))?;
+        let mut names = Vec::new();
+        let mut marker = String::new();
+        loop {
+            let mut query = vec![
+                ("comp", "list"),
+                ("prefix", prefix),
+                ("restype", "container"),
+            ];
+            if !marker.is_empty() {
+                query.push(("marker", &marker));
+            }
+            let listing = self
+                .signed_request(Method::GET, url.clone(), &query, None)
+                .send()
+                .await
+                .and_then(|response| response.error_for_status())
+                .map_err(|e| azure_error("Azure list failed", e))?
+                .text()
+                .await
+                .map_err(|e| azure_error("Failed to read blob listing", e))?;
+            names.extend(xml_elements(&listing, "Name"));
+            marker = xml_elements(&listing, "NextMarker")
+                .pop()
+                .unwrap_or_default();
+            if marker.is_empty() {
+                return Ok(names);
+            }
+        }
+    }
+
+    /// Builds an authenticated request for `blob`. `body` is the length and
+    /// content type of the upload, which Shared Key signatures cover.
+    fn request(
+        &self,
+        method: Method,
+        blob: &str,
+        body: Option<(usize, &str)>,
+    ) -> Result<reqwest::RequestBuilder, DbError> {
+        let url = reqwest::Url::parse(&format!("{}/{}/{}", self.endpoint, self.container, blob))
+            .map_err(|e| azure_error("Invalid blob URL", e))?;
+        Ok(self.signed_request(method, url, &[], body))
+    }
+
+    /// `query` holds the operation's parameters, with lowercase names.
+    fn signed_request(
+        &self,
+        method: Method,
+        mut url: reqwest::Url,
+        query: &[(&str, &str)],
+        body: Option<(usize, &str)>,
+    ) -> reqwest::RequestBuilder {
+        let date = Utc::now().format("%a, %d %b %Y %H:%M:%S GMT").to_string();
+        let mut headers = vec![
+            ("x-ms-date", date),
+            ("x-ms-version", AZURE_API_VERSION.into()),
+        ];
+        if method == Method::PUT {
+            headers.push(("x-ms-blob-type", "BlockBlob".into()));
+        }
+
+        let authorization = match &self.auth {
+            AzureAuth::Sas(token) => {
+                url.set_query(Some(token));
+                None
+            }
+            AzureAuth::SharedKey(key) => {
+                let mut canonicalized_resource = format!("/{}{}", self.account, url.path());
+                let mut params = query.to_vec();
+                params.sort_unstable();
+                for (name, value) in params {
+                    canonicalized_resource.push_str(&format!("\n{name}:{value}"));
+                }
+                let string_to_sign = shared_key_string_to_sign(
+                    method.as_str(),
+                    body,
+                    &headers,
+                    &canonicalized_resource,
+                );
+                let mut mac =
+                    HmacSha256::new_from_slice(key).expect("HMAC accepts keys of any length");
+                mac.update(string_to_sign.as_bytes());
+                let signature = BASE64.encode(mac.finalize().into_bytes());
+                Some(format!("SharedKey {}:{signature}", self.account))
+            }
+        };
+
+        if !query.is_empty() {
+            url.query_pairs_mut().extend_pairs(query);
+        }
+
+        let mut request = self.client.request(method, url);
+        for (name, value) in headers {
+            request = request.header(name, value);
+        }
+        if let Some(authorization) = authorization {
+            request = request.header(reqwest::header::AUTHORIZATION, authorization);
+        }
+        request
+    }
+}
+
+/// Text of each `<tag>` element in a blob listing. Listings only escape the
+/// XML special characters, which never occur in our blob names.
+fn xml_elements(xml: &str, tag: &str) -> Vec<String> {
+    let close = format!("</{tag}>");
+    xml.split(&format!("<{tag}>"))
+        .skip(1)
+        .filter_map(|rest| rest.split_once(close.as_str()))
+        .map(|(text, _)| text.to_string())
+        .collect()
+}
+
+/// Shared Key string-to-sign for the Blob service. `ms_headers` must be the
+/// request's `x-ms-*` headers with lowercase names.
+fn shared_key_string_to_sign(
+    method: &str,
+    body: Option<(usize, &str)>,
+    ms_headers: &[(&str, String)],
+    canonicalized_resource: &str,
+) -> String {
+    let (content_length, content_type) = match body {
+        Some((len, content_type)) if len > 0 => (len.to_string(), content_type),
+        Some((_, content_type)) => (String::new(), content_type),
+        None => (String::new(), ""),
+    };
+    let mut ms_headers = ms_headers.to_vec();
+    ms_headers.sort_by(|a, b| a.0.cmp(b.0));
+    let canonicalized_headers: String = ms_headers
+        .iter()
+        .map(|(name, value)| format!("{name}:{value}\n"))
+        .collect();
+    // Verb, then Content-Encoding, -Language, -Length, -MD5, -Type, Date,
+    // If-Modified-Since, If-Match, If-None-Match, If-Unmodified-Since, Range.
+    let fields = [
+        method,
+        "",
+        "",
+        &content_length,
+        "",
+        content_type,
+        "",
+        "",
+        "",
+        "",
+        "",
+        "",
+    ];
+    format!(
+        "{}\n{canonicalized_headers}{canonicalized_resource}",
+        fields.join("\n")
+    )
+}
+
+fn report_check(check: &str, result: Result<(), DbError>, hint: &str) -> Result<(), DbError> {
+    match result {
+        Ok(()) => {
+            info!(check, "Storage check passed");
+            Ok(())
+        }
+        Err(e) => {
+            error!(check, error = %e, hint, "Storage check failed");
+            Err(e)
+        }
+    }
+}
+
+fn next_id(ids: impl Iterator<Item = i64>) -> i64 {
+    ids.max().unwrap_or(0) + 1
+}
+
+/// A lobby saved to be restored after a restart.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct PersistedLobby {
+    #[serde(flatten)]
+    pub snapshot: LobbySnapshot,
+    /// Address the lobby was created from, so it keeps counting towards that
+    /// address's lobby limit.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub creator_ip: Option<IpAddr>,
+}
+
+/// Stored next to the question data (in the hidden question folder on S3).
+const LOBBY_SNAPSHOT_FILE: &str = "lobby_snapshots.json";
+const ACCOUNTS_FILE: &str = "accounts.json";
+const EVENT_LOG_DIR: &str = "event_logs";
+const HISTORY_DIR: &str = "history";
+
+#[derive(Serialize, Deserialize)]
+struct Checksum {
+    sha256: String,
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
 }
 
 pub struct QuestionDatabase {
     question_file: String,
+    /// Sidecar holding the SHA-256 of the question file, e.g.
+    /// `questions.checksum.json`, checked on every read.
+    checksum_file: String,
     storage: Storage,
+    /// Encrypts the question file when set. Plaintext files are still read,
+    /// so encryption can be enabled on existing data.
+    cipher: Option<DataCipher>,
 }
 
 impl QuestionDatabase {
-    pub fn new(config: &StorageConfig) -> Result<Self, DbError> {
+    pub fn new(config: &StorageConfig, cipher: Option<DataCipher>) -> Result<Self, DbError> {
         let (storage, file_path) = match config {
             StorageConfig::Filesystem {
                 base_path,
@@ -569,6 +1653,8 @@ impl QuestionDatabase {
                     .use_dual_stack(false)
                     .request_checksum_calculation(RequestChecksumCalculation::WhenRequired)
                     .response_checksum_validation(ResponseChecksumValidation::WhenRequired)
+                    // Retries are handled by `S3Backend::send`.
+                    .retry_config(RetryConfig::disabled())
                     .credentials_provider(Credentials::new(
                         access_key_id,
                         secret_access_key,
@@ -585,18 +1671,133 @@ impl QuestionDatabase {
                         bucket: bucket.clone(),
                         prefix: prefix.clone(),
                         question_folder: question_folder.clone(),
+                        retry: RetryPolicy::default(),
+                        breaker: CircuitBreaker::default(),
+                        last_good: DashMap::new(),
+                    }),
+                    file_path.clone(),
+                )
+            }
+            StorageConfig::Azure {
+                account,
+                container,
+                endpoint,
+                prefix,
+                question_folder,
+                question_file: file_path,
+                sas_token,
+                account_key,
+            } => {
+                let auth = match (sas_token, account_key) {
+                    (Some(token), None) => {
+                        AzureAuth::Sas(token.trim_start_matches('?').to_string())
+                    }
+                    (None, Some(key)) => AzureAuth::SharedKey(BASE64.decode(key).map_err(|e| {
+                        DbError::Validation(format!("Invalid Azure account key: {e}"))
+                    })?),
+                    _ => {
+                        return Err(DbError::Validation(
+                            "Azure storage needs exactly one of sas_token or account_key".into(),
+                        ));
+                    }
+                };
+                let endpoint = endpoint
+                    .clone()
+                    .unwrap_or_else(|| format!("https://{account}.blob.core.windows.net"));
+                (
+                    Storage::Azure(AzureBackend {
+                        client: reqwest::Client::new(),
+                        account: account.clone(),
+                        endpoint: endpoint.trim_end_matches('/').to_string(),
+                        container: container.clone(),
+                        prefix: prefix.clone(),
+                        question_folder: question_folder.clone(),
+                        auth,
                     }),
                     file_path.clone(),
                 )
             }
         };
 
+        let checksum_file = Path::new(&file_path)
+            .with_extension("checksum.json")
+            .to_string_lossy()
+            .into_owned();
         Ok(Self {
             question_file: file_path,
+            checksum_file,
             storage,
+            cipher,
         })
     }
 
+    /// Verifies storage access and the question data step by step, logging
+    /// each check with a hint on failure, so a misconfigured deployment
+    /// refuses to start with an actionable message instead of an opaque error.
+    pub async fn check_storage(&self) -> Result<(), DbError> {
+        let location = self.storage.location(&self.question_file);
+        info!(%location, "Checking storage");
+        let access_hint = self.storage.access_hint();
+
+        let content = match self.storage.read_file(&self.question_file).await {
+            Ok(content) => content,
+            Err(e) => return report_check("read", Err(e), access_hint),
+        };
+        report_check("read", Ok(()), access_hint)?;
+
+        // Only looks up the checksum file, so checking never changes storage.
+        let head = self.storage.file_version(&self.checksum_file).await;
+        report_check("head", head.map(|_| ()), access_hint)?;
+
+        let parse = if content.is_empty() {
+            Err(DbError::NoQuestions)
+        } else {
+            self.read_stored_data().await.map(|_| ())
+        };
+        report_check(
+            "parse",
+            parse,
+            "Create the question file, fix the reported error or restore a backup; encrypted data also needs SPEKTRUM__ENCRYPTION__KEY",
+        )
+    }
+
+    /// Validates the stored data and looks for leftovers and dangling
+    /// references that validation allows.
+    #[instrument(target = "storage", level = "debug", skip(self))]
+    pub async fn check_integrity(&self) -> Result<IntegrityReport, DbError> {
+        let data = self.read_stored_data().await?;
+        let mut report = data.integrity_report();
+        for character in &data.characters {
+            // Absolute URLs point outside our storage and can't be checked.
+            let Some(path) = character.image_url.strip_prefix('/') else {
+                continue;
+            };
+            if self.storage.file_version(path).await?.is_none() {
+                report.missing_character_images.push(IntegrityItem {
+                    id: character.id,
+                    name: character.name.clone(),
+                });
+            }
+        }
+        Ok(report)
+    }
+
+    /// Changes whenever the question file or its checksum is rewritten, by
+    /// this instance or any other. The checksum is written second, so a
+    /// reader that caught the data without it reloads once it lands.
+    #[instrument(target = "storage", level = "debug", skip(self))]
+    pub async fn question_file_version(&self) -> Result<Option<String>, DbError> {
+        let Some(version) = self.storage.file_version(&self.question_file).await? else {
+            return Ok(None);
+        };
+        let checksum = self
+            .storage
+            .file_version(&self.checksum_file)
+            .await?
+            .unwrap_or_default();
+        Ok(Some(format!("{version}/{checksum}")))
+    }
+
     #[instrument(target = "storage", level = "debug", skip(self))]
     pub async fn read_stored_data(&self) -> Result<StoredData, DbError> {
         let content = self.storage.read_file(&self.question_file).await?;
@@ -610,17 +1811,84 @@ impl QuestionDatabase {
             });
         }
 
-        let json_content: StoredData = serde_json::from_str(&content)?;
+        match self.verify_checksum(&content).await {
+            Ok(()) => self.parse_stored_data(&content),
+            Err(DbError::Corrupted(reason)) => {
+                error!(%reason, "Question data is corrupted, falling back to the newest valid backup");
+                self.read_newest_backup()
+                    .await?
+                    .ok_or(DbError::Corrupted(reason))
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn parse_stored_data(&self, content: &str) -> Result<StoredData, DbError> {
+        let decrypted;
+        let content = if encryption::is_encrypted(content) {
+            let cipher = self.cipher.as_ref().ok_or_else(|| {
+                DbError::Validation("Question data is encrypted but no key is configured".into())
+            })?;
+            decrypted = cipher.decrypt(content)?;
+            &decrypted
+        } else {
+            content
+        };
+        let json_content: StoredData = serde_json::from_str(content)?;
         json_content.validate_stored_data()?;
         Ok(json_content)
     }
 
+    async fn verify_checksum(&self, content: &str) -> Result<(), DbError> {
+        let sidecar = self.storage.read_file(&self.checksum_file).await?;
+        // Data written before checksums were introduced has none.
+        if sidecar.is_empty() {
+            return Ok(());
+        }
+        let expected: Checksum = serde_json::from_str(&sidecar)
+            .map_err(|e| DbError::Corrupted(format!("Unreadable {}: {e}", self.checksum_file)))?;
+        if expected.sha256 != sha256_hex(content.as_bytes()) {
+            return Err(DbError::Corrupted(format!(
+                "{} does not match {}; delete the checksum file after editing the data by hand",
+                self.question_file, self.checksum_file
+            )));
+        }
+        Ok(())
+    }
+
+    async fn read_newest_backup(&self) -> Result<Option<StoredData>, DbError> {
+        for name in self.storage.list_backups(self.file_stem()?).await? {
+            match self
+                .storage
+                .read_backup(&name)
+                .await
+                .and_then(|content| self.parse_stored_data(&content))
+            {
+                Ok(data) => {
+                    warn!(backup = %name, "Serving question data from backup");
+                    return Ok(Some(data));
+                }
+                Err(e) => warn!(backup = %name, error = %e, "Skipping invalid backup"),
+            }
+        }
+        Ok(None)
+    }
+
     #[instrument(target = "storage", level = "debug", skip(self, data))]
     pub async fn set_stored_data(&self, data: StoredData) -> Result<(), DbError> {
         data.validate_stored_data()?;
-        let json = serde_json::to_string(&data)?;
+        let mut json = serde_json::to_string(&data)?;
+        if let Some(cipher) = &self.cipher {
+            json = cipher.encrypt(json.as_bytes())?;
+        }
         self.storage
             .write_file(&self.question_file, json.as_bytes())
+            .await?;
+        let checksum = serde_json::to_string(&Checksum {
+            sha256: sha256_hex(json.as_bytes()),
+        })?;
+        self.storage
+            .write_file(&self.checksum_file, checksum.as_bytes())
             .await
     }
 
@@ -635,6 +1903,133 @@ impl QuestionDatabase {
             .await
     }
 
+    #[instrument(target = "storage", level = "debug", skip(self), fields(character_name = %character_name))]
+    pub async fn delete_character_image(&self, character_name: &str) -> Result<String, DbError> {
+        self.storage.delete_character_image(character_name).await
+    }
+
+    #[instrument(target = "storage", level = "debug", skip(self, data), fields(media_id, size_bytes = data.len()))]
+    pub async fn store_audio_clip(
+        &self,
+        media_id: i64,
+        format: AudioFormat,
+        data: &[u8],
+    ) -> Result<(), DbError> {
+        let stored = self.read_stored_data().await?;
+        if !stored.media.iter().any(|m| m.id == media_id) {
+            return Err(DbError::Validation(format!("Unknown media ID: {media_id}")));
+        }
+        self.storage.store_audio_clip(media_id, format, data).await
+    }
+
+    #[instrument(target = "storage", level = "debug", skip(self))]
+    pub async fn read_audio_clip(
+        &self,
+        media_id: i64,
+    ) -> Result<Option<(AudioFormat, Vec<u8>)>, DbError> {
+        self.storage.read_audio_clip(media_id).await
+    }
+
+    #[instrument(target = "storage", level = "debug", skip(self), fields(path = %path))]
+    pub async fn presign_upload(
+        &self,
+        path: &str,
+        expires_in: Duration,
+    ) -> Result<Option<PresignedUpload>, DbError> {
+        self.storage.presign_upload(path, expires_in).await
+    }
+
+    #[instrument(target = "storage", level = "debug", skip(self))]
+    pub async fn read_lobby_snapshots(&self) -> Result<Vec<PersistedLobby>, DbError> {
+        let content = self.storage.read_file(LOBBY_SNAPSHOT_FILE).await?;
+        if content.is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    #[instrument(target = "storage", level = "debug", skip(self, snapshots), fields(lobbies = snapshots.len()))]
+    pub async fn write_lobby_snapshots(&self, snapshots: &[PersistedLobby]) -> Result<(), DbError> {
+        let json = serde_json::to_string(snapshots)?;
+        self.storage
+            .write_file(LOBBY_SNAPSHOT_FILE, json.as_bytes())
+            .await
+    }
+
+    #[instrument(target = "storage", level = "debug", skip(self))]
+    pub async fn read_accounts(&self) -> Result<Vec<Account>, DbError> {
+        let content = self.storage.read_file(ACCOUNTS_FILE).await?;
+        if content.is_empty() {
+            return Ok(Vec::new());
+        }
+        Ok(serde_json::from_str(&content)?)
+    }
+
+    #[instrument(target = "storage", level = "debug", skip(self, accounts), fields(accounts = accounts.len()))]
+    pub async fn write_accounts(&self, accounts: &[Account]) -> Result<(), DbError> {
+        let json = serde_json::to_string(accounts)?;
+        self.storage
+            .write_file(ACCOUNTS_FILE, json.as_bytes())
+            .await
+    }
+
+    /// Replaces the stored copy of a lobby's event log. Backends can't append,
+    /// so the whole log is written each time.
+    #[instrument(target = "storage", level = "debug", skip(self, jsonl), fields(size_bytes = jsonl.len()))]
+    pub async fn write_event_log(&self, name: &str, jsonl: &str) -> Result<(), DbError> {
+        if !is_valid_object_name(name) {
+            return Err(DbError::Validation(format!(
+                "Invalid event log name: {name}"
+            )));
+        }
+        self.storage
+            .write_file(&format!("{EVENT_LOG_DIR}/{name}.jsonl"), jsonl.as_bytes())
+            .await
+    }
+
+    #[instrument(target = "storage", level = "debug", skip(self, record), fields(id = %record.id))]
+    pub async fn write_game_record(&self, record: &GameRecord) -> Result<(), DbError> {
+        if !is_valid_object_name(&record.id) {
+            return Err(DbError::Validation(format!(
+                "Invalid game record id: {}",
+                record.id
+            )));
+        }
+        let json = serde_json::to_string(record)?;
+        self.storage
+            .write_file(
+                &format!("{HISTORY_DIR}/{}.json", record.id),
+                json.as_bytes(),
+            )
+            .await
+    }
+
+    /// Game record with `id`, or `None` if there is none.
+    #[instrument(target = "storage", level = "debug", skip(self))]
+    pub async fn read_game_record(&self, id: &str) -> Result<Option<GameRecord>, DbError> {
+        if !is_valid_object_name(id) {
+            return Ok(None);
+        }
+        let content = self
+            .storage
+            .read_file(&format!("{HISTORY_DIR}/{id}.json"))
+            .await?;
+        if content.is_empty() {
+            return Ok(None);
+        }
+        Ok(Some(serde_json::from_str(&content)?))
+    }
+
+    /// Ids of the stored game records, newest first.
+    #[instrument(target = "storage", level = "debug", skip(self))]
+    pub async fn list_game_records(&self) -> Result<Vec<String>, DbError> {
+        let mut ids = self.storage.list_json_files(HISTORY_DIR).await?;
+        ids.retain(|id| is_valid_object_name(id));
+        // Ids start with the start time, so they sort chronologically.
+        ids.sort_unstable_by(|a, b| b.cmp(a));
+        Ok(ids)
+    }
+
     #[instrument(target = "storage", level = "debug", skip(self))]
     pub async fn backup_stored_data(&self) -> Result<(), DbError> {
         let json = self.storage.read_file(&self.question_file).await?;
@@ -642,7 +2037,11 @@ impl QuestionDatabase {
             return Ok(());
         }
 
-        let file_stem = Path::new(&self.question_file)
+        self.storage.create_backup(&json, self.file_stem()?).await
+    }
+
+    fn file_stem(&self) -> Result<&str, DbError> {
+        Path::new(&self.question_file)
             .file_stem()
             .and_then(|s| s.to_str())
             .ok_or_else(|| {
@@ -650,9 +2049,7 @@ impl QuestionDatabase {
                     std::io::ErrorKind::InvalidInput,
                     "Could not extract filename stem",
                 ))
-            })?;
-
-        self.storage.create_backup(&json, file_stem).await
+            })
     }
 
     #[instrument(target = "storage", level = "info", skip(self))]
@@ -683,11 +2080,13 @@ impl QuestionDatabase {
 
                 Some(GameQuestion {
                     id: question.id,
+                    media_id: media.id,
                     question_type: question.question_type,
                     question_text: question.question_text.clone(),
                     title: media.title.clone(),
                     artist: Some(media.artist.clone()),
                     youtube_id: media.youtube_id.clone(),
+                    spotify_uri: media.spotify_uri.clone(),
                     options,
                 })
             })
@@ -888,6 +2287,51 @@ mod tests {
         }
     }
 
+    #[test]
+    fn validate_options_same_after_normalization() {
+        let data = StoredData {
+            media: vec![Media {
+                id: 1,
+                title: Arc::from("Title"),
+                artist: Arc::from("Artist"),
+                release_year: None,
+                spotify_uri: None,
+                youtube_id: Arc::from("youtube_id"),
+            }],
+            characters: vec![],
+            questions: vec![Question {
+                id: 1,
+                media_id: 1,
+                question_type: QuestionType::Text,
+                question_text: Some(Arc::from("Question")),
+                image_url: None,
+                is_active: true,
+            }],
+            options: vec![
+                QuestionOption {
+                    id: 1,
+                    question_id: 1,
+                    option_text: Arc::from("Answer"),
+                    is_correct: true,
+                },
+                QuestionOption {
+                    id: 2,
+                    question_id: 1,
+                    option_text: Arc::from(" answer"),
+                    is_correct: false,
+                },
+            ],
+            sets: vec![],
+        };
+        match data.validate_stored_data() {
+            Err(DbError::Validation(msg)) => assert_eq!(
+                msg,
+                "Options 'Answer' and ' answer' of question 1 are the same answer"
+            ),
+            other => panic!("Expected validation error, got {:?}", other),
+        }
+    }
+
     #[test]
     fn validate_duplicate_set_id() {
         let data = StoredData {
@@ -1164,13 +2608,13 @@ mod tests {
                 image_url: None,
                 is_active: true,
             }],
-            options: vec![
+            options: vec![into_stored(
                 GameQuestionOption {
                     option: Arc::from("Red"),
                     is_correct: true,
-                }
-                .into_stored(1),
-            ],
+                },
+                1,
+            )],
             sets: vec![],
         };
         assert!(data.validate_stored_data().is_ok());
@@ -1200,13 +2644,13 @@ mod tests {
                 image_url: None,
                 is_active: true,
             }],
-            options: vec![
+            options: vec![into_stored(
                 GameQuestionOption {
                     option: Arc::from("CharacterName"),
                     is_correct: true,
-                }
-                .into_stored(1),
-            ],
+                },
+                1,
+            )],
             sets: vec![],
         };
         assert!(data.validate_stored_data().is_ok());
@@ -1386,14 +2830,247 @@ mod tests {
         assert!(data.validate_stored_data().is_ok());
     }
     // Helper function to create a stored QuestionOption from GameQuestionOption for brevity
-    impl GameQuestionOption {
-        fn into_stored(self, question_id: i64) -> QuestionOption {
-            QuestionOption {
-                id: 0, // Dummy ID, not used in validation
-                question_id,
-                option_text: self.option.clone(),
-                is_correct: self.is_correct,
+    fn into_stored(option: GameQuestionOption, question_id: i64) -> QuestionOption {
+        QuestionOption {
+            id: 0, // Dummy ID, not used in validation
+            question_id,
+            option_text: option.option.clone(),
+            is_correct: option.is_correct,
+        }
+    }
+
+    #[test]
+    fn azure_shared_key_string_to_sign() {
+        let headers = [
+            ("x-ms-version", "2021-08-06".to_string()),
+            ("x-ms-date", "Mon, 01 Jan 2024 00:00:00 GMT".to_string()),
+        ];
+        assert_eq!(
+            shared_key_string_to_sign(
+                "PUT",
+                Some((11, "image/avif")),
+                &headers,
+                "/account/container/data/img/Cat.avif",
+            ),
+            "PUT\n\n\n11\n\nimage/avif\n\n\n\n\n\n\n\
+             x-ms-date:Mon, 01 Jan 2024 00:00:00 GMT\n\
+             x-ms-version:2021-08-06\n\
+             /account/container/data/img/Cat.avif"
+        );
+        assert!(
+            shared_key_string_to_sign("GET", None, &headers, "/a/c/b").starts_with("GET\n\n\n\n")
+        );
+    }
+
+    #[tokio::test]
+    async fn azure_backend_round_trip() {
+        use axum::Router;
+        use axum::body::Bytes;
+        use axum::extract::{RawQuery, State};
+        use axum::http::{HeaderMap, Uri};
+        use std::sync::Mutex;
+
+        type Blobs = Arc<Mutex<HashMap<String, Vec<u8>>>>;
+        async fn blob(
+            State(blobs): State<Blobs>,
+            method: Method,
+            uri: Uri,
+            RawQuery(query): RawQuery,
+            headers: HeaderMap,
+            body: Bytes,
+        ) -> (StatusCode, Vec<u8>) {
+            if query.as_deref() != Some("sv=2022&sig=abc") {
+                return (StatusCode::FORBIDDEN, Vec::new());
+            }
+            let mut blobs = blobs.lock().unwrap();
+            if method == Method::PUT {
+                assert_eq!(headers["x-ms-blob-type"], "BlockBlob");
+                blobs.insert(uri.path().to_string(), body.to_vec());
+                return (StatusCode::CREATED, Vec::new());
+            }
+            match blobs.get(uri.path()) {
+                Some(data) => (StatusCode::OK, data.clone()),
+                None => (StatusCode::NOT_FOUND, Vec::new()),
             }
         }
+
+        let blobs = Blobs::default();
+        let app = Router::new().fallback(blob).with_state(blobs.clone());
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let backend = AzureBackend {
+            client: reqwest::Client::new(),
+            account: "account".into(),
+            endpoint: format!("http://{addr}"),
+            container: "spektrum".into(),
+            prefix: "data".into(),
+            question_folder: "hidden".into(),
+            auth: AzureAuth::Sas("sv=2022&sig=abc".into()),
+        };
+
+        assert_eq!(backend.read_file("questions.json").await.unwrap(), "");
+        backend
+            .write_file("questions.json", b"{\"media\":[]}")
+            .await
+            .unwrap();
+        backend.write_file("img/Cat.avif", b"avif").await.unwrap();
+        assert_eq!(
+            backend.read_file("questions.json").await.unwrap(),
+            "{\"media\":[]}"
+        );
+        let blobs = blobs.lock().unwrap();
+        assert!(blobs.contains_key("/spektrum/data/hidden/questions.json.gz"));
+        assert_eq!(blobs["/spektrum/data/img/Cat.avif"], b"avif");
+    }
+
+    #[test]
+    fn media_path_validation() {
+        assert_eq!(media_path("intro-1.mp3").unwrap(), "media/intro-1.mp3");
+        assert!(media_path("intro").is_err());
+        assert!(media_path("intro.exe").is_err());
+        assert!(media_path("../questions.json").is_err());
+        assert!(media_path(".mp3").is_err());
+    }
+
+    #[tokio::test]
+    async fn s3_presign_upload() {
+        let db = QuestionDatabase::new(
+            &StorageConfig::S3 {
+                bucket: "bucket".into(),
+                region: "eu-central-003".into(),
+                prefix: "spektrum".into(),
+                question_folder: "hidden".into(),
+                question_file: "questions.json".into(),
+                access_key_id: "key".into(),
+                secret_access_key: "secret".into(),
+            },
+            None,
+        )
+        .unwrap();
+        let upload = db
+            .presign_upload("media/intro.mp3", Duration::from_secs(600))
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(upload.method, "PUT");
+        assert!(upload.url.starts_with(
+            "https://s3.eu-central-003.backblazeb2.com/bucket/spektrum/media/intro.mp3?"
+        ));
+        assert!(upload.url.contains("X-Amz-Expires=600"));
+        assert_eq!(upload.headers["content-type"], "audio/mpeg");
+        assert_eq!(upload.media_url, "/media/intro.mp3");
+    }
+
+    #[tokio::test]
+    async fn check_storage_steps() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = QuestionDatabase::new(
+            &StorageConfig::Filesystem {
+                base_path: dir.path().to_path_buf(),
+                file_path: "questions.json".into(),
+            },
+            None,
+        )
+        .unwrap();
+        assert!(matches!(
+            db.check_storage().await,
+            Err(DbError::NoQuestions)
+        ));
+        assert_eq!(std::fs::read_dir(dir.path()).unwrap().count(), 0);
+
+        std::fs::write(dir.path().join("questions.json"), "{not json").unwrap();
+        assert!(matches!(db.check_storage().await, Err(DbError::Json(_))));
+
+        db.set_stored_data(StoredData::default()).await.unwrap();
+        assert!(db.check_storage().await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn corrupted_data_falls_back_to_backup() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = QuestionDatabase::new(
+            &StorageConfig::Filesystem {
+                base_path: dir.path().to_path_buf(),
+                file_path: "questions.json".into(),
+            },
+            None,
+        )
+        .unwrap();
+        let backed_up: StoredData = serde_json::from_value(serde_json::json!({
+            "media": [{"id": 1, "title": "Song", "artist": "Artist", "release_year": null, "spotify_uri": null, "youtube_id": "abc"}],
+            "characters": [], "questions": [], "options": [], "sets": []
+        }))
+        .unwrap();
+        db.set_stored_data(backed_up).await.unwrap();
+        db.backup_stored_data().await.unwrap();
+        db.set_stored_data(StoredData::default()).await.unwrap();
+        assert!(dir.path().join("questions.checksum.json").exists());
+        assert!(db.read_stored_data().await.unwrap().media.is_empty());
+
+        // Same length, one byte off.
+        let path = dir.path().join("questions.json");
+        let mut content = std::fs::read(&path).unwrap();
+        content[0] = b' ';
+        std::fs::write(&path, content).unwrap();
+        let restored = db.read_stored_data().await.unwrap();
+        assert_eq!(restored.media.len(), 1);
+        assert_eq!(&*restored.media[0].youtube_id, "abc");
+
+        std::fs::remove_dir_all(dir.path().join("question_backup")).unwrap();
+        assert!(matches!(
+            db.read_stored_data().await,
+            Err(DbError::Corrupted(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn load_questions_keeps_spotify_uri() {
+        let dir = tempfile::tempdir().unwrap();
+        let db = QuestionDatabase::new(
+            &StorageConfig::Filesystem {
+                base_path: dir.path().to_path_buf(),
+                file_path: "questions.json".into(),
+            },
+            None,
+        )
+        .unwrap();
+        let data: StoredData = serde_json::from_value(serde_json::json!({
+            "media": [{"id": 1, "title": "Song", "artist": "Artist", "release_year": null, "spotify_uri": "spotify:track:abc", "youtube_id": "abc"}],
+            "characters": [],
+            "questions": [{"id": 1, "media_id": 1, "question_type": "color", "question_text": null, "image_url": null, "is_active": true}],
+            "options": [{"id": 1, "question_id": 1, "option_text": "Red", "is_correct": true}],
+            "sets": []
+        }))
+        .unwrap();
+        db.set_stored_data(data).await.unwrap();
+
+        let (questions, _) = db.load_questions().await.unwrap();
+        assert_eq!(
+            questions[0].spotify_uri.as_deref(),
+            Some("spotify:track:abc")
+        );
+    }
+
+    #[test]
+    fn backup_names() {
+        assert!(is_backup_of("questions_250101_120000.json.gz", "questions"));
+        assert!(is_backup_of(
+            "data/hidden/backup/questions_250101_120000.json.gz",
+            "questions"
+        ));
+        assert!(!is_backup_of(
+            "questions_old_250101_120000.json.gz",
+            "questions"
+        ));
+        assert!(!is_backup_of("questions_latest.json.gz", "questions"));
+        assert_eq!(
+            xml_elements(
+                "<Blobs><Blob><Name>a</Name></Blob><Blob><Name>b</Name></Blob></Blobs><NextMarker />",
+                "Name"
+            ),
+            ["a", "b"]
+        );
     }
 }
@@ -0,0 +1,397 @@
+//! Discord bot integration over the HTTP interactions API.
+//!
+//! Discord POSTs slash command invocations to `/api/discord/interactions`
+//! (set as the application's Interactions Endpoint URL). Lobbies are created
+//! in-process through [`create_lobby`], and final scores of those lobbies are
+//! relayed to a channel through the Discord REST API.
+
+use crate::DiscordConfig;
+use crate::client_ip::ClientIp;
+use crate::qr;
+use crate::server::{AppState, CreateLobbyRequest, create_lobby, list_sets};
+use crate::webhook::WebhookEvent;
+use axum::Json;
+use axum::Router;
+use axum::body::Bytes;
+use axum::extract::{Extension, State};
+use axum::http::{HeaderMap, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::post;
+use dashmap::DashMap;
+use ring::signature::{ED25519, UnparsedPublicKey};
+use serde::Deserialize;
+use serde_json::{Value, json};
+use std::fmt::Write;
+use std::net::IpAddr;
+use std::sync::Arc;
+use tokio::sync::mpsc::Receiver;
+use tracing::{Instrument, debug, info, info_span, warn};
+
+const API_BASE: &str = "https://discord.com/api/v10";
+const CREATE_LOBBY_COMMAND: &str = "spektrum-lobby";
+const LIST_SETS_COMMAND: &str = "spektrum-sets";
+/// Number of players included in the final score message.
+const SCORES_SHOWN: usize = 10;
+
+// Interaction and response types from the Discord API.
+const INTERACTION_PING: u8 = 1;
+const INTERACTION_APPLICATION_COMMAND: u8 = 2;
+const RESPONSE_PONG: u8 = 1;
+const RESPONSE_CHANNEL_MESSAGE: u8 = 4;
+const MESSAGE_FLAG_EPHEMERAL: u32 = 1 << 6;
+
+#[derive(Debug, Deserialize)]
+struct Interaction {
+    #[serde(rename = "type")]
+    kind: u8,
+    channel_id: Option<String>,
+    data: Option<CommandData>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CommandData {
+    name: String,
+    #[serde(default)]
+    options: Vec<CommandOption>,
+}
+
+#[derive(Debug, Deserialize)]
+struct CommandOption {
+    name: String,
+    value: Value,
+}
+
+impl CommandData {
+    fn integer_option(&self, name: &str) -> Option<i64> {
+        self.options
+            .iter()
+            .find(|option| option.name == name)
+            .and_then(|option| option.value.as_i64())
+    }
+}
+
+pub struct DiscordBot {
+    config: DiscordConfig,
+    public_key: UnparsedPublicKey<Vec<u8>>,
+    client: reqwest::Client,
+    /// Channel each bot-created lobby was announced in, keyed by join code.
+    lobby_channels: DashMap<String, String>,
+}
+
+#[derive(Clone)]
+struct DiscordState {
+    app: AppState,
+    bot: Arc<DiscordBot>,
+}
+
+impl DiscordBot {
+    pub fn new(config: DiscordConfig) -> Result<Self, String> {
+        let public_key = hex::decode(config.public_key.trim())
+            .map_err(|e| format!("Invalid Discord public key: {e}"))?;
+        Ok(Self {
+            config,
+            public_key: UnparsedPublicKey::new(&ED25519, public_key),
+            client: reqwest::Client::new(),
+            lobby_channels: DashMap::new(),
+        })
+    }
+
+    /// Discord signs `timestamp + body` with the application's Ed25519 key.
+    fn verify(&self, headers: &HeaderMap, body: &[u8]) -> bool {
+        let header = |name| headers.get(name).and_then(|value| value.to_str().ok());
+        let (Some(signature), Some(timestamp)) = (
+            header("x-signature-ed25519"),
+            header("x-signature-timestamp"),
+        ) else {
+            return false;
+        };
+        let Ok(signature) = hex::decode(signature) else {
+            return false;
+        };
+        let mut message = timestamp.as_bytes().to_vec();
+        message.extend_from_slice(body);
+        self.public_key.verify(&message, &signature).is_ok()
+    }
+
+    async fn post_message(&self, channel_id: &str, content: &str) {
+        let url = format!("{API_BASE}/channels/{channel_id}/messages");
+        let result = self
+            .client
+            .post(url)
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!("Bot {}", self.config.bot_token),
+            )
+            .json(&json!({ "content": content }))
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(e) = result {
+            warn!(%channel_id, error = %e, "Failed to post Discord message");
+        }
+    }
+
+    /// Registers the slash commands globally. Re-registering is idempotent.
+    async fn register_commands(&self) {
+        let url = format!(
+            "{API_BASE}/applications/{}/commands",
+            self.config.application_id
+        );
+        let commands = json!([
+            {
+                "name": CREATE_LOBBY_COMMAND,
+                "description": "Create a Spektrum lobby",
+                "options": [
+                    {"type": 4, "name": "set_id", "description": "Question set id (see /spektrum-sets)", "required": false},
+                    {"type": 4, "name": "round_duration", "description": "Round duration in seconds", "required": false, "min_value": 10}
+                ]
+            },
+            {"name": LIST_SETS_COMMAND, "description": "List available question sets"}
+        ]);
+        let result = self
+            .client
+            .put(url)
+            .header(
+                reqwest::header::AUTHORIZATION,
+                format!("Bot {}", self.config.bot_token),
+            )
+            .json(&commands)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        match result {
+            Ok(_) => info!("Registered Discord slash commands"),
+            Err(e) => warn!(error = %e, "Failed to register Discord slash commands"),
+        }
+    }
+
+    async fn relay_events(&self, mut events: Receiver<WebhookEvent>) {
+        while let Some(event) = events.recv().await {
+            match event {
+                WebhookEvent::GameOver { join_code, scores } => {
+                    let Some(channel_id) = self.score_channel(&join_code) else {
+                        continue;
+                    };
+                    let mut content = format!("Final scores for lobby **{join_code}**:");
+                    for (rank, score) in scores.iter().take(SCORES_SHOWN).enumerate() {
+                        let _ = write!(content, "\n{}. {} – {}", rank + 1, score.name, score.score);
+                    }
+                    self.post_message(&channel_id, &content).await;
+                }
+                WebhookEvent::LobbyClosed { join_code } => {
+                    self.lobby_channels.remove(&join_code);
+                }
+                _ => {}
+            }
+        }
+    }
+
+    /// Scores go to the configured channel, or else where the lobby was created.
+    fn score_channel(&self, join_code: &str) -> Option<String> {
+        let origin = self.lobby_channels.get(join_code)?;
+        Some(
+            self.config
+                .score_channel_id
+                .clone()
+                .unwrap_or_else(|| origin.clone()),
+        )
+    }
+}
+
+/// Starts the bot and returns its routes. Final scores are read from
+/// `events`, obtained from [`crate::webhook::WebhookDispatcher::subscribe`]
+/// before the
+/// dispatcher is handed to [`AppState`].
+pub fn router(state: AppState, bot: DiscordBot, events: Receiver<WebhookEvent>) -> Router {
+    let bot = Arc::new(bot);
+    {
+        let bot = bot.clone();
+        tokio::spawn(
+            async move {
+                bot.register_commands().await;
+                bot.relay_events(events).await;
+            }
+            .instrument(info_span!(target: "maintenance", "discord_bot")),
+        );
+    }
+    Router::new()
+        .route("/api/discord/interactions", post(interactions_handler))
+        .with_state(DiscordState { app: state, bot })
+}
+
+fn message(content: impl Into<String>, ephemeral: bool) -> Value {
+    let mut data = json!({ "content": content.into() });
+    if ephemeral {
+        data["flags"] = json!(MESSAGE_FLAG_EPHEMERAL);
+    }
+    json!({ "type": RESPONSE_CHANNEL_MESSAGE, "data": data })
+}
+
+async fn interactions_handler(
+    State(DiscordState { app, bot }): State<DiscordState>,
+    Extension(ClientIp(client_ip)): Extension<ClientIp>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Response {
+    if !bot.verify(&headers, &body) {
+        return (StatusCode::UNAUTHORIZED, "invalid request signature").into_response();
+    }
+    let interaction: Interaction = match serde_json::from_slice(&body) {
+        Ok(interaction) => interaction,
+        Err(e) => return (StatusCode::BAD_REQUEST, e.to_string()).into_response(),
+    };
+
+    let response = match (interaction.kind, interaction.data) {
+        (INTERACTION_PING, _) => json!({ "type": RESPONSE_PONG }),
+        (INTERACTION_APPLICATION_COMMAND, Some(data)) => {
+            debug!(command = %data.name, "Discord command");
+            match data.name.as_str() {
+                CREATE_LOBBY_COMMAND => {
+                    handle_create_lobby(&app, &bot, &data, interaction.channel_id, client_ip).await
+                }
+                LIST_SETS_COMMAND => handle_list_sets(&app).await,
+                _ => message("Unknown command", true),
+            }
+        }
+        _ => return StatusCode::BAD_REQUEST.into_response(),
+    };
+    Json(response).into_response()
+}
+
+async fn handle_list_sets(app: &AppState) -> Value {
+    match list_sets(app, None).await {
+        Ok(response) if response.sets.is_empty() => message(
+            format!(
+                "No sets; lobbies use all {} questions.",
+                response.num_questions
+            ),
+            true,
+        ),
+        Ok(response) => {
+            let mut content = String::from("Question sets:");
+            for set in response.sets {
+                let _ = write!(
+                    content,
+                    "\n`{}` {} ({} questions)",
+                    set.id, set.name, set.question_count
+                );
+            }
+            message(content, true)
+        }
+        Err(e) => message(format!("Could not list sets: {e}"), true),
+    }
+}
+
+/// Lobbies created through the bot count against the per-IP lobby cap of the
+/// address Discord's requests arrive from. The channel announcement is posted
+/// in the background, as Discord wants the reply within three seconds.
+async fn handle_create_lobby(
+    app: &AppState,
+    bot: &Arc<DiscordBot>,
+    data: &CommandData,
+    channel_id: Option<String>,
+    client_ip: IpAddr,
+) -> Value {
+    let req = CreateLobbyRequest {
+        round_duration: data
+            .integer_option("round_duration")
+            .and_then(|d| u64::try_from(d).ok()),
+        set_id: data.integer_option("set_id"),
+        ..Default::default()
+    };
+    let lobby = match create_lobby(app, req, client_ip).await {
+        Ok(lobby) => lobby,
+        Err(e) => return message(format!("Could not create lobby: {e}"), true),
+    };
+
+    if let Some(channel_id) = channel_id {
+        let mut announcement = format!("New Spektrum lobby! Join code: **{}**", lobby.join_code);
+        if let Some(base_url) = app.frontend_base_url.as_deref() {
+            let _ = write!(
+                announcement,
+                "\n{}",
+                qr::join_url(base_url, &lobby.join_code)
+            );
+        }
+        bot.lobby_channels
+            .insert(lobby.join_code.clone(), channel_id.clone());
+        let bot = bot.clone();
+        tokio::spawn(async move { bot.post_message(&channel_id, &announcement).await });
+    }
+
+    // Only the invoker sees the admin session token.
+    message(
+        format!(
+            "Lobby **{}** created. Host session token: `{}`",
+            lobby.join_code, lobby.session_token
+        ),
+        true,
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+    use ring::rand::SystemRandom;
+    use ring::signature::{Ed25519KeyPair, KeyPair};
+
+    fn test_bot() -> (DiscordBot, Ed25519KeyPair) {
+        let pkcs8 = Ed25519KeyPair::generate_pkcs8(&SystemRandom::new()).unwrap();
+        let key_pair = Ed25519KeyPair::from_pkcs8(pkcs8.as_ref()).unwrap();
+        let bot = DiscordBot::new(DiscordConfig {
+            application_id: "1".into(),
+            public_key: hex::encode(key_pair.public_key().as_ref()),
+            bot_token: "token".into(),
+            score_channel_id: None,
+        })
+        .unwrap();
+        (bot, key_pair)
+    }
+
+    #[test]
+    fn test_verify_signature() {
+        let (bot, key_pair) = test_bot();
+        let body = br#"{"type":1}"#;
+        let timestamp = "1700000000";
+        let mut signed = timestamp.as_bytes().to_vec();
+        signed.extend_from_slice(body);
+        let signature = hex::encode(key_pair.sign(&signed).as_ref());
+
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            "x-signature-ed25519",
+            HeaderValue::from_str(&signature).unwrap(),
+        );
+        headers.insert("x-signature-timestamp", HeaderValue::from_static(timestamp));
+        assert!(bot.verify(&headers, body));
+        assert!(!bot.verify(&headers, br#"{"type":2}"#));
+        assert!(!bot.verify(&HeaderMap::new(), body));
+    }
+
+    #[test]
+    fn test_score_channel() {
+        let (mut bot, _) = test_bot();
+        assert_eq!(bot.score_channel("123456"), None);
+        bot.lobby_channels.insert("123456".into(), "origin".into());
+        assert_eq!(bot.score_channel("123456").as_deref(), Some("origin"));
+        bot.config.score_channel_id = Some("scores".into());
+        assert_eq!(bot.score_channel("123456").as_deref(), Some("scores"));
+    }
+
+    #[test]
+    fn test_command_options() {
+        let interaction: Interaction = serde_json::from_value(json!({
+            "type": 2,
+            "channel_id": "42",
+            "data": {
+                "name": CREATE_LOBBY_COMMAND,
+                "options": [{"name": "set_id", "type": 4, "value": 7}]
+            }
+        }))
+        .unwrap();
+        let data = interaction.data.unwrap();
+        assert_eq!(data.integer_option("set_id"), Some(7));
+        assert_eq!(data.integer_option("round_duration"), None);
+    }
+}
@@ -0,0 +1,126 @@
+//! AES-256-GCM encryption of the question data at rest, for deployments that
+//! keep licensed content in third-party buckets. The data is compressed before
+//! encryption and stored as a single base64 line behind a version prefix, so
+//! storage backends keep handling it as text.
+
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD as BASE64;
+use ring::aead::{AES_256_GCM, Aad, LessSafeKey, NONCE_LEN, Nonce, UnboundKey};
+use ring::rand::{SecureRandom, SystemRandom};
+use std::io::Read;
+use thiserror::Error;
+
+const PREFIX: &str = "spektrum-aes256gcm-v1:";
+
+#[derive(Error, Debug)]
+pub enum EncryptionError {
+    #[error("Encryption key must be 32 bytes of base64")]
+    InvalidKey,
+    #[error("Failed to encrypt data")]
+    Encrypt,
+    #[error("Failed to decrypt data; wrong key or corrupted file")]
+    Decrypt,
+}
+
+pub struct DataCipher {
+    key: LessSafeKey,
+    rng: SystemRandom,
+}
+
+impl DataCipher {
+    /// Takes a base64-encoded 256-bit key, e.g. from `openssl rand -base64 32`.
+    pub fn from_base64(key: &str) -> Result<Self, EncryptionError> {
+        let bytes = BASE64
+            .decode(key.trim())
+            .map_err(|_| EncryptionError::InvalidKey)?;
+        let key = UnboundKey::new(&AES_256_GCM, &bytes).map_err(|_| EncryptionError::InvalidKey)?;
+        Ok(Self {
+            key: LessSafeKey::new(key),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    pub fn encrypt(&self, plaintext: &[u8]) -> Result<String, EncryptionError> {
+        let mut nonce = [0; NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| EncryptionError::Encrypt)?;
+        let mut data = crate::db::gzip(plaintext).map_err(|_| EncryptionError::Encrypt)?;
+        self.key
+            .seal_in_place_append_tag(Nonce::assume_unique_for_key(nonce), Aad::empty(), &mut data)
+            .map_err(|_| EncryptionError::Encrypt)?;
+        let mut sealed = nonce.to_vec();
+        sealed.extend_from_slice(&data);
+        Ok(format!("{PREFIX}{}", BASE64.encode(sealed)))
+    }
+
+    pub fn decrypt(&self, content: &str) -> Result<String, EncryptionError> {
+        let sealed = content
+            .trim_end()
+            .strip_prefix(PREFIX)
+            .and_then(|encoded| BASE64.decode(encoded).ok())
+            .ok_or(EncryptionError::Decrypt)?;
+        if sealed.len() < NONCE_LEN {
+            return Err(EncryptionError::Decrypt);
+        }
+        let (nonce, ciphertext) = sealed.split_at(NONCE_LEN);
+        let nonce =
+            Nonce::try_assume_unique_for_key(nonce).map_err(|_| EncryptionError::Decrypt)?;
+        let mut ciphertext = ciphertext.to_vec();
+        let compressed = self
+            .key
+            .open_in_place(nonce, Aad::empty(), &mut ciphertext)
+            .map_err(|_| EncryptionError::Decrypt)?;
+        let mut plaintext = String::new();
+        flate2::read::GzDecoder::new(&compressed[..])
+            .read_to_string(&mut plaintext)
+            .map_err(|_| EncryptionError::Decrypt)?;
+        Ok(plaintext)
+    }
+}
+
+/// Whether stored content was written by [`DataCipher::encrypt`].
+pub fn is_encrypted(content: &str) -> bool {
+    content.starts_with(PREFIX)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=";
+
+    #[test]
+    fn test_round_trip() {
+        let cipher = DataCipher::from_base64(KEY).unwrap();
+        let encrypted = cipher.encrypt(br#"{"media":[]}"#).unwrap();
+        assert!(is_encrypted(&encrypted));
+        assert!(!encrypted.contains("media"));
+        assert_eq!(cipher.decrypt(&encrypted).unwrap(), r#"{"media":[]}"#);
+        // Fresh nonce per write.
+        assert_ne!(encrypted, cipher.encrypt(br#"{"media":[]}"#).unwrap());
+    }
+
+    #[test]
+    fn test_rejects_wrong_key_and_tampering() {
+        let cipher = DataCipher::from_base64(KEY).unwrap();
+        let encrypted = cipher.encrypt(b"secret").unwrap();
+        let other = DataCipher::from_base64(&BASE64.encode([7u8; 32])).unwrap();
+        assert!(other.decrypt(&encrypted).is_err());
+
+        let mut tampered = encrypted.into_bytes();
+        let last = tampered.len() - 3;
+        tampered[last] = if tampered[last] == b'A' { b'B' } else { b'A' };
+        assert!(
+            cipher
+                .decrypt(&String::from_utf8(tampered).unwrap())
+                .is_err()
+        );
+    }
+
+    #[test]
+    fn test_invalid_key() {
+        assert!(DataCipher::from_base64("too-short").is_err());
+        assert!(DataCipher::from_base64(&BASE64.encode([0u8; 16])).is_err());
+    }
+}
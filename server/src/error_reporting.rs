@@ -0,0 +1,67 @@
+//! Reports panics and errors to Sentry or a compatible service such as
+//! GlitchTip. Error logs become events, with their fields and the fields of
+//! enclosing spans, like `lobby_key` on `ws_connection`, attached. Storage and
+//! maintenance warnings are reported too; other logs are kept as breadcrumbs.
+
+use crate::SentryConfig;
+use sentry::integrations::tracing::EventFilter;
+use tracing::{Level, Metadata};
+use tracing_subscriber::{Layer, Registry};
+
+pub type ErrorReportingLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Flushes queued reports when dropped.
+pub type ErrorReportingGuard = sentry::ClientInitGuard;
+
+fn event_filter(metadata: &Metadata) -> EventFilter {
+    match *metadata.level() {
+        Level::ERROR => EventFilter::Event,
+        Level::WARN if matches!(metadata.target(), "storage" | "maintenance") => EventFilter::Event,
+        Level::WARN | Level::INFO => EventFilter::Breadcrumb,
+        _ => EventFilter::Ignore,
+    }
+}
+
+/// Starts the client and returns the layer that reports logged errors, or
+/// `None` when no DSN is configured.
+pub fn init(
+    config: Option<&SentryConfig>,
+) -> Result<Option<(ErrorReportingLayer, ErrorReportingGuard)>, String> {
+    let Some(config) = config else {
+        return Ok(None);
+    };
+    let dsn = config
+        .dsn
+        .parse()
+        .map_err(|e| format!("Invalid sentry.dsn: {e}"))?;
+    let guard = sentry::init(sentry::ClientOptions {
+        dsn: Some(dsn),
+        environment: config.environment.clone().map(Into::into),
+        release: sentry::release_name!(),
+        ..Default::default()
+    });
+    let layer = sentry::integrations::tracing::layer()
+        .event_filter(event_filter)
+        .enable_span_attributes()
+        .boxed();
+    Ok(Some((layer, guard)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disabled_without_config() {
+        assert!(init(None).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_dsn() {
+        let config = SentryConfig {
+            dsn: "not a dsn".to_string(),
+            environment: None,
+        };
+        assert!(init(Some(&config)).is_err_and(|e| e.contains("sentry.dsn")));
+    }
+}
@@ -0,0 +1,170 @@
+//! Every lobby's [`GameEngine`] runs in its own task and is driven through a
+//! command queue, so a big lobby's broadcast only delays that lobby's own
+//! events, and no map lock is held while updates are serialized and sent.
+
+use crate::game::GameEngine;
+use crate::lock_metrics::LockMetrics;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio::sync::{mpsc, oneshot};
+use tracing::{Span, debug};
+
+/// Commands a lobby may have queued before senders wait for room.
+const COMMAND_QUEUE_CAPACITY: usize = 1024;
+
+type Command = Box<dyn FnOnce(&mut GameEngine) + Send>;
+
+struct Queued {
+    command: Command,
+    queued_at: Instant,
+    /// The sender's span, so the engine's logs keep its player and request.
+    span: Span,
+}
+
+/// Sends commands to a lobby's task. The task stops, dropping the engine,
+/// once every handle is gone and the queued commands have run.
+#[derive(Clone)]
+pub struct LobbyHandle {
+    tx: mpsc::Sender<Queued>,
+    player_count: Arc<AtomicUsize>,
+    /// When a player last did something, including messages the engine
+    /// never sees such as pings.
+    last_activity: Arc<Mutex<Instant>>,
+}
+
+impl LobbyHandle {
+    pub fn spawn(join_code: String, engine: GameEngine, lock_metrics: Arc<LockMetrics>) -> Self {
+        let (tx, rx) = mpsc::channel(COMMAND_QUEUE_CAPACITY);
+        let player_count = Arc::new(AtomicUsize::new(engine.get_player_count()));
+        tokio::spawn(run(
+            engine,
+            rx,
+            join_code,
+            lock_metrics,
+            player_count.clone(),
+        ));
+        Self {
+            tx,
+            player_count,
+            last_activity: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Queues `command` without waiting for it to run. Returns false if the
+    /// lobby task has stopped.
+    pub async fn send(&self, command: impl FnOnce(&mut GameEngine) + Send + 'static) -> bool {
+        let queued = Queued {
+            command: Box::new(command),
+            queued_at: Instant::now(),
+            span: Span::current(),
+        };
+        self.tx.send(queued).await.is_ok()
+    }
+
+    /// Runs `command` on the engine and returns its result, or `None` if the
+    /// lobby task has stopped.
+    pub async fn call<R: Send + 'static>(
+        &self,
+        command: impl FnOnce(&mut GameEngine) -> R + Send + 'static,
+    ) -> Option<R> {
+        let (reply_tx, reply_rx) = oneshot::channel();
+        let queued = self
+            .send(move |engine| {
+                let _ = reply_tx.send(command(engine));
+            })
+            .await;
+        if !queued {
+            return None;
+        }
+        reply_rx.await.ok()
+    }
+
+    /// Players in the lobby, admin excluded, as of the last command.
+    pub fn player_count(&self) -> usize {
+        self.player_count.load(Ordering::Relaxed)
+    }
+
+    /// Notes player activity without queuing a command.
+    pub fn touch(&self) {
+        *self.last_activity.lock().unwrap() = Instant::now();
+    }
+
+    pub fn last_activity(&self) -> Instant {
+        *self.last_activity.lock().unwrap()
+    }
+}
+
+async fn run(
+    mut engine: GameEngine,
+    mut rx: mpsc::Receiver<Queued>,
+    join_code: String,
+    lock_metrics: Arc<LockMetrics>,
+    player_count: Arc<AtomicUsize>,
+) {
+    while let Some(queued) = rx.recv().await {
+        let wait = queued.queued_at.elapsed();
+        debug!(
+            target: "lock",
+            lobby_key = %join_code,
+            duration_us = wait.as_micros() as u64,
+            "lobby_command_started"
+        );
+        lock_metrics.record_wait(wait);
+        let _hold = lock_metrics.hold(&join_code);
+        let _span = queued.span.enter();
+        (queued.command)(&mut engine);
+        player_count.store(engine.get_player_count(), Ordering::Relaxed);
+    }
+    debug!(target: "lock", lobby_key = %join_code, "lobby_task_stopped");
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::uuid::Uuid;
+    use std::time::Duration;
+
+    fn spawn_test_lobby() -> (LobbyHandle, Arc<LockMetrics>) {
+        let engine = GameEngine::new(
+            Uuid::new_v4(),
+            Arc::from("123456"),
+            Arc::new(Vec::new()),
+            crate::question::baseline_weights(),
+            None,
+            60,
+        );
+        let metrics = Arc::new(LockMetrics::new(Duration::from_secs(1)));
+        let handle = LobbyHandle::spawn("123456".into(), engine, metrics.clone());
+        (handle, metrics)
+    }
+
+    #[tokio::test]
+    async fn test_commands_run_in_order() {
+        let (handle, metrics) = spawn_test_lobby();
+
+        assert!(
+            handle
+                .send(|engine| engine.add_player(Uuid::new_v4(), "Alice".into()).unwrap())
+                .await
+        );
+        let added = handle
+            .call(|engine| engine.add_player(Uuid::new_v4(), "Bob".into()).is_ok())
+            .await;
+        assert_eq!(added, Some(true));
+        assert_eq!(
+            handle.call(|engine| engine.get_player_count()).await,
+            Some(2)
+        );
+        assert_eq!(handle.player_count(), 2);
+        assert_eq!(metrics.snapshot().count, 3);
+    }
+
+    #[tokio::test]
+    async fn test_panicking_command_stops_the_lobby() {
+        let (handle, _metrics) = spawn_test_lobby();
+
+        assert_eq!(handle.call::<()>(|_| panic!("boom")).await, None);
+        assert_eq!(handle.call(|engine| engine.get_player_count()).await, None);
+    }
+}
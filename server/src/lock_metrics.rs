@@ -0,0 +1,145 @@
+//! Lobby contention: a histogram of how long commands wait in a lobby's
+//! queue before its task runs them, and a warning when one command keeps the
+//! lobby busy for too long, as a big lobby's broadcast can.
+
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Upper bounds of the wait time buckets, in microseconds. Waits above the
+/// last bound only count towards the total.
+const BUCKET_BOUNDS_US: [u64; 10] = [
+    10, 50, 100, 500, 1_000, 5_000, 10_000, 50_000, 100_000, 500_000,
+];
+
+#[derive(Debug)]
+pub struct LockMetrics {
+    buckets: [AtomicU64; BUCKET_BOUNDS_US.len()],
+    count: AtomicU64,
+    sum_us: AtomicU64,
+    max_us: AtomicU64,
+    hold_warn: Duration,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct WaitBucket {
+    pub le_us: u64,
+    /// Cumulative: waits of at most `le_us`.
+    pub count: u64,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct LockMetricsSnapshot {
+    pub count: u64,
+    pub sum_us: u64,
+    pub max_us: u64,
+    pub buckets: Vec<WaitBucket>,
+    pub hold_warn_ms: u64,
+}
+
+impl LockMetrics {
+    pub fn new(hold_warn: Duration) -> Self {
+        Self {
+            buckets: Default::default(),
+            count: AtomicU64::new(0),
+            sum_us: AtomicU64::new(0),
+            max_us: AtomicU64::new(0),
+            hold_warn,
+        }
+    }
+
+    pub fn record_wait(&self, wait: Duration) {
+        let us = wait.as_micros() as u64;
+        if let Some(i) = BUCKET_BOUNDS_US.iter().position(|&bound| us <= bound) {
+            self.buckets[i].fetch_add(1, Ordering::Relaxed);
+        }
+        self.count.fetch_add(1, Ordering::Relaxed);
+        self.sum_us.fetch_add(us, Ordering::Relaxed);
+        self.max_us.fetch_max(us, Ordering::Relaxed);
+    }
+
+    /// Starts timing a command on `lobby_key`. Keep the timer alive while the
+    /// command runs; it warns on drop if the command took too long.
+    pub fn hold<'a>(&self, lobby_key: &'a str) -> HoldTimer<'a> {
+        HoldTimer {
+            lobby_key,
+            started: Instant::now(),
+            warn_after: self.hold_warn,
+        }
+    }
+
+    pub fn snapshot(&self) -> LockMetricsSnapshot {
+        let mut cumulative = 0;
+        let buckets = BUCKET_BOUNDS_US
+            .iter()
+            .zip(&self.buckets)
+            .map(|(&le_us, bucket)| {
+                cumulative += bucket.load(Ordering::Relaxed);
+                WaitBucket {
+                    le_us,
+                    count: cumulative,
+                }
+            })
+            .collect();
+        LockMetricsSnapshot {
+            count: self.count.load(Ordering::Relaxed),
+            sum_us: self.sum_us.load(Ordering::Relaxed),
+            max_us: self.max_us.load(Ordering::Relaxed),
+            buckets,
+            hold_warn_ms: self.hold_warn.as_millis() as u64,
+        }
+    }
+}
+
+pub struct HoldTimer<'a> {
+    lobby_key: &'a str,
+    started: Instant,
+    warn_after: Duration,
+}
+
+impl Drop for HoldTimer<'_> {
+    fn drop(&mut self) {
+        let held = self.started.elapsed();
+        if held > self.warn_after {
+            warn!(
+                target: "lock",
+                lobby_key = %self.lobby_key,
+                held_ms = held.as_millis() as u64,
+                "Lobby lock held for too long"
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_wait_histogram_is_cumulative() {
+        let metrics = LockMetrics::new(Duration::from_millis(50));
+        metrics.record_wait(Duration::from_micros(5));
+        metrics.record_wait(Duration::from_micros(80));
+        metrics.record_wait(Duration::from_micros(80));
+        metrics.record_wait(Duration::from_secs(2));
+
+        let snapshot = metrics.snapshot();
+        assert_eq!(snapshot.count, 4);
+        assert_eq!(snapshot.sum_us, 2_000_165);
+        assert_eq!(snapshot.max_us, 2_000_000);
+        let count_at = |le_us| {
+            snapshot
+                .buckets
+                .iter()
+                .find(|b| b.le_us == le_us)
+                .unwrap()
+                .count
+        };
+        assert_eq!(count_at(10), 1);
+        assert_eq!(count_at(50), 1);
+        assert_eq!(count_at(100), 3);
+        // The two second wait is above every bound.
+        assert_eq!(count_at(500_000), 3);
+    }
+}
@@ -0,0 +1,54 @@
+//! Runtime control over the log filter, so e.g. `lock=debug` can be turned on
+//! during an incident without a restart dropping the open lobbies.
+
+use std::sync::Arc;
+use tracing_subscriber::layer::Layered;
+use tracing_subscriber::{EnvFilter, Layer, Registry, reload};
+
+/// Filter used when `RUST_LOG` is unset.
+pub const DEFAULT_FILTER: &str = "spektrum=info,tower_http=info";
+
+/// The subscriber the log output layer sits on: the registry with the trace
+/// export and error reporting layers.
+pub type LogSubscriber = Layered<Vec<Box<dyn Layer<Registry> + Send + Sync>>, Registry>;
+
+#[derive(Clone)]
+pub struct LogFilterHandle(Arc<reload::Handle<EnvFilter, LogSubscriber>>);
+
+impl LogFilterHandle {
+    pub fn new(handle: reload::Handle<EnvFilter, LogSubscriber>) -> Self {
+        Self(Arc::new(handle))
+    }
+
+    /// The active filter in `RUST_LOG` syntax.
+    pub fn current(&self) -> Result<String, String> {
+        self.0
+            .with_current(|filter| filter.to_string())
+            .map_err(|e| e.to_string())
+    }
+
+    /// Replaces the filter with `directives`, in `RUST_LOG` syntax.
+    pub fn set(&self, directives: &str) -> Result<String, String> {
+        let filter = EnvFilter::try_new(directives)
+            .map_err(|e| format!("Invalid log filter '{directives}': {e}"))?;
+        self.0.reload(filter).map_err(|e| e.to_string())?;
+        self.current()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_filter() {
+        let (_layer, handle) = reload::Layer::new(EnvFilter::new(DEFAULT_FILTER));
+        let handle = LogFilterHandle::new(handle);
+        assert!(handle.current().unwrap().contains("spektrum=info"));
+
+        let current = handle.set("spektrum=info,lock=debug").unwrap();
+        assert!(current.contains("lock=debug"));
+        assert!(handle.set("lock=loud").is_err());
+        assert!(handle.current().unwrap().contains("lock=debug"));
+    }
+}
@@ -1,40 +1,83 @@
+use crate::accounts::AccountStore;
+use crate::auth::JwtKeys;
+use crate::client_ip::{ClientIp, ClientIpKeyExtractor, TrustedProxies, resolve_client_ip};
+use crate::cors::CorsOrigin;
+use crate::db::QuestionDatabase;
+use crate::log_level::LogFilterHandle;
 use crate::question::QuestionStore;
+use crate::request_id::{RequestId, X_REQUEST_ID, assign_request_id};
 use crate::server::{
-    AppState, add_no_store_headers, check_sessions_handler, create_lobby_handler,
-    get_stored_data_handler, join_lobby_handler, list_sets_handler, set_stored_data_handler,
-    upload_character_image_handler, ws_handler,
+    AppState, Dataset, account_login_handler, add_no_store_headers, admin_login_handler,
+    audio_clip_handler, batch_join_lobby_handler, check_sessions_handler, cleanup_lobbies,
+    close_lobby_handler, create_account_handler, create_lobby_handler,
+    delete_character_image_handler, export_questions_handler, flush_event_logs,
+    flush_event_logs_periodically, game_history_handler, get_stored_data_handler,
+    import_questions_handler, integrity_handler, join_lobby_handler, list_admin_lobbies_handler,
+    list_game_history_handler, list_public_lobbies_handler, list_sets_handler,
+    lobby_preload_handler, lobby_qr_code_handler, lobby_results_handler, lobby_stats_handler,
+    lock_metrics_handler, media_upload_url_handler, persist_lobbies, persist_lobbies_periodically,
+    refresh_questions_periodically, restore_lobbies, set_log_level_handler,
+    set_stored_data_handler, upload_audio_clip_handler, upload_character_image_handler, ws_handler,
 };
+use crate::spotify::SpotifyPlayer;
+use crate::webhook::WebhookDispatcher;
 use axum::{
     Router,
     body::Body,
-    extract::Request,
+    extract::{DefaultBodyLimit, Request},
     middleware::{self, Next},
     response::Response,
-    routing::{any, get, post},
+    routing::{any, delete, get, post},
 };
+use clap::Parser;
 use config::Config;
-use http::HeaderValue;
 use serde::Deserialize;
+use spektrum_core::{delivery, game, locale, names, uuid};
+use std::collections::HashMap;
 use std::net::SocketAddr;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use tokio::signal;
 use tokio::time::Duration as TokioDuration;
-use tower_governor::{
-    GovernorError, GovernorLayer, governor::GovernorConfigBuilder,
-    key_extractor::SmartIpKeyExtractor,
-};
+use tower_governor::{GovernorError, GovernorLayer, governor::GovernorConfigBuilder};
 use tower_http::compression::{CompressionLayer, CompressionLevel};
-use tower_http::cors::CorsLayer;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+use tower_http::services::{ServeDir, ServeFile};
 use tower_http::trace::TraceLayer;
-use tracing::{Instrument, info, info_span, warn};
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing::{Instrument, error, info, info_span, warn};
+use tracing_subscriber::{Layer, Registry, layer::SubscriberExt, util::SubscriberInitExt};
 
+mod accounts;
+mod audio;
+mod auth;
+mod avif;
+mod check_config;
+mod client_ip;
+mod cors;
 mod db;
-mod game;
+#[cfg(feature = "discord")]
+mod discord;
+mod encryption;
+#[cfg(feature = "sentry")]
+mod error_reporting;
+mod lobby;
+mod lock_metrics;
+mod log_level;
+mod migrate;
+#[cfg(feature = "otlp")]
+mod otlp;
+mod qr;
 mod question;
+mod request_id;
+mod retry;
 mod server;
-mod uuid;
+mod spotify;
+#[cfg(feature = "systemd")]
+mod systemd;
+mod tls;
+#[cfg(feature = "image-transcode")]
+mod transcode;
+mod webhook;
 
 async fn no_store_response_middleware(request: Request, next: Next) -> Response {
     let mut response = next.run(request).await;
@@ -51,7 +94,29 @@ fn governor_error_response(error: GovernorError) -> http::Response<Body> {
 #[derive(Debug, Deserialize)]
 struct ServerConfig {
     port: u16,
+    /// Exact origins or wildcard subdomain patterns, see [`CorsOrigin`].
     cors_origins: Vec<String>,
+    /// Public URL of the player frontend, e.g. `https://spektrum.example`.
+    /// Lobby QR codes are disabled when unset.
+    frontend_base_url: Option<String>,
+    /// Public URL that serves stored media, e.g. a CDN or the bucket's
+    /// public URL. Image URLs returned by the API point there instead of at
+    /// `/img/...` on this server.
+    media_base_url: Option<String>,
+    /// Built frontend (e.g. `frontend/build`) to serve for every path not
+    /// handled by the API, with `index.html` as the SPA fallback.
+    static_dir: Option<PathBuf>,
+    /// Reverse proxy addresses or CIDR ranges whose `Forwarded` and
+    /// `X-Forwarded-For` headers identify the client.
+    #[serde(default)]
+    trusted_proxies: Vec<String>,
+    /// Listen on this unix socket instead of `port`, for a reverse proxy on
+    /// the same host. The proxy is trusted to set the client IP headers.
+    unix_socket: Option<PathBuf>,
+    /// Reject question edits, imports and media uploads with 403, for public
+    /// mirrors that only serve games.
+    #[serde(default)]
+    read_only: bool,
 }
 
 #[derive(Default, Debug, Deserialize)]
@@ -77,6 +142,298 @@ enum StorageConfig {
         access_key_id: String,
         secret_access_key: String,
     },
+    #[serde(rename = "azure")]
+    Azure {
+        account: String,
+        container: String,
+        /// Blob service URL, defaults to `https://{account}.blob.core.windows.net`.
+        endpoint: Option<String>,
+        prefix: String,
+        question_folder: String,
+        question_file: String,
+        /// Exactly one of `sas_token` and `account_key` must be set.
+        sas_token: Option<String>,
+        account_key: Option<String>,
+    },
+}
+
+#[derive(Clone, Debug, Deserialize)]
+#[serde(default)]
+struct LimitsConfig {
+    /// Maximum number of active lobbies a single client IP may have created.
+    max_lobbies_per_ip: usize,
+    /// Maximum number of open lobbies on the server.
+    max_lobbies: usize,
+    /// Maximum number of players joined across all lobbies, admins excluded.
+    max_total_players: usize,
+    /// HTTP rate limit: one request is replenished every this many milliseconds.
+    http_replenish_ms: u64,
+    /// HTTP rate limit: number of requests a client may burst.
+    http_burst: u32,
+    /// Messages per second a single WebSocket connection may send before it is closed.
+    ws_messages_per_sec: usize,
+    /// Seconds between the heartbeats a WebSocket client is expected to
+    /// send, unless it asks for another interval when connecting. The server
+    /// pings at the same interval.
+    heartbeat_interval_secs: u64,
+    /// Shortest heartbeat interval a client may ask for.
+    min_heartbeat_interval_secs: u64,
+    /// Longest heartbeat interval a client may ask for, e.g. a phone on a
+    /// flaky network.
+    max_heartbeat_interval_secs: u64,
+    /// Heartbeat intervals in a row a connection may stay silent, pongs and
+    /// other messages counting as heartbeats, before it is closed.
+    max_missed_heartbeats: u32,
+    /// Largest accepted HTTP request body in bytes.
+    max_body_bytes: usize,
+    /// Largest accepted character image upload in bytes.
+    max_image_bytes: usize,
+    /// Widest and tallest accepted character image in pixels.
+    max_image_dimension: u32,
+    /// Largest accepted audio clip upload in bytes. Uploads are also bound
+    /// by `max_body_bytes`.
+    max_audio_bytes: usize,
+    /// Warn when a single command keeps a lobby busy longer than this.
+    lobby_lock_warn_ms: u64,
+    /// Deprecated in favor of the heartbeat settings. Sets
+    /// `max_missed_heartbeats` so silent connections are closed after about
+    /// this many seconds.
+    ws_idle_timeout_secs: Option<u64>,
+}
+
+impl Default for LimitsConfig {
+    fn default() -> Self {
+        Self {
+            max_lobbies_per_ip: 10,
+            max_lobbies: 1000,
+            max_total_players: 20_000,
+            http_replenish_ms: 500,
+            http_burst: 30,
+            ws_messages_per_sec: 30,
+            heartbeat_interval_secs: 30,
+            min_heartbeat_interval_secs: 5,
+            max_heartbeat_interval_secs: 120,
+            max_missed_heartbeats: 2,
+            max_body_bytes: 2 * 1024 * 1024,
+            max_image_bytes: 512 * 1024,
+            max_image_dimension: 2048,
+            max_audio_bytes: 1024 * 1024,
+            lobby_lock_warn_ms: 50,
+            ws_idle_timeout_secs: None,
+        }
+    }
+}
+
+impl LimitsConfig {
+    /// Maps the deprecated `ws_idle_timeout_secs` onto
+    /// `max_missed_heartbeats`, returning it if it was set.
+    fn apply_idle_timeout(&mut self) -> Option<u64> {
+        let idle_secs = self.ws_idle_timeout_secs.take()?;
+        let missed = (idle_secs / self.heartbeat_interval_secs.max(1)).saturating_sub(1);
+        self.max_missed_heartbeats = u32::try_from(missed).unwrap_or(u32::MAX);
+        Some(idle_secs)
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct AuthConfig {
+    /// HS256 signing secret for admin tokens. A random secret is generated at
+    /// startup when unset, which invalidates issued tokens on restart.
+    jwt_secret: Option<String>,
+    token_ttl_secs: u64,
+}
+
+impl Default for AuthConfig {
+    fn default() -> Self {
+        Self {
+            jwt_secret: None,
+            token_ttl_secs: 3600,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct WebhookConfig {
+    /// URLs that receive a JSON POST for each lobby lifecycle event.
+    urls: Vec<String>,
+    /// Retries after the first failed attempt, with exponential backoff.
+    max_retries: u32,
+    initial_backoff_ms: u64,
+    timeout_secs: u64,
+}
+
+impl Default for WebhookConfig {
+    fn default() -> Self {
+        Self {
+            urls: Vec::new(),
+            max_retries: 3,
+            initial_backoff_ms: 1000,
+            timeout_secs: 10,
+        }
+    }
+}
+
+#[cfg(feature = "discord")]
+#[derive(Debug, Deserialize)]
+struct DiscordConfig {
+    application_id: String,
+    /// Hex-encoded Ed25519 key from the Discord developer portal, used to
+    /// verify interaction requests.
+    public_key: String,
+    bot_token: String,
+    /// Channel for final scores. Defaults to the channel the lobby was
+    /// created from.
+    score_channel_id: Option<String>,
+}
+
+/// Terminates HTTPS/WSS in the server itself, for setups without a reverse
+/// proxy.
+#[derive(Debug, Deserialize)]
+struct TlsConfig {
+    /// PEM certificate chain.
+    cert_path: PathBuf,
+    /// PEM private key (PKCS#8, PKCS#1 or SEC1).
+    key_path: PathBuf,
+    /// Plain HTTP port that redirects to HTTPS, usually 80.
+    redirect_port: Option<u16>,
+}
+
+/// Spotify app credentials and a refresh token for the host's account,
+/// obtained once through the authorization code flow with the
+/// `user-modify-playback-state` scope.
+#[derive(Debug, Deserialize)]
+struct SpotifyConfig {
+    client_id: String,
+    client_secret: String,
+    refresh_token: String,
+    /// Device to play on. Defaults to the account's active device.
+    device_id: Option<String>,
+}
+
+/// Polls the question file and reloads it when another instance (or a
+/// manual edit) changed it.
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct QuestionRefreshConfig {
+    enabled: bool,
+    interval_secs: u64,
+}
+
+impl Default for QuestionRefreshConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            interval_secs: 300,
+        }
+    }
+}
+
+/// Encrypts the question file before it is written to storage.
+#[derive(Debug, Deserialize)]
+struct EncryptionConfig {
+    /// Base64-encoded 256-bit AES-GCM key, e.g. from `openssl rand -base64 32`.
+    key: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct PersistenceConfig {
+    /// Save open lobbies to storage and restore them on startup.
+    enabled: bool,
+    snapshot_interval_secs: u64,
+}
+
+impl Default for PersistenceConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            snapshot_interval_secs: 30,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct EventLogConfig {
+    /// Record every event each lobby processes to `event_logs/` in storage,
+    /// one JSON line per event, for settling disputes after a game.
+    enabled: bool,
+    flush_interval_secs: u64,
+}
+
+impl Default for EventLogConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            flush_interval_secs: 30,
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct NamesConfig {
+    /// Words player names may not contain, on top of the built-in list.
+    /// Matched like the built-in ones, so lookalike spellings are caught too.
+    banned_words: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct AccountsConfig {
+    /// Let players create accounts that keep their name and identity across
+    /// games. Stored as `accounts.json` next to the question data.
+    enabled: bool,
+}
+
+/// Exports tracing spans to an OpenTelemetry collector, e.g. Jaeger or
+/// Tempo, over OTLP/HTTP.
+#[cfg(feature = "otlp")]
+#[derive(Debug, Deserialize)]
+#[serde(default)]
+struct OtlpConfig {
+    enabled: bool,
+    /// The collector's OTLP/HTTP traces endpoint.
+    endpoint: String,
+    service_name: String,
+    /// Fraction of traces to export, from 0 to 1.
+    sample_ratio: f64,
+    /// Spans to export, in `RUST_LOG` syntax. `RUST_LOG` only filters the
+    /// logs, so the WebSocket spans can be exported without logging them.
+    filter: String,
+}
+
+#[cfg(feature = "otlp")]
+impl Default for OtlpConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: "http://localhost:4318/v1/traces".to_string(),
+            service_name: "spektrum".to_string(),
+            sample_ratio: 1.0,
+            filter: "spektrum=info,ws=info,tower_http=info".to_string(),
+        }
+    }
+}
+
+/// Reports panics and errors to Sentry or a compatible service.
+#[cfg(feature = "sentry")]
+#[derive(Debug, Deserialize)]
+struct SentryConfig {
+    dsn: String,
+    /// Reported environment, e.g. `production` or `staging`.
+    environment: Option<String>,
+}
+
+/// A named question library with its own storage and admins, selected per
+/// lobby with `dataset` on lobby creation.
+#[derive(Debug, Deserialize)]
+struct DatasetConfig {
+    storage: StorageConfig,
+    /// Comma-separated passwords that log in as admin of this dataset only.
+    admin_password: String,
 }
 
 #[derive(Debug, Deserialize)]
@@ -85,6 +442,73 @@ struct AppConfig {
     logging: LoggingConfig,
     storage: StorageConfig,
     admin_password: Vec<String>,
+    #[serde(default)]
+    auth: AuthConfig,
+    #[serde(default)]
+    limits: LimitsConfig,
+    #[serde(default)]
+    persistence: PersistenceConfig,
+    #[serde(default)]
+    question_refresh: QuestionRefreshConfig,
+    #[serde(default)]
+    event_log: EventLogConfig,
+    #[serde(default)]
+    accounts: AccountsConfig,
+    #[serde(default)]
+    names: NamesConfig,
+    encryption: Option<EncryptionConfig>,
+    #[serde(default)]
+    webhooks: WebhookConfig,
+    #[cfg(feature = "discord")]
+    discord: Option<DiscordConfig>,
+    spotify: Option<SpotifyConfig>,
+    tls: Option<TlsConfig>,
+    #[cfg(feature = "otlp")]
+    #[serde(default)]
+    otlp: OtlpConfig,
+    #[cfg(feature = "sentry")]
+    sentry: Option<SentryConfig>,
+    /// Keyed by dataset name. Environment keys are lowercased, so names are
+    /// always lowercase.
+    #[serde(default)]
+    datasets: HashMap<String, DatasetConfig>,
+}
+
+/// Spektrum game server. Flags take precedence over the config file and
+/// `SPEKTRUM__*` environment variables.
+#[derive(Parser)]
+#[command(author, version, about)]
+struct Cli {
+    /// Config file to read instead of the optional `config.*` in the working
+    /// directory
+    #[arg(long)]
+    config: Option<PathBuf>,
+
+    /// Port to listen on
+    #[arg(long)]
+    port: Option<u16>,
+
+    /// Serve questions from this JSON file instead of the configured storage
+    #[arg(long, value_name = "FILE")]
+    storage: Option<PathBuf>,
+
+    /// Log as JSON instead of text
+    #[arg(long)]
+    log_json: bool,
+
+    /// Validate the configuration and storage, print a summary without
+    /// secrets and exit, non-zero on problems
+    #[arg(long, conflicts_with = "migrate_csv")]
+    check_config: bool,
+
+    /// Import question sets from the older CSV format into storage and exit
+    #[arg(long, num_args = 1.., value_name = "FILE")]
+    migrate_csv: Vec<String>,
+
+    /// Read a password from standard input, print its argon2 hash for
+    /// `admin_password` and exit
+    #[arg(long, exclusive = true)]
+    hash_password: bool,
 }
 
 /// Initialize tracing with configurable filters.
@@ -97,18 +521,66 @@ struct AppConfig {
 /// - `RUST_LOG=spektrum=info,ws=trace` - verbose WebSocket debugging
 /// - `RUST_LOG=spektrum=info,storage=debug` - storage/S3 operation debugging
 /// - `RUST_LOG=spektrum=info,maintenance=debug` - cleanup task debugging
-fn init_tracing(text_logging: bool) {
+///
+/// The filter only applies to the logs; exported traces and error reports
+/// have their own. It can be changed at runtime through the returned handle.
+fn init_tracing(
+    text_logging: bool,
+    exporters: Vec<Box<dyn Layer<Registry> + Send + Sync>>,
+) -> LogFilterHandle {
     let env_filter = tracing_subscriber::EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| "spektrum=info,tower_http=info".into());
-    let registry = tracing_subscriber::registry().with(env_filter);
+        .unwrap_or_else(|_| log_level::DEFAULT_FILTER.into());
+    let (env_filter, handle) = tracing_subscriber::reload::Layer::new(env_filter);
+    let registry = tracing_subscriber::registry().with(exporters);
 
     if text_logging {
-        registry.with(tracing_subscriber::fmt::layer()).init();
+        registry
+            .with(tracing_subscriber::fmt::layer().with_filter(env_filter))
+            .init();
     } else {
         registry
-            .with(tracing_subscriber::fmt::layer().json())
+            .with(
+                tracing_subscriber::fmt::layer()
+                    .json()
+                    .with_filter(env_filter),
+            )
             .init();
     }
+    LogFilterHandle::new(handle)
+}
+
+#[cfg(unix)]
+async fn serve_unix_socket(
+    path: &Path,
+    app: Router,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+    on_listening: impl FnOnce(),
+) -> Result<(), Box<dyn std::error::Error>> {
+    use std::os::unix::fs::FileTypeExt;
+
+    // A socket left behind by an unclean exit would make bind fail.
+    if std::fs::symlink_metadata(path).is_ok_and(|m| m.file_type().is_socket()) {
+        std::fs::remove_file(path)
+            .map_err(|e| format!("Failed to remove stale socket '{}': {e}", path.display()))?;
+    }
+    info!("Starting server on unix socket {}", path.display());
+    let listener = tokio::net::UnixListener::bind(path)?;
+    on_listening();
+    axum::serve(listener, app.into_make_service())
+        .with_graceful_shutdown(shutdown)
+        .await?;
+    let _ = std::fs::remove_file(path);
+    Ok(())
+}
+
+#[cfg(not(unix))]
+async fn serve_unix_socket(
+    _path: &Path,
+    _app: Router,
+    _shutdown: impl Future<Output = ()> + Send + 'static,
+    _on_listening: impl FnOnce(),
+) -> Result<(), Box<dyn std::error::Error>> {
+    Err("server.unix_socket is only supported on Unix".into())
 }
 
 async fn shutdown_signal() {
@@ -137,53 +609,157 @@ async fn shutdown_signal() {
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    let settings = Config::builder()
+    let cli = Cli::parse();
+    if cli.hash_password {
+        let mut password = String::new();
+        std::io::stdin().read_line(&mut password)?;
+        let password = password.trim_end_matches(['\r', '\n']);
+        if password.is_empty() {
+            return Err("No password given on standard input".into());
+        }
+        println!("{}", auth::hash_password(password)?);
+        return Ok(());
+    }
+    let config_file = match &cli.config {
+        Some(path) => config::File::from(path.as_path()),
+        None => config::File::with_name("config").required(false),
+    };
+    let mut settings = Config::builder()
         .add_source(
             config::Environment::with_prefix("SPEKTRUM")
                 .separator("__")
                 .list_separator(",")
                 .with_list_parse_key("admin_password")
                 .with_list_parse_key("server.cors_origins")
+                .with_list_parse_key("server.trusted_proxies")
+                .with_list_parse_key("webhooks.urls")
+                .with_list_parse_key("names.banned_words")
                 .try_parsing(true),
         )
-        .add_source(config::File::with_name("config").required(false))
+        .add_source(config_file)
+        .set_override_option("server.port", cli.port)?;
+    if let Some(path) = &cli.storage {
+        let base_path = path
+            .parent()
+            .filter(|parent| !parent.as_os_str().is_empty())
+            .unwrap_or(Path::new("."));
+        let file_path = path
+            .file_name()
+            .ok_or_else(|| format!("--storage {} is not a file", path.display()))?;
+        settings = settings
+            .set_override("storage.type", "filesystem")?
+            .set_override("storage.base_path", base_path.to_string_lossy().as_ref())?
+            .set_override("storage.file_path", file_path.to_string_lossy().as_ref())?;
+    }
+    if cli.log_json {
+        settings = settings.set_override("logging.text", false)?;
+    }
+    let settings = settings
         .build()
         .map_err(|e| format!("Failed to build config: {e}"))?;
 
-    let app_config: AppConfig = settings
+    let mut app_config: AppConfig = settings
         .try_deserialize()
         .map_err(|e| format!("Failed to parse config: {e}"))?;
+    app_config.admin_password = auth::rejoin_password_hashes(app_config.admin_password);
+
+    #[allow(unused_mut)]
+    let mut exporters = Vec::new();
+    #[cfg(feature = "sentry")]
+    let _error_reporting_guard = match error_reporting::init(app_config.sentry.as_ref())? {
+        Some((layer, guard)) => {
+            exporters.push(layer);
+            Some(guard)
+        }
+        None => None,
+    };
+    #[cfg(feature = "otlp")]
+    let _trace_export_guard = match otlp::layer(&app_config.otlp)? {
+        Some((layer, guard)) => {
+            exporters.push(layer);
+            Some(guard)
+        }
+        None => None,
+    };
+    let log_filter = init_tracing(app_config.logging.text, exporters);
+    #[cfg(feature = "otlp")]
+    if app_config.otlp.enabled {
+        info!("Exporting traces to {}", app_config.otlp.endpoint);
+    }
+    #[cfg(feature = "sentry")]
+    if app_config.sentry.is_some() {
+        info!("Reporting errors to Sentry");
+    }
 
-    init_tracing(app_config.logging.text);
+    if let Some(idle_secs) = app_config.limits.apply_idle_timeout() {
+        warn!(
+            idle_secs,
+            max_missed_heartbeats = app_config.limits.max_missed_heartbeats,
+            "limits.ws_idle_timeout_secs is deprecated, set limits.max_missed_heartbeats instead"
+        );
+    }
+    if cli.check_config {
+        return Ok(check_config::check_config(&app_config).await?);
+    }
+
+    let cipher = app_config
+        .encryption
+        .as_ref()
+        .map(|config| encryption::DataCipher::from_base64(&config.key))
+        .transpose()?;
+
+    if !cli.migrate_csv.is_empty() {
+        // Not through QuestionStore, which refuses to start on empty storage.
+        let db = QuestionDatabase::new(&app_config.storage, cipher)?;
+        migrate::migrate_csv(&db, &cli.migrate_csv)
+            .await
+            .map_err(|e| format!("Migration failed: {e}"))?;
+        return Ok(());
+    }
+
+    for password in &app_config.admin_password {
+        auth::validate_password_hash(password).map_err(|e| format!("admin_password: {e}"))?;
+    }
 
-    let cors_origins: Vec<HeaderValue> = app_config
+    let cors_origins: Vec<CorsOrigin> = app_config
         .server
         .cors_origins
         .iter()
-        .map(|origin| {
-            origin
-                .parse()
-                .map_err(|e| format!("Invalid CORS origin '{origin}': {e}"))
-        })
+        .map(|origin| CorsOrigin::parse(origin))
         .collect::<Result<Vec<_>, _>>()?;
 
     let cors = CorsLayer::new()
-        .allow_methods(vec![http::Method::GET, http::Method::POST])
-        .allow_origin(cors_origins)
+        .allow_methods(vec![
+            http::Method::GET,
+            http::Method::POST,
+            http::Method::DELETE,
+        ])
+        .allow_origin(AllowOrigin::predicate(move |origin, _| {
+            cors_origins.iter().any(|allowed| allowed.matches(origin))
+        }))
         .allow_credentials(true)
         .allow_headers(vec![
             http::header::CONTENT_TYPE,
             http::header::AUTHORIZATION,
             http::header::ACCEPT,
-        ]);
+        ])
+        // Lets the admin panel name question exports after the server's file
+        // name, and clients quote the request id in bug reports.
+        .expose_headers(vec![http::header::CONTENT_DISPOSITION, X_REQUEST_ID]);
+
+    let trusted_proxies = Arc::new(TrustedProxies::parse(&app_config.server.trusted_proxies)?);
+
+    let max_body_bytes = app_config.limits.max_body_bytes;
 
     let governor_conf = Arc::new(
         GovernorConfigBuilder::default()
-            .per_millisecond(500)
-            .burst_size(30)
-            .key_extractor(SmartIpKeyExtractor)
+            .per_millisecond(app_config.limits.http_replenish_ms)
+            .burst_size(app_config.limits.http_burst)
+            .key_extractor(ClientIpKeyExtractor)
             .finish()
-            .unwrap(),
+            .ok_or(
+                "Invalid rate limit config: http_replenish_ms and http_burst must be non-zero",
+            )?,
     );
 
     let governor_limiter = governor_conf.limiter().clone();
@@ -204,52 +780,270 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
         .instrument(info_span!(target: "maintenance", "rate_limit_cleanup")),
     );
 
-    let question_store = QuestionStore::new(&app_config.storage).await?;
-    let state = AppState::new(question_store, app_config.admin_password);
+    #[cfg(feature = "systemd")]
+    systemd::spawn_watchdog();
+    #[cfg(feature = "systemd")]
+    systemd::status("Loading questions");
+    let question_store = QuestionStore::new(&app_config.storage, cipher)
+        .await?
+        .with_media_base_url(app_config.server.media_base_url.clone());
+    #[cfg(feature = "systemd")]
+    systemd::status("Questions loaded, starting listener");
+    let mut datasets = HashMap::new();
+    for (name, config) in &app_config.datasets {
+        let cipher = app_config
+            .encryption
+            .as_ref()
+            .map(|config| encryption::DataCipher::from_base64(&config.key))
+            .transpose()?;
+        let store = QuestionStore::new(&config.storage, cipher)
+            .await
+            .map_err(|e| format!("Failed to load dataset {name}: {e}"))?
+            .with_media_base_url(app_config.server.media_base_url.clone());
+        let admin_passwords = auth::rejoin_password_hashes(
+            config
+                .admin_password
+                .split(',')
+                .map(str::trim)
+                .filter(|password| !password.is_empty())
+                .map(str::to_string)
+                .collect(),
+        );
+        for password in &admin_passwords {
+            auth::validate_password_hash(password)
+                .map_err(|e| format!("Dataset {name} admin_password: {e}"))?;
+        }
+        info!(dataset = %name, "Loaded question dataset");
+        datasets.insert(
+            name.clone(),
+            Dataset {
+                store: Arc::new(store),
+                admin_passwords,
+            },
+        );
+    }
+    let jwt = JwtKeys::new(
+        app_config.auth.jwt_secret.as_deref(),
+        app_config.auth.token_ttl_secs,
+    )?;
+    let webhooks = WebhookDispatcher::new(app_config.webhooks);
+    #[cfg(feature = "discord")]
+    let (webhooks, discord) = {
+        let mut webhooks = webhooks;
+        let discord = match app_config.discord {
+            Some(config) => {
+                let bot = discord::DiscordBot::new(config)?;
+                Some((bot, webhooks.subscribe()))
+            }
+            None => None,
+        };
+        (webhooks, discord)
+    };
+    let state = AppState::new(
+        question_store,
+        app_config.admin_password,
+        jwt,
+        app_config.limits,
+        app_config.server.frontend_base_url.clone(),
+        webhooks,
+        app_config.spotify.map(SpotifyPlayer::new),
+    )
+    .with_datasets(datasets)
+    .with_read_only(app_config.server.read_only)
+    .with_event_log(app_config.event_log.enabled)
+    .with_banned_words(app_config.names.banned_words)
+    .with_log_filter(log_filter);
+    let state = if app_config.accounts.enabled {
+        let accounts = AccountStore::load(state.store.clone()).await?;
+        info!("Player accounts enabled");
+        state.with_accounts(accounts)
+    } else {
+        state
+    };
+    if app_config.server.read_only {
+        info!("Read-only mode: question data cannot be changed through the API");
+    }
+
+    tokio::spawn(
+        cleanup_lobbies(state.clone())
+            .instrument(info_span!(target: "maintenance", "lobby_cleanup")),
+    );
+    if app_config.persistence.enabled {
+        match restore_lobbies(&state).await {
+            Ok(count) => info!("Restored {} lobbies from snapshot", count),
+            Err(e) => warn!(error = %e, "Failed to restore lobbies from snapshot"),
+        }
+        tokio::spawn(
+            persist_lobbies_periodically(
+                state.clone(),
+                TokioDuration::from_secs(app_config.persistence.snapshot_interval_secs.max(1)),
+            )
+            .instrument(info_span!(target: "maintenance", "lobby_persistence")),
+        );
+    }
+    if app_config.question_refresh.enabled {
+        tokio::spawn(
+            refresh_questions_periodically(
+                state.clone(),
+                TokioDuration::from_secs(app_config.question_refresh.interval_secs.max(1)),
+            )
+            .instrument(info_span!(target: "maintenance", "question_refresh")),
+        );
+    }
+    if app_config.event_log.enabled {
+        tokio::spawn(
+            flush_event_logs_periodically(
+                state.clone(),
+                TokioDuration::from_secs(app_config.event_log.flush_interval_secs.max(1)),
+            )
+            .instrument(info_span!(target: "maintenance", "event_log_flush")),
+        );
+    }
+    let persist_on_shutdown = app_config.persistence.enabled;
+    let shutdown_state =
+        (persist_on_shutdown || app_config.event_log.enabled).then(|| state.clone());
+    let connections = state.clone();
 
     let app = Router::new()
         .route("/ws", any(ws_handler))
         .route("/api/list-sets", get(list_sets_handler))
         .route("/api/create-lobby", post(create_lobby_handler))
+        .route("/api/lobbies", get(list_public_lobbies_handler))
         .route("/api/join-lobby", post(join_lobby_handler))
+        .route("/api/join-lobby/batch", post(batch_join_lobby_handler))
+        .route("/api/accounts", post(create_account_handler))
+        .route("/api/accounts/login", post(account_login_handler))
+        .route("/api/lobby/{join_code}/qr", get(lobby_qr_code_handler))
+        .route("/api/lobby/{join_code}/stats", get(lobby_stats_handler))
+        .route("/api/lobby/{join_code}/preload", get(lobby_preload_handler))
+        .route("/api/lobby/{join_code}/results", get(lobby_results_handler))
         .route("/api/check-sessions", post(check_sessions_handler))
-        .route("/api/questions", post(get_stored_data_handler))
+        .route("/api/admin/login", post(admin_login_handler))
+        .route("/api/admin/lobbies", get(list_admin_lobbies_handler))
+        .route(
+            "/api/admin/lobbies/{join_code}/close",
+            post(close_lobby_handler),
+        )
+        .route("/api/history", get(list_game_history_handler))
+        .route("/api/history/{id}", get(game_history_handler))
+        .route("/api/admin/lock-metrics", get(lock_metrics_handler))
+        .route("/api/admin/log-level", post(set_log_level_handler))
+        .route("/api/questions", get(get_stored_data_handler))
+        .route("/api/export-questions", get(export_questions_handler))
+        .route("/api/integrity", get(integrity_handler))
+        .route("/api/import-questions", post(import_questions_handler))
         .route("/api/update-questions", post(set_stored_data_handler))
         .route(
             "/api/upload-character-image/{character_name}",
             post(upload_character_image_handler),
         )
-        .with_state(state)
+        .route(
+            "/api/upload-audio/{media_id}",
+            post(upload_audio_clip_handler),
+        )
+        .route("/api/audio/{media_id}", get(audio_clip_handler))
+        .route("/api/media-upload-url", post(media_upload_url_handler))
+        .route(
+            "/api/character-image/{character_name}",
+            delete(delete_character_image_handler),
+        )
+        .with_state(state.clone());
+    #[cfg(feature = "discord")]
+    let app = match discord {
+        Some((bot, events)) => app.merge(discord::router(state.clone(), bot, events)),
+        None => app,
+    };
+    let compression = CompressionLayer::new()
+        .quality(CompressionLevel::Default)
+        .gzip(true);
+    let app = app
         .layer(
             TraceLayer::new_for_http().make_span_with(|request: &http::Request<_>| {
-                let request_id = fastrand::u64(..);
+                let request_id = request
+                    .extensions()
+                    .get::<RequestId>()
+                    .map(|id| tracing::field::display(id.0));
+                let client_ip = request
+                    .extensions()
+                    .get::<ClientIp>()
+                    .map(|ip| tracing::field::display(ip.0));
                 tracing::info_span!(
                     "http_request",
-                    request_id = %request_id,
+                    request_id,
+                    client_ip,
                     method = %request.method(),
                     uri = %request.uri(),
                 )
             }),
         )
-        .layer(
-            CompressionLayer::new()
-                .quality(CompressionLevel::Default)
-                .gzip(true),
-        )
+        .layer(DefaultBodyLimit::max(max_body_bytes))
+        .layer(compression.clone())
         .layer(GovernorLayer::new(governor_conf).error_handler(governor_error_response))
         .layer(middleware::from_fn(no_store_response_middleware))
-        .layer(cors);
+        .layer(cors)
+        .layer(middleware::from_fn_with_state(
+            trusted_proxies,
+            resolve_client_ip,
+        ))
+        .layer(middleware::from_fn(assign_request_id));
+    // Added after the layers so frontend assets skip the API rate limit and
+    // no-store headers; a page load fetches many files at once.
+    let app = match &app_config.server.static_dir {
+        Some(dir) => {
+            if !dir.join("index.html").is_file() {
+                return Err(format!("No index.html in static_dir '{}'", dir.display()).into());
+            }
+            info!("Serving frontend from {}", dir.display());
+            let files = ServeDir::new(dir).fallback(ServeFile::new(dir.join("index.html")));
+            app.fallback_service(Router::new().fallback_service(files).layer(compression))
+        }
+        None => app,
+    };
 
     let addr = SocketAddr::from(([0, 0, 0, 0], app_config.server.port));
-    info!("Starting server on {}", addr);
+    let on_listening = || {
+        #[cfg(feature = "systemd")]
+        {
+            systemd::ready();
+            systemd::status("Accepting connections");
+        }
+    };
+    let shutdown = async move {
+        shutdown_signal().await;
+        #[cfg(feature = "systemd")]
+        systemd::stopping();
+        connections.close_connections();
+        // Save before waiting on open connections, which may outlive the
+        // shutdown grace period.
+        if let Some(state) = shutdown_state {
+            if persist_on_shutdown {
+                match persist_lobbies(&state).await {
+                    Ok(count) => info!("Saved {} lobbies before shutdown", count),
+                    Err(e) => error!(error = %e, "Failed to save lobbies before shutdown"),
+                }
+            }
+            flush_event_logs(&state).await;
+        }
+    };
 
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    axum::serve(
-        listener,
-        app.into_make_service_with_connect_info::<SocketAddr>(),
-    )
-    .with_graceful_shutdown(shutdown_signal())
-    .await?;
+    if let Some(tls_config) = &app_config.tls {
+        if app_config.server.unix_socket.is_some() {
+            return Err("server.unix_socket cannot be combined with tls".into());
+        }
+        tls::serve(addr, tls_config, app, shutdown, on_listening).await?;
+    } else if let Some(path) = &app_config.server.unix_socket {
+        serve_unix_socket(path, app, shutdown, on_listening).await?;
+    } else {
+        info!("Starting server on {}", addr);
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        on_listening();
+        axum::serve(
+            listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(shutdown)
+        .await?;
+    }
 
     info!("Server shutdown complete");
     Ok(())
@@ -0,0 +1,235 @@
+//! `--migrate-csv`: imports the legacy color and character question CSVs into
+//! the stored question data.
+//!
+//! Color files have `title,artist,color,spotify_uri,youtube_id` columns with
+//! `;`-separated colors. Character files have
+//! `song,correct_character,other_characters,spotify_uri,youtube_id` columns
+//! with `;`-separated wrong characters. The format is detected from the header.
+
+use crate::db::{DbError, QuestionDatabase, StoredData};
+use crate::question::QuestionType;
+use serde::Deserialize;
+use std::io::Read;
+use std::path::Path;
+use thiserror::Error;
+use tracing::info;
+
+#[derive(Error, Debug)]
+pub enum MigrateError {
+    #[error("{path}: {source}")]
+    Csv {
+        path: String,
+        #[source]
+        source: csv::Error,
+    },
+    #[error("{0}: not a color or character question CSV")]
+    UnknownFormat(String),
+    #[error("{path}, row {row}: {source}")]
+    Row {
+        path: String,
+        row: usize,
+        #[source]
+        source: DbError,
+    },
+    #[error(transparent)]
+    Db(#[from] DbError),
+}
+
+/// A question from a legacy CSV row.
+pub struct LegacyQuestion {
+    pub question_type: QuestionType,
+    pub title: String,
+    pub artist: String,
+    pub spotify_uri: Option<String>,
+    pub youtube_id: String,
+    pub correct: Vec<String>,
+    pub incorrect: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct ColorRow {
+    title: String,
+    artist: String,
+    color: String,
+    spotify_uri: Option<String>,
+    youtube_id: String,
+}
+
+#[derive(Deserialize)]
+struct CharacterRow {
+    song: String,
+    correct_character: String,
+    other_characters: Option<String>,
+    spotify_uri: Option<String>,
+    youtube_id: String,
+}
+
+fn split_list(list: &str) -> Vec<String> {
+    list.split(';')
+        .map(str::trim)
+        .filter(|item| !item.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+fn parse_csv(reader: impl Read, path: &str) -> Result<Vec<LegacyQuestion>, MigrateError> {
+    let csv_error = |source| MigrateError::Csv {
+        path: path.to_string(),
+        source,
+    };
+    let mut reader = csv::Reader::from_reader(reader);
+    let headers = reader.headers().map_err(csv_error)?;
+    if headers.iter().any(|h| h == "color") {
+        reader
+            .deserialize()
+            .map(|row| {
+                let row: ColorRow = row.map_err(csv_error)?;
+                Ok(LegacyQuestion {
+                    question_type: QuestionType::Color,
+                    title: row.title,
+                    artist: row.artist,
+                    spotify_uri: row.spotify_uri,
+                    youtube_id: row.youtube_id,
+                    correct: split_list(&row.color),
+                    incorrect: Vec::new(),
+                })
+            })
+            .collect()
+    } else if headers.iter().any(|h| h == "correct_character") {
+        reader
+            .deserialize()
+            .map(|row| {
+                let row: CharacterRow = row.map_err(csv_error)?;
+                Ok(LegacyQuestion {
+                    question_type: QuestionType::Character,
+                    title: row.song,
+                    // The character format never had artists.
+                    artist: String::new(),
+                    spotify_uri: row.spotify_uri,
+                    youtube_id: row.youtube_id,
+                    correct: vec![row.correct_character.trim().to_string()],
+                    incorrect: row
+                        .other_characters
+                        .as_deref()
+                        .map_or_else(Vec::new, split_list),
+                })
+            })
+            .collect()
+    } else {
+        Err(MigrateError::UnknownFormat(path.to_string()))
+    }
+}
+
+/// Merges the CSV files into `data`, returning the number of questions added.
+/// Media is matched on its YouTube ID and rows whose media already has a
+/// question of the same type are skipped, so migrations can be re-run.
+pub fn merge_csv_files(
+    data: &mut StoredData,
+    files: &[(String, Vec<LegacyQuestion>)],
+) -> Result<usize, MigrateError> {
+    let mut added = 0;
+    for (path, questions) in files {
+        for (index, question) in questions.iter().enumerate() {
+            let is_new =
+                data.merge_legacy_question(question)
+                    .map_err(|source| MigrateError::Row {
+                        path: path.clone(),
+                        // Header is line 1.
+                        row: index + 2,
+                        source,
+                    })?;
+            added += usize::from(is_new);
+        }
+    }
+    data.ensure_default_set();
+    Ok(added)
+}
+
+/// Converts the CSV files at `paths` and writes the result through the
+/// configured storage, backing up the existing data first.
+pub async fn migrate_csv(db: &QuestionDatabase, paths: &[String]) -> Result<(), MigrateError> {
+    let mut files = Vec::new();
+    for path in paths {
+        let file = std::fs::File::open(Path::new(path)).map_err(|e| MigrateError::Csv {
+            path: path.clone(),
+            source: e.into(),
+        })?;
+        let questions = parse_csv(file, path)?;
+        info!(%path, rows = questions.len(), "Read legacy CSV");
+        files.push((path.clone(), questions));
+    }
+
+    let mut data = db.read_stored_data().await?;
+    let added = merge_csv_files(&mut data, &files)?;
+    if added == 0 {
+        info!("No new questions to migrate");
+        return Ok(());
+    }
+    db.backup_stored_data().await?;
+    db.set_stored_data(data).await?;
+    info!(added, "Migrated legacy questions");
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const COLOR_CSV: &str = "\
+id,title,artist,color,spotify_uri,youtube_id
+1,Yellow Song,Band,Yellow;Gold,,yt1
+2,Blue Song,Band,Blue,spotify:track:1,yt2
+";
+
+    const CHARACTER_CSV: &str = "\
+id,song,correct_character,other_characters,spotify_uri,youtube_id
+1,Theme,Mario,Luigi;Peach,,yt3
+2,Blue Song,Luigi,,,yt2
+";
+
+    fn files() -> Vec<(String, Vec<LegacyQuestion>)> {
+        vec![
+            (
+                "colors.csv".into(),
+                parse_csv(COLOR_CSV.as_bytes(), "colors.csv").unwrap(),
+            ),
+            (
+                "characters.csv".into(),
+                parse_csv(CHARACTER_CSV.as_bytes(), "characters.csv").unwrap(),
+            ),
+        ]
+    }
+
+    #[test]
+    fn test_merge_csv_files() {
+        let mut data = StoredData::default();
+        assert_eq!(merge_csv_files(&mut data, &files()).unwrap(), 4);
+        data.validate_stored_data().unwrap();
+        let json = serde_json::to_value(&data).unwrap();
+        // Character question reuses the color question's media.
+        assert_eq!(json["media"].as_array().unwrap().len(), 3);
+        assert_eq!(json["questions"][3]["media_id"], 2);
+        assert_eq!(json["characters"][0]["image_url"], "/img/Mario.avif");
+        assert_eq!(
+            json["sets"][0]["question_ids"],
+            serde_json::json!([1, 2, 3, 4])
+        );
+
+        // Re-running adds nothing and keeps IDs.
+        let before = json;
+        assert_eq!(merge_csv_files(&mut data, &files()).unwrap(), 0);
+        assert_eq!(serde_json::to_value(&data).unwrap(), before);
+    }
+
+    #[test]
+    fn test_rejects_bad_input() {
+        let res = parse_csv("id,name\n1,x\n".as_bytes(), "other.csv");
+        assert!(matches!(res, Err(MigrateError::UnknownFormat(_))));
+
+        let csv =
+            "song,correct_character,other_characters,spotify_uri,youtube_id\nTheme,Bad Name,,,yt\n";
+        let files = vec![("c.csv".into(), parse_csv(csv.as_bytes(), "c.csv").unwrap())];
+        let res = merge_csv_files(&mut StoredData::default(), &files);
+        assert!(matches!(res, Err(MigrateError::Row { row: 2, .. })));
+    }
+}
@@ -0,0 +1,88 @@
+//! Exports tracing spans to an OpenTelemetry collector over OTLP/HTTP, so a
+//! request or a WebSocket session can be followed in Jaeger or Tempo. Fields
+//! recorded on spans, like `lobby_key` and `player_id` on `ws_connection`,
+//! become span attributes.
+
+use crate::OtlpConfig;
+use opentelemetry::trace::TracerProvider;
+use opentelemetry_otlp::{SpanExporter, WithExportConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider};
+use tracing_subscriber::{EnvFilter, Layer, Registry};
+
+pub type OtlpLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Flushes the spans still queued for export when dropped.
+pub struct OtlpGuard(SdkTracerProvider);
+
+impl Drop for OtlpGuard {
+    fn drop(&mut self) {
+        if let Err(e) = self.0.shutdown() {
+            eprintln!("Failed to flush trace export: {e}");
+        }
+    }
+}
+
+/// The layer that exports spans, or `None` when export is disabled.
+pub fn layer(config: &OtlpConfig) -> Result<Option<(OtlpLayer, OtlpGuard)>, String> {
+    if !config.enabled {
+        return Ok(None);
+    }
+    if !(0.0..=1.0).contains(&config.sample_ratio) {
+        return Err(format!(
+            "otlp.sample_ratio must be between 0 and 1, got {}",
+            config.sample_ratio
+        ));
+    }
+    let filter = EnvFilter::try_new(&config.filter)
+        .map_err(|e| format!("Invalid otlp.filter '{}': {e}", config.filter))?;
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(&config.endpoint)
+        .build()
+        .map_err(|e| format!("Failed to create OTLP exporter: {e}"))?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        // Follow the caller's sampling decision when a trace is propagated.
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            config.sample_ratio,
+        ))))
+        .with_resource(
+            Resource::builder()
+                .with_service_name(config.service_name.clone())
+                .build(),
+        )
+        .build();
+    let layer = tracing_opentelemetry::layer()
+        .with_tracer(provider.tracer("spektrum"))
+        .with_filter(filter)
+        .boxed();
+    Ok(Some((layer, OtlpGuard(provider))))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disabled_by_default() {
+        assert!(layer(&OtlpConfig::default()).unwrap().is_none());
+    }
+
+    #[test]
+    fn rejects_invalid_settings() {
+        let config = OtlpConfig {
+            enabled: true,
+            sample_ratio: 1.5,
+            ..OtlpConfig::default()
+        };
+        assert!(layer(&config).is_err_and(|e| e.contains("sample_ratio")));
+
+        let config = OtlpConfig {
+            enabled: true,
+            filter: "ws=loud".to_string(),
+            ..OtlpConfig::default()
+        };
+        assert!(layer(&config).is_err_and(|e| e.contains("otlp.filter")));
+    }
+}
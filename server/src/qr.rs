@@ -0,0 +1,101 @@
+use qrcode::render::svg;
+use qrcode::{Color, QrCode};
+use thiserror::Error;
+
+/// Smallest rendered edge length in pixels, large enough to scan from a
+/// projector or TV across a room.
+const MIN_DIMENSION: u32 = 256;
+
+/// Modules of blank margin around the code, as the QR spec asks for.
+const QUIET_ZONE: usize = 4;
+
+#[derive(Error, Debug)]
+pub enum RenderError {
+    #[error("Failed to encode QR code: {0}")]
+    Qr(#[from] qrcode::types::QrError),
+    #[error("Failed to encode PNG: {0}")]
+    Png(#[from] png::EncodingError),
+}
+
+/// Frontend URL that opens the join screen for `join_code`.
+pub fn join_url(frontend_base_url: &str, join_code: &str) -> String {
+    format!(
+        "{}/join/{}",
+        frontend_base_url.trim_end_matches('/'),
+        join_code
+    )
+}
+
+/// Renders `data` as a standalone SVG QR code.
+pub fn render_svg(data: &str) -> Result<String, RenderError> {
+    let code = QrCode::new(data.as_bytes())?;
+    Ok(code
+        .render::<svg::Color>()
+        .min_dimensions(MIN_DIMENSION, MIN_DIMENSION)
+        .build())
+}
+
+/// Renders `data` as a grayscale PNG QR code, for clients that can't show
+/// SVG.
+pub fn render_png(data: &str) -> Result<Vec<u8>, RenderError> {
+    let code = QrCode::new(data.as_bytes())?;
+    let modules = code.width();
+    let colors = code.to_colors();
+    let edge_modules = modules + 2 * QUIET_ZONE;
+    let scale = (MIN_DIMENSION as usize).div_ceil(edge_modules);
+    let edge = edge_modules * scale;
+
+    let mut pixels = vec![u8::MAX; edge * edge];
+    for (index, color) in colors.iter().enumerate() {
+        if *color == Color::Light {
+            continue;
+        }
+        let x = (index % modules + QUIET_ZONE) * scale;
+        let y = (index / modules + QUIET_ZONE) * scale;
+        for row in y..y + scale {
+            pixels[row * edge + x..row * edge + x + scale].fill(0);
+        }
+    }
+
+    let mut png = Vec::new();
+    let mut encoder = png::Encoder::new(&mut png, edge as u32, edge as u32);
+    encoder.set_color(png::ColorType::Grayscale);
+    encoder.set_depth(png::BitDepth::Eight);
+    let mut writer = encoder.write_header()?;
+    writer.write_image_data(&pixels)?;
+    writer.finish()?;
+    Ok(png)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_join_url() {
+        assert_eq!(
+            join_url("https://spektrum.example/", "123456"),
+            "https://spektrum.example/join/123456"
+        );
+        assert_eq!(
+            join_url("https://spektrum.example", "PARTY24"),
+            "https://spektrum.example/join/PARTY24"
+        );
+    }
+
+    #[test]
+    fn test_render_svg() {
+        let svg = render_svg("https://spektrum.example/join/123456").unwrap();
+        assert!(svg.contains("<svg"));
+        assert!(svg.trim_end().ends_with("</svg>"));
+    }
+
+    #[test]
+    fn test_render_png() {
+        let png = render_png("https://spektrum.example/join/123456").unwrap();
+        let decoder = png::Decoder::new(std::io::Cursor::new(png));
+        let info = decoder.read_info().unwrap().info().clone();
+        assert!(info.width >= MIN_DIMENSION);
+        assert_eq!(info.width, info.height);
+    }
+}
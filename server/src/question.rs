@@ -1,120 +1,20 @@
 use crate::StorageConfig;
-use crate::db::{DbError, QuestionDatabase, QuestionSet, StoredData};
+use crate::accounts::Account;
+use crate::audio::AudioFormat;
+use crate::db::{
+    DbError, IntegrityReport, PersistedLobby, PresignedUpload, QuestionDatabase, StoredData,
+};
+use crate::encryption::DataCipher;
+use crate::game::GameRecord;
 use arc_swap::ArcSwap;
-use serde::{Deserialize, Serialize};
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use thiserror::Error;
 
-#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
-#[serde(rename_all = "lowercase")]
-pub enum QuestionType {
-    Color,
-    Character,
-    Text,
-    Year,
-}
-
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
-pub enum Color {
-    Red,
-    Green,
-    Blue,
-    Yellow,
-    Purple,
-    Gold,
-    Silver,
-    Pink,
-    Black,
-    White,
-    Brown,
-    Orange,
-    Gray,
-}
-
-impl Color {
-    pub const COUNT: usize = 13;
-
-    pub fn all() -> &'static [Color] {
-        use Color::{
-            Black, Blue, Brown, Gold, Gray, Green, Orange, Pink, Purple, Red, Silver, White, Yellow,
-        };
-        &[
-            Red, Green, Blue, Yellow, Purple, Gold, Silver, Pink, Black, White, Brown, Orange, Gray,
-        ]
-    }
-
-    /// Canonical dense index for array-backed color data. Do not assume Color::all() order matches.
-    pub fn idx(self) -> usize {
-        match self {
-            Color::Red => 0,
-            Color::Green => 1,
-            Color::Blue => 2,
-            Color::Yellow => 3,
-            Color::Purple => 4,
-            Color::Gold => 5,
-            Color::Silver => 6,
-            Color::Pink => 7,
-            Color::Black => 8,
-            Color::White => 9,
-            Color::Brown => 10,
-            Color::Orange => 11,
-            Color::Gray => 12,
-        }
-    }
-}
-
-pub fn baseline_weights() -> [f64; Color::COUNT] {
-    debug_assert_eq!(
-        Color::COUNT,
-        Color::all().len(),
-        "Color::COUNT must match Color::all().len()"
-    );
-    [0.15; Color::COUNT]
-}
-
-impl std::fmt::Display for Color {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        let s = match self {
-            Color::Red => "Red",
-            Color::Green => "Green",
-            Color::Blue => "Blue",
-            Color::Yellow => "Yellow",
-            Color::Purple => "Purple",
-            Color::Gold => "Gold",
-            Color::Silver => "Silver",
-            Color::Pink => "Pink",
-            Color::Black => "Black",
-            Color::White => "White",
-            Color::Brown => "Brown",
-            Color::Orange => "Orange",
-            Color::Gray => "Gray",
-        };
-        write!(f, "{}", s)
-    }
-}
-
-impl std::str::FromStr for Color {
-    type Err = String;
-
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s.trim() {
-            "Red" => Ok(Color::Red),
-            "Green" => Ok(Color::Green),
-            "Blue" => Ok(Color::Blue),
-            "Yellow" => Ok(Color::Yellow),
-            "Purple" => Ok(Color::Purple),
-            "Gold" => Ok(Color::Gold),
-            "Silver" => Ok(Color::Silver),
-            "Pink" => Ok(Color::Pink),
-            "Black" => Ok(Color::Black),
-            "White" => Ok(Color::White),
-            "Brown" => Ok(Color::Brown),
-            "Orange" => Ok(Color::Orange),
-            "Gray" | "Grey" => Ok(Color::Gray),
-            _ => Err(format!("Invalid color: {}", s)),
-        }
-    }
-}
+pub use spektrum_core::question::{
+    Color, GameQuestion, GameQuestionOption, QuestionSet, QuestionType, baseline_weights,
+    normalize_answer,
+};
 
 #[derive(Error, Debug)]
 pub enum QuestionError {
@@ -125,160 +25,6 @@ pub enum QuestionError {
     DbError(#[from] crate::db::DbError),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub struct GameQuestionOption {
-    pub option: Arc<str>,
-    pub is_correct: bool,
-}
-
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub struct GameQuestion {
-    pub id: i64,
-    pub question_type: QuestionType,
-    pub question_text: Option<Arc<str>>,
-    pub title: Arc<str>,
-    pub artist: Option<Arc<str>>,
-    pub youtube_id: Arc<str>,
-    pub options: Vec<GameQuestionOption>,
-}
-
-impl GameQuestion {
-    pub fn get_correct_options(&self) -> Vec<&GameQuestionOption> {
-        self.options.iter().filter(|opt| opt.is_correct).collect()
-    }
-
-    pub fn get_question_type(&self) -> &'static str {
-        match self.question_type {
-            QuestionType::Color => "color",
-            QuestionType::Character => "character",
-            QuestionType::Text => "text",
-            QuestionType::Year => "year",
-        }
-    }
-
-    pub fn get_correct_answer(&self) -> Vec<Arc<str>> {
-        self.get_correct_options()
-            .iter()
-            .map(|opt| opt.option.clone())
-            .collect()
-    }
-
-    pub fn generate_round_alternatives(
-        &self,
-        color_weights: &[f64; Color::COUNT],
-    ) -> Vec<Arc<str>> {
-        match self.question_type {
-            QuestionType::Color => self
-                .generate_color_alternatives(color_weights)
-                .into_iter()
-                .map(Arc::from)
-                .collect(),
-            QuestionType::Character | QuestionType::Text => {
-                let mut alternatives: Vec<Arc<str>> =
-                    self.options.iter().map(|opt| opt.option.clone()).collect();
-                fastrand::shuffle(&mut alternatives);
-                alternatives
-            }
-            QuestionType::Year => {
-                if let Some(year) = self
-                    .get_correct_answer()
-                    .first()
-                    .and_then(|y| y.parse().ok())
-                {
-                    self.generate_year_alternatives(year)
-                        .into_iter()
-                        .map(Arc::from)
-                        .collect()
-                } else {
-                    vec![]
-                }
-            }
-        }
-    }
-
-    fn generate_color_alternatives(
-        &self,
-        color_weights: &[f64; Color::COUNT],
-    ) -> Vec<&'static str> {
-        const TARGET_SIZE: usize = 6;
-
-        // Get initial colors from correct options
-        let mut round_colors: Vec<Color> = self
-            .get_correct_options()
-            .iter()
-            .filter_map(|opt| opt.option.parse().ok())
-            .collect();
-
-        // Get available colors (excluding ones we already have)
-        let mut available_colors: Vec<(Color, f64)> = Color::all()
-            .iter()
-            .copied()
-            .filter(|color| !round_colors.contains(color))
-            .map(|color| (color, color_weights[color.idx()]))
-            .collect();
-
-        // Select additional colors based on weights until we have TARGET_SIZE
-        while round_colors.len() < TARGET_SIZE && !available_colors.is_empty() {
-            let total_weight: f64 = available_colors.iter().map(|(_, w)| w).sum();
-
-            if total_weight <= 0.0 {
-                // Fallback to random selection if weights are invalid
-                let idx = fastrand::usize(..available_colors.len());
-                let (color, _) = available_colors.remove(idx);
-                round_colors.push(color);
-                continue;
-            }
-
-            let mut selection = fastrand::f64() * total_weight;
-            let mut selected_idx = 0;
-
-            for (idx, (_, weight)) in available_colors.iter().enumerate() {
-                selection -= weight;
-                if selection <= 0.0 {
-                    selected_idx = idx;
-                    break;
-                }
-            }
-
-            let (color, _) = available_colors.remove(selected_idx);
-            round_colors.push(color);
-        }
-
-        fastrand::shuffle(&mut round_colors);
-        round_colors
-            .into_iter()
-            .map(|c| match c {
-                Color::Red => "Red",
-                Color::Green => "Green",
-                Color::Blue => "Blue",
-                Color::Yellow => "Yellow",
-                Color::Purple => "Purple",
-                Color::Gold => "Gold",
-                Color::Silver => "Silver",
-                Color::Pink => "Pink",
-                Color::Black => "Black",
-                Color::White => "White",
-                Color::Brown => "Brown",
-                Color::Orange => "Orange",
-                Color::Gray => "Gray",
-            })
-            .collect()
-    }
-
-    fn generate_year_alternatives(&self, correct_year: i32) -> Vec<String> {
-        let mut alternatives = [
-            correct_year - 2,
-            correct_year - 1,
-            correct_year,
-            correct_year + 1,
-            correct_year + 2,
-        ];
-        fastrand::shuffle(&mut alternatives);
-
-        alternatives.iter().map(|y| y.to_string()).collect()
-    }
-}
-
 /// Immutable snapshot of question data and derived weights. Always obtain via
 /// `QuestionStore::snapshot()` to keep questions/sets/weights in sync.
 pub struct QuestionSnapshot {
@@ -333,23 +79,60 @@ fn build_snapshot(
 
 pub struct QuestionStore {
     snapshot: ArcSwap<QuestionSnapshot>,
+    /// Question file version the snapshot was built from.
+    version: Mutex<Option<String>>,
     db: QuestionDatabase,
+    /// Public URL that serves stored media, without a trailing slash.
+    media_base_url: Option<String>,
 }
 
 impl QuestionStore {
-    pub async fn new(config: &StorageConfig) -> Result<Self, QuestionError> {
-        let db = QuestionDatabase::new(config).map_err(QuestionError::DbError)?;
-
+    pub async fn new(
+        config: &StorageConfig,
+        cipher: Option<DataCipher>,
+    ) -> Result<Self, QuestionError> {
+        let db = QuestionDatabase::new(config, cipher).map_err(QuestionError::DbError)?;
+        db.check_storage().await.map_err(QuestionError::DbError)?;
+
+        let version = db
+            .question_file_version()
+            .await
+            .map_err(QuestionError::DbError)?;
         let (game_questions, sets) = db.load_questions().await.map_err(QuestionError::DbError)?;
         let snapshot = build_snapshot(game_questions, sets)?;
 
         Ok(Self {
             snapshot: ArcSwap::from_pointee(snapshot),
+            version: Mutex::new(version),
             db,
+            media_base_url: None,
         })
     }
 
+    /// Points image URLs returned to clients at `url`, e.g. a CDN or the
+    /// bucket's public URL, instead of `/img/...` on this server. Stored data
+    /// keeps relative URLs.
+    pub fn with_media_base_url(mut self, url: Option<String>) -> Self {
+        self.media_base_url = url.map(|url| url.trim_end_matches('/').to_string());
+        self
+    }
+
+    /// Client-facing URL for a stored media path like `/img/cat.avif`.
+    pub fn media_url(&self, path: &str) -> String {
+        match &self.media_base_url {
+            Some(base) if path.starts_with('/') => format!("{base}{path}"),
+            _ => path.to_string(),
+        }
+    }
+
     pub async fn reload(&self) -> Result<(), QuestionError> {
+        // Read the version first: a write racing the load then shows up as a
+        // change on the next refresh instead of being missed.
+        let version = self
+            .db
+            .question_file_version()
+            .await
+            .map_err(QuestionError::DbError)?;
         let (game_questions, sets) = self
             .db
             .load_questions()
@@ -358,18 +141,42 @@ impl QuestionStore {
 
         let snapshot = build_snapshot(game_questions, sets)?;
         self.snapshot.store(Arc::new(snapshot));
+        *self.version.lock().unwrap() = version;
         Ok(())
     }
 
+    /// Reloads the snapshot if the question file changed since it was last
+    /// loaded, e.g. by another instance sharing the storage. Returns whether
+    /// it reloaded.
+    pub async fn refresh_if_changed(&self) -> Result<bool, QuestionError> {
+        let current = self
+            .db
+            .question_file_version()
+            .await
+            .map_err(QuestionError::DbError)?;
+        if current.is_some() && current == *self.version.lock().unwrap() {
+            return Ok(false);
+        }
+        self.reload().await?;
+        Ok(true)
+    }
+
     pub fn snapshot(&self) -> Arc<QuestionSnapshot> {
         self.snapshot.load_full()
     }
 
     pub async fn get_stored_data(&self) -> Result<StoredData, DbError> {
-        self.db.read_stored_data().await
+        let mut stored_data = self.db.read_stored_data().await?;
+        if let Some(base) = &self.media_base_url {
+            stored_data.resolve_media_urls(base);
+        }
+        Ok(stored_data)
     }
 
-    pub async fn set_stored_data(&self, stored_data: StoredData) -> Result<(), DbError> {
+    pub async fn set_stored_data(&self, mut stored_data: StoredData) -> Result<(), DbError> {
+        if let Some(base) = &self.media_base_url {
+            stored_data.relativize_media_urls(base);
+        }
         self.db.set_stored_data(stored_data).await
     }
 
@@ -382,7 +189,77 @@ impl QuestionStore {
         character_name: &str,
         data: &[u8],
     ) -> Result<String, DbError> {
-        self.db.store_character_image(character_name, data).await
+        let url = self.db.store_character_image(character_name, data).await?;
+        Ok(self.media_url(&url))
+    }
+
+    pub async fn delete_character_image(&self, character_name: &str) -> Result<String, DbError> {
+        let url = self.db.delete_character_image(character_name).await?;
+        Ok(self.media_url(&url))
+    }
+
+    pub async fn store_audio_clip(
+        &self,
+        media_id: i64,
+        format: AudioFormat,
+        data: &[u8],
+    ) -> Result<(), DbError> {
+        self.db.store_audio_clip(media_id, format, data).await
+    }
+
+    pub async fn read_audio_clip(
+        &self,
+        media_id: i64,
+    ) -> Result<Option<(AudioFormat, Vec<u8>)>, DbError> {
+        self.db.read_audio_clip(media_id).await
+    }
+
+    pub async fn presign_upload(
+        &self,
+        path: &str,
+        expires_in: Duration,
+    ) -> Result<Option<PresignedUpload>, DbError> {
+        let mut upload = self.db.presign_upload(path, expires_in).await?;
+        if let Some(upload) = &mut upload {
+            upload.media_url = self.media_url(&upload.media_url);
+        }
+        Ok(upload)
+    }
+
+    pub async fn check_integrity(&self) -> Result<IntegrityReport, DbError> {
+        self.db.check_integrity().await
+    }
+
+    pub async fn load_lobby_snapshots(&self) -> Result<Vec<PersistedLobby>, DbError> {
+        self.db.read_lobby_snapshots().await
+    }
+
+    pub async fn save_lobby_snapshots(&self, snapshots: &[PersistedLobby]) -> Result<(), DbError> {
+        self.db.write_lobby_snapshots(snapshots).await
+    }
+
+    pub async fn load_accounts(&self) -> Result<Vec<Account>, DbError> {
+        self.db.read_accounts().await
+    }
+
+    pub async fn save_accounts(&self, accounts: &[Account]) -> Result<(), DbError> {
+        self.db.write_accounts(accounts).await
+    }
+
+    pub async fn save_game_record(&self, record: &GameRecord) -> Result<(), DbError> {
+        self.db.write_game_record(record).await
+    }
+
+    pub async fn load_game_record(&self, id: &str) -> Result<Option<GameRecord>, DbError> {
+        self.db.read_game_record(id).await
+    }
+
+    pub async fn list_game_records(&self) -> Result<Vec<String>, DbError> {
+        self.db.list_game_records().await
+    }
+
+    pub async fn save_event_log(&self, name: &str, jsonl: &str) -> Result<(), DbError> {
+        self.db.write_event_log(name, jsonl).await
     }
 }
 
@@ -394,11 +271,13 @@ mod tests {
     fn color_weights_use_correct_answer_denominator() {
         let color_question = GameQuestion {
             id: 1,
+            media_id: 0,
             question_type: QuestionType::Color,
             question_text: None,
             title: Arc::from("Color"),
             artist: None,
             youtube_id: Arc::from("id"),
+            spotify_uri: None,
             options: vec![
                 GameQuestionOption {
                     option: Arc::from("Red"),
@@ -415,11 +294,13 @@ mod tests {
         for idx in 0..9 {
             questions.push(GameQuestion {
                 id: idx + 2,
+                media_id: 0,
                 question_type: QuestionType::Text,
                 question_text: None,
                 title: Arc::from("Other"),
                 artist: None,
                 youtube_id: Arc::from("id"),
+                spotify_uri: None,
                 options: vec![GameQuestionOption {
                     option: Arc::from(format!("Opt{idx}")),
                     is_correct: true,
@@ -456,11 +337,13 @@ mod tests {
     fn color_weights_zero_correct_answers_use_baseline() {
         let questions = vec![GameQuestion {
             id: 1,
+            media_id: 0,
             question_type: QuestionType::Text,
             question_text: None,
             title: Arc::from("Other"),
             artist: None,
             youtube_id: Arc::from("id"),
+            spotify_uri: None,
             options: vec![GameQuestionOption {
                 option: Arc::from("Only"),
                 is_correct: true,
@@ -0,0 +1,99 @@
+use axum::extract::Request;
+use axum::middleware::Next;
+use axum::response::Response;
+use http::{HeaderName, HeaderValue};
+
+pub const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+/// Random id of an HTTP request, recorded on its tracing span and returned
+/// in the `X-Request-Id` header, so a user's bug report can be matched with
+/// the server's logs. Inserted into request extensions by
+/// [`assign_request_id`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RequestId(pub u64);
+
+impl RequestId {
+    fn header_value(self) -> HeaderValue {
+        HeaderValue::from(self.0)
+    }
+}
+
+pub async fn assign_request_id(mut request: Request, next: Next) -> Response {
+    let id = RequestId(fastrand::u64(..));
+    request.extensions_mut().insert(id);
+    let mut response = next.run(request).await;
+    response
+        .headers_mut()
+        .insert(X_REQUEST_ID, id.header_value());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::tests::setup_test_state;
+    use crate::server::ws_handler;
+    use axum::Router;
+    use axum::middleware;
+    use axum::routing::get;
+    use std::sync::{Arc, Mutex};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    #[test]
+    fn test_header_value_matches_logged_id() {
+        let id = RequestId(1234567890123);
+        assert_eq!(id.header_value(), "1234567890123");
+    }
+
+    #[tokio::test]
+    async fn test_ws_upgrade_returns_handler_request_id() {
+        let (state, _dir) = setup_test_state().await;
+        // Records the id the WebSocket route gets, as ws_handler extracts it.
+        let seen = Arc::new(Mutex::new(None));
+        let record = {
+            let seen = seen.clone();
+            move |request: Request, next: Next| {
+                let seen = seen.clone();
+                async move {
+                    *seen.lock().unwrap() = request.extensions().get::<RequestId>().copied();
+                    next.run(request).await
+                }
+            }
+        };
+        let app = Router::new()
+            .route("/ws", get(ws_handler))
+            .route_layer(middleware::from_fn(record))
+            .with_state(state)
+            .layer(middleware::from_fn(assign_request_id));
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let mut stream = tokio::net::TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(
+                b"GET /ws HTTP/1.1\r\n\
+                  Host: localhost\r\n\
+                  Connection: Upgrade\r\n\
+                  Upgrade: websocket\r\n\
+                  Sec-WebSocket-Version: 13\r\n\
+                  Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\r\n",
+            )
+            .await
+            .unwrap();
+        let mut head = Vec::new();
+        while !head.ends_with(b"\r\n\r\n") {
+            let mut byte = [0];
+            stream.read_exact(&mut byte).await.unwrap();
+            head.push(byte[0]);
+        }
+        let head = String::from_utf8(head).unwrap();
+        assert!(head.starts_with("HTTP/1.1 101"), "{head}");
+        let header_id = head
+            .lines()
+            .find_map(|line| line.strip_prefix("x-request-id: "))
+            .unwrap();
+        let seen = seen.lock().unwrap().unwrap();
+        assert_eq!(header_id, seen.0.to_string());
+    }
+}
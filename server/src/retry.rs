@@ -0,0 +1,227 @@
+//! Retries with jittered exponential backoff, and a circuit breaker that stops
+//! calling a backend that keeps failing so requests fail fast instead of
+//! piling up behind timeouts.
+
+use std::future::Future;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+use thiserror::Error;
+use tracing::warn;
+
+#[derive(Error, Debug)]
+pub enum RetryError<E> {
+    #[error("Circuit breaker open after repeated failures")]
+    CircuitOpen,
+    #[error(transparent)]
+    Failed(E),
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    pub max_attempts: u32,
+    pub base_delay: Duration,
+    pub max_delay: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(100),
+            max_delay: Duration::from_secs(2),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// "Full jitter" backoff: a random delay up to the exponential cap, so
+    /// instances retrying the same outage don't do it in lockstep.
+    fn delay(&self, attempt: u32) -> Duration {
+        let cap = self
+            .base_delay
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(self.max_delay);
+        cap.mul_f64(fastrand::f64())
+    }
+}
+
+#[derive(Debug)]
+enum BreakerState {
+    Closed {
+        failures: u32,
+    },
+    Open {
+        until: Instant,
+    },
+    /// A single probe call is in flight. Another probe is let through once
+    /// `since` is a cooldown old, in case the first one was cancelled.
+    HalfOpen {
+        since: Instant,
+    },
+}
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    failure_threshold: u32,
+    cooldown: Duration,
+    state: Mutex<BreakerState>,
+}
+
+impl Default for CircuitBreaker {
+    fn default() -> Self {
+        Self::new(5, Duration::from_secs(30))
+    }
+}
+
+impl CircuitBreaker {
+    /// Opens after `failure_threshold` consecutive failed calls and stays
+    /// open for `cooldown` before letting a probe call through.
+    pub fn new(failure_threshold: u32, cooldown: Duration) -> Self {
+        Self {
+            failure_threshold,
+            cooldown,
+            state: Mutex::new(BreakerState::Closed { failures: 0 }),
+        }
+    }
+
+    fn allow(&self) -> bool {
+        let mut state = self.state.lock().unwrap();
+        let now = Instant::now();
+        match *state {
+            BreakerState::Closed { .. } => true,
+            BreakerState::Open { until } if now < until => false,
+            BreakerState::HalfOpen { since } if now < since + self.cooldown => false,
+            BreakerState::Open { .. } | BreakerState::HalfOpen { .. } => {
+                *state = BreakerState::HalfOpen { since: now };
+                true
+            }
+        }
+    }
+
+    fn record_success(&self) {
+        *self.state.lock().unwrap() = BreakerState::Closed { failures: 0 };
+    }
+
+    fn record_failure(&self) {
+        let mut state = self.state.lock().unwrap();
+        let failures = match *state {
+            BreakerState::Closed { failures } => failures + 1,
+            BreakerState::Open { .. } | BreakerState::HalfOpen { .. } => self.failure_threshold,
+        };
+        *state = if failures >= self.failure_threshold {
+            warn!(
+                cooldown_secs = self.cooldown.as_secs(),
+                "Circuit breaker opened"
+            );
+            BreakerState::Open {
+                until: Instant::now() + self.cooldown,
+            }
+        } else {
+            BreakerState::Closed { failures }
+        };
+    }
+}
+
+/// Runs `op` until it succeeds, fails with an error `is_transient` rejects,
+/// or runs out of attempts. Only exhausted transient failures count against
+/// the breaker; any other outcome shows the backend is reachable.
+pub async fn call<T, E, F, Fut>(
+    policy: &RetryPolicy,
+    breaker: &CircuitBreaker,
+    is_transient: impl Fn(&E) -> bool,
+    mut op: F,
+) -> Result<T, RetryError<E>>
+where
+    F: FnMut() -> Fut,
+    Fut: Future<Output = Result<T, E>>,
+{
+    if !breaker.allow() {
+        return Err(RetryError::CircuitOpen);
+    }
+    let mut attempt = 0;
+    loop {
+        match op().await {
+            Err(e) if is_transient(&e) => {
+                attempt += 1;
+                if attempt >= policy.max_attempts {
+                    breaker.record_failure();
+                    return Err(RetryError::Failed(e));
+                }
+                tokio::time::sleep(policy.delay(attempt)).await;
+            }
+            result => {
+                breaker.record_success();
+                return result.map_err(RetryError::Failed);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::cell::Cell;
+
+    fn policy() -> RetryPolicy {
+        RetryPolicy {
+            max_attempts: 3,
+            base_delay: Duration::from_millis(1),
+            max_delay: Duration::from_millis(1),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_errors() {
+        let breaker = CircuitBreaker::default();
+        let calls = Cell::new(0);
+        let result = call(
+            &policy(),
+            &breaker,
+            |_: &&str| true,
+            || {
+                calls.set(calls.get() + 1);
+                async {
+                    if calls.get() < 3 {
+                        Err("flaky")
+                    } else {
+                        Ok(())
+                    }
+                }
+            },
+        )
+        .await;
+        assert!(result.is_ok());
+        assert_eq!(calls.get(), 3);
+
+        calls.set(0);
+        let result: Result<(), _> = call(
+            &policy(),
+            &breaker,
+            |e: &&str| *e == "flaky",
+            || {
+                calls.set(calls.get() + 1);
+                async { Err("fatal") }
+            },
+        )
+        .await;
+        assert!(matches!(result, Err(RetryError::Failed("fatal"))));
+        assert_eq!(calls.get(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_circuit_breaker() {
+        let breaker = CircuitBreaker::new(2, Duration::from_millis(20));
+        let failing = || async { Err::<(), _>("down") };
+        for _ in 0..2 {
+            let result = call(&policy(), &breaker, |_| true, failing).await;
+            assert!(matches!(result, Err(RetryError::Failed(_))));
+        }
+        let result = call(&policy(), &breaker, |_| true, failing).await;
+        assert!(matches!(result, Err(RetryError::CircuitOpen)));
+
+        tokio::time::sleep(Duration::from_millis(30)).await;
+        let result = call(&policy(), &breaker, |_: &&str| true, || async { Ok(()) }).await;
+        assert!(result.is_ok());
+        assert!(breaker.allow());
+    }
+}
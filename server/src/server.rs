@@ -1,30 +1,54 @@
-use crate::db::{DbError, StoredData};
+use crate::LimitsConfig;
+use crate::accounts::{AccountRequest, AccountResponse, AccountStore};
+use crate::audio::AudioFormat;
+use crate::auth::{AdminSession, IssuedToken, JwtKeys, bearer_token, password_matches};
+use crate::avif;
+use crate::client_ip::ClientIp;
+use crate::db::{self, DbError, IntegrityReport, PersistedLobby, PresignedUpload, StoredData};
+use crate::delivery::{EndReason, Inbox, Outgoing};
 use crate::game::{
-    EventContext, GameAction, GameEngine, GameEvent, GameUpdate, NameValidationError,
+    EventContext, GameAction, GameEngine, GameEvent, GamePhase, GameRecord, GameUpdate, Heartbeat,
+    LobbyStats, MAX_UPCOMING_PREVIEW, NameValidationError, Reclaim, unix_time_ms,
 };
-use crate::question::{QuestionError, QuestionStore};
+use crate::lobby::LobbyHandle;
+use crate::locale::Locale;
+use crate::lock_metrics::{LockMetrics, LockMetricsSnapshot};
+use crate::log_level::LogFilterHandle;
+use crate::names;
+use crate::qr;
+use crate::question::{GameQuestionOption, QuestionError, QuestionStore, QuestionType};
+use crate::request_id::RequestId;
+use crate::spotify::SpotifyPlayer;
+#[cfg(feature = "image-transcode")]
+use crate::transcode;
 use crate::uuid::Uuid;
-use axum::extract::Path;
-use axum::extract::ws::Utf8Bytes;
+use crate::webhook::{FinalScore, WebhookDispatcher, WebhookEvent};
+use axum::extract::ws::{CloseFrame, Utf8Bytes, close_code};
+use axum::extract::{Extension, Path, Query};
 use axum::http::{HeaderMap, HeaderValue, StatusCode, header};
 use axum::{
     Json,
-    extract::Multipart,
     extract::State,
     extract::WebSocketUpgrade,
     extract::ws::{Message, WebSocket},
+    extract::{Multipart, multipart::MultipartError},
     response::IntoResponse,
 };
 use bytes::Bytes;
 use chrono::{DateTime, SecondsFormat, Utc};
 use dashmap::DashMap;
+use futures_util::future::join_all;
 use futures_util::stream::SplitSink;
 use futures_util::{SinkExt, StreamExt};
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::net::IpAddr;
 use std::sync::Arc;
 use std::time::{Duration, Instant, SystemTime};
 use thiserror::Error;
 use tokio::sync::mpsc::{Receiver, Sender, channel};
+use tokio::sync::{oneshot, watch};
 use tokio::task::JoinHandle;
 use tracing::{Instrument, Span, debug, error, info, info_span, trace, warn};
 
@@ -46,6 +70,8 @@ pub enum ApiError {
     Validation(String),
     #[error("Unauthorized")]
     Unauthorized,
+    #[error("Forbidden: {0}")]
+    Forbidden(String),
     #[error("Database error: {0}")]
     Database(String),
     #[error("Lobby error: {0}")]
@@ -56,6 +82,14 @@ pub enum ApiError {
     UnsupportedMediaType,
     #[error("Bad request: {0}")]
     BadRequest(String),
+    #[error("Too many lobbies created from this address")]
+    TooManyLobbies,
+    #[error("Payload too large: {0}")]
+    PayloadTooLarge(String),
+    #[error("Not found: {0}")]
+    NotFound(String),
+    #[error("Server full: {0}")]
+    ServerFull(String),
 }
 
 #[derive(Serialize)]
@@ -71,6 +105,7 @@ impl IntoResponse for ApiError {
                 (StatusCode::BAD_REQUEST, "Validation error", Some(message))
             }
             ApiError::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized", None),
+            ApiError::Forbidden(message) => (StatusCode::FORBIDDEN, "Forbidden", Some(message)),
             ApiError::Database(ref message) => {
                 error!(error = %message, "Database error in API handler");
                 (
@@ -93,6 +128,22 @@ impl IntoResponse for ApiError {
             ApiError::BadRequest(message) => {
                 (StatusCode::BAD_REQUEST, "Bad request", Some(message))
             }
+            ApiError::TooManyLobbies => (
+                StatusCode::TOO_MANY_REQUESTS,
+                "Too many lobbies",
+                Some("Close an existing lobby before creating a new one.".into()),
+            ),
+            ApiError::PayloadTooLarge(message) => (
+                StatusCode::PAYLOAD_TOO_LARGE,
+                "Payload too large",
+                Some(message),
+            ),
+            ApiError::NotFound(message) => (StatusCode::NOT_FOUND, "Not found", Some(message)),
+            ApiError::ServerFull(message) => (
+                StatusCode::SERVICE_UNAVAILABLE,
+                "Server full",
+                Some(message),
+            ),
         };
 
         let body = Json(ErrorResponse {
@@ -111,6 +162,19 @@ fn no_store_json<T: Serialize>(value: T) -> axum::response::Response {
     response
 }
 
+impl ApiError {
+    /// The error with its message in `locale`, for errors shown to players.
+    fn localized(self, locale: Locale) -> Self {
+        let localize = |message: String| locale.localize(&message).to_string();
+        match self {
+            ApiError::Validation(message) => ApiError::Validation(localize(message)),
+            ApiError::Lobby(message) => ApiError::Lobby(localize(message)),
+            ApiError::ServerFull(message) => ApiError::ServerFull(localize(message)),
+            other => other,
+        }
+    }
+}
+
 impl From<DbError> for ApiError {
     fn from(err: DbError) -> Self {
         ApiError::Database(err.to_string())
@@ -132,10 +196,33 @@ impl From<QuestionError> for ApiError {
 #[derive(Debug, Deserialize)]
 #[serde(tag = "type")]
 pub enum ClientMessage {
-    Connect { session_token: String },
+    Connect {
+        session_token: String,
+        /// Language for the errors sent on this connection, e.g. `sv-SE`.
+        #[serde(default)]
+        locale: Option<Locale>,
+        /// How often the client means to send heartbeats. Kept within the
+        /// server's limits; `Connected` tells what was agreed.
+        #[serde(default)]
+        heartbeat_interval_ms: Option<u64>,
+    },
     Leave,
-    Answer { answer: String },
-    AdminAction { action: AdminAction },
+    Answer {
+        answer: String,
+        /// Opaque ID echoed back in the resulting `AnswerAck` or `Error`.
+        /// Resending an answer with the same ID is acknowledged again.
+        #[serde(default)]
+        client_msg_id: Option<Arc<str>>,
+    },
+    AdminAction {
+        action: AdminAction,
+    },
+    /// Asks for the full scoreboard after missing a score update.
+    ResyncScoreboard,
+    /// Answered with a `Pong` right away, also before connecting.
+    Ping {
+        client_time_ms: u64,
+    },
 }
 
 impl ClientMessage {
@@ -146,6 +233,8 @@ impl ClientMessage {
             ClientMessage::Leave => "Leave",
             ClientMessage::Answer { .. } => "Answer",
             ClientMessage::AdminAction { .. } => "AdminAction",
+            ClientMessage::ResyncScoreboard => "ResyncScoreboard",
+            ClientMessage::Ping { .. } => "Ping",
         }
     }
 }
@@ -157,10 +246,35 @@ pub enum AdminAction {
     StartRound,
     EndRound,
     SkipQuestion,
-    KickPlayer { player_name: String },
-    EndGame { reason: String },
-    CloseGame { reason: String },
-    LockLobby { locked: bool },
+    KickPlayer {
+        player_name: String,
+    },
+    EndGame {
+        reason: String,
+    },
+    CloseGame {
+        reason: String,
+    },
+    LockLobby {
+        locked: bool,
+    },
+    UnlockLobby,
+    RequestUpcoming {
+        count: usize,
+    },
+    QueueQuestion {
+        question_id: i64,
+    },
+    ReorderUpcoming {
+        question_ids: Vec<i64>,
+    },
+    InjectQuestion {
+        question_text: String,
+        options: Vec<GameQuestionOption>,
+    },
+    ApproveReclaim {
+        player_name: String,
+    },
 }
 
 impl AdminAction {
@@ -175,58 +289,272 @@ impl AdminAction {
             AdminAction::EndGame { .. } => "EndGame",
             AdminAction::CloseGame { .. } => "CloseGame",
             AdminAction::LockLobby { .. } => "LockLobby",
+            AdminAction::UnlockLobby => "UnlockLobby",
+            AdminAction::RequestUpcoming { .. } => "RequestUpcoming",
+            AdminAction::QueueQuestion { .. } => "QueueQuestion",
+            AdminAction::ReorderUpcoming { .. } => "ReorderUpcoming",
+            AdminAction::InjectQuestion { .. } => "InjectQuestion",
+            AdminAction::ApproveReclaim { .. } => "ApproveReclaim",
+        }
+    }
+}
+
+/// Tracks which client IP created each active lobby so per-IP caps can be
+/// enforced and released again when the lobby is cleaned up.
+#[derive(Default)]
+pub struct LobbyCreationTracker {
+    creators: DashMap<String, IpAddr>,
+    counts: DashMap<IpAddr, usize>,
+}
+
+impl LobbyCreationTracker {
+    /// Reserves a lobby slot for `ip`, failing if it already holds `max` lobbies.
+    fn try_reserve(&self, ip: IpAddr, max: usize) -> bool {
+        let mut count = self.counts.entry(ip).or_insert(0);
+        if *count >= max {
+            return false;
+        }
+        *count += 1;
+        true
+    }
+
+    fn unreserve(&self, ip: IpAddr) {
+        if let dashmap::mapref::entry::Entry::Occupied(mut entry) = self.counts.entry(ip) {
+            *entry.get_mut() -= 1;
+            if *entry.get() == 0 {
+                entry.remove();
+            }
+        }
+    }
+
+    fn record(&self, join_code: &str, ip: IpAddr) {
+        self.creators.insert(join_code.to_string(), ip);
+    }
+
+    fn creator(&self, join_code: &str) -> Option<IpAddr> {
+        self.creators.get(join_code).map(|ip| *ip)
+    }
+
+    /// Counts a restored lobby towards its creator's limit again, even if
+    /// that puts the creator over it.
+    fn restore(&self, join_code: &str, ip: IpAddr) {
+        *self.counts.entry(ip).or_insert(0) += 1;
+        self.record(join_code, ip);
+    }
+
+    /// Releases the slot held by a lobby that is being removed.
+    fn release(&self, join_code: &str) {
+        if let Some((_, ip)) = self.creators.remove(join_code) {
+            self.unreserve(ip);
         }
     }
 }
 
+/// A named question library, e.g. in another language, with its own storage
+/// and admins.
+pub struct Dataset {
+    pub store: Arc<QuestionStore>,
+    pub admin_passwords: Vec<String>,
+}
+
 #[derive(Clone)]
 pub struct AppState {
-    pub lobbies: Arc<DashMap<String, GameEngine>>,
+    pub lobbies: Arc<DashMap<String, LobbyHandle>>,
+    /// The default dataset.
     pub store: Arc<QuestionStore>,
     pub admin_passwords: Vec<String>,
+    pub datasets: Arc<HashMap<String, Dataset>>,
+    /// Rejects every change to the question data, for public mirrors.
+    pub read_only: bool,
+    pub jwt: Arc<JwtKeys>,
+    pub limits: Arc<LimitsConfig>,
+    pub lobby_creations: Arc<LobbyCreationTracker>,
+    /// Public URL of the player frontend, used for join links and QR codes.
+    pub frontend_base_url: Option<Arc<str>>,
+    pub webhooks: WebhookDispatcher,
+    /// Host playback control, for single-host deployments with Spotify
+    /// credentials configured.
+    pub spotify: Option<SpotifyPlayer>,
+    pub lock_metrics: Arc<LockMetrics>,
+    /// Record each lobby's processed events, see [`flush_event_logs`].
+    pub record_events: bool,
+    /// Changes the log filter at runtime. `None` when the server didn't set
+    /// up logging, as in tests.
+    pub log_filter: Option<LogFilterHandle>,
+    /// Player accounts, when enabled.
+    pub accounts: Option<Arc<AccountStore>>,
+    /// Last game of recently removed lobbies, see [`lobby_results`].
+    pub closed_results: Arc<DashMap<String, ClosedLobbyResults>>,
+    /// Words player names may not contain, beyond the built-in ones.
+    pub banned_words: Arc<[String]>,
+    /// Set when the server shuts down, to close every WebSocket.
+    pub shutting_down: watch::Sender<bool>,
+}
+
+/// Final results of a removed lobby, kept for [`RESULTS_GRACE`].
+pub struct ClosedLobbyResults {
+    admin_id: Uuid,
+    record: GameRecord,
+    removed_at: Instant,
 }
 
 impl AppState {
-    /// Constant-time password check to prevent timing attacks.
-    fn verify_admin_password(&self, candidate: &str) -> bool {
-        let mut matched = false;
-        for stored in &self.admin_passwords {
-            let stored_bytes = stored.as_bytes();
-            let candidate_bytes = candidate.as_bytes();
-            // Compare all bytes without short-circuiting.
-            let len_match = stored_bytes.len() == candidate_bytes.len();
-            let mut acc = 0u8;
-            for (a, b) in stored_bytes.iter().zip(candidate_bytes.iter()) {
-                acc |= a ^ b;
-            }
-            if len_match && acc == 0 {
-                matched = true;
+    /// The dataset `password` grants admin access to: `Ok(None)` for the
+    /// global admin passwords, `Ok(Some(name))` for a dataset's own.
+    fn admin_scope(&self, password: &str) -> Result<Option<String>, ApiError> {
+        if password_matches(&self.admin_passwords, password) {
+            return Ok(None);
+        }
+        // Checks every dataset so the time taken doesn't reveal which matched.
+        let mut scope = None;
+        for (name, dataset) in self.datasets.iter() {
+            if password_matches(&dataset.admin_passwords, password) {
+                scope = Some(name.clone());
             }
         }
-        matched
+        scope.map(Some).ok_or(ApiError::Unauthorized)
+    }
+
+    /// Question store of the named dataset, or the default one.
+    pub fn dataset_store(&self, dataset: Option<&str>) -> Result<&Arc<QuestionStore>, ApiError> {
+        match dataset {
+            None => Ok(&self.store),
+            Some(name) => self
+                .datasets
+                .get(name)
+                .map(|dataset| &dataset.store)
+                .ok_or_else(|| ApiError::Validation(format!("Unknown dataset: {name}"))),
+        }
+    }
+
+    /// Closes every WebSocket, telling clients the server is going away.
+    pub fn close_connections(&self) {
+        self.shutting_down.send_replace(true);
     }
 
-    pub fn new(question_manager: QuestionStore, admin_passwords: Vec<String>) -> Self {
-        let state = Self {
+    pub fn new(
+        question_manager: QuestionStore,
+        admin_passwords: Vec<String>,
+        jwt: JwtKeys,
+        limits: LimitsConfig,
+        frontend_base_url: Option<String>,
+        webhooks: WebhookDispatcher,
+        spotify: Option<SpotifyPlayer>,
+    ) -> Self {
+        Self {
             lobbies: Arc::new(DashMap::new()),
             store: Arc::new(question_manager),
             admin_passwords,
-        };
+            datasets: Arc::new(HashMap::new()),
+            read_only: false,
+            jwt: Arc::new(jwt),
+            lock_metrics: Arc::new(LockMetrics::new(Duration::from_millis(
+                limits.lobby_lock_warn_ms,
+            ))),
+            limits: Arc::new(limits),
+            lobby_creations: Arc::new(LobbyCreationTracker::default()),
+            frontend_base_url: frontend_base_url.map(Arc::from),
+            webhooks,
+            spotify,
+            record_events: false,
+            log_filter: None,
+            accounts: None,
+            closed_results: Arc::new(DashMap::new()),
+            banned_words: Arc::from([]),
+            shutting_down: watch::Sender::new(false),
+        }
+    }
 
-        {
-            let lobbies = state.lobbies.clone();
-            tokio::spawn(
-                async move {
-                    cleanup_lobbies(lobbies).await;
-                }
-                .instrument(info_span!(target: "maintenance", "lobby_cleanup")),
-            );
+    pub fn with_datasets(mut self, datasets: HashMap<String, Dataset>) -> Self {
+        self.datasets = Arc::new(datasets);
+        self
+    }
+
+    pub fn with_read_only(mut self, read_only: bool) -> Self {
+        self.read_only = read_only;
+        self
+    }
+
+    pub fn with_event_log(mut self, record_events: bool) -> Self {
+        self.record_events = record_events;
+        self
+    }
+
+    pub fn with_log_filter(mut self, log_filter: LogFilterHandle) -> Self {
+        self.log_filter = Some(log_filter);
+        self
+    }
+
+    pub fn with_banned_words(mut self, banned_words: Vec<String>) -> Self {
+        self.banned_words = Arc::from(banned_words);
+        self
+    }
+
+    /// Rejects names with one of the operator's banned words; the built-in
+    /// ones are checked with the rest of the name rules.
+    fn check_banned_words(&self, name: &str) -> Result<(), ApiError> {
+        if names::contains_banned_word(name, &self.banned_words) {
+            return Err(NameValidationError::Inappropriate.into());
         }
+        Ok(())
+    }
 
-        state
+    pub fn with_accounts(mut self, accounts: AccountStore) -> Self {
+        self.accounts = Some(Arc::new(accounts));
+        self
+    }
+
+    fn account_store(&self) -> Result<&AccountStore, ApiError> {
+        self.accounts
+            .as_deref()
+            .ok_or_else(|| ApiError::NotFound("Accounts are not enabled".into()))
+    }
+
+    /// Players across all lobbies, admins excluded.
+    pub fn total_players(&self) -> usize {
+        self.lobbies
+            .iter()
+            .map(|entry| entry.value().player_count())
+            .sum()
     }
 
-    fn generate_join_code(&self) -> Result<String, ApiError> {
+    /// Handle of the lobby with `join_code`. Never hold a map guard across
+    /// an await, clone the handle instead.
+    pub fn lobby(&self, join_code: &str) -> Option<LobbyHandle> {
+        self.lobbies
+            .get(join_code)
+            .map(|entry| entry.value().clone())
+    }
+
+    /// Handles of all open lobbies, by join code.
+    fn lobby_handles(&self) -> Vec<(String, LobbyHandle)> {
+        self.lobbies
+            .iter()
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
+            .collect()
+    }
+
+    fn spawn_lobby(&self, join_code: &str, engine: GameEngine) -> LobbyHandle {
+        LobbyHandle::spawn(join_code.to_string(), engine, self.lock_metrics.clone())
+    }
+
+    fn ensure_writable(&self) -> Result<(), ApiError> {
+        if self.read_only {
+            return Err(ApiError::Forbidden("Question data is read-only".into()));
+        }
+        Ok(())
+    }
+
+    /// Returns `requested` if no lobby uses it yet, otherwise a random numeric
+    /// code. `requested` must already be normalized.
+    fn generate_join_code(&self, requested: Option<&str>) -> Result<String, ApiError> {
+        if let Some(code) = requested {
+            if !self.lobbies.contains_key(code) {
+                return Ok(code.to_string());
+            }
+            debug!(requested = %code, "Requested join code taken, generating one");
+        }
+
         // First try 6-digit codes (atomic check via entry API)
         for _ in 0..10_000 {
             let code = format!("{:06}", fastrand::u32(0..1_000_000));
@@ -258,10 +586,21 @@ pub struct SetInfo {
 pub struct ListSetsResponse {
     pub num_questions: usize,
     pub sets: Vec<SetInfo>,
+    /// Named datasets lobbies can be created with, besides the default one.
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub datasets: Vec<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ListSetsQuery {
+    pub dataset: Option<String>,
 }
 
-pub async fn list_sets(state: &AppState) -> Result<ListSetsResponse, ApiError> {
-    let snap = state.store.snapshot();
+pub async fn list_sets(
+    state: &AppState,
+    dataset: Option<&str>,
+) -> Result<ListSetsResponse, ApiError> {
+    let snap = state.dataset_store(dataset)?.snapshot();
     let num_questions = snap.questions.len();
     let sets = &*snap.sets;
 
@@ -274,16 +613,64 @@ pub async fn list_sets(state: &AppState) -> Result<ListSetsResponse, ApiError> {
         })
         .collect();
 
+    let mut datasets: Vec<String> = state.datasets.keys().cloned().collect();
+    datasets.sort();
+
     Ok(ListSetsResponse {
         num_questions,
         sets: sets_info,
+        datasets,
     })
 }
 
-#[derive(Debug, Deserialize)]
+#[derive(Debug, Default, Deserialize)]
 pub struct CreateLobbyRequest {
     pub round_duration: Option<u64>,
     pub set_id: Option<i64>,
+    /// List the lobby on `GET /api/lobbies`.
+    #[serde(default)]
+    pub public: bool,
+    /// Name shown in the public lobby list. Defaults to "Lobby <join code>".
+    pub lobby_name: Option<String>,
+    /// Vanity join code such as "PARTY24". A numeric code is generated instead
+    /// if it is already in use.
+    pub join_code: Option<String>,
+    /// Named dataset to draw questions from instead of the default one.
+    pub dataset: Option<String>,
+    /// Upcoming questions shown to the admin, up to 50. Defaults to 3.
+    pub upcoming_preview: Option<usize>,
+    /// Show each player the alternatives in their own order.
+    #[serde(default)]
+    pub shuffle_per_player: bool,
+    /// Let anyone rejoin as a disconnected player by typing their name,
+    /// without asking the admin first.
+    #[serde(default)]
+    pub open_reclaims: bool,
+}
+
+const MAX_LOBBY_NAME_CHARS: usize = 32;
+const CUSTOM_JOIN_CODE_LEN: std::ops::RangeInclusive<usize> = 4..=12;
+
+/// Join codes are case-insensitive; they are stored and matched in uppercase.
+fn normalize_join_code(code: &str) -> String {
+    code.trim().to_ascii_uppercase()
+}
+
+fn validate_custom_join_code(code: &str) -> Result<String, ApiError> {
+    let code = normalize_join_code(code);
+    if !CUSTOM_JOIN_CODE_LEN.contains(&code.len()) {
+        return Err(ApiError::Validation(format!(
+            "Join code must be between {} and {} characters",
+            CUSTOM_JOIN_CODE_LEN.start(),
+            CUSTOM_JOIN_CODE_LEN.end()
+        )));
+    }
+    if !code.chars().all(|c| c.is_ascii_alphanumeric()) {
+        return Err(ApiError::Validation(
+            "Join code may only contain letters and digits".into(),
+        ));
+    }
+    Ok(code)
 }
 
 #[derive(Debug, serde::Serialize, PartialEq)]
@@ -296,6 +683,7 @@ pub struct CreateLobbyResponse {
 pub async fn create_lobby(
     state: &AppState,
     req: CreateLobbyRequest,
+    client_ip: IpAddr,
 ) -> Result<CreateLobbyResponse, ApiError> {
     let round_duration = req.round_duration.unwrap_or(60);
     if round_duration < 10 {
@@ -304,7 +692,39 @@ pub async fn create_lobby(
         ));
     }
 
-    let snap = state.store.snapshot();
+    let lobby_name = match req.lobby_name.as_deref().map(str::trim) {
+        Some("") | None => None,
+        Some(name) if name.chars().count() > MAX_LOBBY_NAME_CHARS => {
+            return Err(ApiError::Validation(format!(
+                "Lobby name must be at most {} characters",
+                MAX_LOBBY_NAME_CHARS
+            )));
+        }
+        Some(name) if name.chars().any(char::is_control) => {
+            return Err(ApiError::Validation(
+                "Lobby name contains invalid characters".into(),
+            ));
+        }
+        Some(name) => Some(name.to_string()),
+    };
+
+    if req
+        .upcoming_preview
+        .is_some_and(|count| count > MAX_UPCOMING_PREVIEW)
+    {
+        return Err(ApiError::Validation(format!(
+            "Upcoming question preview must be at most {MAX_UPCOMING_PREVIEW}"
+        )));
+    }
+
+    let requested_code = req
+        .join_code
+        .as_deref()
+        .map(validate_custom_join_code)
+        .transpose()?;
+
+    let dataset = req.dataset.as_deref();
+    let snap = state.dataset_store(dataset)?.snapshot();
     let questions = snap.questions.clone();
     let sets = &*snap.sets;
     let selected_set = if let Some(set_id) = req.set_id {
@@ -317,10 +737,31 @@ pub async fn create_lobby(
         None
     };
 
+    if state.lobbies.len() >= state.limits.max_lobbies {
+        warn!(lobbies = state.lobbies.len(), "Server lobby limit reached");
+        return Err(ApiError::ServerFull(
+            "The server has too many open lobbies, please try again later.".into(),
+        ));
+    }
+
+    if !state
+        .lobby_creations
+        .try_reserve(client_ip, state.limits.max_lobbies_per_ip)
+    {
+        warn!(%client_ip, "Per-IP lobby limit reached");
+        return Err(ApiError::TooManyLobbies);
+    }
+
     let admin_id = Uuid::new_v4();
-    let join_code = state.generate_join_code()?;
+    let join_code = match state.generate_join_code(requested_code.as_deref()) {
+        Ok(code) => code,
+        Err(e) => {
+            state.lobby_creations.unreserve(client_ip);
+            return Err(e);
+        }
+    };
 
-    let engine = GameEngine::new(
+    let mut engine = GameEngine::new(
         admin_id,
         Arc::from(join_code.as_str()),
         questions,
@@ -328,25 +769,46 @@ pub async fn create_lobby(
         selected_set,
         round_duration,
     );
+    if req.public {
+        let name = lobby_name.unwrap_or_else(|| format!("Lobby {}", join_code));
+        engine.set_public_name(Arc::from(name));
+    }
+    if let Some(dataset) = dataset {
+        engine.set_dataset(Arc::from(dataset));
+    }
+    if let Some(count) = req.upcoming_preview {
+        engine.set_upcoming_preview(count);
+    }
+    engine.set_shuffle_per_player(req.shuffle_per_player);
+    engine.set_open_reclaims(req.open_reclaims);
+    if state.record_events {
+        engine.enable_event_log();
+    }
     trace!("Creating new lobby {}", join_code);
 
     match state.lobbies.entry(join_code.clone()) {
         dashmap::mapref::entry::Entry::Vacant(entry) => {
-            entry.insert(engine);
+            entry.insert(state.spawn_lobby(&join_code, engine));
         }
         dashmap::mapref::entry::Entry::Occupied(_) => {
+            state.lobby_creations.unreserve(client_ip);
             return Err(ApiError::Lobby("Join code collision, please retry".into()));
         }
     }
+    state.lobby_creations.record(&join_code, client_ip);
+    state.webhooks.send(WebhookEvent::LobbyCreated {
+        join_code: join_code.clone(),
+    });
     let session_token = format!("{}:{}", join_code, admin_id);
 
     info!(
-        "Lobby created with join code: {} (round_duration: {}s, set: {})",
+        "Lobby created with join code: {} (round_duration: {}s, set: {}, dataset: {})",
         join_code,
         round_duration,
         selected_set
             .map(|s| s.name.as_ref())
-            .unwrap_or("all questions")
+            .unwrap_or("all questions"),
+        dataset.unwrap_or("default")
     );
 
     Ok(CreateLobbyResponse {
@@ -356,247 +818,1236 @@ pub async fn create_lobby(
     })
 }
 
-#[derive(Debug, Deserialize)]
-pub struct JoinLobbyRequest {
+#[derive(Debug, Serialize, PartialEq)]
+pub struct PublicLobbyInfo {
     pub join_code: String,
-    pub name: String,
+    pub name: Arc<str>,
+    pub player_count: usize,
+    pub phase: GamePhase,
 }
 
 #[derive(Debug, Serialize, PartialEq)]
-pub struct JoinLobbyResponse {
-    pub player_id: Uuid,
-    pub join_code: String,
-    pub session_token: String,
+pub struct ListLobbiesResponse {
+    pub lobbies: Vec<PublicLobbyInfo>,
 }
 
-pub async fn join_lobby(
-    state: &AppState,
-    req: JoinLobbyRequest,
-) -> Result<JoinLobbyResponse, ApiError> {
-    let join_code = req.join_code.trim();
+pub async fn list_public_lobbies(state: &AppState) -> Result<ListLobbiesResponse, ApiError> {
+    // Asks every lobby at once, so a busy one doesn't hold up the rest.
+    let listings = state
+        .lobby_handles()
+        .into_iter()
+        .map(|(join_code, lobby)| async move {
+            let (name, player_count, phase) =
+                lobby.call(|engine| engine.public_listing()).await??;
+            Some(PublicLobbyInfo {
+                join_code,
+                name,
+                player_count,
+                phase,
+            })
+        });
+    let mut lobbies: Vec<_> = join_all(listings).await.into_iter().flatten().collect();
+    lobbies.sort_by(|a, b| a.name.cmp(&b.name));
+
+    Ok(ListLobbiesResponse { lobbies })
+}
 
-    debug!(target: "lock", lobby_key = %join_code, "acquiring_lobby_lock");
-    let t0 = Instant::now();
+#[derive(Debug, Serialize, PartialEq)]
+pub struct AdminLobbyInfo {
+    pub join_code: String,
+    pub player_count: usize,
+    pub questions_played: usize,
+    pub phase: GamePhase,
+    pub uptime_secs: u64,
+    /// Seconds since the last lobby message, if any.
+    pub idle_secs: Option<u64>,
+}
 
-    let mut engine = match state.lobbies.get_mut(join_code) {
-        Some(engine) => {
-            let dt = t0.elapsed();
-            debug!(
-                target: "lock",
-                lobby_key = %join_code,
-                duration_us = dt.as_micros() as u64,
-                "lobby_lock_acquired"
-            );
-            engine
-        }
-        None => {
-            debug!(target: "lock", lobby_key = %join_code, "lobby_not_found");
-            return Err(ApiError::Lobby("Invalid join code.".into()));
-        }
-    };
+#[derive(Debug, Serialize, PartialEq)]
+pub struct AdminLobbiesResponse {
+    pub lobbies: Vec<AdminLobbyInfo>,
+}
 
-    if engine.is_full() {
-        return Err(ApiError::Lobby("Lobby is full.".into()));
+pub async fn list_admin_lobbies(state: &AppState) -> Result<AdminLobbiesResponse, ApiError> {
+    let now = Instant::now();
+    let mut lobbies = Vec::new();
+    for (join_code, lobby) in state.lobby_handles() {
+        let info = lobby
+            .call(move |engine| {
+                let (player_count, questions_played) = engine.get_lobby_stats();
+                AdminLobbyInfo {
+                    join_code,
+                    player_count,
+                    questions_played,
+                    phase: engine.get_phase(),
+                    uptime_secs: now
+                        .saturating_duration_since(engine.get_created_at())
+                        .as_secs(),
+                    idle_secs: engine
+                        .last_update()
+                        .map(|last| now.saturating_duration_since(last).as_secs()),
+                }
+            })
+            .await;
+        lobbies.extend(info);
     }
+    lobbies.sort_by(|a, b| a.join_code.cmp(&b.join_code));
 
-    if engine.is_locked() {
-        return Err(ApiError::Lobby("Lobby is locked.".into()));
-    }
+    Ok(AdminLobbiesResponse { lobbies })
+}
 
-    let new_player_id = Uuid::new_v4();
-    engine.add_player(new_player_id, req.name)?;
-    Ok(JoinLobbyResponse {
-        player_id: new_player_id,
-        join_code: join_code.to_string(),
-        session_token: format!("{}:{}", join_code, new_player_id),
-    })
+/// Detailed statistics for one lobby. `token` must be an operator JWT or the
+/// lobby admin's own session token.
+pub async fn lobby_stats(
+    state: &AppState,
+    join_code: &str,
+    token: Option<&str>,
+) -> Result<LobbyStats, ApiError> {
+    let join_code = normalize_join_code(join_code);
+    let lobby = authorize_lobby_admin(state, &join_code, token).await?;
+
+    lobby
+        .call(|engine| engine.get_detailed_stats())
+        .await
+        .ok_or_else(|| ApiError::Lobby("Invalid join code.".into()))
 }
 
-#[derive(Debug, Deserialize)]
-pub struct GetStoredDataRequest {
-    password: String,
+/// The player id in a session token for the lobby `join_code`.
+fn session_player_id(token: &str, join_code: &str) -> Option<Uuid> {
+    token
+        .split_once(':')
+        .filter(|(code, _)| *code == join_code)
+        .and_then(|(_, id)| id.parse().ok())
 }
 
-pub async fn get_stored_data(
+/// The lobby's handle, if `token` may administer it.
+async fn authorize_lobby_admin(
     state: &AppState,
-    req: GetStoredDataRequest,
-) -> Result<StoredData, ApiError> {
-    if !state.verify_admin_password(&req.password) {
+    join_code: &str,
+    token: Option<&str>,
+) -> Result<LobbyHandle, ApiError> {
+    let lobby = state
+        .lobby(join_code)
+        .ok_or_else(|| ApiError::Lobby("Invalid join code.".into()))?;
+    let token = token.ok_or(ApiError::Unauthorized)?;
+    // Dataset admins only manage questions, not other people's lobbies.
+    if state.jwt.verify(token) == Ok(None) {
+        return Ok(lobby);
+    }
+    let Some(id) = session_player_id(token, join_code) else {
+        return Err(ApiError::Unauthorized);
+    };
+    if lobby.call(move |engine| engine.is_admin(&id)).await != Some(true) {
         return Err(ApiError::Unauthorized);
     }
-    let stored_data = state.store.get_stored_data().await?;
-    Ok(stored_data)
+    Ok(lobby)
 }
 
-#[derive(Debug, Deserialize)]
-pub struct SetStoredDataRequest {
-    password: String,
-    stored_data: StoredData,
-}
+/// How long a removed lobby's results stay available.
+const RESULTS_GRACE: Duration = Duration::from_secs(60 * 60);
 
-pub async fn set_stored_data(
-    state: &AppState,
-    req: SetStoredDataRequest,
-) -> Result<StoredData, ApiError> {
-    if !state.verify_admin_password(&req.password) {
-        return Err(ApiError::Unauthorized);
-    }
-    state.store.backup_stored_data().await?;
-    state.store.set_stored_data(req.stored_data.clone()).await?;
-    state.store.reload().await?;
-    Ok(req.stored_data)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResultsFormat {
+    #[default]
+    Json,
+    Csv,
 }
 
-#[derive(Debug, Serialize, PartialEq)]
-pub struct UploadCharacterImageResponse {
-    image_url: String,
+#[derive(Debug, Default, Deserialize)]
+pub struct LobbyResultsQuery {
+    #[serde(default)]
+    pub format: ResultsFormat,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-pub struct SessionInfo {
-    pub player_id: Uuid,
+/// Final scores and per-round results of the lobby's last game, once it is
+/// over and for [`RESULTS_GRACE`] after the lobby is removed. `token` must be
+/// an operator JWT or the lobby admin's own session token.
+pub async fn lobby_results(
+    state: &AppState,
+    join_code: &str,
+    token: Option<&str>,
+) -> Result<GameRecord, ApiError> {
+    let join_code = normalize_join_code(join_code);
+    if state.lobby(&join_code).is_some() {
+        let lobby = authorize_lobby_admin(state, &join_code, token).await?;
+        return lobby
+            .call(|engine| engine.final_results().cloned())
+            .await
+            .ok_or_else(|| ApiError::Lobby("Invalid join code.".into()))?
+            .ok_or_else(|| {
+                ApiError::NotFound("Results are available once the game is over".into())
+            });
+    }
+    let closed = state
+        .closed_results
+        .get(&join_code)
+        .filter(|closed| closed.removed_at.elapsed() < RESULTS_GRACE)
+        .ok_or_else(|| ApiError::Lobby("Invalid join code.".into()))?;
+    let token = token.ok_or(ApiError::Unauthorized)?;
+    let authorized = state.jwt.verify(token) == Ok(None)
+        || session_player_id(token, &join_code) == Some(closed.admin_id);
+    if !authorized {
+        return Err(ApiError::Unauthorized);
+    }
+    Ok(closed.record.clone())
 }
 
-#[derive(Debug, Deserialize)]
-pub struct CheckSessionsRequest {
-    pub sessions: Vec<SessionInfo>,
+/// The results as CSV: one row per player with the final score and the
+/// score of each round, highest score first.
+pub fn results_csv(record: &GameRecord) -> Result<String, ApiError> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    let mut header = vec!["rank".to_string(), "name".into(), "score".into()];
+    header.extend(
+        record
+            .rounds
+            .iter()
+            .enumerate()
+            .map(|(i, round)| format!("{}. {}", i + 1, round.title)),
+    );
+    let csv_error = |e: csv::Error| ApiError::Database(e.to_string());
+    writer.write_record(&header).map_err(csv_error)?;
+    for (rank, player) in record.final_scores.iter().enumerate() {
+        let mut row = vec![
+            (rank + 1).to_string(),
+            player.name.to_string(),
+            player.score.to_string(),
+        ];
+        row.extend(record.rounds.iter().map(|round| {
+            round
+                .results
+                .iter()
+                .find(|result| result.name == player.name)
+                .map(|result| result.round_score.to_string())
+                .unwrap_or_default()
+        }));
+        writer.write_record(&row).map_err(csv_error)?;
+    }
+    let bytes = writer
+        .into_inner()
+        .map_err(|e| ApiError::Database(e.to_string()))?;
+    String::from_utf8(bytes).map_err(|e| ApiError::Database(e.to_string()))
 }
 
-#[derive(Debug, Clone, Serialize, PartialEq)]
-pub struct ValidSessionInfo {
-    pub player_id: Uuid,
-    pub last_update: String,
+#[derive(Debug, Serialize, PartialEq)]
+pub struct PreloadMedia {
+    question_id: i64,
+    media_id: i64,
+    /// Left out for questions without a video, such as injected ones.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    youtube_id: Option<Arc<str>>,
+    image_urls: Vec<String>,
 }
 
 #[derive(Debug, Serialize, PartialEq)]
-pub struct CheckSessionsResponse {
-    pub valid_sessions: Vec<ValidSessionInfo>,
+pub struct PreloadResponse {
+    upcoming: Vec<PreloadMedia>,
 }
 
-pub async fn check_sessions(
+/// Media of the lobby's next questions, so the host client can buffer it
+/// before the round starts. Lists as many questions as the host's upcoming
+/// preview. Same access rules as [`lobby_stats`].
+pub async fn lobby_preload(
     state: &AppState,
-    req: CheckSessionsRequest,
-) -> Result<CheckSessionsResponse, ApiError> {
-    let mono_now = Instant::now();
-    let sys_now = SystemTime::now();
+    join_code: &str,
+    token: Option<&str>,
+) -> Result<PreloadResponse, ApiError> {
+    let join_code = normalize_join_code(join_code);
+    let lobby = authorize_lobby_admin(state, &join_code, token).await?;
+    let (dataset, questions) = lobby
+        .call(|engine| {
+            (
+                engine.dataset().map(str::to_string),
+                engine.get_upcoming_questions(engine.upcoming_preview()),
+            )
+        })
+        .await
+        .ok_or_else(|| ApiError::Lobby("Invalid join code.".into()))?;
+    let store = state.dataset_store(dataset.as_deref())?;
 
-    let valid_sessions: Vec<ValidSessionInfo> = req
-        .sessions
+    let upcoming = questions
         .into_iter()
-        .filter_map(|session| {
-            let lobby = state
-                .lobbies
-                .iter()
-                .find(|entry| entry.value().has_player(&session.player_id))?;
-
-            if lobby.value().is_finished() {
-                return None;
+        .map(|question| {
+            let image_urls = match question.question_type {
+                QuestionType::Character => question
+                    .options
+                    .iter()
+                    .filter_map(|opt| db::character_image_url(&opt.option).ok())
+                    .map(|url| store.media_url(&url))
+                    .collect(),
+                _ => Vec::new(),
+            };
+            PreloadMedia {
+                question_id: question.id,
+                media_id: question.media_id,
+                youtube_id: Some(question.youtube_id).filter(|id| !id.is_empty()),
+                image_urls,
             }
-            let last_update = lobby.value().last_update()?;
-            let duration = mono_now.duration_since(last_update);
-            let system_time = sys_now.checked_sub(duration)?;
-            let last_update_iso =
-                DateTime::<Utc>::from(system_time).to_rfc3339_opts(SecondsFormat::Millis, true);
-
-            Some(ValidSessionInfo {
-                player_id: session.player_id,
-                last_update: last_update_iso,
-            })
         })
         .collect();
-
-    Ok(CheckSessionsResponse { valid_sessions })
+    Ok(PreloadResponse { upcoming })
 }
 
-pub async fn list_sets_handler(
-    State(state): State<AppState>,
-) -> Result<impl axum::response::IntoResponse, ApiError> {
-    let response = list_sets(&state).await?;
-    Ok(no_store_json(response))
+#[derive(Debug, Default, Deserialize)]
+pub struct CloseLobbyRequest {
+    pub reason: Option<String>,
 }
 
-pub async fn create_lobby_handler(
-    State(state): State<AppState>,
-    Json(req): Json<CreateLobbyRequest>,
-) -> Result<impl axum::response::IntoResponse, ApiError> {
-    let response = create_lobby(&state, req).await?;
-    Ok(no_store_json(response))
+#[derive(Debug, Serialize, PartialEq)]
+pub struct CloseLobbyResponse {
+    pub join_code: String,
 }
 
-pub async fn join_lobby_handler(
-    State(state): State<AppState>,
-    Json(req): Json<JoinLobbyRequest>,
-) -> Result<impl axum::response::IntoResponse, ApiError> {
-    let response = join_lobby(&state, req).await?;
-    Ok(no_store_json(response))
+pub async fn close_lobby(
+    state: &AppState,
+    join_code: &str,
+    req: CloseLobbyRequest,
+) -> Result<CloseLobbyResponse, ApiError> {
+    let join_code = normalize_join_code(join_code);
+    let reason = req
+        .reason
+        .as_deref()
+        .map(str::trim)
+        .filter(|reason| !reason.is_empty())
+        .unwrap_or("Lobby closed by server operator");
+
+    let lobby = state
+        .lobby(&join_code)
+        .ok_or_else(|| ApiError::Lobby("Invalid join code.".into()))?;
+    let state = state.clone();
+    let code = join_code.clone();
+    let reason_arc: Arc<str> = Arc::from(reason);
+    lobby
+        .call(move |engine| {
+            let before = engine.get_phase();
+            if before == GamePhase::GameClosed {
+                return Err(ApiError::Lobby("Lobby is already closed.".into()));
+            }
+            engine.close_by_operator(reason_arc);
+            notify_phase_change(&state.webhooks, &code, before, engine);
+            save_finished_game(&state, engine);
+            Ok(())
+        })
+        .await
+        .ok_or_else(|| ApiError::Lobby("Invalid join code.".into()))??;
+    info!(join_code = %join_code, reason, "Lobby force-closed by operator");
+
+    Ok(CloseLobbyResponse { join_code })
 }
 
-pub async fn get_stored_data_handler(
-    State(state): State<AppState>,
-    Json(req): Json<GetStoredDataRequest>,
-) -> Result<impl axum::response::IntoResponse, ApiError> {
-    let response = get_stored_data(&state, req).await?;
-    Ok(no_store_json(response))
+#[derive(Debug, Default, Deserialize)]
+pub struct JoinLobbyRequest {
+    pub join_code: String,
+    /// Ignored when joining with `account_token`, which gives the name.
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub account_token: Option<String>,
+    /// Language for the errors, e.g. `sv-SE`.
+    #[serde(default)]
+    pub locale: Option<Locale>,
 }
 
-pub async fn set_stored_data_handler(
-    State(state): State<AppState>,
-    Json(req): Json<SetStoredDataRequest>,
-) -> Result<impl axum::response::IntoResponse, ApiError> {
-    let response = set_stored_data(&state, req).await?;
-    Ok(no_store_json(response))
+#[derive(Debug, Serialize, PartialEq)]
+pub struct JoinLobbyResponse {
+    pub player_id: Uuid,
+    pub join_code: String,
+    pub session_token: String,
+}
+
+pub async fn join_lobby(
+    state: &AppState,
+    req: JoinLobbyRequest,
+) -> Result<JoinLobbyResponse, ApiError> {
+    let locale = req.locale.unwrap_or_default();
+    add_to_lobby(state, req, locale, false)
+        .await
+        .map_err(|e| e.localized(locale))
+}
+
+/// Adds the player to the lobby. `by_host` players may join a locked lobby,
+/// as the host added them.
+async fn add_to_lobby(
+    state: &AppState,
+    req: JoinLobbyRequest,
+    locale: Locale,
+    by_host: bool,
+) -> Result<JoinLobbyResponse, ApiError> {
+    let join_code = normalize_join_code(&req.join_code);
+
+    let total_players = state.total_players();
+    if total_players >= state.limits.max_total_players {
+        warn!(total_players, "Server player limit reached");
+        return Err(ApiError::ServerFull(
+            "The server has too many players, please try again later.".into(),
+        ));
+    }
+
+    let Some(lobby) = state.lobby(&join_code) else {
+        debug!(target: "lock", lobby_key = %join_code, "lobby_not_found");
+        return Err(ApiError::Lobby("Invalid join code.".into()));
+    };
+
+    let (name, account_id) = match (req.account_token, &state.accounts) {
+        (Some(token), Some(accounts)) => {
+            let (account_id, name) = accounts
+                .verify_token(&token)
+                .ok_or(ApiError::Unauthorized)?;
+            (name.to_string(), Some(account_id))
+        }
+        (Some(_), None) => return Err(ApiError::NotFound("Accounts are not enabled".into())),
+        (None, Some(accounts)) if accounts.is_reserved(&req.name) => {
+            return Err(ApiError::Validation(
+                "This name belongs to an account. Log in to use it.".into(),
+            ));
+        }
+        (None, _) => (req.name, None),
+    };
+    state.check_banned_words(&name)?;
+
+    let player_id = lobby
+        .call(move |engine| {
+            // An account already in the lobby gets its player back, e.g. on a
+            // new device, with its score.
+            if let Some(player_id) = account_id.and_then(|id| engine.player_with_account(&id)) {
+                return Ok(player_id);
+            }
+            // So does someone who lost their session, by joining under the
+            // name of a player who is no longer connected.
+            if account_id.is_none() {
+                match engine.reclaim_player(&name) {
+                    Some(Reclaim::Player(player_id)) => {
+                        engine.set_locale(player_id, locale);
+                        return Ok(player_id);
+                    }
+                    Some(Reclaim::AwaitingApproval) => {
+                        return Err(ApiError::Lobby(
+                            "This name is taken. The host has been asked to let you rejoin.".into(),
+                        ));
+                    }
+                    None => {}
+                }
+            }
+            if engine.is_full() {
+                return Err(ApiError::Lobby("Lobby is full.".into()));
+            }
+            if engine.is_locked() && !by_host {
+                return Err(ApiError::Lobby("Lobby is locked.".into()));
+            }
+            let player_id = Uuid::new_v4();
+            engine.add_player_with_account(player_id, name, account_id)?;
+            engine.set_locale(player_id, locale);
+            Ok(player_id)
+        })
+        .await
+        .ok_or_else(|| ApiError::Lobby("Invalid join code.".into()))??;
+    lobby.touch();
+    Ok(JoinLobbyResponse {
+        session_token: format!("{}:{}", join_code, player_id),
+        player_id,
+        join_code: join_code.clone(),
+    })
+}
+
+const MAX_BATCH_JOIN_NAMES: usize = 100;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct BatchJoinLobbyRequest {
+    pub join_code: String,
+    pub names: Vec<String>,
+    /// Language for the errors, e.g. `sv-SE`.
+    #[serde(default)]
+    pub locale: Option<Locale>,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct BatchJoinedPlayer {
+    pub name: String,
+    pub player_id: Uuid,
+    pub session_token: String,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct BatchJoinRejection {
+    pub name: String,
+    pub error: String,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct BatchJoinLobbyResponse {
+    pub join_code: String,
+    /// In the order of the request.
+    pub players: Vec<BatchJoinedPlayer>,
+    pub rejected: Vec<BatchJoinRejection>,
+}
+
+/// Registers players under `names` ahead of time, e.g. for a classroom where
+/// the host sets up each device with a session token instead of typing a
+/// name on it. `token` must be an operator JWT or the lobby admin's session
+/// token. Each name joins as through [`join_lobby`], except that a locked
+/// lobby still takes them; names that can't join are listed in `rejected`.
+pub async fn batch_join_lobby(
+    state: &AppState,
+    req: BatchJoinLobbyRequest,
+    token: Option<&str>,
+) -> Result<BatchJoinLobbyResponse, ApiError> {
+    let join_code = normalize_join_code(&req.join_code);
+    authorize_lobby_admin(state, &join_code, token).await?;
+    if req.names.is_empty() {
+        return Err(ApiError::Validation("No names given".into()));
+    }
+    if req.names.len() > MAX_BATCH_JOIN_NAMES {
+        return Err(ApiError::Validation(format!(
+            "At most {MAX_BATCH_JOIN_NAMES} names can be registered at once"
+        )));
+    }
+
+    let locale = req.locale.unwrap_or_default();
+    let mut players = Vec::new();
+    let mut rejected = Vec::new();
+    for name in req.names {
+        let join_req = JoinLobbyRequest {
+            join_code: join_code.clone(),
+            name: name.clone(),
+            locale: Some(locale),
+            ..Default::default()
+        };
+        match add_to_lobby(state, join_req, locale, true).await {
+            Ok(joined) => players.push(BatchJoinedPlayer {
+                name,
+                player_id: joined.player_id,
+                session_token: joined.session_token,
+            }),
+            Err(e) => rejected.push(BatchJoinRejection {
+                name,
+                error: e.localized(locale).to_string(),
+            }),
+        }
+    }
+    info!(
+        join_code = %join_code,
+        registered = players.len(),
+        rejected = rejected.len(),
+        "Players registered in batch"
+    );
+
+    Ok(BatchJoinLobbyResponse {
+        join_code,
+        players,
+        rejected,
+    })
+}
+
+pub async fn create_account(
+    state: &AppState,
+    req: AccountRequest,
+) -> Result<AccountResponse, ApiError> {
+    state.check_banned_words(&req.name)?;
+    state.account_store()?.create(req).await
+}
+
+pub async fn account_login(
+    state: &AppState,
+    req: AccountRequest,
+) -> Result<AccountResponse, ApiError> {
+    state.account_store()?.login(req).await
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QrCodeFormat {
+    #[default]
+    Svg,
+    Png,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct QrCodeQuery {
+    #[serde(default)]
+    pub format: QrCodeFormat,
+}
+
+/// QR code that opens the frontend join screen for `join_code`, with its
+/// content type.
+pub async fn lobby_qr_code(
+    state: &AppState,
+    join_code: &str,
+    format: QrCodeFormat,
+) -> Result<(&'static str, Vec<u8>), ApiError> {
+    let base_url = state
+        .frontend_base_url
+        .as_deref()
+        .ok_or_else(|| ApiError::NotFound("QR codes are not configured".into()))?;
+    let join_code = normalize_join_code(join_code);
+    if !state.lobbies.contains_key(&join_code) {
+        return Err(ApiError::Lobby("Invalid join code.".into()));
+    }
+
+    let url = qr::join_url(base_url, &join_code);
+    let rendered = match format {
+        QrCodeFormat::Svg => qr::render_svg(&url).map(|svg| ("image/svg+xml", svg.into_bytes())),
+        QrCodeFormat::Png => qr::render_png(&url).map(|png| ("image/png", png)),
+    };
+    rendered.map_err(|e| ApiError::Database(format!("Failed to render QR code: {e}")))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdminLoginRequest {
+    password: String,
+}
+
+pub async fn admin_login(
+    state: &AppState,
+    req: AdminLoginRequest,
+) -> Result<IssuedToken, ApiError> {
+    match state.admin_scope(&req.password)? {
+        None => Ok(state.jwt.issue()),
+        Some(dataset) => Ok(state.jwt.issue_for_dataset(&dataset)),
+    }
+}
+
+pub async fn get_stored_data(
+    state: &AppState,
+    dataset: Option<&str>,
+) -> Result<StoredData, ApiError> {
+    let stored_data = state.dataset_store(dataset)?.get_stored_data().await?;
+    Ok(stored_data)
+}
+
+/// The finished games kept in the game history.
+#[derive(Debug, Serialize, PartialEq)]
+pub struct GameHistoryResponse {
+    /// Ids of the recorded games, newest first.
+    pub games: Vec<String>,
+}
+
+pub async fn list_game_history(
+    state: &AppState,
+    dataset: Option<&str>,
+) -> Result<GameHistoryResponse, ApiError> {
+    Ok(GameHistoryResponse {
+        games: state.dataset_store(dataset)?.list_game_records().await?,
+    })
+}
+
+pub async fn game_history(
+    state: &AppState,
+    dataset: Option<&str>,
+    id: &str,
+) -> Result<GameRecord, ApiError> {
+    state
+        .dataset_store(dataset)?
+        .load_game_record(id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Game {id} not found")))
+}
+
+/// The stored data as a gzipped JSON download, with a timestamped file name.
+pub async fn export_questions(
+    state: &AppState,
+    dataset: Option<&str>,
+) -> Result<(String, Vec<u8>), ApiError> {
+    let stored_data = state.dataset_store(dataset)?.get_stored_data().await?;
+    let json = serde_json::to_vec(&stored_data).map_err(|e| ApiError::Database(e.to_string()))?;
+    let compressed = tokio::task::spawn_blocking(move || db::gzip(&json))
+        .await
+        .map_err(|e| ApiError::Database(e.to_string()))?
+        .map_err(|e| ApiError::Database(e.to_string()))?;
+    let file_name = format!("questions_{}.json.gz", Utc::now().format("%y%m%d_%H%M%S"));
+    Ok((file_name, compressed))
+}
+
+#[derive(Debug, Serialize)]
+pub struct IntegrityResponse {
+    ok: bool,
+    #[serde(flatten)]
+    report: IntegrityReport,
+}
+
+pub async fn check_integrity(
+    state: &AppState,
+    dataset: Option<&str>,
+) -> Result<IntegrityResponse, ApiError> {
+    let report = state.dataset_store(dataset)?.check_integrity().await?;
+    Ok(IntegrityResponse {
+        ok: report.is_ok(),
+        report,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetStoredDataRequest {
+    stored_data: StoredData,
+}
+
+pub async fn set_stored_data(
+    state: &AppState,
+    dataset: Option<&str>,
+    req: SetStoredDataRequest,
+) -> Result<StoredData, ApiError> {
+    state.ensure_writable()?;
+    let store = state.dataset_store(dataset)?;
+    store.backup_stored_data().await?;
+    store.set_stored_data(req.stored_data.clone()).await?;
+    store.reload().await?;
+    Ok(req.stored_data)
+}
+
+/// Upper bound on a decompressed question import, so a small gzip file
+/// cannot expand without limit.
+const MAX_IMPORT_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Parses an exported question file, gzipped or plain JSON.
+fn decode_question_import(data: &[u8]) -> Result<StoredData, ApiError> {
+    let json = if data.starts_with(&[0x1f, 0x8b]) {
+        let mut json = Vec::new();
+        flate2::read::GzDecoder::new(data)
+            .take(MAX_IMPORT_BYTES + 1)
+            .read_to_end(&mut json)
+            .map_err(|e| ApiError::Validation(format!("Invalid gzip file: {e}")))?;
+        if json.len() as u64 > MAX_IMPORT_BYTES {
+            return Err(ApiError::PayloadTooLarge(format!(
+                "Decompressed file exceeds {MAX_IMPORT_BYTES} bytes"
+            )));
+        }
+        json
+    } else {
+        data.to_vec()
+    };
+    let stored_data: StoredData = serde_json::from_slice(&json)
+        .map_err(|e| ApiError::Validation(format!("Invalid question file: {e}")))?;
+    stored_data
+        .validate_stored_data()
+        .map_err(|e| ApiError::Validation(e.to_string()))?;
+    Ok(stored_data)
+}
+
+/// Replaces the stored data with an uploaded export, after backing up the
+/// current data.
+pub async fn import_questions(
+    state: &AppState,
+    dataset: Option<&str>,
+    data: &[u8],
+) -> Result<StoredData, ApiError> {
+    state.ensure_writable()?;
+    let stored_data = decode_question_import(data)?;
+    set_stored_data(state, dataset, SetStoredDataRequest { stored_data }).await
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct UploadCharacterImageResponse {
+    image_url: String,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct DeleteCharacterImageResponse {
+    image_url: String,
+}
+
+pub async fn delete_character_image(
+    state: &AppState,
+    dataset: Option<&str>,
+    character_name: &str,
+) -> Result<DeleteCharacterImageResponse, ApiError> {
+    state.ensure_writable()?;
+    let store = state.dataset_store(dataset)?;
+    let image_url =
+        db::character_image_url(character_name).map_err(|e| ApiError::Validation(e.to_string()))?;
+    let users = store
+        .get_stored_data()
+        .await?
+        .characters_using_image(&store.media_url(&image_url));
+    if !users.is_empty() {
+        return Err(ApiError::Validation(format!(
+            "Image {image_url} is still used by: {}",
+            users.join(", ")
+        )));
+    }
+    let image_url = store.delete_character_image(character_name).await?;
+    Ok(DeleteCharacterImageResponse { image_url })
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct UploadAudioClipResponse {
+    audio_url: String,
+}
+
+/// Stores a short ogg or mp3 clip for a media entry so games can play it
+/// instead of the YouTube embed.
+pub async fn upload_audio_clip(
+    state: &AppState,
+    dataset: Option<&str>,
+    media_id: i64,
+    data: &[u8],
+) -> Result<UploadAudioClipResponse, ApiError> {
+    state.ensure_writable()?;
+    let max_bytes = state.limits.max_audio_bytes;
+    if data.len() > max_bytes {
+        return Err(ApiError::PayloadTooLarge(format!(
+            "Audio clip exceeds {max_bytes} bytes"
+        )));
+    }
+    let format = AudioFormat::sniff(data).ok_or(ApiError::UnsupportedMediaType)?;
+    state
+        .dataset_store(dataset)?
+        .store_audio_clip(media_id, format, data)
+        .await
+        .map_err(|e| match e {
+            DbError::Validation(message) => ApiError::Validation(message),
+            e => ApiError::from(e),
+        })?;
+    let audio_url = match dataset {
+        Some(name) => format!("/api/audio/{media_id}?dataset={name}"),
+        None => format!("/api/audio/{media_id}"),
+    };
+    Ok(UploadAudioClipResponse { audio_url })
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AudioClipQuery {
+    pub dataset: Option<String>,
+}
+
+pub async fn audio_clip(
+    state: &AppState,
+    dataset: Option<&str>,
+    media_id: i64,
+) -> Result<(AudioFormat, Vec<u8>), ApiError> {
+    state
+        .dataset_store(dataset)?
+        .read_audio_clip(media_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("No audio clip for media {media_id}")))
+}
+
+/// How long a presigned media upload URL stays valid.
+const MEDIA_UPLOAD_URL_TTL: Duration = Duration::from_secs(15 * 60);
+
+#[derive(Debug, Deserialize)]
+pub struct MediaUploadUrlRequest {
+    file_name: String,
+}
+
+/// Returns a presigned request for uploading a media file directly to the
+/// bucket, so large files don't have to pass through the server.
+pub async fn media_upload_url(
+    state: &AppState,
+    dataset: Option<&str>,
+    req: MediaUploadUrlRequest,
+) -> Result<PresignedUpload, ApiError> {
+    state.ensure_writable()?;
+    let path = db::media_path(&req.file_name).map_err(|e| ApiError::Validation(e.to_string()))?;
+    state
+        .dataset_store(dataset)?
+        .presign_upload(&path, MEDIA_UPLOAD_URL_TTL)
+        .await?
+        .ok_or_else(|| ApiError::BadRequest("Direct uploads require S3 storage".into()))
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionInfo {
+    pub player_id: Uuid,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CheckSessionsRequest {
+    pub sessions: Vec<SessionInfo>,
+}
+
+#[derive(Debug, Clone, Serialize, PartialEq)]
+pub struct ValidSessionInfo {
+    pub player_id: Uuid,
+    pub last_update: String,
+    /// Where the session belongs, so a client can offer to resume it.
+    pub join_code: String,
+    pub name: Arc<str>,
+    pub phase: GamePhase,
+    pub is_admin: bool,
+}
+
+/// What `check_sessions` learns about a player from their lobby.
+struct FoundSession {
+    last_update: Instant,
+    join_code: String,
+    name: Arc<str>,
+    phase: GamePhase,
+    is_admin: bool,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct CheckSessionsResponse {
+    pub valid_sessions: Vec<ValidSessionInfo>,
+}
+
+pub async fn check_sessions(
+    state: &AppState,
+    req: CheckSessionsRequest,
+) -> Result<CheckSessionsResponse, ApiError> {
+    let mono_now = Instant::now();
+    let sys_now = SystemTime::now();
+
+    let player_ids: Arc<Vec<Uuid>> = Arc::new(req.sessions.iter().map(|s| s.player_id).collect());
+    // Looks in every lobby at once, as in `list_public_lobbies`.
+    let lookups = state.lobby_handles().into_iter().map(|(join_code, lobby)| {
+        let player_ids = player_ids.clone();
+        async move {
+            lobby
+                .call(move |engine| {
+                    if engine.is_finished() {
+                        return Vec::new();
+                    }
+                    let Some(last_update) = engine.last_update() else {
+                        return Vec::new();
+                    };
+                    player_ids
+                        .iter()
+                        .filter_map(|id| {
+                            let name = engine.get_player_name(id)?;
+                            Some((
+                                *id,
+                                FoundSession {
+                                    last_update,
+                                    join_code: join_code.clone(),
+                                    name,
+                                    phase: engine.get_phase(),
+                                    is_admin: *id == engine.get_admin_id(),
+                                },
+                            ))
+                        })
+                        .collect()
+                })
+                .await
+                .unwrap_or_default()
+        }
+    });
+    let mut found_sessions: HashMap<_, _> = join_all(lookups).await.into_iter().flatten().collect();
+
+    let valid_sessions: Vec<ValidSessionInfo> = req
+        .sessions
+        .into_iter()
+        .filter_map(|session| {
+            let found = found_sessions.remove(&session.player_id)?;
+            let duration = mono_now.saturating_duration_since(found.last_update);
+            let system_time = sys_now.checked_sub(duration)?;
+            let last_update_iso =
+                DateTime::<Utc>::from(system_time).to_rfc3339_opts(SecondsFormat::Millis, true);
+
+            Some(ValidSessionInfo {
+                player_id: session.player_id,
+                last_update: last_update_iso,
+                join_code: found.join_code,
+                name: found.name,
+                phase: found.phase,
+                is_admin: found.is_admin,
+            })
+        })
+        .collect();
+
+    Ok(CheckSessionsResponse { valid_sessions })
+}
+
+pub async fn list_sets_handler(
+    State(state): State<AppState>,
+    Query(query): Query<ListSetsQuery>,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    let response = list_sets(&state, query.dataset.as_deref()).await?;
+    Ok(no_store_json(response))
+}
+
+pub async fn create_lobby_handler(
+    State(state): State<AppState>,
+    Extension(ClientIp(client_ip)): Extension<ClientIp>,
+    Json(req): Json<CreateLobbyRequest>,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    let response = create_lobby(&state, req, client_ip).await?;
+    Ok(no_store_json(response))
+}
+
+pub async fn list_public_lobbies_handler(
+    State(state): State<AppState>,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    let response = list_public_lobbies(&state).await?;
+    Ok(no_store_json(response))
+}
+
+pub async fn join_lobby_handler(
+    State(state): State<AppState>,
+    Json(req): Json<JoinLobbyRequest>,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    let response = join_lobby(&state, req).await?;
+    Ok(no_store_json(response))
+}
+
+pub async fn batch_join_lobby_handler(
+    State(state): State<AppState>,
+    headers: HeaderMap,
+    Json(req): Json<BatchJoinLobbyRequest>,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    let response = batch_join_lobby(&state, req, bearer_token(&headers)).await?;
+    Ok(no_store_json(response))
+}
+
+pub async fn create_account_handler(
+    State(state): State<AppState>,
+    Json(req): Json<AccountRequest>,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    let response = create_account(&state, req).await?;
+    Ok(no_store_json(response))
+}
+
+pub async fn account_login_handler(
+    State(state): State<AppState>,
+    Json(req): Json<AccountRequest>,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    let response = account_login(&state, req).await?;
+    Ok(no_store_json(response))
+}
+
+pub async fn lobby_qr_code_handler(
+    State(state): State<AppState>,
+    Path(join_code): Path<String>,
+    Query(query): Query<QrCodeQuery>,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    let (content_type, image) = lobby_qr_code(&state, &join_code, query.format).await?;
+    let mut response = ([(header::CONTENT_TYPE, content_type)], image).into_response();
+    add_no_store_headers(response.headers_mut());
+    Ok(response)
+}
+
+pub async fn admin_login_handler(
+    State(state): State<AppState>,
+    Json(req): Json<AdminLoginRequest>,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    let response = admin_login(&state, req).await?;
+    Ok(no_store_json(response))
+}
+
+/// Lobby lock wait time histogram since startup.
+pub async fn lock_metrics(state: &AppState) -> Result<LockMetricsSnapshot, ApiError> {
+    Ok(state.lock_metrics.snapshot())
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LogLevelRequest {
+    /// New log filter in `RUST_LOG` syntax, e.g. `spektrum=info,lock=debug`.
+    pub filter: String,
+}
+
+#[derive(Debug, Serialize, PartialEq)]
+pub struct LogLevelResponse {
+    pub filter: String,
+}
+
+/// Replaces the log filter until the next restart or change.
+pub async fn set_log_level(
+    state: &AppState,
+    req: LogLevelRequest,
+) -> Result<LogLevelResponse, ApiError> {
+    let Some(log_filter) = &state.log_filter else {
+        return Err(ApiError::NotFound("Log filter is not adjustable".into()));
+    };
+    let filter = log_filter.set(&req.filter).map_err(ApiError::Validation)?;
+    info!(%filter, "Log filter changed");
+    Ok(LogLevelResponse { filter })
+}
+
+pub async fn set_log_level_handler(
+    State(state): State<AppState>,
+    admin: AdminSession,
+    Json(req): Json<LogLevelRequest>,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    admin.require_global()?;
+    let response = set_log_level(&state, req).await?;
+    Ok(no_store_json(response))
+}
+
+pub async fn lock_metrics_handler(
+    State(state): State<AppState>,
+    admin: AdminSession,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    admin.require_global()?;
+    let response = lock_metrics(&state).await?;
+    Ok(no_store_json(response))
+}
+
+pub async fn list_admin_lobbies_handler(
+    State(state): State<AppState>,
+    admin: AdminSession,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    admin.require_global()?;
+    let response = list_admin_lobbies(&state).await?;
+    Ok(no_store_json(response))
+}
+
+pub async fn lobby_stats_handler(
+    State(state): State<AppState>,
+    Path(join_code): Path<String>,
+    headers: HeaderMap,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    let response = lobby_stats(&state, &join_code, bearer_token(&headers)).await?;
+    Ok(no_store_json(response))
+}
+
+pub async fn lobby_results_handler(
+    State(state): State<AppState>,
+    Path(join_code): Path<String>,
+    Query(query): Query<LobbyResultsQuery>,
+    headers: HeaderMap,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    let record = lobby_results(&state, &join_code, bearer_token(&headers)).await?;
+    if query.format == ResultsFormat::Json {
+        return Ok(no_store_json(record));
+    }
+    let disposition = HeaderValue::from_str(&format!(
+        "attachment; filename=\"results-{}.csv\"",
+        record.id
+    ))
+    .map_err(|e| ApiError::Database(e.to_string()))?;
+    let mut response = (
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("text/csv; charset=utf-8"),
+            ),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        results_csv(&record)?,
+    )
+        .into_response();
+    add_no_store_headers(response.headers_mut());
+    Ok(response)
+}
+
+pub async fn lobby_preload_handler(
+    State(state): State<AppState>,
+    Path(join_code): Path<String>,
+    headers: HeaderMap,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    let response = lobby_preload(&state, &join_code, bearer_token(&headers)).await?;
+    Ok(no_store_json(response))
+}
+
+pub async fn close_lobby_handler(
+    State(state): State<AppState>,
+    admin: AdminSession,
+    Path(join_code): Path<String>,
+    req: Option<Json<CloseLobbyRequest>>,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    admin.require_global()?;
+    let req = req.map(|Json(req)| req).unwrap_or_default();
+    let response = close_lobby(&state, &join_code, req).await?;
+    Ok(no_store_json(response))
+}
+
+pub async fn get_stored_data_handler(
+    State(state): State<AppState>,
+    admin: AdminSession,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    let response = get_stored_data(&state, admin.dataset.as_deref()).await?;
+    Ok(no_store_json(response))
+}
+
+pub async fn integrity_handler(
+    State(state): State<AppState>,
+    admin: AdminSession,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    let response = check_integrity(&state, admin.dataset.as_deref()).await?;
+    Ok(no_store_json(response))
+}
+
+pub async fn list_game_history_handler(
+    State(state): State<AppState>,
+    admin: AdminSession,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    let response = list_game_history(&state, admin.dataset.as_deref()).await?;
+    Ok(no_store_json(response))
+}
+
+pub async fn game_history_handler(
+    State(state): State<AppState>,
+    admin: AdminSession,
+    Path(id): Path<String>,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    let response = game_history(&state, admin.dataset.as_deref(), &id).await?;
+    Ok(no_store_json(response))
+}
+
+pub async fn export_questions_handler(
+    State(state): State<AppState>,
+    admin: AdminSession,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    let (file_name, body) = export_questions(&state, admin.dataset.as_deref()).await?;
+    let disposition = HeaderValue::from_str(&format!("attachment; filename=\"{file_name}\""))
+        .map_err(|e| ApiError::Database(e.to_string()))?;
+    let mut response = (
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_static("application/gzip"),
+            ),
+            (header::CONTENT_DISPOSITION, disposition),
+        ],
+        body,
+    )
+        .into_response();
+    add_no_store_headers(response.headers_mut());
+    Ok(response)
+}
+
+pub async fn set_stored_data_handler(
+    State(state): State<AppState>,
+    admin: AdminSession,
+    Json(req): Json<SetStoredDataRequest>,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    let response = set_stored_data(&state, admin.dataset.as_deref(), req).await?;
+    Ok(no_store_json(response))
 }
 
 pub async fn upload_character_image_handler(
     State(state): State<AppState>,
+    admin: AdminSession,
     Path(character_name): Path<String>,
     mut multipart: Multipart,
 ) -> Result<impl axum::response::IntoResponse, ApiError> {
-    let mut authenticated = false;
+    state.ensure_writable()?;
+    let max_bytes = state.limits.max_image_bytes;
     let mut image_data = None;
-    while let Some(field) = multipart
-        .next_field()
-        .await
-        .map_err(|e| ApiError::BadRequest(e.to_string()))?
-    {
-        match field.name().unwrap_or_default() {
-            "password" => {
-                let password = field
-                    .text()
-                    .await
-                    .map_err(|e| ApiError::BadRequest(e.to_string()))?;
-                if !state.verify_admin_password(&password) {
-                    return Err(ApiError::Unauthorized);
-                }
-                authenticated = true;
-            }
-            "image" => {
-                if !authenticated {
-                    return Err(ApiError::Unauthorized);
-                }
-                if !field
-                    .content_type()
-                    .unwrap_or("")
-                    .eq_ignore_ascii_case("image/avif")
-                {
-                    return Err(ApiError::UnsupportedMediaType);
-                }
-                image_data = Some(
-                    field
-                        .bytes()
-                        .await
-                        .map_err(|e| ApiError::BadRequest(e.to_string()))?,
-                );
+    while let Some(mut field) = multipart.next_field().await.map_err(multipart_error)? {
+        if field.name() != Some("image") {
+            continue;
+        }
+        let content_type = field.content_type().unwrap_or("").to_ascii_lowercase();
+        let is_avif = content_type == "image/avif";
+        #[cfg(feature = "image-transcode")]
+        let is_accepted = is_avif || transcode::TRANSCODABLE_TYPES.contains(&content_type.as_str());
+        #[cfg(not(feature = "image-transcode"))]
+        let is_accepted = is_avif;
+        if !is_accepted {
+            return Err(ApiError::UnsupportedMediaType);
+        }
+        let mut data = Vec::new();
+        while let Some(chunk) = field.chunk().await.map_err(multipart_error)? {
+            if data.len() + chunk.len() > max_bytes {
+                return Err(ApiError::PayloadTooLarge(format!(
+                    "Image exceeds {max_bytes} bytes"
+                )));
             }
-            _ => continue,
+            data.extend_from_slice(&chunk);
         }
-    }
-    if !authenticated {
-        return Err(ApiError::Unauthorized);
+        #[cfg(feature = "image-transcode")]
+        if !is_avif {
+            data = tokio::task::spawn_blocking(move || transcode::to_avif(&data))
+                .await
+                .map_err(|e| ApiError::Database(e.to_string()))?
+                .map_err(|e| ApiError::Validation(format!("Could not convert image: {e}")))?;
+        }
+        avif::strip_metadata(&mut data, state.limits.max_image_dimension)
+            .map_err(|e| ApiError::Validation(e.to_string()))?;
+        image_data = Some(data);
     }
     let image_data = image_data.ok_or(ApiError::BadRequest("Missing image file".into()))?;
     let url = state
-        .store
+        .dataset_store(admin.dataset.as_deref())?
         .store_character_image(&character_name, &image_data)
         .await?;
     Ok(no_store_json(UploadCharacterImageResponse {
@@ -604,6 +2055,87 @@ pub async fn upload_character_image_handler(
     }))
 }
 
+pub async fn upload_audio_clip_handler(
+    State(state): State<AppState>,
+    admin: AdminSession,
+    Path(media_id): Path<i64>,
+    mut multipart: Multipart,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    let max_bytes = state.limits.max_audio_bytes;
+    let mut audio_data = None;
+    while let Some(mut field) = multipart.next_field().await.map_err(multipart_error)? {
+        if field.name() != Some("audio") {
+            continue;
+        }
+        let mut data = Vec::new();
+        while let Some(chunk) = field.chunk().await.map_err(multipart_error)? {
+            if data.len() + chunk.len() > max_bytes {
+                return Err(ApiError::PayloadTooLarge(format!(
+                    "Audio clip exceeds {max_bytes} bytes"
+                )));
+            }
+            data.extend_from_slice(&chunk);
+        }
+        audio_data = Some(data);
+    }
+    let audio_data = audio_data.ok_or(ApiError::BadRequest("Missing audio file".into()))?;
+    let response =
+        upload_audio_clip(&state, admin.dataset.as_deref(), media_id, &audio_data).await?;
+    Ok(no_store_json(response))
+}
+
+pub async fn audio_clip_handler(
+    State(state): State<AppState>,
+    Path(media_id): Path<i64>,
+    Query(query): Query<AudioClipQuery>,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    let (format, data) = audio_clip(&state, query.dataset.as_deref(), media_id).await?;
+    Ok(([(header::CONTENT_TYPE, format.content_type())], data))
+}
+
+pub async fn import_questions_handler(
+    State(state): State<AppState>,
+    admin: AdminSession,
+    mut multipart: Multipart,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    let mut file = None;
+    while let Some(field) = multipart.next_field().await.map_err(multipart_error)? {
+        if field.name() == Some("file") {
+            file = Some(field.bytes().await.map_err(multipart_error)?);
+        }
+    }
+    let file = file.ok_or(ApiError::BadRequest("Missing question file".into()))?;
+    let response = import_questions(&state, admin.dataset.as_deref(), &file).await?;
+    Ok(no_store_json(response))
+}
+
+pub async fn delete_character_image_handler(
+    State(state): State<AppState>,
+    admin: AdminSession,
+    Path(character_name): Path<String>,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    let response =
+        delete_character_image(&state, admin.dataset.as_deref(), &character_name).await?;
+    Ok(no_store_json(response))
+}
+
+pub async fn media_upload_url_handler(
+    State(state): State<AppState>,
+    admin: AdminSession,
+    Json(req): Json<MediaUploadUrlRequest>,
+) -> Result<impl axum::response::IntoResponse, ApiError> {
+    let response = media_upload_url(&state, admin.dataset.as_deref(), req).await?;
+    Ok(no_store_json(response))
+}
+
+fn multipart_error(e: MultipartError) -> ApiError {
+    if e.status() == StatusCode::PAYLOAD_TOO_LARGE {
+        ApiError::PayloadTooLarge(e.body_text())
+    } else {
+        ApiError::BadRequest(e.to_string())
+    }
+}
+
 pub async fn check_sessions_handler(
     State(state): State<AppState>,
     Json(req): Json<CheckSessionsRequest>,
@@ -617,13 +2149,19 @@ struct WsConnection {
     lobby_key: Option<String>,
     recent_message_count: usize,
     count_reset_time: Instant,
+    /// When the client last sent anything, used to reap half-open connections.
+    last_heard: Instant,
+    /// Keepalive agreed on when connecting, or the server default before.
+    heartbeat: Heartbeat,
+    /// Tells the sender task how often to ping.
+    ping_every: watch::Sender<Duration>,
     connection_id: Uuid,
     /// The connection-level tracing span, stored for explicit field recording
     conn_span: Span,
 }
 
 impl WsConnection {
-    fn new(upgrade_request_id: Option<u64>) -> Self {
+    fn new(upgrade_request_id: Option<u64>, heartbeat: Heartbeat) -> Self {
         let connection_id = Uuid::new_v4();
         let conn_span = info_span!(
             target: "ws",
@@ -638,16 +2176,72 @@ impl WsConnection {
             lobby_key: None,
             recent_message_count: 0,
             count_reset_time: Instant::now(),
+            last_heard: Instant::now(),
+            heartbeat,
+            ping_every: watch::Sender::new(Duration::from_millis(heartbeat.interval_ms)),
             connection_id,
             conn_span,
         }
     }
 }
 
-pub async fn ws_handler(ws: WebSocketUpgrade, State(state): State<AppState>) -> impl IntoResponse {
-    // Generate a unique ID for this upgrade request that links HTTP → WebSocket
-    // This will appear in both the HTTP request span and the WS connection span
-    let upgrade_request_id = fastrand::u64(..);
+/// The keepalive for a client asking for heartbeats every `requested_ms`,
+/// or the server default if it didn't ask.
+fn negotiate_heartbeat(limits: &LimitsConfig, requested_ms: Option<u64>) -> Heartbeat {
+    let min_ms = limits.min_heartbeat_interval_secs * 1000;
+    let max_ms = (limits.max_heartbeat_interval_secs * 1000).max(min_ms);
+    let interval_ms = requested_ms.unwrap_or(limits.heartbeat_interval_secs * 1000);
+    Heartbeat {
+        interval_ms: interval_ms.clamp(min_ms, max_ms),
+        max_missed: limits.max_missed_heartbeats,
+    }
+}
+
+/// Why the server closes a WebSocket. The close code tells the client
+/// whether reconnecting can help.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum CloseReason {
+    RateLimited,
+    Idle,
+    ShuttingDown,
+    /// The client fell too far behind the lobby's updates.
+    FellBehind,
+    Ended(EndReason),
+}
+
+impl CloseReason {
+    fn frame(self) -> CloseFrame {
+        let (code, reason) = match self {
+            CloseReason::RateLimited => (close_code::POLICY, "Rate limit exceeded"),
+            CloseReason::Idle => (close_code::AWAY, "Connection idle"),
+            CloseReason::ShuttingDown => (close_code::AWAY, "Server shutting down"),
+            CloseReason::FellBehind => (close_code::AGAIN, "Too far behind"),
+            CloseReason::Ended(EndReason::GameClosed) => (close_code::NORMAL, "Game closed"),
+            CloseReason::Ended(EndReason::Kicked) => (close_code::POLICY, "Kicked"),
+        };
+        CloseFrame {
+            code,
+            reason: reason.into(),
+        }
+    }
+}
+
+/// How long the sender task gets to write the close frame.
+const CLOSE_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// Upgrades the HTTP request to a WebSocket connection.
+///
+/// Note: permessage-deflate is not negotiated. axum's upgrade is built on
+/// tungstenite, which does not implement the extension, so any
+/// `Sec-WebSocket-Extensions` offer from the client is ignored and frames are
+/// sent uncompressed. HTTP responses are still gzip-compressed by the router.
+pub async fn ws_handler(
+    ws: WebSocketUpgrade,
+    State(state): State<AppState>,
+    Extension(RequestId(upgrade_request_id)): Extension<RequestId>,
+) -> impl IntoResponse {
+    // The upgrade request's id links the HTTP request span to the WS
+    // connection span.
     debug!(
         target: "ws",
         upgrade_request_id = upgrade_request_id,
@@ -660,21 +2254,52 @@ pub async fn ws_handler(ws: WebSocketUpgrade, State(state): State<AppState>) ->
 async fn handle_socket(socket: WebSocket, state: AppState, upgrade_request_id: Option<u64>) {
     let (ws_tx, mut ws_rx) = socket.split();
 
-    let (text_tx, text_rx) = channel::<Utf8Bytes>(128);
+    let (text_tx, text_rx) = channel::<Outgoing>(128);
     let (bin_tx, bin_rx) = channel::<Bytes>(128);
 
-    let mut conn = WsConnection::new(upgrade_request_id);
+    let mut conn = WsConnection::new(upgrade_request_id, negotiate_heartbeat(&state.limits, None));
     // Clone the span for use in .instrument() - conn retains ownership for field recording
     let conn_span = conn.conn_span.clone();
 
+    let (close_tx, close_rx) = oneshot::channel();
     // Ensure the sender task inherits the connection span as its parent
-    let send_task = {
+    let mut send_task = {
         let _guard = conn_span.enter();
-        spawn_sender_task(ws_tx, text_rx, bin_rx, conn.connection_id)
+        spawn_sender_task(
+            ws_tx,
+            Inbox::new(text_rx),
+            bin_rx,
+            conn.ping_every.subscribe(),
+            close_rx,
+            conn.connection_id,
+        )
     };
+    let mut shutting_down = state.shutting_down.subscribe();
 
     async {
-        while let Some(Ok(msg)) = ws_rx.next().await {
+        let close = loop {
+            let idle_timeout = conn.heartbeat.timeout();
+            let deadline = tokio::time::Instant::from_std(conn.last_heard + idle_timeout);
+            let received = tokio::select! {
+                received = tokio::time::timeout_at(deadline, ws_rx.next()) => received,
+                Ok(_) = shutting_down.wait_for(|&down| down) => {
+                    break Some(CloseReason::ShuttingDown);
+                }
+            };
+            let msg = match received {
+                Ok(Some(Ok(msg))) => msg,
+                Ok(_) => break None,
+                Err(_) => {
+                    info!(
+                        target: "ws",
+                        player_id = ?conn.player_id,
+                        idle_secs = idle_timeout.as_secs(),
+                        "Closing silent connection"
+                    );
+                    break Some(CloseReason::Idle);
+                }
+            };
+            conn.last_heard = Instant::now();
             let (msg_kind, size_bytes) = get_message_info(&msg);
             let msg_span = info_span!(
                 target: "ws",
@@ -687,12 +2312,17 @@ async fn handle_socket(socket: WebSocket, state: AppState, upgrade_request_id: O
                 .instrument(msg_span)
                 .await;
 
-            if result.is_err() {
-                break;
+            if let Err(close) = result {
+                break close;
             }
-        }
+        };
 
         handle_disconnect(&conn, &state).await;
+        if let Some(reason) = close
+            && close_tx.send(reason).is_ok()
+        {
+            let _ = tokio::time::timeout(CLOSE_TIMEOUT, &mut send_task).await;
+        }
         send_task.abort();
     }
     .instrument(conn_span)
@@ -718,8 +2348,10 @@ fn get_message_info(msg: &Message) -> (&'static str, usize) {
 
 fn spawn_sender_task(
     mut ws_tx: SplitSink<WebSocket, Message>,
-    mut text_rx: Receiver<Utf8Bytes>,
+    mut inbox: Inbox,
     mut bin_rx: Receiver<Bytes>,
+    mut ping_every: watch::Receiver<Duration>,
+    mut close_rx: oneshot::Receiver<CloseReason>,
     connection_id: Uuid,
 ) -> JoinHandle<()> {
     let sender_span = info_span!(
@@ -731,14 +2363,32 @@ fn spawn_sender_task(
 
     tokio::spawn(
         async move {
-            let mut ping_interval = tokio::time::interval(Duration::from_secs(30));
+            let mut ping_interval = tokio::time::interval(*ping_every.borrow_and_update());
             loop {
                 tokio::select! {
                     _ = ping_interval.tick() => {
                         if ws_tx.send(Message::Ping(vec![].into())).await.is_err() { break; }
                     }
-                    Some(msg) = text_rx.recv() => {
-                        if ws_tx.send(Message::Text(msg)).await.is_err() { break; }
+                    Ok(()) = ping_every.changed() => {
+                        let period = *ping_every.borrow_and_update();
+                        let start = tokio::time::Instant::now() + period;
+                        ping_interval = tokio::time::interval_at(start, period);
+                    }
+                    Ok(reason) = &mut close_rx => {
+                        let _ = ws_tx.send(Message::Close(Some(reason.frame()))).await;
+                        break;
+                    }
+                    msg = inbox.recv() => {
+                        // Ended by the engine, or too far behind the lobby's
+                        // broadcasts.
+                        let Some(msg) = msg else {
+                            let reason = inbox.ended().map_or(CloseReason::FellBehind, CloseReason::Ended);
+                            let _ = ws_tx.send(Message::Close(Some(reason.frame()))).await;
+                            break;
+                        };
+                        // The engine only queues serialized JSON.
+                        let Ok(text) = Utf8Bytes::try_from(msg) else { continue };
+                        if ws_tx.send(Message::Text(text)).await.is_err() { break; }
                     }
                     Some(msg) = bin_rx.recv() => {
                         if ws_tx.send(Message::Binary(msg)).await.is_err() { break; }
@@ -755,9 +2405,9 @@ async fn handle_message(
     msg: Message,
     conn: &mut WsConnection,
     state: &AppState,
-    text_tx: &Sender<Utf8Bytes>,
+    text_tx: &Sender<Outgoing>,
     bin_tx: &Sender<Bytes>,
-) -> Result<(), ()> {
+) -> Result<(), Option<CloseReason>> {
     // Rate limit check
     let now = Instant::now();
 
@@ -767,7 +2417,7 @@ async fn handle_message(
     }
 
     conn.recent_message_count += 1;
-    if conn.recent_message_count > 30 {
+    if conn.recent_message_count > state.limits.ws_messages_per_sec {
         error!(
             target: "ws",
             player_id = ?conn.player_id,
@@ -779,7 +2429,7 @@ async fn handle_message(
             "Rate limit exceeded. Closing connection".to_string(),
             "rate_limit",
         );
-        return Err(());
+        return Err(Some(CloseReason::RateLimited));
     }
 
     // Handle Message
@@ -812,8 +2462,21 @@ async fn handle_message(
                 "Processing client message"
             );
 
-            if let ClientMessage::Connect { session_token } = client_msg {
-                handle_connect(session_token, conn, state, text_tx).await;
+            // Any message keeps the lobby open, heartbeats aside.
+            if let Some(lobby) = conn.lobby_key.as_deref().and_then(|key| state.lobby(key)) {
+                lobby.touch();
+            }
+
+            if let ClientMessage::Connect {
+                session_token,
+                locale,
+                heartbeat_interval_ms,
+            } = client_msg
+            {
+                let heartbeat = negotiate_heartbeat(&state.limits, heartbeat_interval_ms);
+                handle_connect(session_token, locale, heartbeat, conn, state, text_tx).await;
+            } else if let ClientMessage::Ping { client_time_ms } = client_msg {
+                send_pong(text_tx, client_time_ms);
             } else if conn.player_id.is_some() {
                 dispatch_game_action(client_msg, conn, state).await;
             } else {
@@ -832,7 +2495,8 @@ async fn handle_message(
             if let Some(pid) = conn.player_id {
                 trace!("Client initiated close for player {}", pid);
             }
-            return Err(());
+            // The WebSocket answers the client's close frame itself.
+            return Err(None);
         }
         _ => {}
     }
@@ -841,9 +2505,11 @@ async fn handle_message(
 
 async fn handle_connect(
     session_token: String,
+    locale: Option<Locale>,
+    heartbeat: Heartbeat,
     conn: &mut WsConnection,
     state: &AppState,
-    tx: &Sender<Utf8Bytes>,
+    tx: &Sender<Outgoing>,
 ) {
     let (code, pid_str) = match session_token.split_once(':') {
         Some(parts) => parts,
@@ -869,32 +2535,39 @@ async fn handle_connect(
         }
     };
 
-    debug!(target: "lock", lobby_key = %code, "acquiring_lobby_lock");
-    let t0 = Instant::now();
-
-    let mut engine = match state.lobbies.get_mut(code) {
-        Some(engine) => {
-            let dt = t0.elapsed();
-            debug!(
-                target: "lock",
-                lobby_key = %code,
-                duration_us = dt.as_micros() as u64,
-                "lobby_lock_acquired"
-            );
-            engine
-        }
-        None => {
-            debug!(target: "lock", lobby_key = %code, "lobby_not_found");
-            send_error_to_client(
-                tx,
-                "Lobby not found for session token.".to_string(),
-                "connect_lobby_not_found",
-            );
-            return;
-        }
+    let Some(lobby) = state.lobby(code) else {
+        debug!(target: "lock", lobby_key = %code, "lobby_not_found");
+        send_error_to_client(
+            tx,
+            "Lobby not found for session token.".to_string(),
+            "connect_lobby_not_found",
+        );
+        return;
     };
 
-    if !engine.has_player(&player_id) {
+    let player_tx = tx.clone();
+    let connection_id = conn.connection_id;
+    let connected = lobby
+        .call(move |engine| {
+            if !engine.has_player(&player_id) {
+                return false;
+            }
+            engine.update_player_connection(player_id, player_tx, connection_id);
+            if let Some(locale) = locale {
+                engine.set_locale(player_id, locale);
+            }
+            engine.set_heartbeat(player_id, heartbeat);
+            engine.process_event(GameEvent {
+                context: EventContext {
+                    sender_id: player_id,
+                    timestamp: Instant::now(),
+                },
+                action: GameAction::Connect,
+            });
+            true
+        })
+        .await;
+    if connected != Some(true) {
         send_error_to_client(
             tx,
             "Player not found in lobby. Please join again.".to_string(),
@@ -903,9 +2576,11 @@ async fn handle_connect(
         return;
     }
 
-    engine.update_player_connection(player_id, tx.clone(), conn.connection_id);
     conn.player_id = Some(player_id);
     conn.lobby_key = Some(code.to_string());
+    conn.heartbeat = heartbeat;
+    conn.ping_every
+        .send_replace(Duration::from_millis(heartbeat.interval_ms));
 
     // Record player_id and lobby_key in the connection span (explicit, future-proof)
     conn.conn_span
@@ -915,20 +2590,14 @@ async fn handle_connect(
     debug!(
         target: "ws",
         %player_id,
-        lobby_key = %code,
-        "Player connected to lobby"
-    );
-
-    let event = GameEvent {
-        context: EventContext {
-            sender_id: player_id,
-            timestamp: Instant::now(),
-        },
-        action: GameAction::Connect,
-    };
-    engine.process_event(event);
+        lobby_key = %code,
+        "Player connected to lobby"
+    );
 }
 
+/// Longer `client_msg_id`s are dropped rather than echoed.
+const MAX_CLIENT_MSG_ID_BYTES: usize = 64;
+
 async fn dispatch_game_action(msg: ClientMessage, conn: &WsConnection, state: &AppState) {
     let Some(player_id) = conn.player_id else {
         error!("dispatch_game_action called without player_id");
@@ -939,25 +2608,22 @@ async fn dispatch_game_action(msg: ClientMessage, conn: &WsConnection, state: &A
         return;
     };
 
-    debug!(target: "lock", %lobby_key, "acquiring_lobby_lock");
-    let t0 = Instant::now();
-
-    let Some(mut engine) = state.lobbies.get_mut(lobby_key) else {
+    let Some(lobby) = state.lobby(lobby_key) else {
         debug!(target: "lock", %lobby_key, "lobby_not_found");
         return;
     };
 
-    let dt = t0.elapsed();
-    debug!(
-        target: "lock",
-        %lobby_key,
-        duration_us = dt.as_micros() as u64,
-        "lobby_lock_acquired"
-    );
-
     let action = match msg {
         ClientMessage::Leave => GameAction::Leave,
-        ClientMessage::Answer { answer } => GameAction::Answer { answer },
+        ClientMessage::Answer {
+            answer,
+            client_msg_id,
+        } => GameAction::Answer {
+            answer,
+            // Kept with the player's answer until the round ends, so keep it short.
+            client_msg_id: client_msg_id.filter(|id| id.len() <= MAX_CLIENT_MSG_ID_BYTES),
+        },
+        ClientMessage::ResyncScoreboard => GameAction::ResyncScoreboard,
         ClientMessage::AdminAction { action } => {
             debug!(
                 target: "ws",
@@ -981,9 +2647,27 @@ async fn dispatch_game_action(msg: ClientMessage, conn: &WsConnection, state: &A
                     reason: Arc::from(reason),
                 },
                 AdminAction::LockLobby { locked } => GameAction::LockLobby { locked },
+                AdminAction::UnlockLobby => GameAction::LockLobby { locked: false },
+                AdminAction::RequestUpcoming { count } => GameAction::RequestUpcoming { count },
+                AdminAction::QueueQuestion { question_id } => {
+                    GameAction::QueueQuestion { question_id }
+                }
+                AdminAction::ReorderUpcoming { question_ids } => {
+                    GameAction::ReorderUpcoming { question_ids }
+                }
+                AdminAction::InjectQuestion {
+                    question_text,
+                    options,
+                } => GameAction::InjectQuestion {
+                    question_text: Arc::from(question_text),
+                    options,
+                },
+                AdminAction::ApproveReclaim { player_name } => GameAction::ApproveReclaim {
+                    player_name: Arc::from(player_name),
+                },
             }
         }
-        _ => return, // Connect is handled separately
+        _ => return, // Connect and Ping are handled separately
     };
     let event = GameEvent {
         context: EventContext {
@@ -992,7 +2676,101 @@ async fn dispatch_game_action(msg: ClientMessage, conn: &WsConnection, state: &A
         },
         action,
     };
-    engine.process_event(event);
+    let state = state.clone();
+    let lobby_key = lobby_key.clone();
+    lobby
+        .send(move |engine| {
+            let before = engine.get_phase();
+            engine.process_event(event);
+            notify_phase_change(&state.webhooks, &lobby_key, before, engine);
+            if let Some(spotify) = &state.spotify {
+                control_playback(spotify, &lobby_key, before, engine);
+            }
+            save_finished_game(&state, engine);
+        })
+        .await;
+}
+
+/// Adds the game that just ended, if any, to the history of the lobby's
+/// dataset in the background.
+fn save_finished_game(state: &AppState, engine: &mut GameEngine) {
+    let Some(record) = engine.take_finished_game() else {
+        return;
+    };
+    let store = match state.dataset_store(record.dataset.as_deref()) {
+        Ok(store) => store.clone(),
+        Err(e) => {
+            error!(id = %record.id, error = %e, "Failed to save game record");
+            return;
+        }
+    };
+    tokio::spawn(async move {
+        match store.save_game_record(&record).await {
+            Ok(()) => info!(id = %record.id, "Saved game record"),
+            Err(e) => error!(id = %record.id, error = %e, "Failed to save game record"),
+        }
+    });
+}
+
+/// Plays the question's track when a round starts and pauses when it ends.
+/// Frees the device for other lobbies once the game is over.
+fn control_playback(
+    spotify: &SpotifyPlayer,
+    join_code: &str,
+    before: GamePhase,
+    engine: &GameEngine,
+) {
+    let after = engine.get_phase();
+    if after == before {
+        return;
+    }
+    if after == GamePhase::Question {
+        if let Some(uri) = engine.current_spotify_uri() {
+            spotify.play(join_code, uri);
+        }
+    } else if before == GamePhase::Question {
+        spotify.pause(join_code);
+    }
+    if matches!(after, GamePhase::GameOver | GamePhase::GameClosed) {
+        spotify.release(join_code);
+    }
+}
+
+/// Sends webhook events for lifecycle transitions out of `before`.
+fn notify_phase_change(
+    webhooks: &WebhookDispatcher,
+    join_code: &str,
+    before: GamePhase,
+    engine: &GameEngine,
+) {
+    let after = engine.get_phase();
+    if after == before {
+        return;
+    }
+    let join_code = join_code.to_string();
+    let event = match after {
+        GamePhase::Score if matches!(before, GamePhase::Lobby | GamePhase::GameOver) => {
+            WebhookEvent::GameStarted {
+                join_code,
+                player_count: engine.get_lobby_stats().0,
+            }
+        }
+        GamePhase::GameOver => WebhookEvent::GameOver {
+            join_code,
+            scores: engine
+                .get_detailed_stats()
+                .players
+                .into_iter()
+                .map(|p| FinalScore {
+                    name: p.name,
+                    score: p.score,
+                })
+                .collect(),
+        },
+        GamePhase::GameClosed => WebhookEvent::LobbyClosed { join_code },
+        _ => return,
+    };
+    webhooks.send(event);
 }
 
 async fn handle_disconnect(conn: &WsConnection, state: &AppState) {
@@ -1002,72 +2780,291 @@ async fn handle_disconnect(conn: &WsConnection, state: &AppState) {
             player_id, lobby_key
         );
 
-        debug!(target: "lock", %lobby_key, "acquiring_lobby_lock");
-        let t0 = Instant::now();
-
-        if let Some(mut engine) = state.lobbies.get_mut(lobby_key) {
-            let dt = t0.elapsed();
-            debug!(
-                target: "lock",
-                %lobby_key,
-                duration_us = dt.as_micros() as u64,
-                "lobby_lock_acquired"
-            );
-            engine.clear_player_connection(player_id, conn.connection_id);
+        if let Some(lobby) = state.lobby(lobby_key) {
+            let connection_id = conn.connection_id;
+            lobby
+                .send(move |engine| engine.clear_player_connection(player_id, connection_id))
+                .await;
         } else {
             debug!(target: "lock", %lobby_key, "lobby_not_found");
         }
     }
 }
 
-fn send_error_to_client(tx: &Sender<Utf8Bytes>, message: String, context: &str) {
+fn send_error_to_client(tx: &Sender<Outgoing>, message: String, context: &str) {
     let error_update = GameUpdate::Error {
         message: Arc::from(message),
+        client_msg_id: None,
     };
     if let Ok(json) = serde_json::to_string(&error_update)
-        && tx.try_send(Utf8Bytes::from(json)).is_err()
+        && tx.try_send(Outgoing::from(Bytes::from(json))).is_err()
     {
         error!("Failed to send '{}' error to client channel", context);
     }
 }
 
-async fn cleanup_lobbies(lobbies: Arc<DashMap<String, GameEngine>>) {
-    let mut tick = tokio::time::interval(Duration::from_secs(60));
+fn send_pong(tx: &Sender<Outgoing>, client_time_ms: u64) {
+    let pong = GameUpdate::Pong {
+        client_time_ms,
+        server_time_ms: unix_time_ms(),
+    };
+    if let Ok(json) = serde_json::to_string(&pong)
+        && tx.try_send(Outgoing::from(Bytes::from(json))).is_err()
+    {
+        warn!(target: "ws", "Failed to send pong to client channel");
+    }
+}
+
+/// Saves every open lobby to storage so it can be restored after a restart.
+/// Returns the number of lobbies saved.
+pub async fn persist_lobbies(state: &AppState) -> Result<usize, DbError> {
+    let mut snapshots = Vec::new();
+    for (join_code, lobby) in state.lobby_handles() {
+        if let Some(Some(snapshot)) = lobby.call(|engine| engine.to_snapshot()).await {
+            snapshots.push(PersistedLobby {
+                snapshot,
+                creator_ip: state.lobby_creations.creator(&join_code),
+            });
+        }
+    }
+    state.store.save_lobby_snapshots(&snapshots).await?;
+    Ok(snapshots.len())
+}
+
+/// Recreates lobbies saved by [`persist_lobbies`]. Lobbies whose join code is
+/// already in use, or whose dataset is no longer configured, are skipped.
+/// Returns the number of lobbies restored.
+pub async fn restore_lobbies(state: &AppState) -> Result<usize, DbError> {
+    let snapshots = state.store.load_lobby_snapshots().await?;
+    let mut restored = 0;
+    for PersistedLobby {
+        snapshot,
+        creator_ip,
+    } in snapshots
+    {
+        let join_code = snapshot.join_code.to_string();
+        let snapshot_code = join_code.clone();
+        let Ok(store) = state.dataset_store(snapshot.dataset.as_deref()) else {
+            warn!(%join_code, dataset = ?snapshot.dataset, "Skipping lobby of unknown dataset");
+            continue;
+        };
+        let questions = store.snapshot();
+        if let dashmap::mapref::entry::Entry::Vacant(entry) = state.lobbies.entry(join_code) {
+            let mut engine = GameEngine::from_snapshot(
+                snapshot,
+                questions.questions.clone(),
+                questions.color_weights,
+            );
+            if state.record_events {
+                engine.enable_event_log();
+            }
+            entry.insert(state.spawn_lobby(&snapshot_code, engine));
+            if let Some(ip) = creator_ip {
+                state.lobby_creations.restore(&snapshot_code, ip);
+            }
+            restored += 1;
+        }
+    }
+    Ok(restored)
+}
+
+/// Periodically persists lobbies. Writes are skipped while there is nothing
+/// new to save, i.e. no lobbies now and none at the previous write.
+pub async fn persist_lobbies_periodically(state: AppState, interval: Duration) {
+    let mut tick = tokio::time::interval(interval);
+    tick.tick().await;
+    let mut last_saved = usize::MAX;
+    loop {
+        tick.tick().await;
+        if state.lobbies.is_empty() && last_saved == 0 {
+            continue;
+        }
+        match persist_lobbies(&state).await {
+            Ok(count) => {
+                debug!(target: "maintenance", lobbies = count, "Persisted lobby snapshots");
+                last_saved = count;
+            }
+            Err(e) => warn!(target: "maintenance", error = %e, "Failed to persist lobbies"),
+        }
+    }
+}
+
+/// Writes the event logs of lobbies that recorded events since the last
+/// flush. Returns the number of logs written; logs that fail to write are
+/// written again once their lobby records another event.
+pub async fn flush_event_logs(state: &AppState) -> usize {
+    let mut logs: Vec<(String, String)> = Vec::new();
+    for (_, lobby) in state.lobby_handles() {
+        if let Some(Some(log)) = lobby.call(|engine| engine.take_event_log()).await {
+            logs.push(log);
+        }
+    }
+    let mut written = 0;
+    for (name, jsonl) in logs {
+        match state.store.save_event_log(&name, &jsonl).await {
+            Ok(()) => written += 1,
+            Err(e) => {
+                warn!(target: "maintenance", log = %name, error = %e, "Failed to write event log")
+            }
+        }
+    }
+    written
+}
+
+pub async fn flush_event_logs_periodically(state: AppState, interval: Duration) {
+    let mut tick = tokio::time::interval(interval);
+    tick.tick().await;
     loop {
         tick.tick().await;
+        let written = flush_event_logs(&state).await;
+        if written > 0 {
+            debug!(target: "maintenance", logs = written, "Flushed event logs");
+        }
+    }
+}
 
-        // Close inactive lobbies and notify players before cleanup
-        for mut entry in lobbies.iter_mut() {
-            entry.value_mut().close_if_inactive();
+/// Periodically reloads the question snapshot when the stored data was
+/// changed elsewhere, so instances sharing storage converge.
+pub async fn refresh_questions_periodically(state: AppState, interval: Duration) {
+    let mut tick = tokio::time::interval(interval);
+    tick.tick().await;
+    loop {
+        tick.tick().await;
+        let datasets = state
+            .datasets
+            .iter()
+            .map(|(name, dataset)| (name.as_str(), &dataset.store));
+        for (dataset, store) in std::iter::once(("default", &state.store)).chain(datasets) {
+            match store.refresh_if_changed().await {
+                Ok(true) => {
+                    info!(target: "maintenance", dataset, "Reloaded changed question data")
+                }
+                Ok(false) => {}
+                Err(e) => {
+                    warn!(target: "maintenance", dataset, error = %e, "Failed to refresh questions")
+                }
+            }
         }
+    }
+}
+
+/// Warns and closes inactive lobbies every minute and removes finished ones.
+pub async fn cleanup_lobbies(state: AppState) {
+    let AppState {
+        lobbies,
+        lobby_creations,
+        webhooks,
+        store,
+        closed_results,
+        spotify,
+        ..
+    } = state.clone();
+    let mut tick = tokio::time::interval(Duration::from_secs(60));
+    loop {
+        tick.tick().await;
+        closed_results.retain(|_, closed| closed.removed_at.elapsed() < RESULTS_GRACE);
 
-        let finished_lobby_ids: Vec<String> = lobbies
+        let handles: Vec<(String, LobbyHandle)> = lobbies
             .iter()
-            .filter(|entry| entry.value().is_finished())
-            .map(|entry| entry.key().clone())
+            .map(|entry| (entry.key().clone(), entry.value().clone()))
             .collect();
 
+        // Warn or close inactive lobbies and notify players before cleanup
+        let mut finished_lobby_ids = Vec::new();
+        for (join_code, lobby) in handles {
+            let webhooks = webhooks.clone();
+            let code = join_code.clone();
+            let last_activity = lobby.last_activity();
+            let state = state.clone();
+            let finished = lobby
+                .call(move |engine| {
+                    let before = engine.get_phase();
+                    engine.note_activity(last_activity);
+                    engine.warn_if_inactive();
+                    engine.close_if_inactive();
+                    notify_phase_change(&webhooks, &code, before, engine);
+                    save_finished_game(&state, engine);
+                    engine.is_finished()
+                })
+                .await;
+            // A stopped task counts as finished, so its entry is removed.
+            if finished != Some(false) {
+                finished_lobby_ids.push(join_code);
+            }
+        }
+
         for lobby_id in &finished_lobby_ids {
-            if let Some((_, engine)) = lobbies.remove(lobby_id) {
-                let (total_players, questions_played) = engine.get_lobby_stats();
+            if let Some((_, lobby)) = lobbies.remove(lobby_id) {
+                lobby_creations.release(lobby_id);
+                if let Some(spotify) = &spotify {
+                    spotify.release(lobby_id);
+                }
+                let Some((total_players, questions_played, summary, event_log, results)) = lobby
+                    .call(|engine| {
+                        let (total_players, questions_played) = engine.get_lobby_stats();
+                        (
+                            total_players,
+                            questions_played,
+                            engine.lifetime_summary(),
+                            engine.take_event_log(),
+                            engine
+                                .final_results()
+                                .cloned()
+                                .map(|record| ClosedLobbyResults {
+                                    admin_id: engine.get_admin_id(),
+                                    record,
+                                    removed_at: Instant::now(),
+                                }),
+                        )
+                    })
+                    .await
+                else {
+                    warn!(target: "maintenance", lobby_key = %lobby_id, "Removed stopped lobby");
+                    continue;
+                };
+                if let Some(results) = results {
+                    closed_results.insert(lobby_id.clone(), results);
+                }
                 info!(
                     "Lobby closed: {} with {} players, {} questions played",
                     lobby_id, total_players, questions_played,
                 );
+                info!(
+                    lobby_key = %lobby_id,
+                    duration_secs = summary.duration_secs,
+                    peak_players = summary.peak_players,
+                    rounds_played = summary.rounds_played,
+                    messages_sent = summary.messages_sent,
+                    disconnects = summary.disconnects,
+                    "Lobby summary"
+                );
+                webhooks.send(WebhookEvent::LobbySummary {
+                    join_code: lobby_id.clone(),
+                    summary,
+                });
+                if let Some((name, jsonl)) = event_log
+                    && let Err(e) = store.save_event_log(&name, &jsonl).await
+                {
+                    warn!(target: "maintenance", log = %name, error = %e, "Failed to write event log");
+                }
             }
         }
     }
 }
 
 #[cfg(test)]
-mod tests {
+pub(crate) mod tests {
     use super::*;
     use crate::StorageConfig;
+    use crate::encryption::DataCipher;
     use std::fs::File;
     use std::io::Write;
+    use std::net::Ipv4Addr;
     use tempfile::tempdir;
 
-    async fn setup_test_state() -> (AppState, tempfile::TempDir) {
+    const TEST_IP: IpAddr = IpAddr::V4(Ipv4Addr::LOCALHOST);
+
+    pub(crate) async fn setup_test_state() -> (AppState, tempfile::TempDir) {
         let dir = tempdir().unwrap();
         let file_path = dir.path().join("questions.json");
 
@@ -1087,42 +3084,657 @@ mod tests {
             file_path: "questions.json".to_string(),
         };
 
-        let store = QuestionStore::new(&storage_config).await.unwrap();
-        let state = AppState::new(store, vec!["password".to_string()]);
-        (state, dir)
+        let store = QuestionStore::new(&storage_config, None).await.unwrap();
+        let state = AppState::new(
+            store,
+            vec!["password".to_string()],
+            JwtKeys::new(Some("test-secret"), 60).unwrap(),
+            LimitsConfig::default(),
+            Some("https://spektrum.example".into()),
+            WebhookDispatcher::default(),
+            None,
+        );
+        (state, dir)
+    }
+
+    #[tokio::test]
+    async fn test_create_lobby_logic() {
+        let (state, _dir) = setup_test_state().await;
+        let req = CreateLobbyRequest {
+            round_duration: Some(120),
+            set_id: None,
+            shuffle_per_player: true,
+            ..Default::default()
+        };
+
+        let res = create_lobby(&state, req, TEST_IP).await.unwrap();
+
+        assert_eq!(state.lobbies.len(), 1);
+        let lobby = state.lobby(&res.join_code).unwrap();
+        let (admin_id, round_duration, snapshot) = lobby
+            .call(|engine| {
+                (
+                    engine.get_admin_id(),
+                    engine.get_round_duration(),
+                    engine.to_snapshot(),
+                )
+            })
+            .await
+            .unwrap();
+
+        assert_eq!(admin_id, res.player_id);
+        assert_eq!(round_duration, 120);
+        assert!(snapshot.unwrap().shuffle_per_player);
+        assert_eq!(
+            res.session_token,
+            format!("{}:{}", res.join_code, res.player_id)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_create_lobby_invalid_duration() {
+        let (state, _dir) = setup_test_state().await;
+        let req = CreateLobbyRequest {
+            round_duration: Some(5), // Too short
+            set_id: None,
+            ..Default::default()
+        };
+
+        let res = create_lobby(&state, req, TEST_IP).await;
+        assert!(matches!(res, Err(ApiError::Validation(_))));
+
+        let req = CreateLobbyRequest {
+            upcoming_preview: Some(MAX_UPCOMING_PREVIEW + 1),
+            ..Default::default()
+        };
+        let res = create_lobby(&state, req, TEST_IP).await;
+        assert!(matches!(res, Err(ApiError::Validation(_))));
+    }
+
+    #[tokio::test]
+    async fn test_create_lobby_per_ip_limit() {
+        let (mut state, _dir) = setup_test_state().await;
+        state.limits = Arc::new(LimitsConfig {
+            max_lobbies_per_ip: 2,
+            ..LimitsConfig::default()
+        });
+        let new_req = || CreateLobbyRequest {
+            round_duration: None,
+            set_id: None,
+            ..Default::default()
+        };
+
+        let first = create_lobby(&state, new_req(), TEST_IP).await.unwrap();
+        create_lobby(&state, new_req(), TEST_IP).await.unwrap();
+        let res = create_lobby(&state, new_req(), TEST_IP).await;
+        assert!(matches!(res, Err(ApiError::TooManyLobbies)));
+
+        // Other addresses are unaffected
+        let other_ip = IpAddr::V4(Ipv4Addr::new(10, 0, 0, 1));
+        assert!(create_lobby(&state, new_req(), other_ip).await.is_ok());
+
+        // Removing a lobby frees its slot
+        state.lobbies.remove(&first.join_code);
+        state.lobby_creations.release(&first.join_code);
+        assert!(create_lobby(&state, new_req(), TEST_IP).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_server_limits() {
+        let (mut state, _dir) = setup_test_state().await;
+        state.limits = Arc::new(LimitsConfig {
+            max_lobbies: 2,
+            max_total_players: 1,
+            ..LimitsConfig::default()
+        });
+        let first = create_lobby(&state, CreateLobbyRequest::default(), TEST_IP)
+            .await
+            .unwrap();
+        let second = create_lobby(&state, CreateLobbyRequest::default(), TEST_IP)
+            .await
+            .unwrap();
+        let res = create_lobby(&state, CreateLobbyRequest::default(), TEST_IP).await;
+        assert!(matches!(res, Err(ApiError::ServerFull(_))));
+
+        let join = |join_code: &str, name: &str| JoinLobbyRequest {
+            join_code: join_code.to_string(),
+            name: name.into(),
+            ..Default::default()
+        };
+        join_lobby(&state, join(&first.join_code, "Player1"))
+            .await
+            .unwrap();
+        // The limit spans lobbies
+        let res = join_lobby(&state, join(&second.join_code, "Player2")).await;
+        assert!(matches!(res, Err(ApiError::ServerFull(_))));
+    }
+
+    #[tokio::test]
+    async fn test_list_public_lobbies() {
+        let (state, _dir) = setup_test_state().await;
+        create_lobby(&state, CreateLobbyRequest::default(), TEST_IP)
+            .await
+            .unwrap();
+        let named = create_lobby(
+            &state,
+            CreateLobbyRequest {
+                public: true,
+                lobby_name: Some("  Friday quiz ".into()),
+                ..Default::default()
+            },
+            TEST_IP,
+        )
+        .await
+        .unwrap();
+        let unnamed = create_lobby(
+            &state,
+            CreateLobbyRequest {
+                public: true,
+                ..Default::default()
+            },
+            TEST_IP,
+        )
+        .await
+        .unwrap();
+
+        let res = list_public_lobbies(&state).await.unwrap();
+        assert_eq!(
+            res.lobbies,
+            vec![
+                PublicLobbyInfo {
+                    join_code: named.join_code,
+                    name: Arc::from("Friday quiz"),
+                    player_count: 0,
+                    phase: GamePhase::Lobby,
+                },
+                PublicLobbyInfo {
+                    join_code: unnamed.join_code.clone(),
+                    name: Arc::from(format!("Lobby {}", unnamed.join_code)),
+                    player_count: 0,
+                    phase: GamePhase::Lobby,
+                },
+            ]
+        );
+
+        let too_long = CreateLobbyRequest {
+            public: true,
+            lobby_name: Some("x".repeat(MAX_LOBBY_NAME_CHARS + 1)),
+            ..Default::default()
+        };
+        let res = create_lobby(&state, too_long, TEST_IP).await;
+        assert!(matches!(res, Err(ApiError::Validation(_))));
+    }
+
+    #[tokio::test]
+    async fn test_create_lobby_custom_join_code() {
+        let (state, _dir) = setup_test_state().await;
+        let vanity = || CreateLobbyRequest {
+            join_code: Some(" party24 ".into()),
+            ..Default::default()
+        };
+
+        let first = create_lobby(&state, vanity(), TEST_IP).await.unwrap();
+        assert_eq!(first.join_code, "PARTY24");
+
+        // Taken codes fall back to the numeric generator
+        let second = create_lobby(&state, vanity(), TEST_IP).await.unwrap();
+        assert_ne!(second.join_code, "PARTY24");
+        assert!(second.join_code.chars().all(|c| c.is_ascii_digit()));
+
+        // Lookups are case-insensitive
+        let join_req = JoinLobbyRequest {
+            join_code: "Party24".into(),
+            name: "Player1".into(),
+            ..Default::default()
+        };
+        let joined = join_lobby(&state, join_req).await.unwrap();
+        assert_eq!(joined.join_code, "PARTY24");
+
+        for invalid in ["ab", "PARTY-24", "THISCODEISTOOLONG"] {
+            let req = CreateLobbyRequest {
+                join_code: Some(invalid.into()),
+                ..Default::default()
+            };
+            let res = create_lobby(&state, req, TEST_IP).await;
+            assert!(matches!(res, Err(ApiError::Validation(_))), "{invalid}");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lobby_qr_code() {
+        let (mut state, _dir) = setup_test_state().await;
+        let lobby = create_lobby(&state, CreateLobbyRequest::default(), TEST_IP)
+            .await
+            .unwrap();
+
+        let (content_type, svg) = lobby_qr_code(&state, &lobby.join_code, QrCodeFormat::Svg)
+            .await
+            .unwrap();
+        assert_eq!(content_type, "image/svg+xml");
+        assert!(String::from_utf8(svg).unwrap().contains("<svg"));
+        let (content_type, png) = lobby_qr_code(&state, &lobby.join_code, QrCodeFormat::Png)
+            .await
+            .unwrap();
+        assert_eq!(content_type, "image/png");
+        assert!(png.starts_with(b"\x89PNG"));
+
+        let res = lobby_qr_code(&state, "NOSUCHCODE", QrCodeFormat::Svg).await;
+        assert!(matches!(res, Err(ApiError::Lobby(_))));
+
+        state.frontend_base_url = None;
+        let res = lobby_qr_code(&state, &lobby.join_code, QrCodeFormat::Svg).await;
+        assert!(matches!(res, Err(ApiError::NotFound(_))));
+    }
+
+    #[tokio::test]
+    async fn test_list_admin_lobbies() {
+        let (state, _dir) = setup_test_state().await;
+        let lobby = create_lobby(&state, CreateLobbyRequest::default(), TEST_IP)
+            .await
+            .unwrap();
+        let join_req = JoinLobbyRequest {
+            join_code: lobby.join_code.clone(),
+            name: "Player1".into(),
+            ..Default::default()
+        };
+        join_lobby(&state, join_req).await.unwrap();
+
+        let res = list_admin_lobbies(&state).await.unwrap();
+        assert_eq!(
+            res.lobbies,
+            vec![AdminLobbyInfo {
+                join_code: lobby.join_code,
+                player_count: 1,
+                questions_played: 0,
+                phase: GamePhase::Lobby,
+                uptime_secs: 0,
+                idle_secs: Some(0),
+            }]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_lobby_stats() {
+        let (state, _dir) = setup_test_state().await;
+        let lobby = create_lobby(&state, CreateLobbyRequest::default(), TEST_IP)
+            .await
+            .unwrap();
+        let join_req = JoinLobbyRequest {
+            join_code: lobby.join_code.clone(),
+            name: "Player1".into(),
+            ..Default::default()
+        };
+        let player = join_lobby(&state, join_req).await.unwrap();
+
+        let stats = lobby_stats(&state, &lobby.join_code, Some(&lobby.session_token))
+            .await
+            .unwrap();
+        assert_eq!(stats.questions_played, 0);
+        assert_eq!(stats.total_answers, 0);
+        assert!(!stats.admin_connected);
+        assert_eq!(stats.players.len(), 1);
+        assert_eq!(stats.players[0].name.as_ref(), "Player1");
+        assert!(!stats.players[0].connected);
+
+        let operator = state.jwt.issue().token;
+        assert!(
+            lobby_stats(&state, &lobby.join_code, Some(&operator))
+                .await
+                .is_ok()
+        );
+
+        for token in [None, Some(player.session_token.as_str()), Some("junk")] {
+            let res = lobby_stats(&state, &lobby.join_code, token).await;
+            assert!(matches!(res, Err(ApiError::Unauthorized)));
+        }
+    }
+
+    #[tokio::test]
+    async fn test_lobby_results() {
+        let (state, _dir) = setup_test_state().await;
+        let lobby = create_lobby(&state, CreateLobbyRequest::default(), TEST_IP)
+            .await
+            .unwrap();
+        let join_req = JoinLobbyRequest {
+            join_code: lobby.join_code.clone(),
+            name: "Player1".into(),
+            ..Default::default()
+        };
+        let player = join_lobby(&state, join_req).await.unwrap();
+        let admin = Some(lobby.session_token.as_str());
+        let res = lobby_results(&state, &lobby.join_code, admin).await;
+        assert!(matches!(res, Err(ApiError::NotFound(_))));
+
+        let handle = state.lobby(&lobby.join_code).unwrap();
+        let admin_id = lobby.player_id;
+        handle
+            .call(move |engine| {
+                for action in [
+                    GameAction::StartGame,
+                    GameAction::StartRound,
+                    GameAction::EndRound,
+                    GameAction::EndGame {
+                        reason: Arc::from("Done"),
+                    },
+                ] {
+                    engine.process_event(GameEvent {
+                        context: EventContext {
+                            sender_id: admin_id,
+                            timestamp: Instant::now(),
+                        },
+                        action,
+                    });
+                }
+            })
+            .await
+            .unwrap();
+        let record = lobby_results(&state, &lobby.join_code, admin)
+            .await
+            .unwrap();
+        assert_eq!(record.final_scores[0].name.as_ref(), "Player1");
+        assert_eq!(record.rounds.len(), 1);
+        let csv = results_csv(&record).unwrap();
+        assert_eq!(
+            csv,
+            format!(
+                "rank,name,score,1. {}\n1,Player1,0,0\n",
+                record.rounds[0].title
+            )
+        );
+        let res = lobby_results(&state, &lobby.join_code, Some(&player.session_token)).await;
+        assert!(matches!(res, Err(ApiError::Unauthorized)));
+
+        // Still available for a while after the lobby is removed.
+        state.lobbies.remove(&lobby.join_code);
+        state.closed_results.insert(
+            lobby.join_code.clone(),
+            ClosedLobbyResults {
+                admin_id,
+                record: record.clone(),
+                removed_at: Instant::now(),
+            },
+        );
+        let closed = lobby_results(&state, &lobby.join_code, admin)
+            .await
+            .unwrap();
+        assert_eq!(closed, record);
+        let res = lobby_results(&state, &lobby.join_code, Some(&player.session_token)).await;
+        assert!(matches!(res, Err(ApiError::Unauthorized)));
+        state
+            .closed_results
+            .get_mut(&lobby.join_code)
+            .unwrap()
+            .removed_at -= RESULTS_GRACE;
+        let res = lobby_results(&state, &lobby.join_code, admin).await;
+        assert!(matches!(res, Err(ApiError::Lobby(_))));
+    }
+
+    #[tokio::test]
+    async fn test_lobby_preload() {
+        let (state, _dir) = setup_test_state().await;
+        let lobby = create_lobby(&state, CreateLobbyRequest::default(), TEST_IP)
+            .await
+            .unwrap();
+        let join_req = JoinLobbyRequest {
+            join_code: lobby.join_code.clone(),
+            name: "Player1".into(),
+            ..Default::default()
+        };
+        let player = join_lobby(&state, join_req).await.unwrap();
+
+        let res = lobby_preload(&state, &lobby.join_code, Some(&lobby.session_token))
+            .await
+            .unwrap();
+        assert_eq!(
+            res.upcoming,
+            [PreloadMedia {
+                question_id: 1,
+                media_id: 1,
+                youtube_id: Some(Arc::from("test123")),
+                image_urls: Vec::new(),
+            }]
+        );
+
+        // Injected questions have no video to buffer.
+        state
+            .lobby(&lobby.join_code)
+            .unwrap()
+            .call(|engine| {
+                engine.process_event(GameEvent {
+                    context: EventContext {
+                        sender_id: engine.get_admin_id(),
+                        timestamp: Instant::now(),
+                    },
+                    action: GameAction::InjectQuestion {
+                        question_text: Arc::from("Whose birthday is it?"),
+                        options: vec![
+                            GameQuestionOption {
+                                option: Arc::from("Alice"),
+                                is_correct: true,
+                            },
+                            GameQuestionOption {
+                                option: Arc::from("Bob"),
+                                is_correct: false,
+                            },
+                        ],
+                    },
+                })
+            })
+            .await
+            .unwrap();
+        let res = lobby_preload(&state, &lobby.join_code, Some(&lobby.session_token))
+            .await
+            .unwrap();
+        assert_eq!(res.upcoming[0].youtube_id, None);
+        let json = serde_json::to_value(&res.upcoming[0]).unwrap();
+        assert!(json.get("youtube_id").is_none());
+
+        // The manifest follows the lobby's own preview depth.
+        state
+            .lobby(&lobby.join_code)
+            .unwrap()
+            .call(|engine| engine.set_upcoming_preview(0))
+            .await
+            .unwrap();
+        let res = lobby_preload(&state, &lobby.join_code, Some(&lobby.session_token))
+            .await
+            .unwrap();
+        assert!(res.upcoming.is_empty());
+
+        let res = lobby_preload(&state, &lobby.join_code, Some(&player.session_token)).await;
+        assert!(matches!(res, Err(ApiError::Unauthorized)));
+        let res = lobby_preload(&state, "nope", Some(&lobby.session_token)).await;
+        assert!(matches!(res, Err(ApiError::Lobby(_))));
+    }
+
+    #[tokio::test]
+    async fn test_persist_and_restore_lobbies() {
+        let (state, dir) = setup_test_state().await;
+        let open = create_lobby(&state, CreateLobbyRequest::default(), TEST_IP)
+            .await
+            .unwrap();
+        let closed = create_lobby(&state, CreateLobbyRequest::default(), TEST_IP)
+            .await
+            .unwrap();
+        close_lobby(&state, &closed.join_code, CloseLobbyRequest::default())
+            .await
+            .unwrap();
+        let join_req = JoinLobbyRequest {
+            join_code: open.join_code.clone(),
+            name: "Player1".into(),
+            ..Default::default()
+        };
+        let player = join_lobby(&state, join_req).await.unwrap();
+
+        assert_eq!(persist_lobbies(&state).await.unwrap(), 1);
+
+        // A fresh server over the same storage picks the open lobby back up
+        let store = QuestionStore::new(
+            &StorageConfig::Filesystem {
+                base_path: dir.path().to_path_buf(),
+                file_path: "questions.json".into(),
+            },
+            None,
+        )
+        .await
+        .unwrap();
+        let restarted = AppState::new(
+            store,
+            Vec::new(),
+            JwtKeys::new(None, 60).unwrap(),
+            LimitsConfig::default(),
+            None,
+            WebhookDispatcher::default(),
+            None,
+        );
+        assert_eq!(restore_lobbies(&restarted).await.unwrap(), 1);
+        let lobby = restarted.lobby(&open.join_code).unwrap();
+        let (player_id, admin_id) = (player.player_id, open.player_id);
+        let (has_player, is_admin) = lobby
+            .call(move |engine| (engine.has_player(&player_id), engine.is_admin(&admin_id)))
+            .await
+            .unwrap();
+        assert!(has_player);
+        assert!(is_admin);
+        assert!(!restarted.lobbies.contains_key(&closed.join_code));
+        // The restored lobby still counts towards its creator's limit.
+        assert_eq!(
+            restarted.lobby_creations.creator(&open.join_code),
+            Some(TEST_IP)
+        );
+        assert_eq!(
+            restarted.lobby_creations.counts.get(&TEST_IP).map(|c| *c),
+            Some(1)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_event_log() {
+        let (state, dir) = setup_test_state().await;
+        let state = state.with_event_log(true);
+        let lobby = create_lobby(&state, CreateLobbyRequest::default(), TEST_IP)
+            .await
+            .unwrap();
+        assert_eq!(flush_event_logs(&state).await, 0);
+
+        let admin_id = lobby.player_id;
+        state
+            .lobby(&lobby.join_code)
+            .unwrap()
+            .call(move |engine| {
+                engine.process_event(GameEvent {
+                    context: EventContext {
+                        sender_id: admin_id,
+                        timestamp: Instant::now(),
+                    },
+                    action: GameAction::StartGame,
+                })
+            })
+            .await
+            .unwrap();
+        assert_eq!(flush_event_logs(&state).await, 1);
+        // Nothing new to write
+        assert_eq!(flush_event_logs(&state).await, 0);
+
+        let log_path = std::fs::read_dir(dir.path().join("event_logs"))
+            .unwrap()
+            .next()
+            .unwrap()
+            .unwrap()
+            .path();
+        let log = std::fs::read_to_string(log_path).unwrap();
+        let lines: Vec<serde_json::Value> = log
+            .lines()
+            .map(|line| serde_json::from_str(line).unwrap())
+            .collect();
+        assert_eq!(lines.len(), 1);
+        assert_eq!(lines[0]["action"]["type"], "StartGame");
+        assert_eq!(lines[0]["phase_before"], "lobby");
+        assert_eq!(lines[0]["phase_after"], "score");
     }
 
     #[tokio::test]
-    async fn test_create_lobby_logic() {
+    async fn test_game_history() {
         let (state, _dir) = setup_test_state().await;
-        let req = CreateLobbyRequest {
-            round_duration: Some(120),
-            set_id: None,
-        };
-
-        let res = create_lobby(&state, req).await.unwrap();
-
-        assert_eq!(state.lobbies.len(), 1);
-        let lobby = state.lobbies.get(&res.join_code).unwrap();
+        assert!(
+            list_game_history(&state, None)
+                .await
+                .unwrap()
+                .games
+                .is_empty()
+        );
 
-        assert_eq!(lobby.get_admin_id(), res.player_id);
-        assert_eq!(lobby.get_round_duration(), 120);
+        let record = |id: &str| GameRecord {
+            id: id.to_string(),
+            join_code: Arc::from("ABC123"),
+            set_name: None,
+            dataset: None,
+            started_at: "2025-03-01T19:30:00Z".to_string(),
+            ended_at: Some("2025-03-01T20:30:00Z".to_string()),
+            end_reason: Some(Arc::from("Done")),
+            rounds: Vec::new(),
+            final_scores: Vec::new(),
+        };
+        for id in ["20250301193000000-ABC123", "20250308193000000-ABC123"] {
+            state.store.save_game_record(&record(id)).await.unwrap();
+        }
         assert_eq!(
-            res.session_token,
-            format!("{}:{}", res.join_code, res.player_id)
+            list_game_history(&state, None).await.unwrap().games,
+            ["20250308193000000-ABC123", "20250301193000000-ABC123"]
         );
+        assert_eq!(
+            game_history(&state, None, "20250301193000000-ABC123")
+                .await
+                .unwrap(),
+            record("20250301193000000-ABC123")
+        );
+        assert!(matches!(
+            game_history(&state, None, "20250315193000000-ABC123").await,
+            Err(ApiError::NotFound(_))
+        ));
+        assert!(matches!(
+            game_history(&state, None, "../accounts").await,
+            Err(ApiError::NotFound(_))
+        ));
     }
 
     #[tokio::test]
-    async fn test_create_lobby_invalid_duration() {
+    async fn test_close_lobby() {
         let (state, _dir) = setup_test_state().await;
-        let req = CreateLobbyRequest {
-            round_duration: Some(5), // Too short
-            set_id: None,
-        };
-
-        let res = create_lobby(&state, req).await;
-        assert!(matches!(res, Err(ApiError::Validation(_))));
+        let lobby = create_lobby(&state, CreateLobbyRequest::default(), TEST_IP)
+            .await
+            .unwrap();
+
+        let res = close_lobby(&state, &lobby.join_code, CloseLobbyRequest::default())
+            .await
+            .unwrap();
+        assert_eq!(res.join_code, lobby.join_code);
+        let (phase, finished) = state
+            .lobby(&lobby.join_code)
+            .unwrap()
+            .call(|engine| (engine.get_phase(), engine.is_finished()))
+            .await
+            .unwrap();
+        assert_eq!(phase, GamePhase::GameClosed);
+        assert!(finished);
+
+        let res = close_lobby(&state, &lobby.join_code, CloseLobbyRequest::default()).await;
+        assert!(matches!(res, Err(ApiError::Lobby(_))));
+        let res = close_lobby(&state, "NOSUCHCODE", CloseLobbyRequest::default()).await;
+        assert!(matches!(res, Err(ApiError::Lobby(_))));
+
+        // The reason is optional, so the request may have no body at all.
+        let lobby = create_lobby(&state, CreateLobbyRequest::default(), TEST_IP)
+            .await
+            .unwrap();
+        let res = close_lobby_handler(
+            State(state.clone()),
+            AdminSession::default(),
+            Path(lobby.join_code),
+            None,
+        )
+        .await;
+        assert!(res.is_ok());
     }
 
     #[tokio::test]
@@ -1132,32 +3744,197 @@ mod tests {
         let create_req = CreateLobbyRequest {
             round_duration: None,
             set_id: None,
+            ..Default::default()
         };
-        let create_res = create_lobby(&state, create_req).await.unwrap();
+        let create_res = create_lobby(&state, create_req, TEST_IP).await.unwrap();
 
         // Now, try to join it
         let join_code = create_res.join_code.clone();
         let join_req = JoinLobbyRequest {
             join_code: create_res.join_code,
             name: "Player1".to_string(),
+            ..Default::default()
         };
         let join_res = join_lobby(&state, join_req).await.unwrap();
 
-        let lobby = state.lobbies.get(&join_code).unwrap();
-        assert_eq!(lobby.get_player_count(), 1); // Player1 (admin is separate)
-        assert!(lobby.has_player(&join_res.player_id));
+        let lobby = state.lobby(&join_code).unwrap();
+        let player_id = join_res.player_id;
+        let (player_count, has_player) = lobby
+            .call(move |engine| (engine.get_player_count(), engine.has_player(&player_id)))
+            .await
+            .unwrap();
+        assert_eq!(player_count, 1); // Player1 (admin is separate)
+        assert_eq!(lobby.player_count(), 1);
+        assert!(has_player);
         assert_eq!(
             join_res.session_token,
             format!("{}:{}", join_code, join_res.player_id)
         );
     }
 
+    #[tokio::test]
+    async fn test_join_locked_lobby() {
+        let (state, _dir) = setup_test_state().await;
+        let create_res = create_lobby(&state, CreateLobbyRequest::default(), TEST_IP)
+            .await
+            .unwrap();
+        let join_req = |name: &str| JoinLobbyRequest {
+            join_code: create_res.join_code.clone(),
+            name: name.to_string(),
+            ..Default::default()
+        };
+        let joined = join_lobby(&state, join_req("Player1")).await.unwrap();
+
+        let mut admin_conn = WsConnection::new(None, Heartbeat::default());
+        admin_conn.player_id = Some(create_res.player_id);
+        admin_conn.lobby_key = Some(create_res.join_code.clone());
+        let admin_action = |action| ClientMessage::AdminAction { action };
+        dispatch_game_action(
+            admin_action(AdminAction::LockLobby { locked: true }),
+            &admin_conn,
+            &state,
+        )
+        .await;
+        let res = join_lobby(&state, join_req("Player2")).await;
+        assert!(matches!(res, Err(ApiError::Lobby(msg)) if msg == "Lobby is locked."));
+
+        // Players already in the lobby can still reconnect.
+        let check_req = CheckSessionsRequest {
+            sessions: vec![SessionInfo {
+                player_id: joined.player_id,
+            }],
+        };
+        let res = check_sessions(&state, check_req).await.unwrap();
+        assert_eq!(res.valid_sessions.len(), 1);
+        let session = &res.valid_sessions[0];
+        assert_eq!(session.join_code, create_res.join_code);
+        assert_eq!(session.name.as_ref(), "Player1");
+        assert_eq!(session.phase, GamePhase::Lobby);
+        assert!(!session.is_admin);
+
+        dispatch_game_action(admin_action(AdminAction::UnlockLobby), &admin_conn, &state).await;
+        assert!(join_lobby(&state, join_req("Player2")).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_join_reclaims_disconnected_player() {
+        let (state, _dir) = setup_test_state().await;
+        let create_res = create_lobby(&state, CreateLobbyRequest::default(), TEST_IP)
+            .await
+            .unwrap();
+        let join_req = || JoinLobbyRequest {
+            join_code: create_res.join_code.clone(),
+            name: "Player1".to_string(),
+            ..Default::default()
+        };
+        let joined = join_lobby(&state, join_req()).await.unwrap();
+        let player_id = joined.player_id;
+        let lobby = state.lobby(&create_res.join_code).unwrap();
+        let (tx, _rx) = tokio::sync::mpsc::channel(8);
+        lobby
+            .call(move |engine| {
+                let conn_id = Uuid::new_v4();
+                engine.update_player_connection(player_id, tx, conn_id);
+                engine.clear_player_connection(player_id, conn_id);
+            })
+            .await
+            .unwrap();
+
+        // Not without the host's approval, unless the lobby allows it.
+        let res = join_lobby(&state, join_req()).await;
+        assert!(matches!(res, Err(ApiError::Lobby(_))));
+        lobby
+            .call(|engine| engine.set_open_reclaims(true))
+            .await
+            .unwrap();
+        let rejoined = join_lobby(&state, join_req()).await.unwrap();
+        assert_eq!(rejoined.player_id, player_id);
+        assert_eq!(rejoined.session_token, joined.session_token);
+    }
+
+    #[tokio::test]
+    async fn test_batch_join_lobby() {
+        let (state, _dir) = setup_test_state().await;
+        let create_res = create_lobby(&state, CreateLobbyRequest::default(), TEST_IP)
+            .await
+            .unwrap();
+        let batch_req = |names: &[&str]| BatchJoinLobbyRequest {
+            join_code: create_res.join_code.clone(),
+            names: names.iter().map(|name| name.to_string()).collect(),
+            ..Default::default()
+        };
+        let lobby = state.lobby(&create_res.join_code).unwrap();
+        let admin_id = create_res.player_id;
+        lobby
+            .call(move |engine| {
+                engine.process_event(GameEvent {
+                    context: EventContext {
+                        sender_id: admin_id,
+                        timestamp: Instant::now(),
+                    },
+                    action: GameAction::LockLobby { locked: true },
+                })
+            })
+            .await
+            .unwrap();
+
+        // Players registered by the host join even a locked lobby.
+        let res = batch_join_lobby(
+            &state,
+            batch_req(&["Ann", "Bo", "Ann", "x"]),
+            Some(&create_res.session_token),
+        )
+        .await
+        .unwrap();
+        let names: Vec<_> = res.players.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["Ann", "Bo"]);
+        let rejected: Vec<_> = res.rejected.iter().map(|r| r.name.as_str()).collect();
+        assert_eq!(rejected, ["Ann", "x"]);
+        for player in &res.players {
+            assert_eq!(
+                player.session_token,
+                format!("{}:{}", create_res.join_code, player.player_id)
+            );
+        }
+        let player_names = lobby
+            .call(move |engine| {
+                let mut names: Vec<_> = engine
+                    .get_detailed_stats()
+                    .players
+                    .into_iter()
+                    .map(|p| p.name.to_string())
+                    .collect();
+                names.sort();
+                names
+            })
+            .await
+            .unwrap();
+        assert_eq!(player_names, ["Ann", "Bo"]);
+        let player_token = res.players[0].session_token.clone();
+
+        let operator = state.jwt.issue().token;
+        let res = batch_join_lobby(&state, batch_req(&["Cid"]), Some(&operator)).await;
+        assert_eq!(res.unwrap().players.len(), 1);
+
+        for token in [None, Some(player_token.as_str()), Some("junk")] {
+            let res = batch_join_lobby(&state, batch_req(&["Dan"]), token).await;
+            assert!(matches!(res, Err(ApiError::Unauthorized)));
+        }
+
+        let res = batch_join_lobby(&state, batch_req(&[]), Some(&operator)).await;
+        assert!(matches!(res, Err(ApiError::Validation(_))));
+        let too_many = vec!["Name"; MAX_BATCH_JOIN_NAMES + 1];
+        let res = batch_join_lobby(&state, batch_req(&too_many), Some(&operator)).await;
+        assert!(matches!(res, Err(ApiError::Validation(_))));
+    }
+
     #[tokio::test]
     async fn test_join_lobby_invalid_code() {
         let (state, _dir) = setup_test_state().await;
         let join_req = JoinLobbyRequest {
             join_code: "123456".to_string(),
             name: "Player1".to_string(),
+            ..Default::default()
         };
 
         let res = join_lobby(&state, join_req).await;
@@ -1170,17 +3947,562 @@ mod tests {
         let create_req = CreateLobbyRequest {
             round_duration: None,
             set_id: None,
+            ..Default::default()
         };
-        let create_res = create_lobby(&state, create_req).await.unwrap();
+        let create_res = create_lobby(&state, create_req, TEST_IP).await.unwrap();
 
         // Try to join with a name that is too short
         let join_req = JoinLobbyRequest {
             join_code: create_res.join_code.clone(),
             name: "a".to_string(),
+            ..Default::default()
+        };
+
+        let res = join_lobby(&state, join_req).await;
+        assert!(matches!(res, Err(ApiError::Validation(_))));
+
+        let join_req = JoinLobbyRequest {
+            join_code: create_res.join_code.clone(),
+            name: "a".to_string(),
+            locale: Some(Locale::Sv),
+            ..Default::default()
         };
+        let res = join_lobby(&state, join_req).await;
+        assert!(
+            matches!(res, Err(ApiError::Validation(msg)) if msg == "Namnet måste vara minst 2 tecken långt.")
+        );
 
+        let state = state.with_banned_words(vec!["bingo".into()]);
+        let join_req = JoinLobbyRequest {
+            join_code: create_res.join_code.clone(),
+            name: "B1NG0 Bob".to_string(),
+            ..Default::default()
+        };
         let res = join_lobby(&state, join_req).await;
+        assert!(
+            matches!(res, Err(ApiError::Validation(msg)) if msg == "This name is not allowed.")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_player_accounts() {
+        let (state, _dir) = setup_test_state().await;
+        let account_req = || AccountRequest {
+            name: "Regular".into(),
+            pin: "1234".into(),
+        };
+        assert!(matches!(
+            create_account(&state, account_req()).await,
+            Err(ApiError::NotFound(_))
+        ));
+
+        let accounts = AccountStore::load(state.store.clone()).await.unwrap();
+        let state = state.with_accounts(accounts);
+        let created = create_account(&state, account_req()).await.unwrap();
+        assert!(matches!(
+            create_account(&state, account_req()).await,
+            Err(ApiError::Validation(_))
+        ));
+        let wrong_pin = AccountRequest {
+            pin: "0000".into(),
+            ..account_req()
+        };
+        assert!(matches!(
+            account_login(&state, wrong_pin).await,
+            Err(ApiError::Unauthorized)
+        ));
+        let logged_in = account_login(&state, account_req()).await.unwrap();
+        assert_eq!(logged_in.account_id, created.account_id);
+
+        let join_code = create_lobby(&state, CreateLobbyRequest::default(), TEST_IP)
+            .await
+            .unwrap()
+            .join_code;
+        let squatter = JoinLobbyRequest {
+            join_code: join_code.clone(),
+            name: "regular".into(),
+            ..Default::default()
+        };
+        assert!(matches!(
+            join_lobby(&state, squatter).await,
+            Err(ApiError::Validation(_))
+        ));
+
+        let join = |token: &str| JoinLobbyRequest {
+            join_code: join_code.clone(),
+            account_token: Some(token.to_string()),
+            ..Default::default()
+        };
+        let first = join_lobby(&state, join(&created.account_token))
+            .await
+            .unwrap();
+        // Both tokens are valid and reclaim the same player.
+        let second = join_lobby(&state, join(&logged_in.account_token))
+            .await
+            .unwrap();
+        assert_eq!(first.player_id, second.player_id);
+        assert!(matches!(
+            join_lobby(&state, join("not-a-token")).await,
+            Err(ApiError::Unauthorized)
+        ));
+
+        // Accounts survive a restart.
+        let reloaded = AccountStore::load(state.store.clone()).await.unwrap();
+        assert!(reloaded.is_reserved("REGULAR"));
+        assert!(reloaded.verify_token(&created.account_token).is_some());
+    }
+
+    #[tokio::test]
+    async fn test_admin_login() {
+        let (state, _dir) = setup_test_state().await;
+        let res = admin_login(
+            &state,
+            AdminLoginRequest {
+                password: "wrong".to_string(),
+            },
+        )
+        .await;
+        assert!(matches!(res, Err(ApiError::Unauthorized)));
+
+        let issued = admin_login(
+            &state,
+            AdminLoginRequest {
+                password: "password".to_string(),
+            },
+        )
+        .await
+        .unwrap();
+        assert!(state.jwt.verify(&issued.token).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_named_datasets() {
+        let (state, dir) = setup_test_state().await;
+        let sv_dir = dir.path().join("sv");
+        std::fs::create_dir(&sv_dir).unwrap();
+        let mut data = serde_json::to_value(state.store.get_stored_data().await.unwrap()).unwrap();
+        data["questions"][0]["question_text"] = "Vilken färg?".into();
+        std::fs::write(sv_dir.join("questions.json"), data.to_string()).unwrap();
+        let sv_storage = StorageConfig::Filesystem {
+            base_path: sv_dir,
+            file_path: "questions.json".into(),
+        };
+        let sv_dataset = || async {
+            Dataset {
+                store: Arc::new(QuestionStore::new(&sv_storage, None).await.unwrap()),
+                admin_passwords: vec!["svpassword".into()],
+            }
+        };
+        let state = state.with_datasets(HashMap::from([("sv".into(), sv_dataset().await)]));
+
+        let issued = admin_login(
+            &state,
+            AdminLoginRequest {
+                password: "svpassword".into(),
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(state.jwt.verify(&issued.token), Ok(Some("sv".into())));
+        let sv_data = serde_json::to_value(get_stored_data(&state, Some("sv")).await.unwrap());
+        assert_eq!(
+            sv_data.unwrap()["questions"][0]["question_text"],
+            "Vilken färg?"
+        );
+        let default_data = serde_json::to_value(get_stored_data(&state, None).await.unwrap());
+        assert!(default_data.unwrap()["questions"][0]["question_text"].is_null());
+        assert!(matches!(
+            get_stored_data(&state, Some("en")).await,
+            Err(ApiError::Validation(_))
+        ));
+        assert_eq!(list_sets(&state, None).await.unwrap().datasets, ["sv"]);
+
+        let session = AdminSession {
+            dataset: Some("sv".into()),
+        };
+        assert!(matches!(
+            session.require_global(),
+            Err(ApiError::Forbidden(_))
+        ));
+        assert!(AdminSession::default().require_global().is_ok());
+
+        let req = CreateLobbyRequest {
+            dataset: Some("sv".into()),
+            ..Default::default()
+        };
+        let lobby = create_lobby(&state, req, TEST_IP).await.unwrap();
+        let dataset = state
+            .lobby(&lobby.join_code)
+            .unwrap()
+            .call(|engine| engine.dataset().map(str::to_string))
+            .await
+            .unwrap();
+        assert_eq!(dataset.as_deref(), Some("sv"));
+        // Dataset admins can't inspect lobbies, not even their dataset's.
+        let res = lobby_stats(&state, &lobby.join_code, Some(&issued.token)).await;
+        assert!(matches!(res, Err(ApiError::Unauthorized)));
+        let req = CreateLobbyRequest {
+            dataset: Some("en".into()),
+            ..Default::default()
+        };
+        let res = create_lobby(&state, req, TEST_IP).await;
+        assert!(matches!(res, Err(ApiError::Validation(_))));
+
+        // Restoring needs the lobby's dataset to still be configured.
+        persist_lobbies(&state).await.unwrap();
+        let restarted = |datasets| {
+            let state = state.clone();
+            AppState {
+                lobbies: Arc::new(DashMap::new()),
+                datasets: Arc::new(datasets),
+                ..state
+            }
+        };
+        assert_eq!(
+            restore_lobbies(&restarted(HashMap::new())).await.unwrap(),
+            0
+        );
+        let with_sv = restarted(HashMap::from([("sv".into(), sv_dataset().await)]));
+        assert_eq!(restore_lobbies(&with_sv).await.unwrap(), 1);
+
+        // Closing a game mid-way records it in the lobby's dataset.
+        state
+            .lobby(&lobby.join_code)
+            .unwrap()
+            .call(|engine| {
+                engine.process_event(GameEvent {
+                    context: EventContext {
+                        sender_id: engine.get_admin_id(),
+                        timestamp: Instant::now(),
+                    },
+                    action: GameAction::StartGame,
+                })
+            })
+            .await
+            .unwrap();
+        close_lobby(&state, &lobby.join_code, CloseLobbyRequest::default())
+            .await
+            .unwrap();
+        let mut games = Vec::new();
+        for _ in 0..50 {
+            games = list_game_history(&state, Some("sv")).await.unwrap().games;
+            if !games.is_empty() {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        assert_eq!(games.len(), 1);
+        assert!(games[0].ends_with(&format!("-{}", lobby.join_code)));
+        assert!(
+            list_game_history(&state, None)
+                .await
+                .unwrap()
+                .games
+                .is_empty()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_read_only_mode() {
+        let (state, _dir) = setup_test_state().await;
+        let state = state.with_read_only(true);
+        let stored_data = get_stored_data(&state, None).await.unwrap();
+        let res = set_stored_data(&state, None, SetStoredDataRequest { stored_data }).await;
+        assert!(matches!(res, Err(ApiError::Forbidden(_))));
+        let export = export_questions(&state, None).await.unwrap().1;
+        let res = import_questions(&state, None, &export).await;
+        assert!(matches!(res, Err(ApiError::Forbidden(_))));
+        let res = upload_audio_clip(&state, None, 1, b"OggS clip").await;
+        assert!(matches!(res, Err(ApiError::Forbidden(_))));
+        let res = delete_character_image(&state, None, "bob").await;
+        assert!(matches!(res, Err(ApiError::Forbidden(_))));
+        // Games are unaffected.
+        assert!(
+            create_lobby(&state, CreateLobbyRequest::default(), TEST_IP)
+                .await
+                .is_ok()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delete_character_image() {
+        let (state, dir) = setup_test_state().await;
+        let image_path = dir.path().join("img/bob.avif");
+        state
+            .store
+            .store_character_image("bob", b"image")
+            .await
+            .unwrap();
+
+        let mut data: serde_json::Value =
+            serde_json::to_value(state.store.get_stored_data().await.unwrap()).unwrap();
+        data["characters"] = serde_json::json!([
+            {"id": 1, "name": "bob", "image_url": "/img/bob.avif"}
+        ]);
+        let data: StoredData = serde_json::from_value(data).unwrap();
+        state.store.set_stored_data(data.clone()).await.unwrap();
+        let res = delete_character_image(&state, None, "bob").await;
+        assert!(matches!(res, Err(ApiError::Validation(_))));
+        assert!(image_path.exists());
+
+        let mut data = serde_json::to_value(data).unwrap();
+        data["characters"] = serde_json::json!([]);
+        state
+            .store
+            .set_stored_data(serde_json::from_value(data).unwrap())
+            .await
+            .unwrap();
+        let res = delete_character_image(&state, None, "bob").await.unwrap();
+        assert_eq!(res.image_url, "/img/bob.avif");
+        assert!(!image_path.exists());
+
+        let res = delete_character_image(&state, None, "../questions").await;
+        assert!(matches!(res, Err(ApiError::Validation(_))));
+    }
+
+    #[tokio::test]
+    async fn test_audio_clips() {
+        let (state, dir) = setup_test_state().await;
+        let res = audio_clip(&state, None, 1).await;
+        assert!(matches!(res, Err(ApiError::NotFound(_))));
+
+        let res = upload_audio_clip(&state, None, 1, b"OggS clip")
+            .await
+            .unwrap();
+        assert_eq!(res.audio_url, "/api/audio/1");
+        let (format, data) = audio_clip(&state, None, 1).await.unwrap();
+        assert_eq!(format, AudioFormat::Ogg);
+        assert_eq!(data, b"OggS clip");
+
+        // Re-uploading in another format replaces the clip.
+        let res = upload_audio_clip(&state, None, 1, b"ID3 clip")
+            .await
+            .unwrap();
+        assert_eq!(res.audio_url, "/api/audio/1");
+        assert!(!dir.path().join("audio/1.ogg").exists());
+        let (format, _) = audio_clip(&state, None, 1).await.unwrap();
+        assert_eq!(format, AudioFormat::Mp3);
+
+        let res = upload_audio_clip(&state, None, 1, b"RIFF wav").await;
+        assert!(matches!(res, Err(ApiError::UnsupportedMediaType)));
+        let res = upload_audio_clip(&state, None, 99, b"OggS clip").await;
+        assert!(matches!(res, Err(ApiError::Validation(_))));
+        let too_large = vec![0; state.limits.max_audio_bytes + 1];
+        let res = upload_audio_clip(&state, None, 1, &too_large).await;
+        assert!(matches!(res, Err(ApiError::PayloadTooLarge(_))));
+    }
+
+    #[tokio::test]
+    async fn test_named_dataset_audio_clips() {
+        let (state, dir) = setup_test_state().await;
+        let sv_dir = dir.path().join("sv");
+        std::fs::create_dir(&sv_dir).unwrap();
+        std::fs::copy(
+            dir.path().join("questions.json"),
+            sv_dir.join("questions.json"),
+        )
+        .unwrap();
+        let sv_storage = StorageConfig::Filesystem {
+            base_path: sv_dir.clone(),
+            file_path: "questions.json".into(),
+        };
+        let sv_dataset = Dataset {
+            store: Arc::new(QuestionStore::new(&sv_storage, None).await.unwrap()),
+            admin_passwords: vec!["svpassword".into()],
+        };
+        let state = state.with_datasets(HashMap::from([("sv".into(), sv_dataset)]));
+
+        let res = upload_audio_clip(&state, Some("sv"), 1, b"OggS clip")
+            .await
+            .unwrap();
+        assert_eq!(res.audio_url, "/api/audio/1?dataset=sv");
+        assert!(sv_dir.join("audio/1.ogg").exists());
+        let (format, data) = audio_clip(&state, Some("sv"), 1).await.unwrap();
+        assert_eq!(format, AudioFormat::Ogg);
+        assert_eq!(data, b"OggS clip");
+
+        let res = audio_clip(&state, None, 1).await;
+        assert!(matches!(res, Err(ApiError::NotFound(_))));
+        let res = audio_clip(&state, Some("en"), 1).await;
+        assert!(matches!(res, Err(ApiError::Validation(_))));
+    }
+
+    #[tokio::test]
+    async fn test_refresh_questions_when_changed() {
+        let (state, dir) = setup_test_state().await;
+        assert!(!state.store.refresh_if_changed().await.unwrap());
+
+        // Another instance adds a second question.
+        let path = dir.path().join("questions.json");
+        let mut data: serde_json::Value =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        data["questions"].as_array_mut().unwrap().push(serde_json::json!(
+            {"id": 2, "media_id": 1, "question_type": "color", "question_text": null, "image_url": null, "is_active": true}
+        ));
+        data["options"]
+            .as_array_mut()
+            .unwrap()
+            .push(serde_json::json!(
+                {"id": 2, "question_id": 2, "option_text": "Blue", "is_correct": true}
+            ));
+        std::fs::write(&path, data.to_string()).unwrap();
+
+        assert!(state.store.refresh_if_changed().await.unwrap());
+        assert_eq!(state.store.snapshot().questions.len(), 2);
+        assert!(!state.store.refresh_if_changed().await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_export_questions() {
+        let (state, _dir) = setup_test_state().await;
+        let (file_name, body) = export_questions(&state, None).await.unwrap();
+        assert!(file_name.starts_with("questions_") && file_name.ends_with(".json.gz"));
+        let mut json = String::new();
+        flate2::read::GzDecoder::new(body.as_slice())
+            .read_to_string(&mut json)
+            .unwrap();
+        let exported: StoredData = serde_json::from_str(&json).unwrap();
+        assert_eq!(
+            serde_json::to_value(exported).unwrap(),
+            serde_json::to_value(state.store.get_stored_data().await.unwrap()).unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_questions() {
+        let (state, dir) = setup_test_state().await;
+        let (_, export) = export_questions(&state, None).await.unwrap();
+        let mut data: serde_json::Value =
+            serde_json::to_value(decode_question_import(&export).unwrap()).unwrap();
+        data["options"][0]["option_text"] = serde_json::json!("Blue");
+        let json = data.to_string();
+
+        let imported = import_questions(&state, None, json.as_bytes())
+            .await
+            .unwrap();
+        assert_eq!(serde_json::to_value(imported).unwrap(), data);
+        assert!(
+            std::fs::read_dir(dir.path().join("question_backup"))
+                .unwrap()
+                .count()
+                > 0
+        );
+
+        let gzipped = db::gzip(json.as_bytes()).unwrap();
+        assert!(import_questions(&state, None, &gzipped).await.is_ok());
+
+        data["options"][0]["question_id"] = serde_json::json!(99);
+        let res = import_questions(&state, None, data.to_string().as_bytes()).await;
         assert!(matches!(res, Err(ApiError::Validation(_))));
+        let res = import_questions(&state, None, b"not json").await;
+        assert!(matches!(res, Err(ApiError::Validation(_))));
+    }
+
+    #[tokio::test]
+    async fn test_check_integrity() {
+        let (state, _dir) = setup_test_state().await;
+        let res = check_integrity(&state, None).await.unwrap();
+        assert!(res.ok);
+
+        let mut data = serde_json::to_value(state.store.get_stored_data().await.unwrap()).unwrap();
+        data["media"].as_array_mut().unwrap().push(serde_json::json!(
+            {"id": 2, "title": "Unused", "artist": "Nobody", "release_year": null, "spotify_uri": null, "youtube_id": "unused"}
+        ));
+        data["questions"].as_array_mut().unwrap().push(serde_json::json!(
+            {"id": 2, "media_id": 1, "question_type": "color", "question_text": null, "image_url": null, "is_active": false}
+        ));
+        data["characters"] = serde_json::json!([
+            {"id": 1, "name": "bob", "image_url": "/img/bob.avif"},
+            {"id": 2, "name": "alice", "image_url": "/img/alice.avif"}
+        ]);
+        data["sets"] = serde_json::json!([
+            {"id": 1, "name": "Inactive", "question_ids": [2]},
+            {"id": 2, "name": "Mixed", "question_ids": [1, 2]}
+        ]);
+        state
+            .store
+            .set_stored_data(serde_json::from_value(data).unwrap())
+            .await
+            .unwrap();
+        state
+            .store
+            .store_character_image("bob", b"image")
+            .await
+            .unwrap();
+
+        let res = check_integrity(&state, None).await.unwrap();
+        assert!(!res.ok);
+        assert!(res.report.validation_error.is_none());
+        let names = |items: &[db::IntegrityItem]| -> Vec<String> {
+            items.iter().map(|i| i.name.to_string()).collect()
+        };
+        assert_eq!(names(&res.report.unused_media), ["Unused"]);
+        assert_eq!(names(&res.report.missing_character_images), ["alice"]);
+        assert_eq!(names(&res.report.inactive_sets), ["Inactive"]);
+    }
+
+    #[tokio::test]
+    async fn test_encrypted_question_data() {
+        let (state, dir) = setup_test_state().await;
+        let config = StorageConfig::Filesystem {
+            base_path: dir.path().to_path_buf(),
+            file_path: "questions.json".into(),
+        };
+        let key = "MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY=";
+        let cipher = || Some(DataCipher::from_base64(key).unwrap());
+
+        // Existing plaintext data is still readable and encrypted on write.
+        let store = QuestionStore::new(&config, cipher()).await.unwrap();
+        let data = state.store.get_stored_data().await.unwrap();
+        store.set_stored_data(data).await.unwrap();
+        let raw = std::fs::read_to_string(dir.path().join("questions.json")).unwrap();
+        assert!(raw.starts_with("spektrum-aes256gcm-v1:"));
+        assert!(!raw.contains("Test Song"));
+
+        let reopened = QuestionStore::new(&config, cipher()).await.unwrap();
+        assert_eq!(reopened.snapshot().questions.len(), 1);
+        assert!(QuestionStore::new(&config, None).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_media_base_url() {
+        let (state, dir) = setup_test_state().await;
+        let store = QuestionStore::new(
+            &StorageConfig::Filesystem {
+                base_path: dir.path().to_path_buf(),
+                file_path: "questions.json".into(),
+            },
+            None,
+        )
+        .await
+        .unwrap()
+        .with_media_base_url(Some("https://cdn.example/".into()));
+
+        let url = store.store_character_image("bob", b"image").await.unwrap();
+        assert_eq!(url, "https://cdn.example/img/bob.avif");
+
+        let mut data = serde_json::to_value(store.get_stored_data().await.unwrap()).unwrap();
+        data["characters"] = serde_json::json!([
+            {"id": 1, "name": "bob", "image_url": url},
+            {"id": 2, "name": "alice", "image_url": "https://elsewhere.example/alice.avif"}
+        ]);
+        store
+            .set_stored_data(serde_json::from_value(data).unwrap())
+            .await
+            .unwrap();
+
+        // Stored relative, served absolute.
+        let stored = serde_json::to_value(state.store.get_stored_data().await.unwrap()).unwrap();
+        assert_eq!(stored["characters"][0]["image_url"], "/img/bob.avif");
+        assert_eq!(
+            stored["characters"][1]["image_url"],
+            "https://elsewhere.example/alice.avif"
+        );
+        let served = serde_json::to_value(store.get_stored_data().await.unwrap()).unwrap();
+        assert_eq!(
+            served["characters"][0]["image_url"],
+            "https://cdn.example/img/bob.avif"
+        );
     }
 
     #[tokio::test]
@@ -1191,7 +4513,9 @@ mod tests {
             CreateLobbyRequest {
                 round_duration: None,
                 set_id: None,
+                ..Default::default()
             },
+            TEST_IP,
         )
         .await
         .unwrap();
@@ -1206,6 +4530,8 @@ mod tests {
         let res = check_sessions(&state, check_req).await.unwrap();
         assert_eq!(res.valid_sessions.len(), 1);
         assert_eq!(res.valid_sessions[0].player_id, create_res.player_id);
+        assert_eq!(res.valid_sessions[0].join_code, create_res.join_code);
+        assert!(res.valid_sessions[0].is_admin);
 
         // Check an invalid session
         let check_req_invalid = CheckSessionsRequest {
@@ -1216,4 +4542,119 @@ mod tests {
         let res_invalid = check_sessions(&state, check_req_invalid).await.unwrap();
         assert_eq!(res_invalid.valid_sessions.len(), 0);
     }
+
+    #[tokio::test]
+    async fn test_ping_before_connect() {
+        let (state, _dir) = setup_test_state().await;
+        let (text_tx, text_rx) = tokio::sync::mpsc::channel(8);
+        let (bin_tx, _bin_rx) = tokio::sync::mpsc::channel(8);
+        let mut inbox = Inbox::new(text_rx);
+        let mut conn = WsConnection::new(None, Heartbeat::default());
+
+        let ping = Message::Text(r#"{"type":"Ping","client_time_ms":1234}"#.into());
+        assert!(
+            handle_message(ping, &mut conn, &state, &text_tx, &bin_tx)
+                .await
+                .is_ok()
+        );
+
+        let reply = inbox.recv().await.unwrap();
+        match serde_json::from_slice(&reply).unwrap() {
+            GameUpdate::Pong {
+                client_time_ms,
+                server_time_ms,
+            } => {
+                assert_eq!(client_time_ms, 1234);
+                assert!(server_time_ms > 0);
+            }
+            other => panic!("Expected Pong, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_connect_negotiates_heartbeat() {
+        let (state, _dir) = setup_test_state().await;
+        let lobby = create_lobby(&state, CreateLobbyRequest::default(), TEST_IP)
+            .await
+            .unwrap();
+        let limits = &state.limits;
+        assert_eq!(
+            negotiate_heartbeat(limits, None).interval_ms,
+            limits.heartbeat_interval_secs * 1000
+        );
+        assert_eq!(
+            negotiate_heartbeat(limits, Some(u64::MAX)).interval_ms,
+            limits.max_heartbeat_interval_secs * 1000
+        );
+
+        let (text_tx, text_rx) = tokio::sync::mpsc::channel(8);
+        let (bin_tx, _bin_rx) = tokio::sync::mpsc::channel(8);
+        let mut inbox = Inbox::new(text_rx);
+        let mut conn = WsConnection::new(None, negotiate_heartbeat(limits, None));
+        let connect = Message::Text(
+            serde_json::json!({
+                "type": "Connect",
+                "session_token": lobby.session_token,
+                "heartbeat_interval_ms": 1,
+            })
+            .to_string()
+            .into(),
+        );
+        handle_message(connect, &mut conn, &state, &text_tx, &bin_tx)
+            .await
+            .unwrap();
+
+        let expected = Heartbeat {
+            interval_ms: limits.min_heartbeat_interval_secs * 1000,
+            max_missed: limits.max_missed_heartbeats,
+        };
+        assert_eq!(conn.heartbeat, expected);
+        assert_eq!(
+            *conn.ping_every.borrow(),
+            Duration::from_millis(expected.interval_ms)
+        );
+        let reply = inbox.recv().await.unwrap();
+        match serde_json::from_slice(&reply).unwrap() {
+            GameUpdate::Connected { heartbeat, .. } => assert_eq!(heartbeat, expected),
+            other => panic!("Expected Connected, got {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_close_reasons() {
+        let (state, _dir) = setup_test_state().await;
+        let (text_tx, _text_rx) = tokio::sync::mpsc::channel(64);
+        let (bin_tx, _bin_rx) = tokio::sync::mpsc::channel(8);
+        let mut conn = WsConnection::new(None, Heartbeat::default());
+
+        let close = Message::Close(None);
+        let res = handle_message(close, &mut conn, &state, &text_tx, &bin_tx).await;
+        assert_eq!(res, Err(None));
+
+        let ping = || Message::Text(r#"{"type":"Ping","client_time_ms":1}"#.into());
+        // The close above counts towards the limit as well.
+        for _ in 1..state.limits.ws_messages_per_sec {
+            let res = handle_message(ping(), &mut conn, &state, &text_tx, &bin_tx).await;
+            assert_eq!(res, Ok(()));
+        }
+        let res = handle_message(ping(), &mut conn, &state, &text_tx, &bin_tx).await;
+        assert_eq!(res, Err(Some(CloseReason::RateLimited)));
+
+        // Clients should reconnect after these, but not after the others.
+        for reason in [
+            CloseReason::Idle,
+            CloseReason::ShuttingDown,
+            CloseReason::FellBehind,
+        ] {
+            assert!(matches!(
+                reason.frame().code,
+                close_code::AWAY | close_code::AGAIN
+            ));
+        }
+        assert_eq!(
+            CloseReason::Ended(EndReason::GameClosed).frame().code,
+            close_code::NORMAL
+        );
+        assert_eq!(CloseReason::RateLimited.frame().code, close_code::POLICY);
+    }
 }
@@ -0,0 +1,201 @@
+use crate::SpotifyConfig;
+use serde::Deserialize;
+use serde_json::json;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{Receiver, Sender, channel};
+use tracing::{Instrument, debug, info_span, warn};
+
+const TOKEN_URL: &str = "https://accounts.spotify.com/api/token";
+const PLAYER_URL: &str = "https://api.spotify.com/v1/me/player";
+/// Refresh the access token this long before Spotify says it expires.
+const TOKEN_EXPIRY_MARGIN: Duration = Duration::from_secs(60);
+const QUEUE_CAPACITY: usize = 64;
+
+#[derive(Debug, PartialEq)]
+pub enum PlaybackCommand {
+    Play { uri: Arc<str> },
+    Pause,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    access_token: String,
+    expires_in: u64,
+}
+
+struct AccessToken {
+    value: String,
+    expires_at: Instant,
+}
+
+/// Drives playback on the host's Spotify device so the current question's
+/// track plays during the round. Commands are handled in order by a
+/// background task; cloning is cheap.
+///
+/// There is one device, so the first lobby to start a round claims it and
+/// other lobbies are ignored until that lobby's game is over.
+#[derive(Clone)]
+pub struct SpotifyPlayer {
+    tx: Sender<PlaybackCommand>,
+    /// Join code of the lobby playing on the device.
+    owner: Arc<Mutex<Option<String>>>,
+}
+
+impl SpotifyPlayer {
+    pub fn new(config: SpotifyConfig) -> Self {
+        let (tx, rx) = channel(QUEUE_CAPACITY);
+        tokio::spawn(
+            run_worker(reqwest::Client::new(), config, rx)
+                .instrument(info_span!(target: "maintenance", "spotify_player")),
+        );
+        Self::with_sender(tx)
+    }
+
+    fn with_sender(tx: Sender<PlaybackCommand>) -> Self {
+        Self {
+            tx,
+            owner: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Plays `uri` for `join_code`'s round, claiming the device if no other
+    /// lobby holds it.
+    pub fn play(&self, join_code: &str, uri: Arc<str>) {
+        let mut owner = self.owner.lock().unwrap();
+        match owner.as_deref() {
+            Some(current) if current != join_code => {
+                debug!(%join_code, owner = %current, "Spotify device in use by another lobby");
+                return;
+            }
+            Some(_) => {}
+            None => *owner = Some(join_code.to_string()),
+        }
+        self.send(PlaybackCommand::Play { uri });
+    }
+
+    /// Pauses when `join_code`'s round ends, if it holds the device.
+    pub fn pause(&self, join_code: &str) {
+        if self.owner.lock().unwrap().as_deref() == Some(join_code) {
+            self.send(PlaybackCommand::Pause);
+        }
+    }
+
+    /// Lets another lobby claim the device once `join_code` is done with it.
+    pub fn release(&self, join_code: &str) {
+        let mut owner = self.owner.lock().unwrap();
+        if owner.as_deref() == Some(join_code) {
+            *owner = None;
+        }
+    }
+
+    fn send(&self, command: PlaybackCommand) {
+        if let Err(e) = self.tx.try_send(command) {
+            warn!(error = %e, "Dropping Spotify playback command");
+        }
+    }
+}
+
+async fn run_worker(
+    client: reqwest::Client,
+    config: SpotifyConfig,
+    mut rx: Receiver<PlaybackCommand>,
+) {
+    let mut token: Option<AccessToken> = None;
+    while let Some(command) = rx.recv().await {
+        let access_token = match &token {
+            Some(t) if t.expires_at > Instant::now() => t.value.clone(),
+            _ => match refresh_token(&client, &config).await {
+                Ok(t) => {
+                    let value = t.value.clone();
+                    token = Some(t);
+                    value
+                }
+                Err(e) => {
+                    warn!(error = %e, "Failed to refresh Spotify access token");
+                    continue;
+                }
+            },
+        };
+
+        let request = match &command {
+            PlaybackCommand::Play { uri } => client
+                .put(format!("{PLAYER_URL}/play"))
+                .json(&json!({ "uris": [uri.as_ref()] })),
+            PlaybackCommand::Pause => client.put(format!("{PLAYER_URL}/pause")).body(""),
+        };
+        let request = match &config.device_id {
+            Some(device_id) => request.query(&[("device_id", device_id)]),
+            None => request,
+        };
+        let result = request
+            .bearer_auth(access_token)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        match result {
+            Ok(_) => debug!(?command, "Spotify playback updated"),
+            Err(e) => warn!(?command, error = %e, "Spotify playback request failed"),
+        }
+    }
+}
+
+async fn refresh_token(
+    client: &reqwest::Client,
+    config: &SpotifyConfig,
+) -> Result<AccessToken, reqwest::Error> {
+    let response: TokenResponse = client
+        .post(TOKEN_URL)
+        .basic_auth(&config.client_id, Some(&config.client_secret))
+        .form(&[
+            ("grant_type", "refresh_token"),
+            ("refresh_token", config.refresh_token.as_str()),
+        ])
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(AccessToken {
+        value: response.access_token,
+        expires_at: Instant::now()
+            + Duration::from_secs(response.expires_in).saturating_sub(TOKEN_EXPIRY_MARGIN),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_other_lobbies_leave_device_alone() {
+        let (tx, mut rx) = channel(8);
+        let player = SpotifyPlayer::with_sender(tx);
+
+        player.play("AAAA", Arc::from("spotify:track:a"));
+        assert_eq!(
+            rx.try_recv(),
+            Ok(PlaybackCommand::Play {
+                uri: Arc::from("spotify:track:a")
+            })
+        );
+        // Another lobby's rounds neither take over nor stop the device.
+        player.play("BBBB", Arc::from("spotify:track:b"));
+        player.pause("BBBB");
+        assert!(rx.try_recv().is_err());
+        player.release("BBBB");
+        player.play("BBBB", Arc::from("spotify:track:b"));
+        assert!(rx.try_recv().is_err());
+
+        player.pause("AAAA");
+        assert_eq!(rx.try_recv(), Ok(PlaybackCommand::Pause));
+        player.release("AAAA");
+        player.play("BBBB", Arc::from("spotify:track:b"));
+        assert_eq!(
+            rx.try_recv(),
+            Ok(PlaybackCommand::Play {
+                uri: Arc::from("spotify:track:b")
+            })
+        );
+    }
+}
@@ -0,0 +1,48 @@
+//! systemd notifications for `Type=notify` units: readiness once the server
+//! accepts connections, status while starting and watchdog pings, so systemd
+//! can tell a hung server from a slow start. Does nothing when the server
+//! wasn't started by systemd.
+
+use sd_notify::NotifyState;
+use std::time::Duration;
+use tracing::{Instrument, info, info_span, warn};
+
+fn notify(state: NotifyState) {
+    // Keep NOTIFY_SOCKET set: it is needed for every later notification.
+    if let Err(e) = sd_notify::notify(false, &[state]) {
+        warn!(error = %e, "Failed to notify systemd");
+    }
+}
+
+pub fn status(status: &str) {
+    notify(NotifyState::Status(status));
+}
+
+pub fn ready() {
+    notify(NotifyState::Ready);
+}
+
+pub fn stopping() {
+    notify(NotifyState::Stopping);
+}
+
+/// Pings the watchdog at half the unit's `WatchdogSec` from a runtime task,
+/// so the pings stop, and systemd restarts the server, if the runtime hangs.
+pub fn spawn_watchdog() {
+    let mut usec = 0;
+    if !sd_notify::watchdog_enabled(false, &mut usec) {
+        return;
+    }
+    let interval = Duration::from_micros(usec) / 2;
+    info!("Pinging the systemd watchdog every {:?}", interval);
+    tokio::spawn(
+        async move {
+            let mut tick = tokio::time::interval(interval);
+            loop {
+                tick.tick().await;
+                notify(NotifyState::Watchdog);
+            }
+        }
+        .instrument(info_span!(target: "maintenance", "systemd_watchdog")),
+    );
+}
@@ -0,0 +1,125 @@
+use crate::TlsConfig;
+use axum::Router;
+use axum::http::{HeaderMap, Uri, header};
+use axum::response::{IntoResponse, Redirect, Response};
+use axum_server::Handle;
+use axum_server::tls_rustls::RustlsConfig;
+use http::StatusCode;
+use std::net::SocketAddr;
+use tracing::{error, info};
+
+/// Serves `app` over HTTPS until `shutdown` resolves, plus a plain HTTP
+/// listener that redirects to it when `redirect_port` is set.
+pub async fn serve(
+    addr: SocketAddr,
+    config: &TlsConfig,
+    app: Router,
+    shutdown: impl Future<Output = ()> + Send + 'static,
+    on_listening: impl FnOnce() + Send + 'static,
+) -> Result<(), Box<dyn std::error::Error>> {
+    // Several rustls providers are compiled in through other dependencies,
+    // so one has to be chosen explicitly.
+    let _ = rustls::crypto::ring::default_provider().install_default();
+    let rustls_config = RustlsConfig::from_pem_file(&config.cert_path, &config.key_path)
+        .await
+        .map_err(|e| {
+            format!(
+                "Failed to load TLS certificate '{}' or key '{}': {e}",
+                config.cert_path.display(),
+                config.key_path.display()
+            )
+        })?;
+
+    // The shutdown future can only be awaited once, so its completion is
+    // passed on to both listeners.
+    let (stopping_tx, mut stopping_rx) = tokio::sync::watch::channel(false);
+    let handle = Handle::new();
+    tokio::spawn({
+        let handle = handle.clone();
+        async move {
+            shutdown.await;
+            handle.graceful_shutdown(None);
+            stopping_tx.send_replace(true);
+        }
+    });
+
+    if let Some(redirect_port) = config.redirect_port {
+        let redirect_addr = SocketAddr::new(addr.ip(), redirect_port);
+        let listener = tokio::net::TcpListener::bind(redirect_addr).await?;
+        let https_port = addr.port();
+        let redirect = Router::new().fallback(move |headers: HeaderMap, uri: Uri| async move {
+            redirect_to_https(&headers, &uri, https_port)
+        });
+        info!("Redirecting HTTP on {} to HTTPS", redirect_addr);
+        tokio::spawn(async move {
+            let stopped = async move {
+                let _ = stopping_rx.wait_for(|stopping| *stopping).await;
+            };
+            if let Err(e) = axum::serve(listener, redirect)
+                .with_graceful_shutdown(stopped)
+                .await
+            {
+                error!(error = %e, "HTTP redirect listener failed");
+            }
+        });
+    }
+
+    tokio::spawn({
+        let handle = handle.clone();
+        async move {
+            if handle.listening().await.is_some() {
+                on_listening();
+            }
+        }
+    });
+
+    info!("Starting server on {} with TLS", addr);
+    axum_server::bind_rustls(addr, rustls_config)
+        .handle(handle)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .await?;
+    Ok(())
+}
+
+fn redirect_to_https(headers: &HeaderMap, uri: &Uri, https_port: u16) -> Response {
+    match https_url(headers, uri, https_port) {
+        Some(url) => Redirect::permanent(&url).into_response(),
+        None => StatusCode::BAD_REQUEST.into_response(),
+    }
+}
+
+/// Same host and path as the request, on the HTTPS port.
+fn https_url(headers: &HeaderMap, uri: &Uri, https_port: u16) -> Option<String> {
+    let host = headers.get(header::HOST)?.to_str().ok()?;
+    let authority: http::uri::Authority = host.parse().ok()?;
+    let path = uri.path_and_query().map_or("/", |p| p.as_str());
+    Some(match https_port {
+        443 => format!("https://{}{}", authority.host(), path),
+        port => format!("https://{}:{}{}", authority.host(), port, path),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn host(value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(header::HOST, value.parse().unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_https_url() {
+        let uri: Uri = "/join/123456?x=1".parse().unwrap();
+        assert_eq!(
+            https_url(&host("quiz.example.com"), &uri, 443).as_deref(),
+            Some("https://quiz.example.com/join/123456?x=1")
+        );
+        assert_eq!(
+            https_url(&host("quiz.example.com:8080"), &uri, 8443).as_deref(),
+            Some("https://quiz.example.com:8443/join/123456?x=1")
+        );
+        assert_eq!(https_url(&HeaderMap::new(), &uri, 443), None);
+    }
+}
@@ -0,0 +1,75 @@
+use image::codecs::avif::AvifEncoder;
+use image::imageops::FilterType;
+use image::{ImageEncoder, ImageError, ImageReader, Limits};
+use std::io::Cursor;
+
+/// Character images are shown at 300x300, so converted images are scaled
+/// down to fit.
+const TARGET_DIMENSION: u32 = 300;
+/// Uploads larger than this are rejected before decoding, so a small file
+/// cannot expand into an enormous bitmap.
+const MAX_SOURCE_DIMENSION: u32 = 4096;
+/// rav1e speed from 1 (slowest) to 10. Character images are small, so a
+/// middling speed keeps uploads responsive without bloating the output.
+const AVIF_SPEED: u8 = 6;
+const AVIF_QUALITY: u8 = 80;
+
+/// Content types that [`to_avif`] can decode.
+pub const TRANSCODABLE_TYPES: &[&str] = &["image/png", "image/jpeg", "image/webp"];
+
+/// Decodes a PNG, JPEG or WebP image and re-encodes it as AVIF, scaled down
+/// to fit [`TARGET_DIMENSION`]. Only pixels are carried over, so any
+/// metadata in the source is dropped. CPU heavy; call from a blocking task.
+pub fn to_avif(data: &[u8]) -> Result<Vec<u8>, ImageError> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_DIMENSION);
+    limits.max_image_height = Some(MAX_SOURCE_DIMENSION);
+    let mut reader = ImageReader::new(Cursor::new(data)).with_guessed_format()?;
+    reader.limits(limits);
+    let mut image = reader.decode()?;
+    if image.width() > TARGET_DIMENSION || image.height() > TARGET_DIMENSION {
+        image = image.resize(TARGET_DIMENSION, TARGET_DIMENSION, FilterType::Lanczos3);
+    }
+    let image = image.to_rgba8();
+    let mut avif = Vec::new();
+    AvifEncoder::new_with_speed_quality(&mut avif, AVIF_SPEED, AVIF_QUALITY).write_image(
+        image.as_raw(),
+        image.width(),
+        image.height(),
+        image::ExtendedColorType::Rgba8,
+    )?;
+    Ok(avif)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::avif;
+    use image::{ImageFormat, RgbaImage};
+
+    fn png(width: u32, height: u32) -> Vec<u8> {
+        let mut png = Vec::new();
+        RgbaImage::from_pixel(width, height, image::Rgba([200, 30, 30, 255]))
+            .write_to(&mut Cursor::new(&mut png), ImageFormat::Png)
+            .unwrap();
+        png
+    }
+
+    #[test]
+    fn test_to_avif() {
+        let mut avif = to_avif(&png(600, 400)).unwrap();
+        assert_eq!(
+            avif::strip_metadata(&mut avif, TARGET_DIMENSION),
+            Ok(avif::Dimensions {
+                width: 300,
+                height: 200
+            })
+        );
+    }
+
+    #[test]
+    fn test_rejects_non_image() {
+        assert!(to_avif(b"not an image").is_err());
+        assert!(to_avif(&png(MAX_SOURCE_DIMENSION + 1, 1)).is_err());
+    }
+}
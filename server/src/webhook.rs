@@ -0,0 +1,259 @@
+use crate::WebhookConfig;
+use crate::game::LobbySummary;
+use chrono::{SecondsFormat, Utc};
+use serde::Serialize;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::mpsc::{Receiver, Sender, channel};
+use tracing::{Instrument, debug, info_span, warn};
+
+/// Events queued beyond this are dropped rather than blocking game traffic.
+const QUEUE_CAPACITY: usize = 1024;
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+pub struct FinalScore {
+    pub name: Arc<str>,
+    pub score: i32,
+}
+
+#[derive(Clone, Debug, Serialize, PartialEq)]
+#[serde(tag = "event")]
+pub enum WebhookEvent {
+    LobbyCreated {
+        join_code: String,
+    },
+    GameStarted {
+        join_code: String,
+        player_count: usize,
+    },
+    GameOver {
+        join_code: String,
+        /// Sorted by score, highest first.
+        scores: Vec<FinalScore>,
+    },
+    LobbyClosed {
+        join_code: String,
+    },
+    /// Sent when a closed or inactive lobby is removed.
+    LobbySummary {
+        join_code: String,
+        #[serde(flatten)]
+        summary: LobbySummary,
+    },
+}
+
+#[derive(Serialize)]
+struct WebhookPayload<'a> {
+    #[serde(flatten)]
+    event: &'a WebhookEvent,
+    timestamp: String,
+}
+
+/// Fire-and-forget delivery of [`WebhookEvent`]s to the configured URLs and
+/// any in-process subscribers. Cloning is cheap; all clones feed the same
+/// background workers.
+#[derive(Clone, Default)]
+pub struct WebhookDispatcher {
+    targets: Vec<Sender<WebhookEvent>>,
+}
+
+impl WebhookDispatcher {
+    /// Starts the delivery worker. Returns a no-op dispatcher when no URLs
+    /// are configured.
+    pub fn new(config: WebhookConfig) -> Self {
+        if config.urls.is_empty() {
+            return Self::default();
+        }
+        let client = match reqwest::Client::builder()
+            .timeout(Duration::from_secs(config.timeout_secs))
+            .build()
+        {
+            Ok(client) => client,
+            Err(e) => {
+                warn!(error = %e, "Failed to build webhook client, webhooks disabled");
+                return Self::default();
+            }
+        };
+
+        let (tx, rx) = channel(QUEUE_CAPACITY);
+        tokio::spawn(
+            run_worker(client, Arc::new(config), rx)
+                .instrument(info_span!(target: "maintenance", "webhook_worker")),
+        );
+        Self { targets: vec![tx] }
+    }
+
+    /// Receives every event sent after this call, for integrations that run
+    /// inside the server.
+    #[cfg_attr(not(feature = "discord"), allow(dead_code))]
+    pub fn subscribe(&mut self) -> Receiver<WebhookEvent> {
+        let (tx, rx) = channel(QUEUE_CAPACITY);
+        self.targets.push(tx);
+        rx
+    }
+
+    pub fn send(&self, event: WebhookEvent) {
+        for tx in &self.targets {
+            if let Err(e) = tx.try_send(event.clone()) {
+                warn!(error = %e, "Dropping webhook event");
+            }
+        }
+    }
+}
+
+async fn run_worker(
+    client: reqwest::Client,
+    config: Arc<WebhookConfig>,
+    mut rx: Receiver<WebhookEvent>,
+) {
+    while let Some(event) = rx.recv().await {
+        let payload = WebhookPayload {
+            event: &event,
+            timestamp: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+        };
+        let body = match serde_json::to_vec(&payload) {
+            Ok(body) => bytes::Bytes::from(body),
+            Err(e) => {
+                warn!(error = %e, "Failed to serialize webhook event");
+                continue;
+            }
+        };
+        // Each URL retries independently so one slow receiver does not hold
+        // up the others.
+        for url in &config.urls {
+            tokio::spawn(deliver(
+                client.clone(),
+                config.clone(),
+                url.clone(),
+                body.clone(),
+            ));
+        }
+    }
+}
+
+async fn deliver(
+    client: reqwest::Client,
+    config: Arc<WebhookConfig>,
+    url: String,
+    body: bytes::Bytes,
+) {
+    let mut backoff = Duration::from_millis(config.initial_backoff_ms);
+    for attempt in 0..=config.max_retries {
+        let result = client
+            .post(&url)
+            .header(reqwest::header::CONTENT_TYPE, "application/json")
+            .body(body.clone())
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        match result {
+            Ok(_) => {
+                debug!(%url, attempt, "Webhook delivered");
+                return;
+            }
+            Err(e) if attempt < config.max_retries => {
+                debug!(%url, attempt, error = %e, "Webhook delivery failed, retrying");
+                tokio::time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            Err(e) => {
+                warn!(%url, attempts = attempt + 1, error = %e, "Webhook delivery failed");
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::Router;
+    use axum::http::StatusCode;
+    use axum::routing::post;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    #[test]
+    fn test_payload_format() {
+        let event = WebhookEvent::GameOver {
+            join_code: "123456".into(),
+            scores: vec![FinalScore {
+                name: Arc::from("Player1"),
+                score: 4200,
+            }],
+        };
+        let payload = WebhookPayload {
+            event: &event,
+            timestamp: "2024-01-01T00:00:00.000Z".into(),
+        };
+        assert_eq!(
+            serde_json::to_value(&payload).unwrap(),
+            serde_json::json!({
+                "event": "GameOver",
+                "join_code": "123456",
+                "scores": [{"name": "Player1", "score": 4200}],
+                "timestamp": "2024-01-01T00:00:00.000Z",
+            })
+        );
+    }
+
+    #[test]
+    fn test_lobby_summary_is_flat() {
+        let event = WebhookEvent::LobbySummary {
+            join_code: "123456".into(),
+            summary: LobbySummary {
+                duration_secs: 600,
+                peak_players: 8,
+                rounds_played: 10,
+                messages_sent: 900,
+                disconnects: 3,
+            },
+        };
+        assert_eq!(
+            serde_json::to_value(&event).unwrap(),
+            serde_json::json!({
+                "event": "LobbySummary",
+                "join_code": "123456",
+                "duration_secs": 600,
+                "peak_players": 8,
+                "rounds_played": 10,
+                "messages_sent": 900,
+                "disconnects": 3,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_delivery_retries_until_success() {
+        let hits = Arc::new(AtomicUsize::new(0));
+        let app = Router::new().route(
+            "/hook",
+            post({
+                let hits = hits.clone();
+                move || async move {
+                    if hits.fetch_add(1, Ordering::SeqCst) == 0 {
+                        StatusCode::INTERNAL_SERVER_ERROR
+                    } else {
+                        StatusCode::OK
+                    }
+                }
+            }),
+        );
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await });
+
+        let config = Arc::new(WebhookConfig {
+            urls: vec![format!("http://{addr}/hook")],
+            max_retries: 3,
+            initial_backoff_ms: 1,
+            timeout_secs: 5,
+        });
+        deliver(
+            reqwest::Client::new(),
+            config.clone(),
+            config.urls[0].clone(),
+            bytes::Bytes::from_static(b"{}"),
+        )
+        .await;
+        assert_eq!(hits.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,247 @@
+//! Distributed throughput test. Workers on several machines register with a
+//! coordinator over TCP, each runs its share of the lobbies against the
+//! server, and the coordinator adds up the numbers they stream back.
+//!
+//! Messages are JSON, one per line.
+
+use crate::heartbeat::HeartbeatSummary;
+use crate::{
+    Args, ConnectionSchedule, LatencyStats, LatencySummary, Server, TestError, ThroughputMetrics,
+    print_throughput_summary, run_metrics_reporter, run_throughput_test,
+};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::tcp::OwnedWriteHalf;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc;
+
+/// A worker's running totals.
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize)]
+pub struct MetricsSnapshot {
+    pub messages_sent: u64,
+    pub messages_received: u64,
+    pub bytes_sent: u64,
+    pub bytes_received: u64,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum WorkerMessage {
+    Register,
+    Metrics {
+        snapshot: MetricsSnapshot,
+    },
+    Done {
+        snapshot: MetricsSnapshot,
+        latency: LatencySummary,
+        heartbeats: HeartbeatSummary,
+        error: Option<String>,
+    },
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+enum CoordinatorMessage {
+    Assignment {
+        num_lobbies: usize,
+        players_per_lobby: usize,
+        duration_secs: u64,
+    },
+}
+
+async fn send(write: &mut OwnedWriteHalf, message: &impl Serialize) -> Result<(), TestError> {
+    let mut line = serde_json::to_string(message)?;
+    line.push('\n');
+    write.write_all(line.as_bytes()).await?;
+    Ok(())
+}
+
+/// Lobbies for worker `index`, spreading the remainder over the first ones.
+fn lobby_quota(num_lobbies: usize, workers: usize, index: usize) -> usize {
+    num_lobbies / workers + usize::from(index < num_lobbies % workers)
+}
+
+/// Waits for `--workers` workers, hands out the lobbies and reports the
+/// combined throughput until every worker is done.
+pub async fn run_coordinator(args: &Args) -> Result<(), TestError> {
+    if args.workers == 0 {
+        return Err(TestError::Other("--workers must be at least 1".to_string()));
+    }
+    let listener = TcpListener::bind(&args.listen).await?;
+    println!("Waiting for {} workers on {}", args.workers, args.listen);
+
+    let mut workers = Vec::new();
+    while workers.len() < args.workers {
+        let (stream, addr) = listener.accept().await?;
+        let (read, write) = stream.into_split();
+        let mut lines = BufReader::new(read).lines();
+        let registered =
+            lines.next_line().await.ok().flatten().is_some_and(|line| {
+                matches!(serde_json::from_str(&line), Ok(WorkerMessage::Register))
+            });
+        if !registered {
+            eprintln!("Ignoring {addr}: it did not register as a worker");
+            continue;
+        }
+        workers.push((addr, lines, write));
+        println!(
+            "Worker {} registered ({}/{})",
+            addr,
+            workers.len(),
+            args.workers
+        );
+    }
+
+    let (tx, mut rx) = mpsc::unbounded_channel();
+    let mut names = Vec::new();
+    for (index, (addr, mut lines, mut write)) in workers.into_iter().enumerate() {
+        let assignment = CoordinatorMessage::Assignment {
+            num_lobbies: lobby_quota(args.num_lobbies, args.workers, index),
+            players_per_lobby: args.players_per_lobby,
+            duration_secs: args.duration_or_rounds,
+        };
+        send(&mut write, &assignment).await?;
+        names.push(addr);
+        let tx = tx.clone();
+        tokio::spawn(async move {
+            // Keep the write half open so the worker doesn't see EOF.
+            let _write = write;
+            while let Ok(Some(line)) = lines.next_line().await {
+                match serde_json::from_str::<WorkerMessage>(&line) {
+                    Ok(message) => {
+                        if tx.send((index, message)).is_err() {
+                            break;
+                        }
+                    }
+                    Err(e) => eprintln!("Bad message from worker {addr}: {e}"),
+                }
+            }
+        });
+    }
+    drop(tx);
+
+    // The combined totals, for the same per-second table a single process prints.
+    let totals = Arc::new(ThroughputMetrics::new());
+    let start_time = Instant::now();
+    let reporter_handle = tokio::spawn(run_metrics_reporter(
+        Arc::clone(&totals),
+        start_time,
+        Duration::from_secs(args.duration_or_rounds),
+    ));
+
+    let mut snapshots = vec![MetricsSnapshot::default(); names.len()];
+    let mut results: Vec<Option<(LatencySummary, HeartbeatSummary, Option<String>)>> =
+        vec![None; names.len()];
+    while let Some((index, message)) = rx.recv().await {
+        match message {
+            WorkerMessage::Register => {}
+            WorkerMessage::Metrics { snapshot } => snapshots[index] = snapshot,
+            WorkerMessage::Done {
+                snapshot,
+                latency,
+                heartbeats,
+                error,
+            } => {
+                snapshots[index] = snapshot;
+                results[index] = Some((latency, heartbeats, error));
+            }
+        }
+        let sum = |field: fn(&MetricsSnapshot) -> u64| snapshots.iter().map(field).sum::<u64>();
+        totals
+            .messages_sent
+            .store(sum(|s| s.messages_sent), Ordering::Relaxed);
+        totals
+            .messages_received
+            .store(sum(|s| s.messages_received), Ordering::Relaxed);
+        totals
+            .bytes_sent
+            .store(sum(|s| s.bytes_sent), Ordering::Relaxed);
+        totals
+            .bytes_received
+            .store(sum(|s| s.bytes_received), Ordering::Relaxed);
+        if results.iter().all(Option::is_some) {
+            break;
+        }
+    }
+    reporter_handle.abort();
+
+    print_throughput_summary(&totals, start_time.elapsed().as_secs_f64());
+    let mut failed = 0;
+    for (addr, result) in names.iter().zip(&results) {
+        match result {
+            Some((latency, heartbeats, None)) => {
+                latency.report(&format!("Worker {addr} ack latency"));
+                heartbeats.report(&format!("Worker {addr} heartbeats"));
+            }
+            Some((_, _, Some(error))) => {
+                eprintln!("Worker {addr} failed: {error}");
+                failed += 1;
+            }
+            None => {
+                eprintln!("Worker {addr} disconnected before finishing");
+                failed += 1;
+            }
+        }
+    }
+    if failed > 0 {
+        return Err(TestError::Other(format!("{failed} workers failed")));
+    }
+    Ok(())
+}
+
+/// Registers with the coordinator, runs the assigned share of the throughput
+/// test and streams its totals back every second.
+pub async fn run_worker(coordinator: &str, server: &Server, args: &Args) -> Result<(), TestError> {
+    let (read, mut write) = TcpStream::connect(coordinator).await?.into_split();
+    let mut lines = BufReader::new(read).lines();
+    send(&mut write, &WorkerMessage::Register).await?;
+    println!("Registered with coordinator {coordinator}, waiting for the other workers");
+
+    let line = lines.next_line().await?.ok_or_else(|| {
+        TestError::Other("Coordinator closed the connection before assigning work".to_string())
+    })?;
+    let CoordinatorMessage::Assignment {
+        num_lobbies,
+        players_per_lobby,
+        duration_secs,
+    } = serde_json::from_str(&line)?;
+    println!(
+        "Assigned {num_lobbies} lobbies with {players_per_lobby} players each for {duration_secs} seconds"
+    );
+
+    let metrics = Arc::new(ThroughputMetrics::new());
+    let latency = Arc::new(LatencyStats::new());
+    let schedule = ConnectionSchedule::new(args, num_lobbies * (players_per_lobby + 1));
+    let test = run_throughput_test(
+        num_lobbies,
+        players_per_lobby,
+        Duration::from_secs(duration_secs),
+        server,
+        schedule,
+        Arc::clone(&metrics),
+        Arc::clone(&latency),
+    );
+    tokio::pin!(test);
+    let mut ticker = tokio::time::interval(Duration::from_secs(1));
+    let result = loop {
+        tokio::select! {
+            result = &mut test => break result,
+            _ = ticker.tick() => {
+                let snapshot = metrics.snapshot();
+                send(&mut write, &WorkerMessage::Metrics { snapshot }).await?;
+            }
+        }
+    };
+
+    let done = WorkerMessage::Done {
+        snapshot: metrics.snapshot(),
+        latency: latency.summary(),
+        heartbeats: latency.heartbeats.summary(),
+        error: result.as_ref().err().map(ToString::to_string),
+    };
+    send(&mut write, &done).await?;
+    result
+}
@@ -0,0 +1,140 @@
+//! Heartbeats the way the web client sends them: a `0x42` binary message every
+//! few seconds, which the server echoes back. Players also answer the server's
+//! pings right away instead of waiting for their next read.
+
+use crate::percentile_ms;
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::time::{Interval, MissedTickBehavior};
+use tokio_tungstenite::tungstenite::Message;
+
+/// The byte the web client sends as a heartbeat.
+pub const HEARTBEAT_BYTE: u8 = 0x42;
+
+/// Heartbeat round-trips and pings, collected from every player.
+#[derive(Default)]
+pub struct HeartbeatStats {
+    round_trips: Mutex<Vec<Duration>>,
+    /// Heartbeats not echoed before the next one was due.
+    lost: AtomicU64,
+    pings_answered: AtomicU64,
+}
+
+impl HeartbeatStats {
+    pub fn summary(&self) -> HeartbeatSummary {
+        let mut round_trips = self.round_trips.lock().unwrap().clone();
+        round_trips.sort();
+        HeartbeatSummary {
+            echoed: round_trips.len(),
+            lost: self.lost.load(Ordering::Relaxed),
+            pings_answered: self.pings_answered.load(Ordering::Relaxed),
+            p50_ms: percentile_ms(&round_trips, 50),
+            p95_ms: percentile_ms(&round_trips, 95),
+            p99_ms: percentile_ms(&round_trips, 99),
+            max_ms: percentile_ms(&round_trips, 100),
+        }
+    }
+}
+
+/// Heartbeat round-trip percentiles, also sent from workers to the
+/// coordinator.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HeartbeatSummary {
+    echoed: usize,
+    lost: u64,
+    pings_answered: u64,
+    p50_ms: f64,
+    p95_ms: f64,
+    p99_ms: f64,
+    max_ms: f64,
+}
+
+impl HeartbeatSummary {
+    pub fn report(&self, label: &str) {
+        if self.echoed == 0 && self.lost == 0 {
+            println!(
+                "{label}: no heartbeats sent, {} pings answered",
+                self.pings_answered
+            );
+            return;
+        }
+        println!(
+            "{label}: {} echoed, {} lost, p50 {:.2} ms, p95 {:.2} ms, p99 {:.2} ms, max {:.2} ms, {} pings answered",
+            self.echoed,
+            self.lost,
+            self.p50_ms,
+            self.p95_ms,
+            self.p99_ms,
+            self.max_ms,
+            self.pings_answered
+        );
+    }
+}
+
+/// Times one player's heartbeats. Like the web client, only the latest
+/// heartbeat is waited for.
+#[derive(Clone)]
+pub struct HeartbeatTracker {
+    pending: Arc<Mutex<Option<Instant>>>,
+    stats: Arc<HeartbeatStats>,
+}
+
+impl HeartbeatTracker {
+    pub fn new(stats: Arc<HeartbeatStats>) -> Self {
+        Self {
+            pending: Arc::new(Mutex::new(None)),
+            stats,
+        }
+    }
+
+    /// Returns the heartbeat message about to be sent.
+    pub fn start(&self) -> Message {
+        if self
+            .pending
+            .lock()
+            .unwrap()
+            .replace(Instant::now())
+            .is_some()
+        {
+            self.stats.lost.fetch_add(1, Ordering::Relaxed);
+        }
+        Message::Binary(vec![HEARTBEAT_BYTE])
+    }
+
+    /// Handles heartbeat echoes and pings, returning the pong to send for a
+    /// ping. Any other message is given back as `Err` for the caller.
+    pub fn handle(&self, msg: Message) -> Result<Option<Message>, Message> {
+        match msg {
+            Message::Binary(data) if data == [HEARTBEAT_BYTE] => {
+                if let Some(sent) = self.pending.lock().unwrap().take() {
+                    self.stats.round_trips.lock().unwrap().push(sent.elapsed());
+                }
+                Ok(None)
+            }
+            Message::Ping(data) => {
+                self.stats.pings_answered.fetch_add(1, Ordering::Relaxed);
+                Ok(Some(Message::Pong(data)))
+            }
+            Message::Pong(_) => Ok(None),
+            other => Err(other),
+        }
+    }
+
+    /// Counts a heartbeat still waiting for its echo as lost.
+    pub fn finish(&self) {
+        if self.pending.lock().unwrap().take().is_some() {
+            self.stats.lost.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// Ticks when the next heartbeat is due. `None` when heartbeats are off.
+pub fn heartbeat_ticker(interval: Option<Duration>) -> Option<Interval> {
+    interval.map(|interval| {
+        let mut ticker = tokio::time::interval_at((Instant::now() + interval).into(), interval);
+        ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        ticker
+    })
+}
@@ -1,14 +1,32 @@
+mod distributed;
+mod heartbeat;
+mod rate_limit;
+mod scenario;
+mod score_check;
+mod soak;
+
 use clap::{Parser, ValueEnum};
 use futures_util::{SinkExt, StreamExt};
+use heartbeat::{HeartbeatStats, HeartbeatTracker, heartbeat_ticker};
 use rand::Rng;
 use rand::SeedableRng;
 use rand::seq::SliceRandom;
+use rate_limit::RateLimitStats;
+use scenario::ThinkTime;
+use score_check::{AnswerLog, ScoreCheck};
+use serde::{Deserialize, Serialize};
 use serde_json::json;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::path::PathBuf;
 use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::time::{Duration, Instant};
 use tokio::net::TcpStream;
-use tokio_tungstenite::{MaybeTlsStream, WebSocketStream, connect_async, tungstenite::Message};
+use tokio::sync::{mpsc, watch};
+use tokio::time::Interval;
+use tokio_tungstenite::{
+    Connector, MaybeTlsStream, WebSocketStream, connect_async_tls_with_config, tungstenite::Message,
+};
 
 type WsStream = WebSocketStream<MaybeTlsStream<TcpStream>>;
 
@@ -23,10 +41,26 @@ enum TestError {
     Http(#[from] reqwest::Error),
     #[error("UUID error: {0}")]
     Uuid(#[from] uuid::Error),
+    #[error("TLS error: {0}")]
+    Tls(#[from] native_tls::Error),
+    #[error("Scenario error: {0}")]
+    Scenario(String),
+    #[error("Rate limited: {0}")]
+    RateLimited(String),
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
     #[error("Generic error: {0}")]
     Other(String),
 }
 
+impl TestError {
+    /// Whether the server refused because of its rate limits, which is
+    /// counted apart from other failures.
+    fn is_rate_limited(&self) -> bool {
+        matches!(self, TestError::RateLimited(_))
+    }
+}
+
 /// Command‐line options.
 #[derive(Debug, Clone, Copy, ValueEnum, Default)]
 enum TestMode {
@@ -34,6 +68,12 @@ enum TestMode {
     GameplayTest,
     #[default]
     UiTest,
+    ChaosTest,
+    SoakTest,
+    /// Splits a throughput test between workers and adds up their numbers
+    Coordinator,
+    /// Runs its share of a coordinator's throughput test
+    Worker,
 }
 
 #[derive(Parser)]
@@ -43,6 +83,11 @@ struct Args {
     #[arg(value_enum, default_value = "ui-test")]
     mode: TestMode,
 
+    /// Run the phases of a scenario file (TOML, or JSON with a .json
+    /// extension) instead of a test mode
+    #[arg(long, conflicts_with = "mode")]
+    scenario: Option<PathBuf>,
+
     /// Number of concurrent lobbies/games (or batch size for gameplay test)
     #[arg(short, long, default_value_t = 10)]
     num_lobbies: usize,
@@ -51,7 +96,7 @@ struct Args {
     #[arg(short, long, default_value_t = 10)]
     players_per_lobby: usize,
 
-    /// Test duration in seconds (for throughput test) or rounds per game (for gameplay/ui test)
+    /// Test duration in seconds (for throughput/soak test) or rounds per game (for gameplay/ui test)
     #[arg(short, long, default_value_t = 60)]
     duration_or_rounds: u64,
 
@@ -62,9 +107,275 @@ struct Args {
     /// Host address (e.g., "localhost:8765")
     #[arg(long, default_value = "localhost:8765")]
     host: String,
+
+    /// Connect with https:// and wss://
+    #[arg(long)]
+    tls: bool,
+
+    /// Accept invalid or self-signed certificates
+    #[arg(long, requires = "tls")]
+    insecure: bool,
+
+    /// Connect with `player_id` instead of `session_token`, for servers from
+    /// before session tokens
+    #[arg(long)]
+    legacy_connect: bool,
+
+    /// Milliseconds between the heartbeats players send, like the web
+    /// client. 0 turns heartbeats off.
+    #[arg(long, default_value_t = 3000)]
+    heartbeat_interval_ms: u64,
+
+    /// Spread lobby creation and player joins evenly over this many seconds
+    #[arg(long, conflicts_with = "connections_per_second", value_parser = positive_f64)]
+    ramp_up_seconds: Option<f64>,
+
+    /// Open connections (lobby creations and player joins) at this rate
+    #[arg(long, value_parser = positive_f64)]
+    connections_per_second: Option<f64>,
+
+    /// Chance that a player drops its connection during a round (chaos test)
+    #[arg(long, default_value_t = 0.3, value_parser = probability)]
+    disconnect_probability: f64,
+
+    /// Longest time a dropped player waits before reconnecting (chaos test)
+    #[arg(long, default_value_t = 3000)]
+    max_reconnect_delay_ms: u64,
+
+    /// How long players think before answering (gameplay, UI and chaos
+    /// tests): fixed:SECONDS, uniform:MIN:MAX, normal:MEAN:STD_DEV or
+    /// exponential:MEAN. Defaults to uniform:0:40, or uniform:0:3 for chaos.
+    #[arg(long)]
+    think_time: Option<ThinkTime>,
+
+    /// Fraction of answers that are correct, using the answers the host sees
+    /// (gameplay and chaos tests). Answers are uniformly random when unset.
+    #[arg(long, value_parser = probability)]
+    correct_ratio: Option<f64>,
+
+    /// Check every game's scores against the answers its players sent, and
+    /// fail the run on any mismatch (gameplay test)
+    #[arg(long)]
+    check_scores: bool,
+
+    /// Seconds between samples of the server's numbers (soak test)
+    #[arg(long, default_value_t = 60.0, value_parser = positive_f64)]
+    sample_interval_seconds: f64,
+
+    /// Seconds between replacing a random lobby with a new one (soak test)
+    #[arg(long, default_value_t = 60.0, value_parser = positive_f64)]
+    lobby_churn_seconds: f64,
+
+    /// Admin password, to sample lobby and player counts (soak test)
+    #[arg(long)]
+    admin_password: Option<String>,
+
+    /// Address the coordinator listens on for workers
+    #[arg(long, default_value = "0.0.0.0:9000")]
+    listen: String,
+
+    /// Number of workers the coordinator waits for before starting
+    #[arg(long, default_value_t = 1)]
+    workers: usize,
+
+    /// Coordinator address to register with (worker)
+    #[arg(long)]
+    coordinator: Option<String>,
+
+    /// Seed for the players' random choices, to replay a run. A random seed
+    /// is used and printed when unset.
+    #[arg(long)]
+    seed: Option<u64>,
+}
+
+/// Master seed for the random choices of the run, set once in `main`.
+static SEED: OnceLock<u64> = OnceLock::new();
+
+/// A random number generator derived from the master seed and `name`, so the
+/// same seed replays the same choices.
+fn seeded_rng(name: &str) -> rand::rngs::StdRng {
+    // FNV-1a, which unlike the std hasher is stable across Rust versions.
+    let hash = name.bytes().fold(0xcbf2_9ce4_8422_2325_u64, |hash, byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    });
+    rand::rngs::StdRng::seed_from_u64(SEED.get().copied().unwrap_or_default() ^ hash)
+}
+
+fn probability(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(number) if (0.0..=1.0).contains(&number) => Ok(number),
+        _ => Err(format!("expected a number between 0 and 1, got {value}")),
+    }
+}
+
+fn positive_f64(value: &str) -> Result<f64, String> {
+    match value.parse::<f64>() {
+        Ok(number) if number > 0.0 && number.is_finite() => Ok(number),
+        _ => Err(format!("expected a positive number, got {value}")),
+    }
+}
+
+/// When each lobby creation or player join may start, so connections arrive
+/// spread out instead of all at once. Each one is delayed by its index times
+/// the interval, plus up to one interval of random jitter.
+#[derive(Clone, Copy)]
+struct ConnectionSchedule {
+    start: Instant,
+    interval: Duration,
+}
+
+impl ConnectionSchedule {
+    /// `total` is the number of connections the test will open.
+    fn new(args: &Args, total: usize) -> Self {
+        let interval = match (args.ramp_up_seconds, args.connections_per_second) {
+            (Some(seconds), _) => seconds / total.max(1) as f64,
+            (None, Some(rate)) => 1.0 / rate,
+            (None, None) => 0.0,
+        };
+        Self::with_interval(Duration::from_secs_f64(interval))
+    }
+
+    fn with_interval(interval: Duration) -> Self {
+        Self {
+            start: Instant::now(),
+            interval,
+        }
+    }
+
+    /// Waits until connection number `index` is due.
+    async fn wait(&self, index: usize) {
+        if self.interval.is_zero() {
+            return;
+        }
+        let jitter = self
+            .interval
+            .mul_f64(rand::thread_rng().gen_range(0.0..1.0));
+        let due = self.start + self.interval * index as u32 + jitter;
+        tokio::time::sleep_until(due.into()).await;
+    }
+}
+
+/// The server under test, with the HTTP client and WebSocket connector to
+/// reach it over plain or TLS connections.
+#[derive(Clone)]
+struct Server {
+    host: String,
+    tls: bool,
+    http: reqwest::Client,
+    connector: Connector,
+    legacy_connect: bool,
+    heartbeat_interval: Option<Duration>,
+    rate_limits: Arc<RateLimitStats>,
+}
+
+impl Server {
+    fn new(
+        host: String,
+        tls: bool,
+        insecure: bool,
+        legacy_connect: bool,
+        heartbeat_interval: Option<Duration>,
+    ) -> Result<Self, TestError> {
+        let http = reqwest::Client::builder()
+            .danger_accept_invalid_certs(insecure)
+            .build()?;
+        let connector = if tls {
+            Connector::NativeTls(
+                native_tls::TlsConnector::builder()
+                    .danger_accept_invalid_certs(insecure)
+                    .build()?,
+            )
+        } else {
+            Connector::Plain
+        };
+        Ok(Self {
+            host,
+            tls,
+            http,
+            connector,
+            legacy_connect,
+            heartbeat_interval,
+            rate_limits: Arc::default(),
+        })
+    }
+
+    /// Sends an HTTP request, retrying with backoff while the server answers
+    /// 429 Too Many Requests.
+    async fn send(&self, request: reqwest::RequestBuilder) -> Result<reqwest::Response, TestError> {
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let retry = request
+                .try_clone()
+                .ok_or_else(|| TestError::Other("Request body can't be retried".to_string()))?;
+            let response = retry.send().await?;
+            if response.status() != reqwest::StatusCode::TOO_MANY_REQUESTS {
+                return Ok(response);
+            }
+            self.back_off(attempt, response.headers()).await?;
+        }
+    }
+
+    /// Waits before retrying a request the server rate limited, or gives up
+    /// after the last attempt.
+    async fn back_off(
+        &self,
+        attempt: u32,
+        headers: &reqwest::header::HeaderMap,
+    ) -> Result<(), TestError> {
+        if attempt >= rate_limit::MAX_ATTEMPTS {
+            self.rate_limits.gave_up.fetch_add(1, Ordering::Relaxed);
+            return Err(TestError::RateLimited(format!(
+                "still limited after {attempt} attempts"
+            )));
+        }
+        self.rate_limits.retries.fetch_add(1, Ordering::Relaxed);
+        tokio::time::sleep(rate_limit::backoff(attempt, headers)).await;
+        Ok(())
+    }
+
+    fn http_url(&self, path: &str) -> String {
+        let scheme = if self.tls { "https" } else { "http" };
+        format!("{}://{}{}", scheme, self.host, path)
+    }
+
+    /// The WebSocket `Connect` message for a join or create lobby response.
+    fn connect_message(&self, response: &serde_json::Value) -> Result<String, String> {
+        let field = if self.legacy_connect {
+            "player_id"
+        } else {
+            "session_token"
+        };
+        let credential = response[field]
+            .as_str()
+            .ok_or_else(|| format!("Missing {field} in lobby response"))?;
+        Ok(json!({ "type": "Connect", field: credential }).to_string())
+    }
+
+    async fn connect_ws(&self) -> Result<WsStream, TestError> {
+        let scheme = if self.tls { "wss" } else { "ws" };
+        let ws_url = format!("{}://{}/ws", scheme, self.host);
+        let mut attempt = 0;
+        loop {
+            attempt += 1;
+            let connect =
+                connect_async_tls_with_config(&ws_url, None, false, Some(self.connector.clone()));
+            match connect.await {
+                Ok((ws_stream, _)) => return Ok(ws_stream),
+                // The upgrade request counts against the HTTP rate limit.
+                Err(tokio_tungstenite::tungstenite::Error::Http(response))
+                    if response.status().as_u16() == 429 =>
+                {
+                    self.back_off(attempt, response.headers()).await?;
+                }
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
 }
 
 /// Shared metrics for throughput testing.
+#[derive(Default)]
 struct ThroughputMetrics {
     messages_sent: AtomicU64,
     messages_received: AtomicU64,
@@ -74,11 +385,15 @@ struct ThroughputMetrics {
 
 impl ThroughputMetrics {
     fn new() -> Self {
-        Self {
-            messages_sent: AtomicU64::new(0),
-            messages_received: AtomicU64::new(0),
-            bytes_sent: AtomicU64::new(0),
-            bytes_received: AtomicU64::new(0),
+        Self::default()
+    }
+
+    fn snapshot(&self) -> distributed::MetricsSnapshot {
+        distributed::MetricsSnapshot {
+            messages_sent: self.messages_sent.load(Ordering::Relaxed),
+            messages_received: self.messages_received.load(Ordering::Relaxed),
+            bytes_sent: self.bytes_sent.load(Ordering::Relaxed),
+            bytes_received: self.bytes_received.load(Ordering::Relaxed),
         }
     }
 }
@@ -100,63 +415,326 @@ impl GameplayMetrics {
     }
 }
 
+/// The `p`th percentile of sorted samples in milliseconds, 0 without samples.
+fn percentile_ms(sorted: &[Duration], p: usize) -> f64 {
+    sorted
+        .get(sorted.len().saturating_sub(1) * p / 100)
+        .map_or(0.0, |sample| sample.as_secs_f64() * 1000.0)
+}
+
+/// Server acknowledgement latencies, collected from every player.
+struct LatencyStats {
+    samples: Mutex<Vec<Duration>>,
+    unacknowledged: AtomicU64,
+    heartbeats: Arc<HeartbeatStats>,
+}
+
+impl LatencyStats {
+    fn new() -> Self {
+        Self {
+            samples: Mutex::new(Vec::new()),
+            unacknowledged: AtomicU64::new(0),
+            heartbeats: Arc::new(HeartbeatStats::default()),
+        }
+    }
+
+    fn summary(&self) -> LatencySummary {
+        let mut samples = self.samples.lock().unwrap().clone();
+        samples.sort();
+        LatencySummary {
+            acknowledged: samples.len(),
+            unacknowledged: self.unacknowledged.load(Ordering::Relaxed),
+            p50_ms: percentile_ms(&samples, 50),
+            p95_ms: percentile_ms(&samples, 95),
+            p99_ms: percentile_ms(&samples, 99),
+            max_ms: percentile_ms(&samples, 100),
+        }
+    }
+
+    fn report(&self) {
+        self.summary().report("Ack latency");
+        self.heartbeats.summary().report("Heartbeats");
+    }
+}
+
+/// Acknowledgement latency percentiles, also sent from workers to the
+/// coordinator.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct LatencySummary {
+    acknowledged: usize,
+    unacknowledged: u64,
+    p50_ms: f64,
+    p95_ms: f64,
+    p99_ms: f64,
+    max_ms: f64,
+}
+
+impl LatencySummary {
+    fn report(&self, label: &str) {
+        if self.acknowledged == 0 {
+            println!(
+                "{label}: no acknowledged messages ({} unacknowledged)",
+                self.unacknowledged
+            );
+            return;
+        }
+        println!(
+            "{label}: {} acknowledged, {} unacknowledged, p50 {:.2} ms, p95 {:.2} ms, p99 {:.2} ms, max {:.2} ms",
+            self.acknowledged,
+            self.unacknowledged,
+            self.p50_ms,
+            self.p95_ms,
+            self.p99_ms,
+            self.max_ms
+        );
+    }
+}
+
+/// Tags one player's messages with a `client_msg_id` and times how long the
+/// server takes to echo it back in `AnswerAck`, `AnswerRejected` or `Error`.
+#[derive(Clone)]
+struct AckTracker {
+    next_id: Arc<AtomicU64>,
+    pending: Arc<Mutex<HashMap<String, Instant>>>,
+    stats: Arc<LatencyStats>,
+}
+
+impl AckTracker {
+    fn new(stats: Arc<LatencyStats>) -> Self {
+        Self {
+            next_id: Arc::new(AtomicU64::new(0)),
+            pending: Arc::new(Mutex::new(HashMap::new())),
+            stats,
+        }
+    }
+
+    /// Returns the ID for a message about to be sent.
+    fn start(&self) -> String {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed).to_string();
+        self.pending
+            .lock()
+            .unwrap()
+            .insert(id.clone(), Instant::now());
+        id
+    }
+
+    /// Records the latency if `data` acknowledges one of this player's
+    /// messages.
+    fn acknowledge(&self, data: &serde_json::Value) {
+        let Some(id) = data["client_msg_id"].as_str() else {
+            return;
+        };
+        if let Some(sent) = self.pending.lock().unwrap().remove(id) {
+            self.stats.samples.lock().unwrap().push(sent.elapsed());
+        }
+    }
+
+    /// Counts messages that never got a reply.
+    fn finish(&self) {
+        let pending = self.pending.lock().unwrap().drain().count();
+        self.stats
+            .unacknowledged
+            .fetch_add(pending as u64, Ordering::Relaxed);
+    }
+}
+
+/// Correct options of a game's current question, published by its host from
+/// `AdminInfo` so players can answer correctly on purpose. Empty until known.
+type AnswerKey = watch::Sender<Vec<String>>;
+
+/// How long a player waits for the host to publish the answer key.
+const ANSWER_KEY_TIMEOUT: Duration = Duration::from_secs(1);
+
+/// The correct options of the question in an `AdminInfo` message.
+fn correct_options(admin_info: &serde_json::Value) -> Vec<String> {
+    admin_info["current_question"]["options"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter(|option| option["is_correct"].as_bool() == Some(true))
+        .filter_map(|option| option["option"].as_str().map(str::to_string))
+        .collect()
+}
+
+/// Think time of gameplay and UI test players unless `--think-time` is given.
+const DEFAULT_THINK_TIME: ThinkTime = ThinkTime::Uniform {
+    min_seconds: 0.0,
+    max_seconds: 40.0,
+};
+
+/// Think time of chaos test players unless `--think-time` is given.
+const DEFAULT_CHAOS_THINK_TIME: ThinkTime = ThinkTime::Uniform {
+    min_seconds: 0.0,
+    max_seconds: 3.0,
+};
+
+/// Knobs for the games of the gameplay test.
+#[derive(Clone, Copy)]
+struct GameOptions {
+    rounds: usize,
+    think_time: ThinkTime,
+    correct_ratio: Option<f64>,
+    check_scores: bool,
+}
+
+/// Knobs for the chaos test.
+#[derive(Clone, Copy)]
+struct ChaosOptions {
+    think_time: ThinkTime,
+    disconnect_probability: f64,
+    max_reconnect_delay: Duration,
+}
+
+/// Shared metrics for the chaos test.
+struct ChaosMetrics {
+    disconnects: AtomicUsize,
+    reconnects: AtomicUsize,
+    invalid_states: AtomicUsize,
+}
+
+impl ChaosMetrics {
+    fn new() -> Self {
+        Self {
+            disconnects: AtomicUsize::new(0),
+            reconnects: AtomicUsize::new(0),
+            invalid_states: AtomicUsize::new(0),
+        }
+    }
+}
+
 /// A test player simulates a client joining a lobby via HTTP and then opening a WebSocket.
 /// After joining, it sends a Connect message (using the new protocol) and later submits answers.
 struct TestPlayer {
     name: String,
     join_code: String,
-    session_token: String,
+    /// Sent again on every reconnect.
+    connect_msg: String,
     ws_write: futures_util::stream::SplitSink<WsStream, Message>,
     ws_read: futures_util::stream::SplitStream<WsStream>,
     rng: rand::rngs::StdRng,
+    acks: AckTracker,
+    /// The game's answer key and how often to answer correctly.
+    answer_key: Option<(watch::Receiver<Vec<String>>, f64)>,
+    /// Where submitted answers are recorded for `--check-scores`.
+    answer_log: Option<AnswerLog>,
+    heartbeat: HeartbeatTracker,
+    heartbeat_ticker: Option<Interval>,
+    rate_limits: Arc<RateLimitStats>,
 }
 
 impl TestPlayer {
     /// Create a new test player.
     /// First it calls the HTTP endpoint `/api/join-lobby` with its name and join code to obtain a session token.
-    /// Then it connects to the WebSocket endpoint and sends a `Connect { session_token }` message.
-    async fn new(name: String, join_code: String, host: &str) -> Result<Self, TestError> {
+    /// Then it connects to the WebSocket endpoint and sends a `Connect { session_token }` message
+    /// (`Connect { player_id }` with `--legacy-connect`).
+    async fn new(
+        name: String,
+        join_code: String,
+        server: &Server,
+        latency: Arc<LatencyStats>,
+    ) -> Result<Self, TestError> {
         // Join lobby via HTTP POST
-        let join_url = format!("http://{}/api/join-lobby", host);
-        let client = reqwest::Client::new();
-        let res = client
-            .post(&join_url)
-            .json(&json!({
-                "join_code": join_code,
-                "name": name,
-            }))
-            .send()
+        let res = server
+            .send(
+                server
+                    .http
+                    .post(server.http_url("/api/join-lobby"))
+                    .json(&json!({
+                        "join_code": join_code,
+                        "name": name,
+                    })),
+            )
             .await?;
         let join_response: serde_json::Value = res.json().await?;
-        let session_token = join_response["session_token"]
-            .as_str()
-            .ok_or_else(|| TestError::Other("Missing session_token in join response".to_string()))?
-            .to_string();
+        let connect_msg = server
+            .connect_message(&join_response)
+            .map_err(TestError::Other)?;
         // Connect via WebSocket
-        let ws_url = format!("ws://{}/ws", host);
-        let (ws_stream, _) = connect_async(&ws_url).await?;
+        let ws_stream = server.connect_ws().await?;
         let (mut write, read) = ws_stream.split();
-        // Send the new protocol connect message
-        let connect_msg = json!({
-            "type": "Connect",
-            "session_token": session_token,
-        });
-        write.send(Message::Text(connect_msg.to_string())).await?;
+        write.send(Message::Text(connect_msg.clone())).await?;
         Ok(Self {
+            rng: seeded_rng(&name),
             name,
             join_code,
-            session_token,
+            connect_msg,
             ws_write: write,
             ws_read: read,
-            rng: rand::rngs::StdRng::from_entropy(),
+            heartbeat: HeartbeatTracker::new(Arc::clone(&latency.heartbeats)),
+            heartbeat_ticker: heartbeat_ticker(server.heartbeat_interval),
+            rate_limits: Arc::clone(&server.rate_limits),
+            acks: AckTracker::new(latency),
+            answer_key: None,
+            answer_log: None,
         })
     }
 
+    fn with_answer_key(
+        mut self,
+        answer_key: watch::Receiver<Vec<String>>,
+        correct_ratio: f64,
+    ) -> Self {
+        self.answer_key = Some((answer_key, correct_ratio));
+        self
+    }
+
+    /// Picks one of the question's alternatives: a correct one with the
+    /// configured ratio if the answer key is known, otherwise any.
+    async fn choose_answer(&mut self, alternatives: &serde_json::Value) -> Option<String> {
+        let alternatives: Vec<&str> = alternatives
+            .as_array()?
+            .iter()
+            .filter_map(|alternative| alternative.as_str())
+            .collect();
+        if let Some((answer_key, correct_ratio)) = &mut self.answer_key {
+            let correct_ratio = *correct_ratio;
+            // The host reads the answer key right after the question goes out.
+            let correct = tokio::time::timeout(
+                ANSWER_KEY_TIMEOUT,
+                answer_key.wait_for(|correct| !correct.is_empty()),
+            )
+            .await
+            .ok()
+            .and_then(Result::ok)
+            .map(|correct| correct.clone());
+            if let Some(correct) = correct {
+                let want_correct = self.rng.gen_bool(correct_ratio);
+                let pool: Vec<&str> = alternatives
+                    .iter()
+                    .copied()
+                    .filter(|alternative| correct.iter().any(|c| c == alternative) == want_correct)
+                    .collect();
+                if let Some(answer) = pool.choose(&mut self.rng) {
+                    return Some(answer.to_string());
+                }
+            }
+        }
+        alternatives
+            .choose(&mut self.rng)
+            .map(|answer| answer.to_string())
+    }
+
+    /// Follows the server's reply to one of this player's messages.
+    fn acknowledge(&self, data: &serde_json::Value) {
+        self.acks.acknowledge(data);
+        if let Some(answer_log) = &self.answer_log {
+            answer_log.lock().unwrap().reply(&self.name, data);
+        }
+    }
+
     /// Submit an answer over the WebSocket.
     async fn submit_answer(&mut self, answer: String) -> Result<(), TestError> {
+        let client_msg_id = self.acks.start();
+        if let Some(answer_log) = &self.answer_log {
+            answer_log
+                .lock()
+                .unwrap()
+                .sent(&self.name, &client_msg_id, answer.clone());
+        }
         let answer_msg = json!({
             "type": "Answer",
             "answer": answer,
+            "client_msg_id": client_msg_id,
         });
         self.ws_write
             .send(Message::Text(answer_msg.to_string()))
@@ -164,11 +742,136 @@ impl TestPlayer {
         Ok(())
     }
 
+    /// Drops the WebSocket without a close frame, like a lost connection, then
+    /// reconnects with the same credentials after `delay`.
+    async fn reconnect(self, server: &Server, delay: Duration) -> Result<Self, TestError> {
+        let Self {
+            name,
+            join_code,
+            connect_msg,
+            ws_write,
+            ws_read,
+            rng,
+            acks,
+            answer_key,
+            answer_log,
+            heartbeat,
+            heartbeat_ticker: _,
+            rate_limits,
+        } = self;
+        drop(ws_write);
+        drop(ws_read);
+        // Its echo can't arrive on the new connection.
+        heartbeat.finish();
+        tokio::time::sleep(delay).await;
+
+        let (mut write, read) = server.connect_ws().await?.split();
+        write.send(Message::Text(connect_msg.clone())).await?;
+        Ok(Self {
+            name,
+            join_code,
+            connect_msg,
+            ws_write: write,
+            ws_read: read,
+            rng,
+            acks,
+            answer_key,
+            answer_log,
+            heartbeat,
+            heartbeat_ticker: heartbeat_ticker(server.heartbeat_interval),
+            rate_limits,
+        })
+    }
+
+    /// Next message from the server. Sends heartbeats while waiting and
+    /// answers pings, which are not returned, like heartbeat echoes.
+    async fn next_message(&mut self) -> Option<Result<Message, TestError>> {
+        loop {
+            let ticker = &mut self.heartbeat_ticker;
+            let heartbeat_due = async {
+                match ticker {
+                    Some(ticker) => ticker.tick().await,
+                    None => std::future::pending().await,
+                }
+            };
+            let reply = tokio::select! {
+                // Read an echo that already arrived before sending the next.
+                biased;
+                msg = self.ws_read.next() => {
+                    let msg = match msg? {
+                        Ok(msg) => msg,
+                        Err(e) => return Some(Err(e.into())),
+                    };
+                    match self.heartbeat.handle(msg) {
+                        Ok(Some(reply)) => reply,
+                        Ok(None) => continue,
+                        Err(Message::Text(text)) if rate_limit::is_ws_rate_limit(&text) => {
+                            self.rate_limits.ws_closed.fetch_add(1, Ordering::Relaxed);
+                            return Some(Err(TestError::RateLimited(
+                                "connection closed for sending too fast".to_string(),
+                            )));
+                        }
+                        Err(msg) => return Some(Ok(msg)),
+                    }
+                }
+                _ = heartbeat_due => self.heartbeat.start(),
+            };
+            if let Err(e) = self.ws_write.send(reply).await {
+                return Some(Err(e.into()));
+            }
+        }
+    }
+
+    /// Counts what never got a reply.
+    fn finish(&self) {
+        self.acks.finish();
+        self.heartbeat.finish();
+    }
+
+    /// Reads the full state the server sends after a reconnect and checks it.
+    /// Mid-round it must carry the remaining time and who has answered,
+    /// including this player if it answered before dropping.
+    async fn verify_reconnect_state(
+        &mut self,
+        answered: bool,
+    ) -> Result<Option<String>, TestError> {
+        let state = loop {
+            let Some(msg) = self.next_message().await else {
+                return Ok(Some("connection closed before state was sent".to_string()));
+            };
+            if let Message::Text(text) = msg? {
+                let data: serde_json::Value = serde_json::from_str(&text)?;
+                match data["type"].as_str() {
+                    Some("Connected") => {}
+                    Some("StateDelta") => break data,
+                    other => return Ok(Some(format!("expected StateDelta, got {other:?}"))),
+                }
+            }
+        };
+        if state["phase"].as_str() != Some("question") {
+            return Ok(None);
+        }
+        if !state["question_time_remaining_ms"].is_u64() {
+            return Ok(Some("missing question_time_remaining_ms".to_string()));
+        }
+        let Some(answered_names) = state["answered_player_names"].as_array() else {
+            return Ok(Some("missing answered_player_names".to_string()));
+        };
+        if answered
+            && !answered_names
+                .iter()
+                .any(|n| n.as_str() == Some(&self.name))
+        {
+            return Ok(Some(format!("{} missing from answered players", self.name)));
+        }
+        Ok(None)
+    }
+
     /// Process incoming WebSocket messages.
-    /// For example, when a StateDelta is received with phase "question", wait a random delay
+    /// For example, when a StateDelta is received with phase "question", wait the think time
     /// then pick one of the alternatives and submit it as the answer.
-    async fn handle_messages(&mut self) -> Result<(), TestError> {
-        while let Some(msg) = self.ws_read.next().await {
+    async fn handle_messages(&mut self, think_time: ThinkTime) -> Result<(), TestError> {
+        while let Some(msg) = self.next_message().await {
             let msg = msg?;
             if let Message::Text(text) = msg {
                 let data: serde_json::Value = serde_json::from_str(&text)?;
@@ -176,16 +879,15 @@ impl TestPlayer {
                     Some("Connected") => {
                         // Connection acknowledgement received.
                     }
+                    Some("AnswerAck") | Some("AnswerRejected") | Some("Error") => {
+                        self.acknowledge(&data)
+                    }
                     Some("StateDelta") => {
                         if data["phase"].as_str() == Some("question") {
-                            // Simulate thinking time before answering.
-                            let delay = self.rng.gen_range(0.0..40.0);
-                            tokio::time::sleep(Duration::from_secs_f32(delay)).await;
-                            if let Some(alternatives) = data["alternatives"].as_array()
-                                && let Some(answer_val) = alternatives.choose(&mut self.rng)
-                                    && let Some(answer_str) = answer_val.as_str() {
-                                        self.submit_answer(answer_str.to_string()).await?;
-                                    }
+                            tokio::time::sleep(think_time.sample(&mut self.rng)).await;
+                            if let Some(answer) = self.choose_answer(&data["alternatives"]).await {
+                                self.submit_answer(answer).await?;
+                            }
                         }
                     }
                     Some("GameOver") | Some("GameClosed") => break,
@@ -193,6 +895,7 @@ impl TestPlayer {
                 }
             }
         }
+        self.finish();
         Ok(())
     }
 }
@@ -200,21 +903,25 @@ impl TestPlayer {
 /// A test admin joins a lobby (using the HTTP create endpoint) and then opens a WebSocket connection
 /// to send admin actions.
 struct TestAdmin {
-    session_token: String,
     ws_write: futures_util::stream::SplitSink<WsStream, Message>,
     ws_read: futures_util::stream::SplitStream<WsStream>,
+    /// Set once `GameOver` has been read.
+    game_over: bool,
+    /// Follows the game's scores for `--check-scores`.
+    score_check: Option<ScoreCheck>,
 }
 
 impl TestAdmin {
     /// Create a new lobby via HTTP POST to `/api/create-lobby` and then connect via WS.
     /// Returns both the TestAdmin instance and the lobby's join code.
-    async fn new(host: &str) -> Result<(Self, String), TestError> {
-        let create_url = format!("http://{}/api/create-lobby", host);
-        let client = reqwest::Client::new();
-        let res = client
-            .post(&create_url)
-            .json(&json!({ "round_duration": 60 }))
-            .send()
+    async fn new(server: &Server) -> Result<(Self, String), TestError> {
+        let res = server
+            .send(
+                server
+                    .http
+                    .post(server.http_url("/api/create-lobby"))
+                    .json(&json!({ "round_duration": 60 })),
+            )
             .await?;
         let create_response: serde_json::Value = res.json().await?;
         let join_code = create_response["join_code"]
@@ -223,23 +930,19 @@ impl TestAdmin {
                 TestError::Other("Missing join_code in create lobby response".to_string())
             })?
             .to_string();
-        let session_token = create_response["session_token"].as_str().ok_or_else(|| {
-            TestError::Other("Missing session_token in create lobby response".to_string())
-        })?;
+        let connect_msg = server
+            .connect_message(&create_response)
+            .map_err(TestError::Other)?;
         // Connect via WebSocket
-        let ws_url = format!("ws://{}/ws", host);
-        let (ws_stream, _) = connect_async(&ws_url).await?;
+        let ws_stream = server.connect_ws().await?;
         let (mut write, read) = ws_stream.split();
-        let connect_msg = json!({
-            "type": "Connect",
-            "session_token": session_token,
-        });
-        write.send(Message::Text(connect_msg.to_string())).await?;
+        write.send(Message::Text(connect_msg)).await?;
         Ok((
             Self {
-                session_token: session_token.to_string(),
                 ws_write: write,
                 ws_read: read,
+                game_over: false,
+                score_check: None,
             },
             join_code,
         ))
@@ -281,10 +984,25 @@ impl TestAdmin {
         Ok(())
     }
 
+    async fn close_game(&mut self) -> Result<(), TestError> {
+        let msg = json!({
+            "type": "AdminAction",
+            "action": { "type": "CloseGame", "reason": "Test complete" }
+        });
+        self.ws_write.send(Message::Text(msg.to_string())).await?;
+        Ok(())
+    }
+
     async fn handle_messages(&mut self) -> Result<(), TestError> {
+        if self.game_over {
+            return Ok(());
+        }
         while let Some(msg) = self.ws_read.next().await {
             if let Message::Text(text) = msg? {
                 let data: serde_json::Value = serde_json::from_str(&text)?;
+                if let Some(score_check) = &mut self.score_check {
+                    score_check.observe(&data);
+                }
                 if data["type"].as_str() == Some("GameOver") {
                     break;
                 }
@@ -292,6 +1010,58 @@ impl TestAdmin {
         }
         Ok(())
     }
+
+    /// Starts a round and, with an answer key, fills it with the correct
+    /// options from the `AdminInfo` the host receives.
+    async fn start_round_with_key(
+        &mut self,
+        answer_key: Option<&AnswerKey>,
+    ) -> Result<(), TestError> {
+        let Some(answer_key) = answer_key else {
+            return self.start_round().await;
+        };
+        answer_key.send_replace(Vec::new());
+        self.start_round().await?;
+        while let Some(msg) = self.ws_read.next().await {
+            let Message::Text(text) = msg? else {
+                continue;
+            };
+            let data: serde_json::Value = serde_json::from_str(&text)?;
+            if let Some(score_check) = &mut self.score_check {
+                score_check.observe(&data);
+            }
+            match data["type"].as_str() {
+                Some("AdminInfo") => {
+                    answer_key.send_replace(correct_options(&data));
+                    break;
+                }
+                Some("GameOver") => {
+                    self.game_over = true;
+                    break;
+                }
+                Some("Error") => break,
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    /// Drives a whole game: the rounds, then ending the game.
+    async fn run_game(
+        &mut self,
+        rounds: usize,
+        answer_key: Option<&AnswerKey>,
+    ) -> Result<(), TestError> {
+        self.start_game().await?;
+        for _ in 0..rounds {
+            self.start_round_with_key(answer_key).await?;
+            tokio::time::sleep(Duration::from_secs(5)).await;
+            self.end_round().await?;
+            tokio::time::sleep(Duration::from_secs(2)).await;
+        }
+        self.end_game().await?;
+        self.handle_messages().await
+    }
 }
 
 /// Throughput test: Each player repeatedly sends answer messages (with a fixed “stress_test” answer)
@@ -299,19 +1069,42 @@ impl TestAdmin {
 async fn run_throughput_player(
     join_code: String,
     name: String,
-    host: &str,
+    server: &Server,
     metrics: Arc<ThroughputMetrics>,
+    latency: Arc<LatencyStats>,
     test_duration: Duration,
 ) -> Result<(), TestError> {
-    let player = TestPlayer::new(name, join_code, host).await?;
+    let player = TestPlayer::new(name, join_code, server, latency).await?;
+    let acks = player.acks.clone();
+    let heartbeat = player.heartbeat.clone();
+    // The receiver hands pings to the sender, which owns the write half.
+    let (pong_tx, mut pong_rx) = mpsc::unbounded_channel();
     let sender_metrics = Arc::clone(&metrics);
     let mut ws_write = player.ws_write;
+    let heartbeat_interval = server.heartbeat_interval;
+    let sender_acks = acks.clone();
+    let sender_heartbeat = heartbeat.clone();
     let sender = tokio::spawn(async move {
         let start = Instant::now();
+        let mut next_heartbeat = heartbeat_interval.map(|interval| start + interval);
         while start.elapsed() < test_duration {
+            let control = pong_rx.try_recv().ok().or_else(|| {
+                let due = next_heartbeat
+                    .as_mut()
+                    .filter(|due| **due <= Instant::now())?;
+                *due += heartbeat_interval?;
+                Some(sender_heartbeat.start())
+            });
+            if let Some(msg) = control {
+                if ws_write.send(msg).await.is_err() {
+                    break;
+                }
+                continue;
+            }
             let msg = json!({
                 "type": "Answer",
-                "answer": "stress_test"
+                "answer": "stress_test",
+                "client_msg_id": sender_acks.start(),
             });
             let msg_str = msg.to_string();
             if ws_write.send(Message::Text(msg_str.clone())).await.is_ok() {
@@ -326,21 +1119,47 @@ async fn run_throughput_player(
     });
     let receiver_metrics = Arc::clone(&metrics);
     let mut ws_read = player.ws_read;
+    let receiver_acks = acks.clone();
+    let receiver_heartbeat = heartbeat.clone();
+    let rate_limits = Arc::clone(&server.rate_limits);
     let receiver = tokio::spawn(async move {
         while let Some(msg) = ws_read.next().await {
-            if let Ok(Message::Text(text)) = msg {
+            let Ok(msg) = msg else {
+                // The server drops a connection over its message rate without
+                // a close frame, usually before its error gets out. These
+                // players never go idle, so a reset means the rate limit.
+                rate_limits.ws_closed.fetch_add(1, Ordering::Relaxed);
+                break;
+            };
+            let msg = match receiver_heartbeat.handle(msg) {
+                Ok(Some(pong)) => {
+                    let _ = pong_tx.send(pong);
+                    continue;
+                }
+                Ok(None) => continue,
+                Err(msg) => msg,
+            };
+            if let Message::Text(text) = msg {
                 receiver_metrics
                     .messages_received
                     .fetch_add(1, Ordering::Relaxed);
                 receiver_metrics
                     .bytes_received
                     .fetch_add(text.len() as u64, Ordering::Relaxed);
+                if rate_limit::is_ws_rate_limit(&text) {
+                    rate_limits.ws_closed.fetch_add(1, Ordering::Relaxed);
+                    break;
+                } else if let Ok(data) = serde_json::from_str::<serde_json::Value>(&text) {
+                    receiver_acks.acknowledge(&data);
+                }
             }
         }
     });
     tokio::time::sleep(test_duration).await;
     sender.abort();
     receiver.abort();
+    acks.finish();
+    heartbeat.finish();
     Ok(())
 }
 
@@ -400,9 +1219,11 @@ async fn run_throughput_test(
     num_lobbies: usize,
     players_per_lobby: usize,
     test_duration: Duration,
-    host: &str,
+    server: &Server,
+    schedule: ConnectionSchedule,
+    metrics: Arc<ThroughputMetrics>,
+    latency: Arc<LatencyStats>,
 ) -> Result<(), TestError> {
-    let metrics = Arc::new(ThroughputMetrics::new());
     let start_time = Instant::now();
     let mut handles = vec![];
 
@@ -414,14 +1235,17 @@ async fn run_throughput_test(
     ));
 
     // Create lobbies and spawn players
-    for _ in 0..num_lobbies {
+    for lobby_idx in 0..num_lobbies {
+        let first_connection = lobby_idx * (players_per_lobby + 1);
+        schedule.wait(first_connection).await;
         // Create lobby via HTTP
-        let create_url = format!("http://{}/api/create-lobby", host);
-        let client = reqwest::Client::new();
-        let res = client
-            .post(&create_url)
-            .json(&json!({ "round_duration": 60 }))
-            .send()
+        let res = server
+            .send(
+                server
+                    .http
+                    .post(server.http_url("/api/create-lobby"))
+                    .json(&json!({ "round_duration": 60 })),
+            )
             .await?;
         let create_response: serde_json::Value = res.json().await?;
         let join_code = create_response["join_code"]
@@ -432,16 +1256,19 @@ async fn run_throughput_test(
             .to_string();
 
         for i in 0..players_per_lobby {
-            let name = format!("LobbyPlayer{}", i + 1);
+            let name = format!("Lobby{}Player{}", lobby_idx, i + 1);
             let join_code_clone = join_code.clone();
-            let host_clone = host.to_string();
+            let server_clone = server.clone();
             let metrics_clone = Arc::clone(&metrics);
+            let latency_clone = Arc::clone(&latency);
             let handle = tokio::spawn(async move {
+                schedule.wait(first_connection + 1 + i).await;
                 if let Err(e) = run_throughput_player(
                     join_code_clone,
                     name,
-                    &host_clone,
+                    &server_clone,
                     metrics_clone,
+                    latency_clone,
                     test_duration,
                 )
                 .await
@@ -455,8 +1282,19 @@ async fn run_throughput_test(
     }
 
     tokio::time::sleep(test_duration).await;
+    // Let the players stop and count their unacknowledged messages.
+    for handle in handles {
+        let _ = handle.await;
+    }
+
+    print_throughput_summary(&metrics, start_time.elapsed().as_secs_f64());
+    latency.report();
+
+    reporter_handle.abort();
+    Ok(())
+}
 
-    let duration = start_time.elapsed().as_secs_f64();
+fn print_throughput_summary(metrics: &ThroughputMetrics, duration: f64) {
     let total_sent = metrics.messages_sent.load(Ordering::Relaxed);
     let total_received = metrics.messages_received.load(Ordering::Relaxed);
 
@@ -480,9 +1318,6 @@ async fn run_throughput_test(
         "Total Data Received: {:.2} MB",
         metrics.bytes_received.load(Ordering::Relaxed) as f64 / 1_048_576.0
     );
-
-    reporter_handle.abort();
-    Ok(())
 }
 
 /// Gameplay test: spawn several games in parallel. In each game, an admin (created via HTTP)
@@ -490,21 +1325,35 @@ async fn run_throughput_test(
 async fn run_game_batch(
     batch_size: usize,
     players_per_game: usize,
-    rounds: usize,
-    host: &str,
+    server: &Server,
+    schedule: ConnectionSchedule,
+    options: GameOptions,
 ) -> Result<(), TestError> {
     let metrics = Arc::new(GameplayMetrics::new());
+    let latency = Arc::new(LatencyStats::new());
     let mut game_handles = Vec::new();
     let batch_start = Instant::now();
 
     for game_idx in 0..batch_size {
-        let host_clone = host.to_string();
+        let server_clone = server.clone();
         let metrics_clone = Arc::clone(&metrics);
+        let latency_clone = Arc::clone(&latency);
         let game_handle = tokio::spawn(async move {
             metrics_clone.active_games.fetch_add(1, Ordering::SeqCst);
-            if let Err(e) = run_single_game(game_idx, players_per_game, rounds, &host_clone).await {
+            if let Err(e) = run_single_game(
+                game_idx,
+                players_per_game,
+                &server_clone,
+                latency_clone,
+                schedule,
+                options,
+            )
+            .await
+            {
                 eprintln!("Game {} error: {}", game_idx, e);
-                metrics_clone.errors.fetch_add(1, Ordering::SeqCst);
+                if !e.is_rate_limited() {
+                    metrics_clone.errors.fetch_add(1, Ordering::SeqCst);
+                }
             }
             metrics_clone.active_games.fetch_sub(1, Ordering::SeqCst);
             metrics_clone.completed_games.fetch_add(1, Ordering::SeqCst);
@@ -540,7 +1389,14 @@ async fn run_game_batch(
         duration.as_secs_f64(),
         batch_size as f64 / duration.as_secs_f64()
     );
+    latency.report();
 
+    let errors = metrics.errors.load(Ordering::SeqCst);
+    if options.check_scores && errors > 0 {
+        return Err(TestError::Other(format!(
+            "{errors} games failed with --check-scores"
+        )));
+    }
     Ok(())
 }
 
@@ -549,33 +1405,34 @@ async fn run_game_batch(
 async fn run_single_game(
     game_idx: usize,
     players_per_game: usize,
-    rounds: usize,
-    host: &str,
+    server: &Server,
+    latency: Arc<LatencyStats>,
+    schedule: ConnectionSchedule,
+    options: GameOptions,
 ) -> Result<(), TestError> {
-    // Create lobby via HTTP
-    let create_url = format!("http://{}/api/create-lobby", host);
-    let client = reqwest::Client::new();
-    let res = client
-        .post(&create_url)
-        .json(&json!({ "round_duration": 60 }))
-        .send()
-        .await?;
-    let lobby_data: serde_json::Value = res.json().await?;
-    let join_code = lobby_data["join_code"].as_str().unwrap().to_string();
-    // Admin's session_token is returned in the create response (kept for completeness).
-    let _admin_session = lobby_data["session_token"]
-        .as_str()
-        .unwrap_or_default()
-        .to_string();
-
-    // Create admin via TestAdmin::new (which also connects via WS)
-    let (mut admin, _) = TestAdmin::new(host).await?;
+    // The admin's lobby, then the players.
+    let first_connection = game_idx * (players_per_game + 1);
+    schedule.wait(first_connection).await;
+    // Create the lobby and connect its admin
+    let (mut admin, join_code) = TestAdmin::new(server).await?;
+    let (answer_key, answer_key_rx) = watch::channel(Vec::new());
+    if options.check_scores {
+        admin.score_check = Some(ScoreCheck::default());
+    }
 
     // Create players (HTTP join then WS connect)
     let mut players = Vec::new();
     for i in 0..players_per_game {
         let player_name = format!("Game{}Player{}", game_idx, i + 1);
-        let player = TestPlayer::new(player_name, join_code.clone(), host).await?;
+        schedule.wait(first_connection + 1 + i).await;
+        let mut player =
+            TestPlayer::new(player_name, join_code.clone(), server, Arc::clone(&latency)).await?;
+        if let Some(ratio) = options.correct_ratio {
+            player = player.with_answer_key(answer_key_rx.clone(), ratio);
+        }
+        if let Some(score_check) = &admin.score_check {
+            player.answer_log = Some(Arc::clone(&score_check.answers));
+        }
         players.push(player);
     }
 
@@ -584,35 +1441,158 @@ async fn run_single_game(
     let mut player_handles = Vec::new();
     for mut player in players {
         player_handles.push(tokio::spawn(async move {
-            if let Err(e) = player.handle_messages().await {
+            if let Err(e) = player.handle_messages(options.think_time).await {
                 eprintln!("Player error in game {}: {}", game_idx, e);
             }
         }));
     }
     let admin_handle = tokio::spawn(async move {
-        if let Err(e) = async {
-            tokio::time::sleep(Duration::from_millis(200)).await;
-            admin.start_game().await?;
-            for _ in 0..rounds {
-                admin.start_round().await?;
-                tokio::time::sleep(Duration::from_secs(5)).await;
-                admin.end_round().await?;
-                tokio::time::sleep(Duration::from_secs(2)).await;
-            }
-            admin.end_game().await?;
-            admin.handle_messages().await
-        }
-        .await
-        {
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        // The score check needs the answer key as well.
+        let answer_key =
+            (options.correct_ratio.is_some() || options.check_scores).then_some(&answer_key);
+        if let Err(e) = admin.run_game(options.rounds, answer_key).await {
             eprintln!("Admin error in game {}: {}", game_idx, e);
         }
+        admin.score_check
     });
     for handle in player_handles {
         handle.await.map_err(|e| TestError::Other(e.to_string()))?;
     }
-    admin_handle
+    let problems = admin_handle
         .await
-        .map_err(|e| TestError::Other(e.to_string()))?;
+        .map_err(|e| TestError::Other(e.to_string()))?
+        .map(ScoreCheck::finish)
+        .unwrap_or_default();
+    for problem in &problems {
+        eprintln!("Score check in game {}: {}", game_idx, problem);
+    }
+    if !problems.is_empty() {
+        return Err(TestError::Other(format!(
+            "{} score check problems",
+            problems.len()
+        )));
+    }
+    Ok(())
+}
+
+/// Chaos player: answers like a normal player, but in some rounds drops its
+/// connection at a random point (sometimes after answering) and reconnects,
+/// then checks the state the server resends.
+async fn run_chaos_player(
+    mut player: TestPlayer,
+    server: &Server,
+    metrics: &ChaosMetrics,
+    options: ChaosOptions,
+) -> Result<(), TestError> {
+    while let Some(msg) = player.next_message().await {
+        let Message::Text(text) = msg? else {
+            continue;
+        };
+        let data: serde_json::Value = serde_json::from_str(&text)?;
+        match data["type"].as_str() {
+            Some("AnswerAck") | Some("AnswerRejected") | Some("Error") => player.acknowledge(&data),
+            Some("StateDelta") if data["phase"].as_str() == Some("question") => {
+                let answer = player.choose_answer(&data["alternatives"]).await;
+                let drop_connection = player.rng.gen_bool(options.disconnect_probability);
+                let answer_first = !drop_connection || player.rng.gen_bool(0.5);
+                tokio::time::sleep(options.think_time.sample(&mut player.rng)).await;
+                let mut answered = false;
+                if answer_first && let Some(answer) = answer {
+                    player.submit_answer(answer).await?;
+                    answered = true;
+                }
+                if !drop_connection {
+                    continue;
+                }
+                let delay = options
+                    .max_reconnect_delay
+                    .mul_f64(player.rng.gen_range(0.0..1.0));
+                metrics.disconnects.fetch_add(1, Ordering::SeqCst);
+                player = player.reconnect(server, delay).await?;
+                metrics.reconnects.fetch_add(1, Ordering::SeqCst);
+                if let Some(problem) = player.verify_reconnect_state(answered).await? {
+                    eprintln!("Invalid reconnect state for {}: {}", player.name, problem);
+                    metrics.invalid_states.fetch_add(1, Ordering::SeqCst);
+                }
+            }
+            Some("StateDelta")
+                if matches!(data["phase"].as_str(), Some("gameover" | "gameclosed")) =>
+            {
+                break;
+            }
+            Some("GameOver") | Some("GameClosed") => break,
+            _ => {}
+        }
+    }
+    player.finish();
+    Ok(())
+}
+
+/// Chaos test: games like the gameplay test, where players randomly drop and
+/// restore their connections mid-round.
+async fn run_chaos_test(
+    num_games: usize,
+    players_per_game: usize,
+    rounds: usize,
+    server: &Server,
+    schedule: ConnectionSchedule,
+    correct_ratio: Option<f64>,
+    options: ChaosOptions,
+) -> Result<(), TestError> {
+    let metrics = Arc::new(ChaosMetrics::new());
+    let latency = Arc::new(LatencyStats::new());
+    let start = Instant::now();
+    let mut player_handles = Vec::new();
+    let mut admin_handles = Vec::new();
+
+    for game_idx in 0..num_games {
+        let first_connection = game_idx * (players_per_game + 1);
+        schedule.wait(first_connection).await;
+        let (mut admin, join_code) = TestAdmin::new(server).await?;
+        let (answer_key, answer_key_rx) = watch::channel(Vec::new());
+        for i in 0..players_per_game {
+            schedule.wait(first_connection + 1 + i).await;
+            let name = format!("Chaos{}Player{}", game_idx, i + 1);
+            let mut player =
+                TestPlayer::new(name, join_code.clone(), server, Arc::clone(&latency)).await?;
+            if let Some(ratio) = correct_ratio {
+                player = player.with_answer_key(answer_key_rx.clone(), ratio);
+            }
+            let server = server.clone();
+            let metrics = Arc::clone(&metrics);
+            player_handles.push(tokio::spawn(async move {
+                if let Err(e) = run_chaos_player(player, &server, &metrics, options).await {
+                    eprintln!("Player error in game {}: {}", game_idx, e);
+                }
+            }));
+        }
+        admin_handles.push(tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(500)).await;
+            let answer_key = correct_ratio.map(|_| &answer_key);
+            if let Err(e) = admin.run_game(rounds, answer_key).await {
+                eprintln!("Admin error in game {}: {}", game_idx, e);
+            }
+        }));
+    }
+
+    for handle in admin_handles.into_iter().chain(player_handles) {
+        handle.await.map_err(|e| TestError::Other(e.to_string()))?;
+    }
+
+    println!(
+        "Chaos test complete - Duration: {:.2}s, Disconnects: {}, Reconnects: {}, Invalid reconnect states: {}",
+        start.elapsed().as_secs_f64(),
+        metrics.disconnects.load(Ordering::SeqCst),
+        metrics.reconnects.load(Ordering::SeqCst),
+        metrics.invalid_states.load(Ordering::SeqCst)
+    );
+    latency.report();
+    if metrics.invalid_states.load(Ordering::SeqCst) > 0 {
+        return Err(TestError::Other(
+            "Server sent invalid state after reconnect".to_string(),
+        ));
+    }
     Ok(())
 }
 
@@ -621,7 +1601,9 @@ async fn run_ui_test(
     join_code: String,
     num_players: usize,
     rounds: usize,
-    host: &str,
+    server: &Server,
+    schedule: ConnectionSchedule,
+    think_time: ThinkTime,
 ) -> Result<(), TestError> {
     println!("Starting UI test with:");
     println!("Join code: {}", join_code);
@@ -629,10 +1611,13 @@ async fn run_ui_test(
     println!("Rounds: {}", rounds);
 
     let metrics = Arc::new(GameplayMetrics::new());
+    let latency = Arc::new(LatencyStats::new());
     let mut players = Vec::new();
     for i in 0..num_players {
         let player_name = format!("UITestPlayer{}", i + 1);
-        let player = TestPlayer::new(player_name, join_code.clone(), host).await?;
+        schedule.wait(i).await;
+        let player =
+            TestPlayer::new(player_name, join_code.clone(), server, Arc::clone(&latency)).await?;
         players.push(player);
         tokio::time::sleep(Duration::from_millis(100)).await;
     }
@@ -643,9 +1628,11 @@ async fn run_ui_test(
         let metrics = Arc::clone(&metrics);
         let handle = tokio::spawn(async move {
             metrics.active_games.fetch_add(1, Ordering::SeqCst);
-            if let Err(e) = player.handle_messages().await {
+            if let Err(e) = player.handle_messages(think_time).await {
                 eprintln!("Player error: {}", e);
-                metrics.errors.fetch_add(1, Ordering::SeqCst);
+                if !e.is_rate_limited() {
+                    metrics.errors.fetch_add(1, Ordering::SeqCst);
+                }
             }
             metrics.active_games.fetch_sub(1, Ordering::SeqCst);
             metrics.completed_games.fetch_add(1, Ordering::SeqCst);
@@ -669,6 +1656,7 @@ async fn run_ui_test(
         handle.await.map_err(|e| TestError::Other(e.to_string()))?;
     }
     metrics_handle.abort();
+    latency.report();
     println!("UI test completed successfully");
     Ok(())
 }
@@ -676,19 +1664,40 @@ async fn run_ui_test(
 #[tokio::main]
 async fn main() -> Result<(), TestError> {
     let args = Args::parse();
-    let host = args.host.clone();
+    let seed = args.seed.unwrap_or_else(rand::random);
+    println!("Seed: {seed}");
+    SEED.set(seed).expect("seed is only set once");
+    let server = Server::new(
+        args.host.clone(),
+        args.tls,
+        args.insecure,
+        args.legacy_connect,
+        (args.heartbeat_interval_ms > 0).then(|| Duration::from_millis(args.heartbeat_interval_ms)),
+    )?;
+
+    if let Some(path) = &args.scenario {
+        let scenario = scenario::Scenario::load(path).map_err(TestError::Scenario)?;
+        let result = scenario::run_scenario(&scenario, &server, &args).await;
+        server.rate_limits.report();
+        return result;
+    }
 
-    match args.mode {
+    let result = match args.mode {
         TestMode::ThroughputTest => {
             println!("Starting throughput test with:");
             println!("Number of lobbies: {}", args.num_lobbies);
             println!("Players per lobby: {}", args.players_per_lobby);
             println!("Test duration: {} seconds", args.duration_or_rounds);
+            let connections = args.num_lobbies * (args.players_per_lobby + 1);
+            let schedule = ConnectionSchedule::new(&args, connections);
             run_throughput_test(
                 args.num_lobbies,
                 args.players_per_lobby,
                 Duration::from_secs(args.duration_or_rounds),
-                &host,
+                &server,
+                schedule,
+                Arc::new(ThroughputMetrics::new()),
+                Arc::new(LatencyStats::new()),
             )
             .await
         }
@@ -697,15 +1706,78 @@ async fn main() -> Result<(), TestError> {
             println!("Batch size: {}", args.num_lobbies);
             println!("Players per game: {}", args.players_per_lobby);
             println!("Rounds per game: {}", args.duration_or_rounds);
+            let connections = args.num_lobbies * (args.players_per_lobby + 1);
+            let schedule = ConnectionSchedule::new(&args, connections);
             run_game_batch(
+                args.num_lobbies,
+                args.players_per_lobby,
+                &server,
+                schedule,
+                GameOptions {
+                    rounds: args.duration_or_rounds as usize,
+                    think_time: args.think_time.unwrap_or(DEFAULT_THINK_TIME),
+                    correct_ratio: args.correct_ratio,
+                    check_scores: args.check_scores,
+                },
+            )
+            .await
+        }
+        TestMode::ChaosTest => {
+            println!("Starting chaos test with:");
+            println!("Games: {}", args.num_lobbies);
+            println!("Players per game: {}", args.players_per_lobby);
+            println!("Rounds per game: {}", args.duration_or_rounds);
+            println!("Disconnect probability: {}", args.disconnect_probability);
+            let connections = args.num_lobbies * (args.players_per_lobby + 1);
+            let schedule = ConnectionSchedule::new(&args, connections);
+            run_chaos_test(
                 args.num_lobbies,
                 args.players_per_lobby,
                 args.duration_or_rounds as usize,
-                &host,
+                &server,
+                schedule,
+                args.correct_ratio,
+                ChaosOptions {
+                    think_time: args.think_time.unwrap_or(DEFAULT_CHAOS_THINK_TIME),
+                    disconnect_probability: args.disconnect_probability,
+                    max_reconnect_delay: Duration::from_millis(args.max_reconnect_delay_ms),
+                },
             )
             .await
         }
+        TestMode::SoakTest => {
+            println!("Starting soak test with:");
+            println!("Lobbies: {}", args.num_lobbies);
+            println!("Players per lobby: {}", args.players_per_lobby);
+            println!("Duration: {} seconds", args.duration_or_rounds);
+            let connections = args.num_lobbies * (args.players_per_lobby + 1);
+            let schedule = ConnectionSchedule::new(&args, connections);
+            let options = soak::SoakOptions {
+                num_lobbies: args.num_lobbies,
+                players_per_lobby: args.players_per_lobby,
+                duration: Duration::from_secs(args.duration_or_rounds),
+                sample_interval: Duration::from_secs_f64(args.sample_interval_seconds),
+                churn_interval: Duration::from_secs_f64(args.lobby_churn_seconds),
+                admin_password: args.admin_password.clone(),
+            };
+            soak::run_soak_test(options, &server, schedule).await
+        }
+        TestMode::Coordinator => {
+            println!("Starting coordinator with:");
+            println!("Workers: {}", args.workers);
+            println!("Number of lobbies: {}", args.num_lobbies);
+            println!("Players per lobby: {}", args.players_per_lobby);
+            println!("Test duration: {} seconds", args.duration_or_rounds);
+            distributed::run_coordinator(&args).await
+        }
+        TestMode::Worker => {
+            let coordinator = args.coordinator.as_deref().ok_or_else(|| {
+                TestError::Other("--coordinator is required for worker mode".to_string())
+            })?;
+            distributed::run_worker(coordinator, &server, &args).await
+        }
         TestMode::UiTest => {
+            let schedule = ConnectionSchedule::new(&args, args.players_per_lobby);
             let join_code = args.join_code.ok_or_else(|| {
                 TestError::Other("Join code is required for UI test mode".to_string())
             })?;
@@ -713,9 +1785,16 @@ async fn main() -> Result<(), TestError> {
                 join_code,
                 args.players_per_lobby,
                 args.duration_or_rounds as usize,
-                &host,
+                &server,
+                schedule,
+                args.think_time.unwrap_or(DEFAULT_THINK_TIME),
             )
             .await
         }
+    };
+    // The coordinator itself doesn't talk to the server.
+    if !matches!(args.mode, TestMode::Coordinator) {
+        server.rate_limits.report();
     }
+    result
 }
@@ -0,0 +1,64 @@
+//! Rate limiting by the server. HTTP requests and WebSocket upgrades answered
+//! with 429 are retried with jittered exponential backoff, and WebSocket
+//! connections closed for sending too fast are counted apart from other
+//! failures.
+
+use rand::Rng;
+use reqwest::header::HeaderMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::Duration;
+
+/// Attempts at a rate limited request before giving up.
+pub const MAX_ATTEMPTS: u32 = 5;
+
+/// Backoff before the first retry, doubled for each one after it.
+const BASE_BACKOFF: Duration = Duration::from_millis(500);
+
+/// Start of the error the server sends before closing a connection that
+/// exceeded its message rate.
+const WS_RATE_LIMIT_MESSAGE: &str = "Rate limit exceeded";
+
+#[derive(Default)]
+pub struct RateLimitStats {
+    /// Requests retried after a 429.
+    pub retries: AtomicU64,
+    /// Requests still rate limited after every attempt.
+    pub gave_up: AtomicU64,
+    /// WebSocket connections closed for sending too fast.
+    pub ws_closed: AtomicU64,
+}
+
+impl RateLimitStats {
+    pub fn report(&self) {
+        println!(
+            "Rate limited: {} HTTP retries, {} HTTP requests gave up, {} WebSocket connections closed",
+            self.retries.load(Ordering::Relaxed),
+            self.gave_up.load(Ordering::Relaxed),
+            self.ws_closed.load(Ordering::Relaxed)
+        );
+    }
+}
+
+/// How long to wait before retry number `attempt` (from 1): the server's
+/// `Retry-After` if longer than the backoff, plus up to as much again in
+/// jitter so limited clients don't all retry at once.
+pub fn backoff(attempt: u32, headers: &HeaderMap) -> Duration {
+    let retry_after = ["retry-after", "x-ratelimit-after"]
+        .iter()
+        .find_map(|name| headers.get(*name)?.to_str().ok()?.parse().ok())
+        .map(Duration::from_secs)
+        .unwrap_or_default();
+    let wait = retry_after.max(BASE_BACKOFF * 2u32.pow(attempt - 1));
+    wait + wait.mul_f64(rand::thread_rng().gen_range(0.0..1.0))
+}
+
+/// Whether a text message from the server is its rate limit error.
+pub fn is_ws_rate_limit(text: &str) -> bool {
+    text.contains(WS_RATE_LIMIT_MESSAGE)
+        && serde_json::from_str::<serde_json::Value>(text).is_ok_and(|data| {
+            data["type"] == "Error"
+                && data["message"]
+                    .as_str()
+                    .is_some_and(|message| message.starts_with(WS_RATE_LIMIT_MESSAGE))
+        })
+}
@@ -0,0 +1,651 @@
+//! Scripted load profiles. A scenario file (TOML, or JSON with a `.json`
+//! extension) lists phases that run in order against the same lobbies:
+//!
+//! ```toml
+//! [[phases]]
+//! type = "create_lobbies"
+//! count = 10
+//!
+//! [[phases]]
+//! type = "join_players"
+//! per_lobby = 8
+//! connections_per_second = 20
+//!
+//! [[phases]]
+//! type = "run_rounds"
+//! rounds = 5
+//! round_seconds = 20
+//! think_time = { distribution = "normal", mean_seconds = 6, std_dev_seconds = 3 }
+//! correct_ratio = 0.6
+//!
+//! [[phases]]
+//! type = "churn"
+//! seconds = 30
+//! per_second = 2
+//! ```
+
+use crate::{
+    Args, ConnectionSchedule, LatencyStats, Server, TestAdmin, TestError, TestPlayer, seeded_rng,
+};
+use futures_util::SinkExt;
+use futures_util::future::join_all;
+use rand::Rng;
+use serde::Deserialize;
+use serde_json::json;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio_tungstenite::tungstenite::Message;
+
+#[derive(Debug, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Scenario {
+    /// Printed at the start of the run.
+    #[serde(default)]
+    name: Option<String>,
+    phases: Vec<Phase>,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case", deny_unknown_fields)]
+enum Phase {
+    /// Creates lobbies, each with a connected host.
+    CreateLobbies {
+        count: usize,
+        #[serde(default)]
+        connections_per_second: Option<f64>,
+    },
+    /// Adds players to every lobby.
+    JoinPlayers {
+        per_lobby: usize,
+        #[serde(default)]
+        connections_per_second: Option<f64>,
+    },
+    /// Plays rounds in every lobby, starting the game if needed.
+    RunRounds {
+        rounds: usize,
+        #[serde(default = "default_round_seconds")]
+        round_seconds: f64,
+        #[serde(default = "default_pause_seconds")]
+        pause_seconds: f64,
+        #[serde(default)]
+        think_time: Option<ThinkTime>,
+        /// Answers are uniformly random when unset.
+        #[serde(default)]
+        correct_ratio: Option<f64>,
+    },
+    /// Replaces random players with new ones at a steady rate.
+    Churn { seconds: f64, per_second: f64 },
+    /// Idles with every connection open.
+    Wait { seconds: f64 },
+}
+
+fn default_round_seconds() -> f64 {
+    20.0
+}
+
+fn default_pause_seconds() -> f64 {
+    3.0
+}
+
+/// How long a player takes to answer after the question appears.
+#[derive(Debug, Clone, Copy, Deserialize)]
+#[serde(tag = "distribution", rename_all = "snake_case", deny_unknown_fields)]
+pub enum ThinkTime {
+    Fixed {
+        seconds: f64,
+    },
+    Uniform {
+        min_seconds: f64,
+        max_seconds: f64,
+    },
+    /// Clamped at zero.
+    Normal {
+        mean_seconds: f64,
+        std_dev_seconds: f64,
+    },
+    /// Most answers come right after the question, with a long tail.
+    Exponential {
+        mean_seconds: f64,
+    },
+}
+
+impl ThinkTime {
+    pub fn sample(&self, rng: &mut impl Rng) -> Duration {
+        let seconds = match *self {
+            ThinkTime::Fixed { seconds } => seconds,
+            ThinkTime::Uniform {
+                min_seconds,
+                max_seconds,
+            } => rng.gen_range(min_seconds..=max_seconds),
+            ThinkTime::Normal {
+                mean_seconds,
+                std_dev_seconds,
+            } => {
+                // Box-Muller transform.
+                let u1: f64 = 1.0 - rng.gen_range(0.0..1.0);
+                let u2: f64 = rng.gen_range(0.0..1.0);
+                let z = (-2.0 * u1.ln()).sqrt() * (std::f64::consts::TAU * u2).cos();
+                mean_seconds + z * std_dev_seconds
+            }
+            ThinkTime::Exponential { mean_seconds } => {
+                let u: f64 = 1.0 - rng.gen_range(0.0..1.0);
+                -mean_seconds * u.ln()
+            }
+        };
+        Duration::from_secs_f64(seconds.max(0.0))
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        let valid = match *self {
+            ThinkTime::Fixed { seconds } => non_negative(seconds),
+            ThinkTime::Uniform {
+                min_seconds,
+                max_seconds,
+            } => {
+                non_negative(min_seconds) && non_negative(max_seconds) && min_seconds <= max_seconds
+            }
+            ThinkTime::Normal {
+                mean_seconds,
+                std_dev_seconds,
+            } => non_negative(mean_seconds) && non_negative(std_dev_seconds),
+            ThinkTime::Exponential { mean_seconds } => non_negative(mean_seconds),
+        };
+        if valid {
+            Ok(())
+        } else {
+            Err(format!("invalid think_time {self:?}"))
+        }
+    }
+}
+
+/// `fixed:SECONDS`, `uniform:MIN:MAX`, `normal:MEAN:STD_DEV` or
+/// `exponential:MEAN`, as given to `--think-time`.
+impl FromStr for ThinkTime {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, String> {
+        let (distribution, params) = value.split_once(':').unwrap_or((value, ""));
+        let params = params
+            .split(':')
+            .map(str::parse)
+            .collect::<Result<Vec<f64>, _>>()
+            .map_err(|e| format!("invalid think time {value:?}: {e}"))?;
+        let think_time = match (distribution, params.as_slice()) {
+            ("fixed", &[seconds]) => ThinkTime::Fixed { seconds },
+            ("uniform", &[min_seconds, max_seconds]) => ThinkTime::Uniform {
+                min_seconds,
+                max_seconds,
+            },
+            ("normal", &[mean_seconds, std_dev_seconds]) => ThinkTime::Normal {
+                mean_seconds,
+                std_dev_seconds,
+            },
+            ("exponential", &[mean_seconds]) => ThinkTime::Exponential { mean_seconds },
+            _ => {
+                return Err(format!(
+                    "expected fixed:SECONDS, uniform:MIN:MAX, normal:MEAN:STD_DEV or exponential:MEAN, got {value:?}"
+                ));
+            }
+        };
+        think_time.validate()?;
+        Ok(think_time)
+    }
+}
+
+fn non_negative(value: f64) -> bool {
+    value >= 0.0 && value.is_finite()
+}
+
+fn positive(value: f64) -> bool {
+    value > 0.0 && value.is_finite()
+}
+
+impl Scenario {
+    pub fn load(path: &Path) -> Result<Self, String> {
+        let error = |e: String| format!("{}: {e}", path.display());
+        let text = std::fs::read_to_string(path).map_err(|e| error(e.to_string()))?;
+        let scenario: Scenario = if path.extension().is_some_and(|ext| ext == "json") {
+            serde_json::from_str(&text).map_err(|e| error(e.to_string()))?
+        } else {
+            toml::from_str(&text).map_err(|e| error(e.to_string()))?
+        };
+        scenario.validate().map_err(error)?;
+        Ok(scenario)
+    }
+
+    fn validate(&self) -> Result<(), String> {
+        if self.phases.is_empty() {
+            return Err("no phases".to_string());
+        }
+        let mut has_lobbies = false;
+        for (index, phase) in self.phases.iter().enumerate() {
+            let invalid = |what: &str| Err(format!("phase {}: {what}", index + 1));
+            match phase {
+                Phase::CreateLobbies {
+                    connections_per_second,
+                    ..
+                }
+                | Phase::JoinPlayers {
+                    connections_per_second,
+                    ..
+                } if connections_per_second.is_some_and(|rate| !positive(rate)) => {
+                    return invalid("connections_per_second must be positive");
+                }
+                Phase::CreateLobbies { count, .. } => has_lobbies |= *count > 0,
+                Phase::JoinPlayers { .. } | Phase::RunRounds { .. } | Phase::Churn { .. }
+                    if !has_lobbies =>
+                {
+                    return invalid("needs a create_lobbies phase before it");
+                }
+                Phase::RunRounds {
+                    round_seconds,
+                    pause_seconds,
+                    think_time,
+                    correct_ratio,
+                    ..
+                } => {
+                    if !positive(*round_seconds) || !non_negative(*pause_seconds) {
+                        return invalid(
+                            "round_seconds must be positive and pause_seconds not negative",
+                        );
+                    }
+                    if correct_ratio.is_some_and(|ratio| !(0.0..=1.0).contains(&ratio)) {
+                        return invalid("correct_ratio must be between 0 and 1");
+                    }
+                    if let Some(Err(e)) = think_time.as_ref().map(ThinkTime::validate) {
+                        return invalid(&e);
+                    }
+                }
+                Phase::Churn {
+                    seconds,
+                    per_second,
+                } if !non_negative(*seconds) || !positive(*per_second) => {
+                    return invalid("seconds must not be negative and per_second must be positive");
+                }
+                Phase::Wait { seconds } if !non_negative(*seconds) => {
+                    return invalid("seconds must not be negative");
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Counters for the whole scenario.
+#[derive(Default)]
+struct ScenarioMetrics {
+    joins: AtomicUsize,
+    leaves: AtomicUsize,
+    rounds: AtomicUsize,
+    answers: AtomicUsize,
+    errors: AtomicUsize,
+}
+
+impl ScenarioMetrics {
+    fn error(&self, context: &str, error: TestError) {
+        eprintln!("{context}: {error}");
+        // Counted by the server's rate limit stats instead.
+        if !error.is_rate_limited() {
+            self.errors.fetch_add(1, Ordering::Relaxed);
+        }
+    }
+}
+
+/// A lobby created by the scenario, with its host and current players.
+struct ScenarioLobby {
+    index: usize,
+    admin: TestAdmin,
+    join_code: String,
+    answer_key: watch::Sender<Vec<String>>,
+    players: Vec<TestPlayer>,
+    game_started: bool,
+    /// Numbers player names, so players joining through churn get new ones.
+    next_player: usize,
+}
+
+impl ScenarioLobby {
+    fn next_player_name(&mut self) -> String {
+        self.next_player += 1;
+        format!("Scenario{}Player{}", self.index, self.next_player)
+    }
+}
+
+/// Runs the scenario's phases in order, then ends every started game.
+pub async fn run_scenario(
+    scenario: &Scenario,
+    server: &Server,
+    args: &Args,
+) -> Result<(), TestError> {
+    let start = Instant::now();
+    let metrics = Arc::new(ScenarioMetrics::default());
+    let latency = Arc::new(LatencyStats::new());
+    let mut lobbies: Vec<ScenarioLobby> = Vec::new();
+
+    if let Some(name) = &scenario.name {
+        println!("Scenario: {name}");
+    }
+    for (index, phase) in scenario.phases.iter().enumerate() {
+        println!("Phase {}: {:?}", index + 1, phase);
+        let phase_start = Instant::now();
+        match *phase {
+            Phase::CreateLobbies {
+                count,
+                connections_per_second,
+            } => {
+                let schedule = schedule(args, connections_per_second, count);
+                let handles: Vec<_> = (0..count)
+                    .map(|i| {
+                        let server = server.clone();
+                        tokio::spawn(async move {
+                            schedule.wait(i).await;
+                            TestAdmin::new(&server).await
+                        })
+                    })
+                    .collect();
+                for handle in handles {
+                    match handle.await.map_err(|e| TestError::Other(e.to_string()))? {
+                        Ok((admin, join_code)) => lobbies.push(ScenarioLobby {
+                            index: lobbies.len(),
+                            admin,
+                            join_code,
+                            answer_key: watch::channel(Vec::new()).0,
+                            players: Vec::new(),
+                            game_started: false,
+                            next_player: 0,
+                        }),
+                        Err(e) => metrics.error("Lobby creation failed", e),
+                    }
+                }
+            }
+            Phase::JoinPlayers {
+                per_lobby,
+                connections_per_second,
+            } => {
+                let schedule = schedule(args, connections_per_second, lobbies.len() * per_lobby);
+                let mut handles = Vec::new();
+                for (lobby_idx, lobby) in lobbies.iter_mut().enumerate() {
+                    for i in 0..per_lobby {
+                        let connection = lobby_idx * per_lobby + i;
+                        let name = lobby.next_player_name();
+                        let join_code = lobby.join_code.clone();
+                        let server = server.clone();
+                        let latency = Arc::clone(&latency);
+                        handles.push(tokio::spawn(async move {
+                            schedule.wait(connection).await;
+                            let player = TestPlayer::new(name, join_code, &server, latency).await;
+                            (lobby_idx, player)
+                        }));
+                    }
+                }
+                for handle in handles {
+                    let (lobby_idx, player) =
+                        handle.await.map_err(|e| TestError::Other(e.to_string()))?;
+                    match player {
+                        Ok(player) => {
+                            lobbies[lobby_idx].players.push(player);
+                            metrics.joins.fetch_add(1, Ordering::Relaxed);
+                        }
+                        Err(e) => metrics.error("Player join failed", e),
+                    }
+                }
+            }
+            Phase::RunRounds {
+                rounds,
+                round_seconds,
+                pause_seconds,
+                think_time,
+                correct_ratio,
+            } => {
+                let round = RoundOptions {
+                    rounds,
+                    duration: Duration::from_secs_f64(round_seconds),
+                    pause: Duration::from_secs_f64(pause_seconds),
+                    think_time: think_time.unwrap_or(ThinkTime::Uniform {
+                        min_seconds: 0.0,
+                        max_seconds: round_seconds,
+                    }),
+                    correct_ratio,
+                };
+                let handles: Vec<_> = lobbies
+                    .drain(..)
+                    .map(|lobby| tokio::spawn(run_rounds(lobby, round, Arc::clone(&metrics))))
+                    .collect();
+                for handle in handles {
+                    lobbies.push(handle.await.map_err(|e| TestError::Other(e.to_string()))?);
+                }
+            }
+            Phase::Churn {
+                seconds,
+                per_second,
+            } => {
+                churn(
+                    &mut lobbies,
+                    Duration::from_secs_f64(seconds),
+                    Duration::from_secs_f64(1.0 / per_second),
+                    server,
+                    &latency,
+                    &metrics,
+                )
+                .await;
+            }
+            Phase::Wait { seconds } => {
+                tokio::time::sleep(Duration::from_secs_f64(seconds)).await;
+            }
+        }
+        println!(
+            "Phase {} done in {:.2}s - Lobbies: {}, Players: {}, Errors: {}",
+            index + 1,
+            phase_start.elapsed().as_secs_f64(),
+            lobbies.len(),
+            lobbies
+                .iter()
+                .map(|lobby| lobby.players.len())
+                .sum::<usize>(),
+            metrics.errors.load(Ordering::Relaxed)
+        );
+    }
+
+    for lobby in &mut lobbies {
+        if lobby.game_started
+            && let Err(e) = lobby.admin.end_game().await
+        {
+            metrics.error("Ending game failed", e);
+        }
+        for player in &lobby.players {
+            player.finish();
+        }
+    }
+
+    println!(
+        "Scenario complete - Duration: {:.2}s, Joins: {}, Leaves: {}, Rounds: {}, Answers: {}, Errors: {}",
+        start.elapsed().as_secs_f64(),
+        metrics.joins.load(Ordering::Relaxed),
+        metrics.leaves.load(Ordering::Relaxed),
+        metrics.rounds.load(Ordering::Relaxed),
+        metrics.answers.load(Ordering::Relaxed),
+        metrics.errors.load(Ordering::Relaxed)
+    );
+    latency.report();
+    Ok(())
+}
+
+/// The phase's own rate if set, otherwise the command-line ramp-up options.
+fn schedule(args: &Args, connections_per_second: Option<f64>, total: usize) -> ConnectionSchedule {
+    match connections_per_second {
+        Some(rate) => ConnectionSchedule::with_interval(Duration::from_secs_f64(1.0 / rate)),
+        None => ConnectionSchedule::new(args, total),
+    }
+}
+
+#[derive(Clone, Copy)]
+struct RoundOptions {
+    rounds: usize,
+    duration: Duration,
+    pause: Duration,
+    think_time: ThinkTime,
+    correct_ratio: Option<f64>,
+}
+
+/// Plays the rounds in one lobby and hands the lobby back.
+async fn run_rounds(
+    mut lobby: ScenarioLobby,
+    options: RoundOptions,
+    metrics: Arc<ScenarioMetrics>,
+) -> ScenarioLobby {
+    let ScenarioLobby {
+        index,
+        admin,
+        answer_key,
+        players,
+        game_started,
+        ..
+    } = &mut lobby;
+    for player in players.iter_mut() {
+        player.answer_key = options
+            .correct_ratio
+            .map(|ratio| (answer_key.subscribe(), ratio));
+    }
+    if !*game_started {
+        if let Err(e) = admin.start_game().await {
+            metrics.error(&format!("Starting game in lobby {index} failed"), e);
+            return lobby;
+        }
+        *game_started = true;
+    }
+
+    for _ in 0..options.rounds {
+        let deadline = Instant::now() + options.duration;
+        let host = async {
+            admin.start_round_with_key(Some(answer_key)).await?;
+            tokio::time::sleep_until(deadline.into()).await;
+            admin.end_round().await
+        };
+        let answers = join_all(
+            players
+                .iter_mut()
+                .map(|player| play_round(player, options.think_time, deadline)),
+        );
+        let (host, answers) = tokio::join!(host, answers);
+        if let Err(e) = host {
+            metrics.error(&format!("Host of lobby {index} failed"), e);
+        }
+        for answer in answers {
+            match answer {
+                Ok(true) => {
+                    metrics.answers.fetch_add(1, Ordering::Relaxed);
+                }
+                Ok(false) => {}
+                Err(e) => metrics.error(&format!("Player in lobby {index} failed"), e),
+            }
+        }
+        metrics.rounds.fetch_add(1, Ordering::Relaxed);
+        if admin.game_over {
+            break;
+        }
+        tokio::time::sleep(options.pause).await;
+    }
+    lobby
+}
+
+/// Reads the player's messages until the round's deadline, answering the
+/// question after the think time if that is still within the round. Returns
+/// whether an answer was sent.
+async fn play_round(
+    player: &mut TestPlayer,
+    think_time: ThinkTime,
+    deadline: Instant,
+) -> Result<bool, TestError> {
+    let mut question = None;
+    let mut answer_at = None;
+    let mut answered = false;
+    loop {
+        let answer_due = async {
+            match answer_at {
+                Some(at) => tokio::time::sleep_until(at).await,
+                None => std::future::pending().await,
+            }
+        };
+        tokio::select! {
+            _ = tokio::time::sleep_until(deadline.into()) => break,
+            _ = answer_due => {
+                answer_at = None;
+                let alternatives: serde_json::Value = question.take().unwrap_or_default();
+                if let Some(answer) = player.choose_answer(&alternatives).await {
+                    player.submit_answer(answer).await?;
+                    answered = true;
+                }
+            }
+            msg = player.next_message() => {
+                let Some(msg) = msg else {
+                    break;
+                };
+                let Message::Text(text) = msg? else {
+                    continue;
+                };
+                let data: serde_json::Value = serde_json::from_str(&text)?;
+                match data["type"].as_str() {
+                    Some("AnswerAck") | Some("AnswerRejected") | Some("Error") => {
+                        player.acknowledge(&data)
+                    }
+                    Some("StateDelta") if data["phase"].as_str() == Some("question") => {
+                        let at = Instant::now() + think_time.sample(&mut player.rng);
+                        if at < deadline {
+                            question = Some(data["alternatives"].clone());
+                            answer_at = Some(at.into());
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    Ok(answered)
+}
+
+/// Every `interval`, a random player leaves and a new one joins its lobby.
+async fn churn(
+    lobbies: &mut [ScenarioLobby],
+    duration: Duration,
+    interval: Duration,
+    server: &Server,
+    latency: &Arc<LatencyStats>,
+    metrics: &ScenarioMetrics,
+) {
+    if lobbies.is_empty() {
+        return;
+    }
+    let mut rng = seeded_rng("scenario churn");
+    let end = Instant::now() + duration;
+    let mut ticker = tokio::time::interval(interval);
+    ticker.tick().await;
+    while Instant::now() < end {
+        ticker.tick().await;
+        let lobby_idx = rng.gen_range(0..lobbies.len());
+        let lobby = &mut lobbies[lobby_idx];
+        if !lobby.players.is_empty() {
+            let leaving = rng.gen_range(0..lobby.players.len());
+            let mut player = lobby.players.swap_remove(leaving);
+            player.finish();
+            let leave = json!({ "type": "Leave" });
+            match player.ws_write.send(Message::Text(leave.to_string())).await {
+                Ok(()) => {
+                    metrics.leaves.fetch_add(1, Ordering::Relaxed);
+                }
+                Err(e) => metrics.error("Leaving failed", e.into()),
+            }
+        }
+        let name = lobby.next_player_name();
+        match TestPlayer::new(name, lobby.join_code.clone(), server, Arc::clone(latency)).await {
+            Ok(player) => {
+                lobby.players.push(player);
+                metrics.joins.fetch_add(1, Ordering::Relaxed);
+            }
+            Err(e) => metrics.error("Player join failed", e),
+        }
+    }
+}
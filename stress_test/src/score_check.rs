@@ -0,0 +1,185 @@
+//! `--check-scores`: the host of a gameplay test game follows every `Answered`
+//! broadcast and checks the scores against what its players submitted. The
+//! broadcast names only the player, so each one is paired with the answer the
+//! player had acknowledged in an `AnswerAck`.
+
+use crate::correct_options;
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Arc, Mutex};
+
+/// Most points a single answer can score.
+const MAX_ANSWER_SCORE: i64 = 5000;
+
+/// How far a later correct answer may outscore an earlier one, since answers
+/// are timed when received but scored in processing order.
+const SCORE_ORDER_TOLERANCE: i64 = 10;
+
+/// Answers the players submitted, shared between a game's players and host.
+pub type AnswerLog = Arc<Mutex<AnswerBook>>;
+
+#[derive(Default)]
+pub struct AnswerBook {
+    /// Answers waiting for a reply, by player name and `client_msg_id`.
+    sent: HashMap<(String, String), String>,
+    /// Answers the server acknowledged and their scores, by player name.
+    acked: HashMap<String, VecDeque<(String, i64)>>,
+}
+
+impl AnswerBook {
+    pub fn sent(&mut self, name: &str, client_msg_id: &str, answer: String) {
+        self.sent
+            .insert((name.to_string(), client_msg_id.to_string()), answer);
+    }
+
+    /// Follows a reply to one of `name`'s answers: an `AnswerAck` makes the
+    /// answer count, while a rejected answer is never scored.
+    pub fn reply(&mut self, name: &str, data: &serde_json::Value) {
+        let Some(id) = data["client_msg_id"].as_str() else {
+            return;
+        };
+        let Some(answer) = self.sent.remove(&(name.to_string(), id.to_string())) else {
+            return;
+        };
+        if data["type"].as_str() == Some("AnswerAck") {
+            let score = data["score"].as_i64().unwrap_or_default();
+            self.acked
+                .entry(name.to_string())
+                .or_default()
+                .push_back((answer, score));
+        }
+    }
+
+    fn take_acked(&mut self, name: &str) -> Option<(String, i64)> {
+        self.acked.get_mut(name)?.pop_front()
+    }
+}
+
+/// What the host saw, in order, until the matching acknowledgement arrives.
+enum Seen {
+    Round(Vec<String>),
+    Answered { name: String, score: i64 },
+}
+
+#[derive(Default)]
+pub struct ScoreCheck {
+    /// Shared with the game's players.
+    pub answers: AnswerLog,
+    /// Broadcasts not yet paired with a player's acknowledgement. Players
+    /// read their own socket, so an `Answered` can arrive first.
+    unpaired: VecDeque<Seen>,
+    /// Correct options of the current round.
+    correct: Vec<String>,
+    /// Score of the previous correct answer this round.
+    last_correct_score: Option<i64>,
+    /// Sum of each player's answer scores.
+    totals: HashMap<String, i64>,
+    problems: Vec<String>,
+}
+
+impl ScoreCheck {
+    /// Follows one message the host received.
+    pub fn observe(&mut self, data: &serde_json::Value) {
+        match data["type"].as_str() {
+            Some("AdminInfo") => self.unpaired.push_back(Seen::Round(correct_options(data))),
+            Some("Answered") => {
+                let (Some(name), Some(score)) = (data["name"].as_str(), data["score"].as_i64())
+                else {
+                    self.problems.push(format!("malformed Answered: {data}"));
+                    return;
+                };
+                if !(0..=MAX_ANSWER_SCORE).contains(&score) {
+                    self.problems
+                        .push(format!("{name} scored {score} for a single answer"));
+                }
+                *self.totals.entry(name.to_string()).or_default() += score;
+                self.unpaired.push_back(Seen::Answered {
+                    name: name.to_string(),
+                    score,
+                });
+            }
+            Some("GameOver") => self.observe_game_over(data),
+            _ => {}
+        }
+        self.pair();
+    }
+
+    /// Checks the answers whose acknowledgements have arrived, in the order
+    /// they were broadcast.
+    fn pair(&mut self) {
+        while let Some(seen) = self.unpaired.front() {
+            match seen {
+                Seen::Round(correct) => {
+                    self.correct = correct.clone();
+                    self.last_correct_score = None;
+                }
+                Seen::Answered { name, score } => {
+                    let Some(acked) = self.answers.lock().unwrap().take_acked(name) else {
+                        return;
+                    };
+                    let (name, score) = (name.clone(), *score);
+                    self.check_answer(&name, score, acked);
+                }
+            }
+            self.unpaired.pop_front();
+        }
+    }
+
+    fn check_answer(&mut self, name: &str, score: i64, (answer, acked_score): (String, i64)) {
+        if acked_score != score {
+            self.problems.push(format!(
+                "{name} was told it scored {acked_score}, but everyone else saw {score}"
+            ));
+        }
+        let correct = self.correct.contains(&answer);
+        if correct != (score > 0) {
+            self.problems.push(format!(
+                "{name} scored {score} for {} answer {answer:?}",
+                if correct { "correct" } else { "wrong" }
+            ));
+        }
+        if correct {
+            if let Some(previous) = self.last_correct_score
+                && score > previous + SCORE_ORDER_TOLERANCE
+            {
+                self.problems.push(format!(
+                    "{name} scored {score}, more than the faster correct answer's {previous}"
+                ));
+            }
+            self.last_correct_score = Some(score);
+        }
+    }
+
+    fn observe_game_over(&mut self, data: &serde_json::Value) {
+        for entry in data["final_scores"].as_array().into_iter().flatten() {
+            let (Some(name), Some(score)) = (entry[0].as_str(), entry[1].as_i64()) else {
+                self.problems
+                    .push(format!("malformed final score: {entry}"));
+                continue;
+            };
+            if score < 0 {
+                self.problems
+                    .push(format!("{name} finished with {score} points"));
+            }
+            // Players who missed rounds must not have gained points for them.
+            let expected = self.totals.get(name).copied().unwrap_or_default();
+            if score != expected {
+                self.problems.push(format!(
+                    "{name} finished with {score} points, but its answers scored {expected}"
+                ));
+            }
+        }
+    }
+
+    /// What went wrong, once the game's players have stopped. Scores still
+    /// unpaired then were for answers the player never had acknowledged.
+    pub fn finish(mut self) -> Vec<String> {
+        self.pair();
+        for seen in self.unpaired {
+            if let Seen::Answered { name, .. } = seen {
+                self.problems
+                    .push(format!("{name} was scored for an answer it never sent"));
+            }
+        }
+        self.problems
+    }
+}
@@ -0,0 +1,286 @@
+//! Soak test: holds a steady population of idle lobbies for a long time,
+//! slowly replacing whole lobbies, and samples server-reported numbers to spot
+//! leaks. With a steady population, a series that only ever grows is flagged.
+
+use crate::{
+    ConnectionSchedule, LatencyStats, Server, TestAdmin, TestError, TestPlayer, seeded_rng,
+};
+use futures_util::StreamExt;
+use futures_util::future::join_all;
+use rand::Rng;
+use serde_json::json;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::oneshot;
+use tokio::task::JoinHandle;
+
+/// Fewer samples than this are not enough to call a trend.
+const MIN_TREND_SAMPLES: usize = 5;
+
+pub struct SoakOptions {
+    pub num_lobbies: usize,
+    pub players_per_lobby: usize,
+    pub duration: Duration,
+    pub sample_interval: Duration,
+    /// Time between replacing a random lobby with a new one.
+    pub churn_interval: Duration,
+    /// Lists lobbies through the admin API when set.
+    pub admin_password: Option<String>,
+}
+
+/// A lobby whose connections are kept open by a background task.
+struct SoakLobby {
+    close: oneshot::Sender<()>,
+    handle: JoinHandle<()>,
+}
+
+/// Creates a lobby, joins its players and keeps reading their connections
+/// until told to close the lobby.
+async fn open_lobby(
+    index: usize,
+    players_per_lobby: usize,
+    server: &Server,
+    schedule: ConnectionSchedule,
+    first_connection: usize,
+    latency: &Arc<LatencyStats>,
+) -> Result<SoakLobby, TestError> {
+    schedule.wait(first_connection).await;
+    let (mut admin, join_code) = TestAdmin::new(server).await?;
+    let mut players = Vec::new();
+    for i in 0..players_per_lobby {
+        schedule.wait(first_connection + 1 + i).await;
+        let name = format!("Soak{}Player{}", index, i + 1);
+        players.push(TestPlayer::new(name, join_code.clone(), server, Arc::clone(latency)).await?);
+    }
+
+    let (close, closed) = oneshot::channel();
+    let handle = tokio::spawn(async move {
+        let drain = async {
+            let admin_read = async { while let Some(Ok(_)) = admin.ws_read.next().await {} };
+            let player_reads =
+                join_all(players.iter_mut().map(|player| async {
+                    while let Some(Ok(_)) = player.next_message().await {}
+                }));
+            tokio::join!(admin_read, player_reads);
+        };
+        tokio::select! {
+            _ = closed => {}
+            _ = drain => return,
+        }
+        if let Err(e) = admin.close_game().await {
+            eprintln!("Closing soak lobby {index} failed: {e}");
+        }
+    });
+    Ok(SoakLobby { close, handle })
+}
+
+impl SoakLobby {
+    async fn close(self) {
+        let _ = self.close.send(());
+        let _ = self.handle.await;
+    }
+}
+
+/// Reads the numbers the server reports: lobby and player counts from the
+/// admin API, and the unlabelled series of a Prometheus `/metrics` endpoint if
+/// the server has one.
+async fn sample(server: &Server, admin_password: Option<&str>) -> BTreeMap<String, f64> {
+    let mut values = BTreeMap::new();
+    if let Some(password) = admin_password {
+        match admin_lobbies(server, password).await {
+            Ok(lobbies) => {
+                let players: u64 = lobbies
+                    .iter()
+                    .filter_map(|lobby| lobby["player_count"].as_u64())
+                    .sum();
+                values.insert("lobbies".to_string(), lobbies.len() as f64);
+                values.insert("players".to_string(), players as f64);
+            }
+            Err(e) => eprintln!("Sampling lobbies failed: {e}"),
+        }
+    }
+    if let Ok(res) = server.http.get(server.http_url("/metrics")).send().await
+        && res.status().is_success()
+        && let Ok(text) = res.text().await
+    {
+        values.extend(parse_metrics(&text));
+    }
+    values
+}
+
+async fn admin_lobbies(
+    server: &Server,
+    password: &str,
+) -> Result<Vec<serde_json::Value>, TestError> {
+    let login: serde_json::Value = server
+        .send(
+            server
+                .http
+                .post(server.http_url("/api/admin/login"))
+                .json(&json!({ "password": password })),
+        )
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    let token = login["token"]
+        .as_str()
+        .ok_or_else(|| TestError::Other("Missing token in admin login response".to_string()))?;
+    let response: serde_json::Value = server
+        .send(
+            server
+                .http
+                .get(server.http_url("/api/admin/lobbies"))
+                .bearer_auth(token),
+        )
+        .await?
+        .error_for_status()?
+        .json()
+        .await?;
+    Ok(response["lobbies"].as_array().cloned().unwrap_or_default())
+}
+
+/// Unlabelled `name value` lines of the Prometheus text format.
+fn parse_metrics(text: &str) -> impl Iterator<Item = (String, f64)> + '_ {
+    text.lines()
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            let mut parts = line.split_whitespace();
+            let name = parts.next()?;
+            let value = parts.next()?.parse().ok()?;
+            (!name.contains('{')).then(|| (name.to_string(), value))
+        })
+}
+
+/// A series that never went down and ended higher than it started.
+fn is_growing(samples: &[f64]) -> bool {
+    samples.len() >= MIN_TREND_SAMPLES
+        && samples.windows(2).all(|pair| pair[1] >= pair[0])
+        && samples.last() > samples.first()
+}
+
+pub async fn run_soak_test(
+    options: SoakOptions,
+    server: &Server,
+    schedule: ConnectionSchedule,
+) -> Result<(), TestError> {
+    let latency = Arc::new(LatencyStats::new());
+    let start = Instant::now();
+    let connections_per_lobby = options.players_per_lobby + 1;
+
+    let mut lobbies = Vec::new();
+    for index in 0..options.num_lobbies {
+        let lobby = open_lobby(
+            index,
+            options.players_per_lobby,
+            server,
+            schedule,
+            index * connections_per_lobby,
+            &latency,
+        )
+        .await?;
+        lobbies.push(lobby);
+    }
+    println!(
+        "Soak population ready in {:.2}s: {} lobbies, {} players each",
+        start.elapsed().as_secs_f64(),
+        options.num_lobbies,
+        options.players_per_lobby
+    );
+    if options.admin_password.is_none() {
+        println!("No --admin-password given, so lobby and player counts are not sampled");
+    }
+
+    let end = start + options.duration;
+    let mut series: BTreeMap<String, Vec<f64>> = BTreeMap::new();
+    let mut sample_ticker = tokio::time::interval(options.sample_interval);
+    let mut churn_ticker = tokio::time::interval(options.churn_interval);
+    churn_ticker.tick().await;
+    let mut next_index = options.num_lobbies;
+    let mut replaced = 0;
+    let mut errors = 0;
+    let unbounded = ConnectionSchedule::with_interval(Duration::ZERO);
+    let mut rng = seeded_rng("soak churn");
+
+    while Instant::now() < end {
+        tokio::select! {
+            _ = tokio::time::sleep_until(end.into()) => break,
+            _ = sample_ticker.tick() => {
+                let values = sample(server, options.admin_password.as_deref()).await;
+                let summary: Vec<String> = values
+                    .iter()
+                    .map(|(name, value)| format!("{name}={value}"))
+                    .collect();
+                let summary = if summary.is_empty() {
+                    "no server metrics".to_string()
+                } else {
+                    summary.join(" ")
+                };
+                println!("[{:>6.0}s] {}", start.elapsed().as_secs_f64(), summary);
+                for (name, value) in values {
+                    series.entry(name).or_default().push(value);
+                }
+            }
+            _ = churn_ticker.tick(), if !lobbies.is_empty() => {
+                let index = rng.gen_range(0..lobbies.len());
+                lobbies.swap_remove(index).close().await;
+                let lobby = open_lobby(
+                    next_index,
+                    options.players_per_lobby,
+                    server,
+                    unbounded,
+                    0,
+                    &latency,
+                )
+                .await;
+                match lobby {
+                    Ok(lobby) => {
+                        lobbies.push(lobby);
+                        replaced += 1;
+                    }
+                    Err(e) => {
+                        eprintln!("Replacing soak lobby failed: {e}");
+                        if !e.is_rate_limited() {
+                            errors += 1;
+                        }
+                    }
+                }
+                next_index += 1;
+            }
+        }
+    }
+
+    for lobby in lobbies {
+        lobby.close().await;
+    }
+
+    println!(
+        "Soak test complete - Duration: {:.2}s, Lobbies replaced: {}, Errors: {}",
+        start.elapsed().as_secs_f64(),
+        replaced,
+        errors
+    );
+    let growing: Vec<&String> = series
+        .iter()
+        .filter(|(_, samples)| is_growing(samples))
+        .map(|(name, _)| name)
+        .collect();
+    for name in &growing {
+        let samples = &series[*name];
+        println!(
+            "Possible leak: {} never went down over {} samples, {} -> {}",
+            name,
+            samples.len(),
+            samples[0],
+            samples[samples.len() - 1]
+        );
+    }
+    if growing.is_empty() {
+        return Ok(());
+    }
+    Err(TestError::Other(format!(
+        "{} server metrics kept growing",
+        growing.len()
+    )))
+}